@@ -0,0 +1,69 @@
+//! Frontend skeleton of a GUI widget (egui, iced...) embedding the editor.
+//!
+//! The GUI thread forwards the keys pressed to the editor running in a
+//! worker thread, and paints the grids received on each frame. Here the
+//! "GUI" types a scripted session and paints the grids as text.
+extern crate rustyline;
+
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use rustyline::embed::{Grid, Surface, Widget};
+use rustyline::testing::parse_keys;
+use rustyline::Editor;
+
+/// Surface sending the grids to the GUI thread
+struct Frames(Sender<Grid>);
+
+impl Surface for Frames {
+    fn size(&self) -> (usize, usize) {
+        (40, 6)
+    }
+
+    fn draw(&mut self, grid: &Grid) {
+        let _ = self.0.send(grid.clone());
+    }
+}
+
+/// Paint `grid` like a widget would (styled cells and cursor).
+fn paint(grid: &Grid) {
+    let (cols, rows) = grid.size();
+    let (cursor_col, cursor_row) = grid.cursor();
+    println!("+{}+", "-".repeat(cols));
+    for row in 0..rows {
+        let mut text = String::new();
+        for col in 0..cols {
+            match grid.cell(col, row) {
+                _ if (col, row) == (cursor_col, cursor_row) => text.push('_'),
+                Some(cell) if cell.text.is_empty() => {} // after a wide grapheme
+                Some(cell) => text.push_str(&cell.text),
+                None => text.push(' '),
+            }
+        }
+        println!("|{}|", text);
+    }
+    println!("+{}+", "-".repeat(cols));
+}
+
+fn main() {
+    let (keys, events) = mpsc::channel();
+    let (frames, grids) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        let mut events = events;
+        let mut editor = Editor::<()>::new();
+        let mut widget = Widget::new(Frames(frames));
+        while let Ok(line) = widget.readline(&mut editor, "> ", &mut events) {
+            widget.print(&format!("echo: {}\n", line));
+        }
+    });
+
+    // GUI event loop: key events from the window system
+    for key in parse_keys("hello Space world Enter bye Space world C-a M-f C-k Enter") {
+        keys.send(key).unwrap();
+    }
+    drop(keys); // window closed
+    worker.join().unwrap();
+    if let Some(grid) = grids.try_iter().last() {
+        paint(&grid);
+    }
+}
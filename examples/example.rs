@@ -69,7 +69,7 @@ fn main() {
         let readline = rl.readline(PROMPT);
         match readline {
             Ok(line) => {
-                rl.add_history_entry(line.as_ref());
+                rl.add_history_entry::<&str>(line.as_ref());
                 println!("Line: {}", line);
             }
             Err(ReadlineError::Interrupted) => {
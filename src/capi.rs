@@ -0,0 +1,127 @@
+//! GNU Readline compatible C API (`capi` feature)
+//!
+//! Exposes `readline`, `add_history`, `clear_history`, `read_history` and
+//! `write_history` symbols so that a C program linked against GNU Readline
+//! can use Rustyline instead, without code changes.
+//! Build a shared library with:
+//!
+//! ```sh
+//! cargo rustc --release --features capi -- --crate-type cdylib
+//! ```
+//!
+//! The editor is per thread and uses the default configuration.
+use libc::{self, c_char, c_int};
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::path::PathBuf;
+use std::ptr;
+
+use dirs;
+use error::ReadlineError;
+use {Editor, Result};
+
+thread_local! {
+    static EDITOR: RefCell<Editor<()>> = RefCell::new(Editor::new());
+}
+
+/// Read a line with the specified `prompt`.
+///
+/// Return a `malloc`ed string (without the trailing newline) that the caller
+/// must `free`, or a null pointer on EOF or error.
+///
+/// # Safety
+///
+/// `prompt` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn readline(prompt: *const c_char) -> *mut c_char {
+    let prompt = if prompt.is_null() {
+        ""
+    } else {
+        CStr::from_ptr(prompt).to_str().unwrap_or("")
+    };
+    match EDITOR.with(|editor| editor.borrow_mut().readline(prompt)) {
+        Ok(line) => to_malloced(&line),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Add `line` to the history.
+///
+/// # Safety
+///
+/// `line` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn add_history(line: *const c_char) {
+    if line.is_null() {
+        return;
+    }
+    let line = CStr::from_ptr(line).to_string_lossy().into_owned();
+    EDITOR.with(|editor| editor.borrow_mut().add_history_entry(line));
+}
+
+/// Clear the history.
+#[no_mangle]
+pub extern "C" fn clear_history() {
+    EDITOR.with(|editor| editor.borrow_mut().clear_history());
+}
+
+/// Add the contents of `filename` to the history (`~/.history` when null).
+///
+/// Return 0 on success, an `errno` value otherwise.
+///
+/// # Safety
+///
+/// `filename` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn read_history(filename: *const c_char) -> c_int {
+    match history_path(filename) {
+        Some(path) => to_errno(EDITOR.with(|editor| editor.borrow_mut().load_history(&path))),
+        None => libc::ENOENT,
+    }
+}
+
+/// Write the history to `filename` (`~/.history` when null).
+///
+/// Return 0 on success, an `errno` value otherwise.
+///
+/// # Safety
+///
+/// `filename` must be null or a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn write_history(filename: *const c_char) -> c_int {
+    match history_path(filename) {
+        Some(path) => to_errno(EDITOR.with(|editor| editor.borrow().save_history(&path))),
+        None => libc::ENOENT,
+    }
+}
+
+unsafe fn history_path(filename: *const c_char) -> Option<PathBuf> {
+    if filename.is_null() {
+        dirs::home_dir().map(|home| home.join(".history"))
+    } else {
+        Some(PathBuf::from(
+            CStr::from_ptr(filename).to_string_lossy().into_owned(),
+        ))
+    }
+}
+
+fn to_errno(result: Result<()>) -> c_int {
+    match result {
+        Ok(_) => 0,
+        Err(ReadlineError::Io(ref err)) => err.raw_os_error().unwrap_or(libc::EIO),
+        Err(_) => libc::EIO,
+    }
+}
+
+/// Copy `s` (truncated at the first NUL) into a buffer allocated by `malloc`.
+unsafe fn to_malloced(s: &str) -> *mut c_char {
+    let bytes = s.as_bytes();
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    let buf = libc::malloc(len + 1) as *mut u8;
+    if buf.is_null() {
+        return ptr::null_mut();
+    }
+    ptr::copy_nonoverlapping(bytes.as_ptr(), buf, len);
+    *buf.add(len) = 0;
+    buf as *mut c_char
+}
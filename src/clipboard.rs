@@ -0,0 +1,77 @@
+//! System clipboard access with the OSC 52 escape sequence (which works over
+//! SSH, when the terminal permits it).
+
+/// Query of the clipboard content
+pub const QUERY: &str = "\x1b]52;c;?\x07";
+
+/// Start of the reply to `QUERY`
+pub const REPLY_PREFIX: &str = "\x1b]52;";
+
+/// Extract the clipboard content from the terminal `reply`
+/// (`ESC ] 52 ; c ; base64 BEL`, or terminated by `ESC \`).
+pub fn parse_reply(reply: &str) -> Option<String> {
+    if !reply.starts_with(REPLY_PREFIX) {
+        return None;
+    }
+    // skip the selection parameter
+    let start = REPLY_PREFIX.len();
+    let data = match reply[start..].find(';') {
+        Some(i) => &reply[start + i + 1..],
+        None => return None,
+    };
+    let end = data.find(&['\x07', '\x1b'][..]).unwrap_or(data.len());
+    let data = &data[..end];
+    decode_base64(data).and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for b in data.bytes() {
+        let value = match b {
+            b'A'...b'Z' => b - b'A',
+            b'a'...b'z' => b - b'a' + 26,
+            b'0'...b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn decode_base64() {
+        assert_eq!(Some(b"".to_vec()), super::decode_base64(""));
+        assert_eq!(Some(b"f".to_vec()), super::decode_base64("Zg=="));
+        assert_eq!(Some(b"fo".to_vec()), super::decode_base64("Zm8="));
+        assert_eq!(Some(b"foo".to_vec()), super::decode_base64("Zm9v"));
+        assert_eq!(Some(b"foobar".to_vec()), super::decode_base64("Zm9vYmFy"));
+        assert_eq!(None, super::decode_base64("Zm9v!"));
+    }
+
+    #[test]
+    fn parse_reply() {
+        assert_eq!(
+            Some("foo".to_owned()),
+            super::parse_reply("\x1b]52;c;Zm9v\x07")
+        );
+        assert_eq!(
+            Some("foo".to_owned()),
+            super::parse_reply("\x1b]52;c;Zm9v\x1b\\")
+        );
+        assert_eq!(Some("".to_owned()), super::parse_reply("\x1b]52;c;\x07"));
+        assert_eq!(None, super::parse_reply("\x1b]11;rgb:0000/0000/0000\x07"));
+    }
+}
@@ -0,0 +1,163 @@
+//! User-defined commands
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use config::Config;
+use edit::State;
+use keymap::{InputState, Refresher};
+use keys::KeyPress;
+use tty::RawReader;
+use Result;
+
+/// Handler of a user-defined command, bound to a key with
+/// `Cmd::Custom(name)` (or in the `.inputrc` by its name), and executed by
+/// its name with `Cmd::ExecuteNamedCommand` (Meta-X).
+///
+/// ```
+/// use rustyline::command::Context;
+/// use rustyline::{Cmd, Editor, KeyPress};
+/// let mut editor = Editor::<()>::new();
+/// editor.add_command(
+///     "insert-register",
+///     Box::new(|ctx: &mut Context| {
+///         if let Some(name) = try!(ctx.read_line("Register name: ")) {
+///             let (line, pos) = (ctx.line().to_owned(), ctx.pos());
+///             let line = format!("{}{}{}", &line[..pos], name, &line[pos..]);
+///             ctx.set_line(&line, pos + name.len());
+///         }
+///         Ok(())
+///     }),
+/// );
+/// editor.bind_sequence(KeyPress::Meta('r'), Cmd::Custom("insert-register".to_owned()));
+/// ```
+pub trait CommandHandler {
+    /// Execute the command.
+    fn handle(&self, ctx: &mut Context) -> Result<()>;
+}
+
+impl<F> CommandHandler for F
+where
+    F: Fn(&mut Context) -> Result<()>,
+{
+    fn handle(&self, ctx: &mut Context) -> Result<()> {
+        self(ctx)
+    }
+}
+
+/// Application-provided picker (of emoji, snippets, files...) run by
+/// `Cmd::Pick`.
+pub trait Picker {
+    /// Return the string to insert at the cursor (`None` when cancelled).
+    ///
+    /// The choices can be displayed under the line with `Context::set_menu`
+    /// while keys are read with `Context::next_key`. A picker taking over the
+    /// whole screen should use the alternate screen buffer so that the line
+    /// can be repainted afterwards.
+    fn pick(&self, ctx: &mut Context) -> Result<Option<String>>;
+}
+
+impl<F> Picker for F
+where
+    F: Fn(&mut Context) -> Result<Option<String>>,
+{
+    fn pick(&self, ctx: &mut Context) -> Result<Option<String>> {
+        self(ctx)
+    }
+}
+
+/// Application-provided handler of the text pasted in the terminal (see
+/// `Config::bracketed_paste`), run by `Cmd::Paste`.
+///
+/// ```
+/// use rustyline::command::Context;
+/// use rustyline::Editor;
+/// let mut editor = Editor::<()>::new();
+/// editor.set_paste_handler(Some(Box::new(|text: &str, ctx: &mut Context| {
+///     if text.lines().count() > 10 && !try!(ctx.confirm("Paste this block?")) {
+///         return Ok(None);
+///     }
+///     Ok(Some(text.trim_right().to_owned()))
+/// })));
+/// ```
+pub trait PasteHandler {
+    /// Return the text to insert at the cursor instead of the pasted `text`
+    /// (like the block indented differently), `None` to drop it.
+    fn paste(&self, text: &str, ctx: &mut Context) -> Result<Option<String>>;
+}
+
+impl<F> PasteHandler for F
+where
+    F: Fn(&str, &mut Context) -> Result<Option<String>>,
+{
+    fn paste(&self, text: &str, ctx: &mut Context) -> Result<Option<String>> {
+        self(text, ctx)
+    }
+}
+
+/// Access to the edited line given to a `CommandHandler`, a `Picker` or a
+/// `PasteHandler`.
+pub struct Context<'c, 'out: 'c, 'prompt: 'c> {
+    state: &'c mut State<'out, 'prompt>,
+    rdr: &'c mut RawReader,
+    config: &'c Config,
+}
+
+impl<'c, 'out: 'c, 'prompt: 'c> Context<'c, 'out, 'prompt> {
+    pub(crate) fn new(
+        state: &'c mut State<'out, 'prompt>,
+        rdr: &'c mut RawReader,
+        config: &'c Config,
+    ) -> Context<'c, 'out, 'prompt> {
+        Context { state, rdr, config }
+    }
+
+    /// Currently edited line
+    pub fn line(&self) -> &str {
+        self.state.line.as_str()
+    }
+
+    /// Cursor position (in bytes)
+    pub fn pos(&self) -> usize {
+        self.state.line.pos()
+    }
+
+    /// Replace the edited line and move the cursor to `pos`.
+    pub fn set_line(&mut self, line: &str, pos: usize) {
+        self.state.line.update(line, pos);
+    }
+
+    /// Insert `text` at the cursor and move the cursor after it (as a single
+    /// undo unit).
+    pub fn insert(&mut self, text: &str) {
+        self.state.changes.borrow_mut().begin();
+        self.state.line.insert_bulk(text);
+        self.state.changes.borrow_mut().end();
+    }
+
+    /// Display `menu` under the line (or hide it).
+    pub fn set_menu(&mut self, menu: Option<String>) -> Result<()> {
+        self.state.menu = menu;
+        self.state.refresh_line()
+    }
+
+    /// Wait for the next key pressed.
+    pub fn next_key(&mut self) -> Result<KeyPress> {
+        self.rdr.next_key(true)
+    }
+
+    /// Read a line after `prompt` on the rows below the edited line (like
+    /// "Search pattern: "), then resume editing.
+    ///
+    /// Return `None` when aborted (by Esc, Ctrl-G or Ctrl-C).
+    pub fn read_line(&mut self, prompt: &str) -> Result<Option<String>> {
+        let mut input_state = InputState::new(self.config, Arc::new(RwLock::new(HashMap::new())));
+        self.state
+            .read_input(&mut self.rdr, &mut input_state, prompt)
+    }
+
+    /// Ask the `msg` question (like "really delete 120 rows?") on the rows
+    /// below the edited line and wait for a y/n answer, then resume editing.
+    pub fn confirm(&mut self, msg: &str) -> Result<bool> {
+        self.state.confirm(&mut self.rdr, msg)
+    }
+}
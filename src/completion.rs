@@ -1,11 +1,16 @@
 //! Completion API
-use std::borrow::Cow::{self, Borrowed, Owned};
 use std::fs;
 use std::path::{self, Path};
+use std::result;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
 
-use super::Result;
+use super::{HelperContext, Result};
 use line_buffer::LineBuffer;
-use memchr::memchr;
+#[cfg(test)]
+use quote::{find_unclosed_quote, DEFAULT_BREAK_CHARS};
+pub use quote::{escape, extract_word, extract_word_with, unescape, Quote, QuoteRules};
 
 // TODO: let the implementers choose/find word boudaries ???
 // (line, pos) is like (rl_line_buffer, rl_point) to make contextual completion
@@ -18,6 +23,12 @@ pub trait Candidate {
     fn display(&self) -> &str;
     /// Text to insert in line.
     fn replacement(&self) -> &str;
+    /// Short description (like the summary of a command line option),
+    /// displayed dimmed and right-aligned after the candidate when the
+    /// candidates are listed (one per row).
+    fn description(&self) -> Option<&str> {
+        None
+    }
 }
 
 impl Candidate for String {
@@ -45,6 +56,28 @@ impl Candidate for Pair {
     }
 }
 
+/// Candidate with a description (like the subcommands or the options of a
+/// command line tool: `-p` and `-P`).
+pub struct Described {
+    pub display: String,
+    pub replacement: String,
+    pub description: String,
+}
+
+impl Candidate for Described {
+    fn display(&self) -> &str {
+        self.display.as_str()
+    }
+
+    fn replacement(&self) -> &str {
+        self.replacement.as_str()
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some(self.description.as_str())
+    }
+}
+
 /// To be called for tab-completion.
 pub trait Completer {
     type Candidate: Candidate;
@@ -55,11 +88,83 @@ pub trait Completer {
     ///
     /// ("ls /usr/loc", 11) => Ok((3, vec!["/usr/local/"]))
     fn complete(&self, line: &str, pos: usize) -> Result<(usize, Vec<Self::Candidate>)>;
+    /// Like `complete`, with access to the history and to the completion
+    /// settings (`ctx`): this is the method called by the editor, which
+    /// ignores `ctx` by default.
+    fn complete_with_context(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &HelperContext,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        let _ = ctx;
+        self.complete(line, pos)
+    }
+    /// Like `complete`, but returns the start position and a stream of the
+    /// candidates sent from another thread (like the responses of a language
+    /// server or of a remote database), so that the prompt is not frozen
+    /// while they are fetched: they are listed under the line as they
+    /// arrive, and the completion is cancelled (the stream dropped) when a
+    /// key is pressed.
+    ///
+    /// By default, `None`: the candidates returned by `complete` are used.
+    ///
+    /// ```
+    /// use rustyline::completion::{candidate_stream, CandidateStream, Completer};
+    /// use rustyline::{HelperContext, Result};
+    /// use std::thread;
+    ///
+    /// struct Remote;
+    ///
+    /// impl Completer for Remote {
+    ///     type Candidate = String;
+    ///
+    ///     fn complete(&self, _: &str, _: usize) -> Result<(usize, Vec<String>)> {
+    ///         Ok((0, Vec::new()))
+    ///     }
+    ///
+    ///     fn complete_stream(
+    ///         &self,
+    ///         line: &str,
+    ///         _pos: usize,
+    ///         _ctx: &HelperContext,
+    ///     ) -> Option<(usize, CandidateStream<String>)> {
+    ///         let (sender, stream) = candidate_stream();
+    ///         let word = line.to_owned();
+    ///         thread::spawn(move || {
+    ///             for page in 0..10 {
+    ///                 // fetch the next page of candidates...
+    ///                 if !sender.send(vec![format!("{}{}", word, page)]) {
+    ///                     break; // cancelled
+    ///                 }
+    ///             }
+    ///         });
+    ///         Some((0, stream))
+    ///     }
+    /// }
+    /// ```
+    fn complete_stream(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &HelperContext,
+    ) -> Option<(usize, CandidateStream<Self::Candidate>)> {
+        let _ = (line, pos, ctx);
+        None
+    }
     /// Updates the edited `line` with the `elected` candidate.
     fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
         let end = line.pos();
         line.replace(start..end, elected)
     }
+    /// Returns the documentation (like a function signature) of the selected
+    /// `candidate`, displayed under the line.
+    ///
+    /// Currently, used only with `CompletionType::Circular`.
+    fn candidate_doc(&self, candidate: &Self::Candidate) -> Option<String> {
+        let _ = candidate;
+        None
+    }
 }
 
 impl Completer for () {
@@ -81,9 +186,31 @@ impl<'c, C: ?Sized + Completer> Completer for &'c C {
         (**self).complete(line, pos)
     }
 
+    fn complete_with_context(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &HelperContext,
+    ) -> Result<(usize, Vec<Self::Candidate>)> {
+        (**self).complete_with_context(line, pos, ctx)
+    }
+
+    fn complete_stream(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &HelperContext,
+    ) -> Option<(usize, CandidateStream<Self::Candidate>)> {
+        (**self).complete_stream(line, pos, ctx)
+    }
+
     fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
         (**self).update(line, start, elected)
     }
+
+    fn candidate_doc(&self, candidate: &Self::Candidate) -> Option<String> {
+        (**self).candidate_doc(candidate)
+    }
 }
 macro_rules! box_completer {
     ($($id: ident)*) => {
@@ -94,9 +221,28 @@ macro_rules! box_completer {
                 fn complete(&self, line: &str, pos: usize) -> Result<(usize, Vec<Self::Candidate>)> {
                     (**self).complete(line, pos)
                 }
+                fn complete_with_context(
+                    &self,
+                    line: &str,
+                    pos: usize,
+                    ctx: &HelperContext,
+                ) -> Result<(usize, Vec<Self::Candidate>)> {
+                    (**self).complete_with_context(line, pos, ctx)
+                }
+                fn complete_stream(
+                    &self,
+                    line: &str,
+                    pos: usize,
+                    ctx: &HelperContext,
+                ) -> Option<(usize, CandidateStream<Self::Candidate>)> {
+                    (**self).complete_stream(line, pos, ctx)
+                }
                 fn update(&self, line: &mut LineBuffer, start: usize, elected: &str) {
                     (**self).update(line, start, elected)
                 }
+                fn candidate_doc(&self, candidate: &Self::Candidate) -> Option<String> {
+                    (**self).candidate_doc(candidate)
+                }
             }
         )*
     }
@@ -106,51 +252,79 @@ use std::rc::Rc;
 use std::sync::Arc;
 box_completer! { Box Rc Arc }
 
-/// A `Completer` for file and folder names.
-pub struct FilenameCompleter {
-    break_chars: &'static [u8],
-    double_quotes_special_chars: &'static [u8],
+/// Candidates sent from another thread, returned by
+/// `Completer::complete_stream` (see `candidate_stream`).
+pub struct CandidateStream<C> {
+    batches: Receiver<Vec<C>>,
+    cancelled: Arc<AtomicBool>,
 }
 
-static DOUBLE_QUOTES_ESCAPE_CHAR: Option<char> = Some('\\');
-
-// rl_basic_word_break_characters, rl_completer_word_break_characters
-#[cfg(unix)]
-static DEFAULT_BREAK_CHARS: [u8; 18] = [
-    b' ', b'\t', b'\n', b'"', b'\\', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&',
-    b'{', b'(', b'\0',
-];
-#[cfg(unix)]
-static ESCAPE_CHAR: Option<char> = Some('\\');
-// Remove \ to make file completion works on windows
-#[cfg(windows)]
-static DEFAULT_BREAK_CHARS: [u8; 17] = [
-    b' ', b'\t', b'\n', b'"', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&', b'{',
-    b'(', b'\0',
-];
-#[cfg(windows)]
-static ESCAPE_CHAR: Option<char> = None;
-
-// In double quotes, not all break_chars need to be escaped
-// https://www.gnu.org/software/bash/manual/html_node/Double-Quotes.html
-#[cfg(unix)]
-static DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 4] = [b'"', b'$', b'\\', b'`'];
-#[cfg(windows)]
-static DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 1] = [b'"']; // TODO Validate: only '"' ?
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum Quote {
-    Double,
-    Single,
-    None,
+impl<C> CandidateStream<C> {
+    pub(crate) fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> result::Result<Vec<C>, RecvTimeoutError> {
+        self.batches.recv_timeout(timeout)
+    }
+}
+
+impl<C> Drop for CandidateStream<C> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Sending side of a `CandidateStream`, to be moved to the thread fetching
+/// the candidates: the stream ends when it is dropped.
+pub struct CandidateSender<C> {
+    batches: Sender<Vec<C>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl<C> CandidateSender<C> {
+    /// Send some more `candidates`, returning `false` when the completion
+    /// has been cancelled (the fetching can stop).
+    pub fn send(&self, candidates: Vec<C>) -> bool {
+        !self.is_cancelled() && self.batches.send(candidates).is_ok()
+    }
+
+    /// Tell if the completion has been cancelled (by a key pressed) or is
+    /// over.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Create a stream of candidates and its sender.
+pub fn candidate_stream<C>() -> (CandidateSender<C>, CandidateStream<C>) {
+    let (tx, rx) = mpsc::channel();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    (
+        CandidateSender {
+            batches: tx,
+            cancelled: cancelled.clone(),
+        },
+        CandidateStream {
+            batches: rx,
+            cancelled,
+        },
+    )
+}
+
+/// A `Completer` for file and folder names.
+pub struct FilenameCompleter {
+    rules: QuoteRules,
 }
 
 impl FilenameCompleter {
     pub fn new() -> FilenameCompleter {
-        FilenameCompleter {
-            break_chars: &DEFAULT_BREAK_CHARS,
-            double_quotes_special_chars: &DOUBLE_QUOTES_SPECIAL_CHARS,
-        }
+        Self::with_rules(QuoteRules::default())
+    }
+
+    /// Find the path and quote the candidates with specific `rules` (for
+    /// non-POSIX shells).
+    pub fn with_rules(rules: QuoteRules) -> FilenameCompleter {
+        FilenameCompleter { rules }
     }
 }
 
@@ -164,108 +338,30 @@ impl Completer for FilenameCompleter {
     type Candidate = Pair;
 
     fn complete(&self, line: &str, pos: usize) -> Result<(usize, Vec<Pair>)> {
-        let (start, path, esc_char, break_chars, quote) =
-            if let Some((idx, quote)) = find_unclosed_quote(&line[..pos]) {
-                let start = idx + 1;
-                if quote == Quote::Double {
-                    (
-                        start,
-                        unescape(&line[start..pos], DOUBLE_QUOTES_ESCAPE_CHAR),
-                        DOUBLE_QUOTES_ESCAPE_CHAR,
-                        &self.double_quotes_special_chars,
-                        quote,
-                    )
-                } else {
-                    (
-                        start,
-                        Borrowed(&line[start..pos]),
-                        None,
-                        &self.break_chars,
-                        quote,
-                    )
-                }
-            } else {
-                let (start, path) = extract_word(line, pos, ESCAPE_CHAR, &self.break_chars);
-                let path = unescape(path, ESCAPE_CHAR);
-                (start, path, ESCAPE_CHAR, &self.break_chars, Quote::None)
-            };
-        let matches = try!(filename_complete(&path, esc_char, break_chars, quote));
+        self.complete_with_context(line, pos, &HelperContext::new(None, 0))
+    }
+
+    fn complete_with_context(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &HelperContext,
+    ) -> Result<(usize, Vec<Pair>)> {
+        let rules = self.rules.word_chars(ctx.word_chars());
+        let (start, path, quote) = rules.word_at(line, pos);
+        let ignore_case = ctx.completion_ignore_case();
+        let fuzzy = ctx.fuzzy_completion();
+        let matches = try!(filename_complete(&path, &rules, quote, ignore_case, fuzzy));
         Ok((start, matches))
     }
 }
 
-/// Remove escape char
-pub fn unescape(input: &str, esc_char: Option<char>) -> Cow<str> {
-    if esc_char.is_none() {
-        return Borrowed(input);
-    }
-    let esc_char = esc_char.unwrap();
-    if !input.chars().any(|c| c == esc_char) {
-        return Borrowed(input);
-    }
-    let mut result = String::with_capacity(input.len());
-    let mut chars = input.chars();
-    while let Some(ch) = chars.next() {
-        if ch == esc_char {
-            if let Some(ch) = chars.next() {
-                if cfg!(windows) && ch != '"' {
-                    // TODO Validate: only '"' ?
-                    result.push(esc_char);
-                }
-                result.push(ch);
-            } else if cfg!(windows) {
-                result.push(ch);
-            }
-        } else {
-            result.push(ch);
-        }
-    }
-    Owned(result)
-}
-
-/// Escape any `break_chars` in `input` string with `esc_char`.
-/// For example, '/User Information' becomes '/User\ Information'
-/// when space is a breaking char and '\\' the escape char.
-pub fn escape(
-    mut input: String,
-    esc_char: Option<char>,
-    break_chars: &[u8],
-    quote: Quote,
-) -> String {
-    if quote == Quote::Single {
-        return input; // no escape in single quotes
-    }
-    let n = input
-        .bytes()
-        .filter(|b| memchr(*b, break_chars).is_some())
-        .count();
-    if n == 0 {
-        return input; // no need to escape
-    }
-    if esc_char.is_none() {
-        if cfg!(windows) && quote == Quote::None {
-            input.insert(0, '"'); // force double quote
-            return input;
-        }
-        return input;
-    }
-    let esc_char = esc_char.unwrap();
-    let mut result = String::with_capacity(input.len() + n);
-
-    for c in input.chars() {
-        if c.is_ascii() && memchr(c as u8, break_chars).is_some() {
-            result.push(esc_char);
-        }
-        result.push(c);
-    }
-    result
-}
-
 fn filename_complete(
     path: &str,
-    esc_char: Option<char>,
-    break_chars: &[u8],
+    rules: &QuoteRules,
     quote: Quote,
+    ignore_case: bool,
+    fuzzy: bool,
 ) -> Result<Vec<Pair>> {
     use dirs::home_dir;
     use std::env::current_dir;
@@ -298,11 +394,15 @@ fn filename_complete(
         dir_path.to_path_buf()
     };
 
+    let lowercase_name = file_name.to_lowercase();
     let mut entries: Vec<Pair> = Vec::new();
     for entry in try!(dir.read_dir()) {
         let entry = try!(entry);
         if let Some(s) = entry.file_name().to_str() {
-            if s.starts_with(file_name) {
+            if s.starts_with(file_name)
+                || ignore_case && s.to_lowercase().starts_with(&lowercase_name)
+                || fuzzy && fuzzy_match(file_name, s, ignore_case).is_some()
+            {
                 if let Ok(metadata) = fs::metadata(entry.path()) {
                     let mut path = String::from(dir_name) + s;
                     if metadata.is_dir() {
@@ -310,7 +410,7 @@ fn filename_complete(
                     }
                     entries.push(Pair {
                         display: String::from(s),
-                        replacement: escape(path, esc_char, break_chars, quote),
+                        replacement: rules.requote(path, quote),
                     });
                 } // else ignore PermissionDenied
             }
@@ -319,45 +419,6 @@ fn filename_complete(
     Ok(entries)
 }
 
-/// Given a `line` and a cursor `pos`ition,
-/// try to find backward the start of a word.
-/// Return (0, `line[..pos]`) if no break char has been found.
-/// Return the word and its start position (idx, `line[idx..pos]`) otherwise.
-pub fn extract_word<'l>(
-    line: &'l str,
-    pos: usize,
-    esc_char: Option<char>,
-    break_chars: &[u8],
-) -> (usize, &'l str) {
-    let line = &line[..pos];
-    if line.is_empty() {
-        return (0, line);
-    }
-    let mut start = None;
-    for (i, c) in line.char_indices().rev() {
-        if esc_char.is_some() && start.is_some() {
-            if esc_char.unwrap() == c {
-                // escaped break char
-                start = None;
-                continue;
-            } else {
-                break;
-            }
-        }
-        if c.is_ascii() && memchr(c as u8, break_chars).is_some() {
-            start = Some(i + c.len_utf8());
-            if esc_char.is_none() {
-                break;
-            } // else maybe escaped...
-        }
-    }
-
-    match start {
-        Some(start) => (start, &line[start..]),
-        None => (0, line),
-    }
-}
-
 pub fn longest_common_prefix<C: Candidate>(candidates: &[C]) -> Option<&str> {
     if candidates.is_empty() {
         return None;
@@ -388,62 +449,124 @@ pub fn longest_common_prefix<C: Candidate>(candidates: &[C]) -> Option<&str> {
     Some(&candidate[0..longest_common_prefix])
 }
 
-#[derive(PartialEq)]
-enum ScanMode {
-    DoubleQuote,
-    Escape,
-    EscapeInDoubleQuote,
-    Normal,
-    SingleQuote,
+/// Candidate matched by `fuzzy_match`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// The higher, the better: the consecutive characters and the ones at
+    /// the start of a word score more, the gaps less.
+    pub score: i64,
+    /// Byte positions of the matched characters in the candidate
+    pub indices: Vec<usize>,
 }
 
-/// try to find an unclosed single/double quote in `s`.
-/// Return `None` if no unclosed quote is found.
-/// Return the unclosed quote position and if it is a double quote.
-fn find_unclosed_quote(s: &str) -> Option<(usize, Quote)> {
-    let char_indices = s.char_indices();
-    let mut mode = ScanMode::Normal;
-    let mut quote_index = 0;
-    for (index, char) in char_indices {
-        match mode {
-            ScanMode::DoubleQuote => {
-                if char == '"' {
-                    mode = ScanMode::Normal;
-                } else if char == '\\' {
-                    // both windows and unix support escape in double quote
-                    mode = ScanMode::EscapeInDoubleQuote;
-                }
-            }
-            ScanMode::Escape => {
-                mode = ScanMode::Normal;
-            }
-            ScanMode::EscapeInDoubleQuote => {
-                mode = ScanMode::DoubleQuote;
-            }
-            ScanMode::Normal => {
-                if char == '"' {
-                    mode = ScanMode::DoubleQuote;
-                    quote_index = index;
-                } else if char == '\\' && cfg!(not(windows)) {
-                    mode = ScanMode::Escape;
-                } else if char == '\'' && cfg!(not(windows)) {
-                    mode = ScanMode::SingleQuote;
-                    quote_index = index;
-                }
+/// Match the characters of `pattern` in order in `candidate` (like fzf:
+/// `fb` matches `foo_bar`), without regard to case when `ignore_case` or when
+/// `pattern` has no uppercase character.
+pub fn fuzzy_match(pattern: &str, candidate: &str, ignore_case: bool) -> Option<FuzzyMatch> {
+    let ignore_case = ignore_case || !pattern.chars().any(char::is_uppercase);
+    let fold = |c: char| {
+        if ignore_case {
+            c.to_lowercase().next().unwrap_or(c)
+        } else {
+            c
+        }
+    };
+    let pattern: Vec<char> = pattern.chars().map(&fold).collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            indices: Vec::new(),
+        });
+    }
+    let chars: Vec<(usize, char)> = candidate.char_indices().collect();
+    // end of the first occurrence of the whole pattern...
+    let mut p = 0;
+    let mut end = 0;
+    for (k, &(_, c)) in chars.iter().enumerate() {
+        if fold(c) == pattern[p] {
+            p += 1;
+            if p == pattern.len() {
+                end = k;
+                break;
             }
-            ScanMode::SingleQuote => {
-                if char == '\'' {
-                    mode = ScanMode::Normal;
-                } // no escape in single quotes
+        }
+    }
+    if p < pattern.len() {
+        return None;
+    }
+    // ...and its shortest start
+    let mut begin = end;
+    for (k, &(_, c)) in chars.iter().enumerate().take(end + 1).rev() {
+        if fold(c) == pattern[p - 1] {
+            p -= 1;
+            if p == 0 {
+                begin = k;
+                break;
             }
-        };
+        }
     }
-    if ScanMode::DoubleQuote == mode || ScanMode::EscapeInDoubleQuote == mode {
-        return Some((quote_index, Quote::Double));
-    } else if ScanMode::SingleQuote == mode {
-        return Some((quote_index, Quote::Single));
+    let mut score = 0;
+    let mut indices = Vec::with_capacity(pattern.len());
+    let mut last: Option<usize> = None;
+    for (k, &(i, c)) in chars.iter().enumerate().take(end + 1).skip(begin) {
+        if p == pattern.len() || fold(c) != pattern[p] {
+            continue;
+        }
+        score += 16;
+        // start of a word
+        match k.checked_sub(1).map(|k| chars[k].1) {
+            None => score += 8,
+            Some(prev) if !prev.is_alphanumeric() => score += 8,
+            Some(prev) if prev.is_lowercase() && c.is_uppercase() => score += 7,
+            _ => {}
+        }
+        match last {
+            Some(last) if last + 1 == k => score += 8,
+            Some(last) => score -= 3 + (k - last - 2) as i64, // gap
+            None => {}
+        }
+        last = Some(k);
+        indices.push(i);
+        p += 1;
     }
-    None
+    Some(FuzzyMatch { score, indices })
+}
+
+/// Keep the `candidates` whose replacement fuzzy matches `pattern`, sorted by
+/// score (then by length), with the byte positions of the matched
+/// characters in their display.
+pub(crate) fn fuzzy_sort<C: Candidate>(
+    pattern: &str,
+    candidates: Vec<C>,
+    ignore_case: bool,
+) -> (Vec<C>, Vec<Vec<usize>>) {
+    let mut matched: Vec<(FuzzyMatch, C)> = candidates
+        .into_iter()
+        .filter_map(|c| fuzzy_match(pattern, c.replacement(), ignore_case).map(|m| (m, c)))
+        .collect();
+    // stable: in the order of the completer for the same score
+    matched.sort_by(|a, b| {
+        b.0.score
+            .cmp(&a.0.score)
+            .then(a.1.replacement().len().cmp(&b.1.replacement().len()))
+    });
+    let mut sorted = Vec::with_capacity(matched.len());
+    let mut displayed = Vec::with_capacity(matched.len());
+    for (m, c) in matched {
+        // like a file name at the end of its path
+        let indices = match c.replacement().rfind(c.display()) {
+            Some(offset) => m
+                .indices
+                .into_iter()
+                .filter(|&i| i >= offset && i < offset + c.display().len())
+                .map(|i| i - offset)
+                .collect(),
+            None => Vec::new(),
+        };
+        displayed.push(indices);
+        sorted.push(c);
+    }
+    (sorted, displayed)
 }
 
 #[cfg(test)]
@@ -463,6 +586,21 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn extract_word_with() {
+        use config::WordChars;
+        let break_chars: &[u8] = &super::DEFAULT_BREAK_CHARS;
+        let line = "make CFLAGS=-O2";
+        assert_eq!(
+            (12, "-O2"),
+            super::extract_word_with(line, line.len(), None, break_chars, WordChars::default())
+        );
+        assert_eq!(
+            (5, "CFLAGS=-O2"),
+            super::extract_word_with(line, line.len(), None, break_chars, WordChars::new("="))
+        );
+    }
+
     #[test]
     pub fn unescape() {
         use std::borrow::Cow::{self, Borrowed, Owned};
@@ -530,6 +668,34 @@ mod tests {
         assert_eq!(Some("f"), lcp);
     }
 
+    #[test]
+    pub fn fuzzy_match() {
+        let m = super::fuzzy_match("fb", "foo_bar", false).unwrap();
+        assert_eq!(vec![0, 4], m.indices);
+        assert!(super::fuzzy_match("bf", "foo_bar", false).is_none());
+        assert!(super::fuzzy_match("FB", "foo_bar", false).is_none());
+        assert!(super::fuzzy_match("FB", "foo_bar", true).is_some());
+        assert!(super::fuzzy_match("fb", "FooBar", false).is_some());
+        // shortest occurrence
+        let m = super::fuzzy_match("ab", "a_xab", false).unwrap();
+        assert_eq!(vec![3, 4], m.indices);
+        // consecutive and word starts score more
+        let score = |candidate| super::fuzzy_match("lib", candidate, false).unwrap().score;
+        assert!(score("lib.rs") > score("line_buffer.rs"));
+        assert!(score("my_lib") > score("mylib"));
+        assert!(score("l_i_b") > score("lxxixxb"));
+
+        let candidates = vec![
+            String::from("line_buffer.rs"),
+            String::from("edit.rs"),
+            String::from("lib.rs"),
+        ];
+        let (sorted, indices) = super::fuzzy_sort("lb", candidates, false);
+        // `b` at the start of a word
+        assert_eq!(vec!["line_buffer.rs", "lib.rs"], sorted);
+        assert_eq!(vec![vec![0, 5], vec![0, 2]], indices);
+    }
+
     #[test]
     pub fn find_unclosed_quote() {
         assert_eq!(None, super::find_unclosed_quote("ls /etc"));
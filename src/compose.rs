@@ -0,0 +1,70 @@
+//! Compose key sequences (like `' e` => `é`), for terminals or systems
+//! without a native compose key.
+use std::collections::HashMap;
+
+/// Accents (typed first), base characters and the composed characters
+static ACCENTS: [(char, &str, &str); 7] = [
+    ('\'', "aeiouyAEIOUY", "áéíóúýÁÉÍÓÚÝ"),
+    ('`', "aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+    ('^', "aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+    ('"', "aeiouyAEIOU", "äëïöüÿÄËÏÖÜ"),
+    ('~', "anoANO", "ãñõÃÑÕ"),
+    (',', "cC", "çÇ"),
+    ('o', "aA", "åÅ"),
+];
+
+/// Other sequences
+static SYMBOLS: [(char, char, char); 15] = [
+    ('s', 's', 'ß'),
+    ('a', 'e', 'æ'),
+    ('A', 'E', 'Æ'),
+    ('o', 'e', 'œ'),
+    ('O', 'E', 'Œ'),
+    ('/', 'o', 'ø'),
+    ('/', 'O', 'Ø'),
+    ('o', 'c', '©'),
+    ('o', 'r', '®'),
+    ('<', '<', '«'),
+    ('>', '>', '»'),
+    ('!', '!', '¡'),
+    ('?', '?', '¿'),
+    ('=', 'e', '€'),
+    ('-', 'L', '£'),
+];
+
+/// Sequences known by default
+pub fn default_table() -> HashMap<(char, char), char> {
+    let mut table = HashMap::new();
+    for &(accent, bases, composed) in ACCENTS.iter() {
+        for (base, c) in bases.chars().zip(composed.chars()) {
+            table.insert((accent, base), c);
+        }
+    }
+    for &(first, second, c) in SYMBOLS.iter() {
+        table.insert((first, second), c);
+    }
+    table
+}
+
+/// Character composed from `first` and `second` (typed in any order).
+pub fn compose(table: &HashMap<(char, char), char>, first: char, second: char) -> Option<char> {
+    table
+        .get(&(first, second))
+        .or_else(|| table.get(&(second, first)))
+        .cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{compose, default_table};
+
+    #[test]
+    fn default() {
+        let table = default_table();
+        assert_eq!(Some('é'), compose(&table, '\'', 'e'));
+        assert_eq!(Some('é'), compose(&table, 'e', '\''));
+        assert_eq!(Some('Ñ'), compose(&table, '~', 'N'));
+        assert_eq!(Some('ß'), compose(&table, 's', 's'));
+        assert_eq!(None, compose(&table, '\'', 'x'));
+    }
+}
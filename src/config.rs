@@ -1,5 +1,8 @@
 //! Customize line editor
+use highlight::Theme;
 use std::default::Default;
+use std::env;
+use tokenizer::Tokenizer;
 
 /// User preferences
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -8,13 +11,53 @@ pub struct Config {
     max_history_size: usize, // history_max_entries
     history_duplicates: HistoryDuplicates,
     history_ignore_space: bool,
+    history_format: HistoryFormat,
     completion_type: CompletionType,
+    /// Match the completions without regard to case.
+    completion_ignore_case: bool,
+    /// Match the completions by subsequence, sorted by score.
+    fuzzy_completion: bool,
+    /// List the completions on the first Tab (instead of ringing the bell)
+    /// when they are ambiguous.
+    show_all_if_ambiguous: bool,
     /// When listing completion alternatives, only display
     /// one screen of possibilities at a time.
     completion_prompt_limit: usize,
+    /// Pause with a `--More--` prompt when the list of possible completions
+    /// does not fit on the screen.
+    page_completions: bool,
+    /// Number of screen columns used to display possible completions
+    /// (`None` means the terminal width).
+    completion_display_width: Option<usize>,
+    /// Display completions sorted horizontally (instead of vertically).
+    print_completions_horizontally: bool,
+    /// Append a path separator to directory names when listing completions.
+    mark_directories: bool,
+    /// Idle delay (milliseconds) after which possible completions are
+    /// listed without pressing Tab.
+    auto_menu_delay: Option<usize>,
+    /// Number the first nine candidates of the completion menu, inserted by
+    /// pressing their digit.
+    menu_quick_select: bool,
+    /// Interval (milliseconds) between the calls of `Helper::on_idle` while
+    /// waiting for input.
+    idle_tick: Option<usize>,
     /// Duration (milliseconds) Rustyline will wait for a character when
     /// reading an ambiguous key sequence.
     keyseq_timeout: i32,
+    /// How the bell is rung
+    bell_style: BellStyle,
+    /// Two characters leaving the Vi insert mode (like `jj`)
+    vi_insert_escape: Option<(char, char)>,
+    /// Maximum number of repaints per second while typing.
+    max_refresh_rate: Option<usize>,
+    /// Replace undecodable input with U+FFFD instead of failing.
+    replace_invalid_input: bool,
+    /// Ask the terminal to bracket the pasted text (received as
+    /// `Cmd::Paste`).
+    bracketed_paste: bool,
+    /// Consecutive changes undone at once
+    undo_grouping: UndoGrouping,
     /// Emacs or Vi mode
     edit_mode: EditMode,
     /// If true, each nonblank line returned by `readline` will be
@@ -22,6 +65,29 @@ pub struct Config {
     auto_add_history: bool,
     /// if colors should be enabled.
     color_mode: ColorMode,
+    /// Whether to use stdout or stderr
+    output_stream: OutputStreamType,
+    /// Line editing or lines read as is
+    behavior: Behavior,
+    /// Display width of East Asian ambiguous characters
+    ambiguous_width: AmbiguousWidth,
+    /// Characters considered as word constituents (in addition to
+    /// alphanumerics).
+    word_chars: WordChars,
+    /// Use Unicode word boundaries (UAX #29) for word commands
+    unicode_words: bool,
+    /// Turkish and Azeri mappings of `i` for case commands
+    turkic_case: bool,
+    /// Application syntax whose words are used by word commands
+    tokenizer: Option<Tokenizer>,
+    /// Styles used by the editor itself.
+    theme: Theme,
+    /// Screen-reader friendly display (echo-only updates).
+    screen_reader: bool,
+    /// Displayed before the prompt when a recalled history entry is edited
+    modified_marker: Option<&'static str>,
+    /// Columns reserved before the prompt for `Editor::set_prompt_widget`
+    prompt_widget_width: usize,
 }
 
 impl Config {
@@ -66,22 +132,167 @@ impl Config {
         self.history_ignore_space = yes;
     }
 
+    /// Format of the history file written by `History::save`.
+    ///
+    /// By default, `HistoryFormat::Plain`.
+    pub fn history_format(&self) -> HistoryFormat {
+        self.history_format
+    }
+
+    pub(crate) fn set_history_format(&mut self, format: HistoryFormat) {
+        self.history_format = format;
+    }
+
     pub fn completion_type(&self) -> CompletionType {
         self.completion_type
     }
 
+    /// Tell if the completions are matched without regard to case (by the
+    /// `FilenameCompleter`, and by the completers checking
+    /// `HelperContext::completion_ignore_case`).
+    ///
+    /// By default, they are not.
+    pub fn completion_ignore_case(&self) -> bool {
+        self.completion_ignore_case
+    }
+
+    /// Tell if the candidates are matched with the word to complete by
+    /// subsequence (like fzf: `fb` matches `foo_bar`), sorted by score, with
+    /// the matched characters highlighted when they are listed (see
+    /// `completion::fuzzy_match`).
+    ///
+    /// By default, they are not: the completers return only the candidates
+    /// starting with the word.
+    pub fn fuzzy_completion(&self) -> bool {
+        self.fuzzy_completion
+    }
+
+    /// Tell if ambiguous completions are listed on the first Tab (with
+    /// `CompletionType::List`), instead of ringing the bell and waiting for a
+    /// second Tab.
+    ///
+    /// By default, they are not.
+    pub fn show_all_if_ambiguous(&self) -> bool {
+        self.show_all_if_ambiguous
+    }
+
     pub fn completion_prompt_limit(&self) -> usize {
         self.completion_prompt_limit
     }
 
+    /// Tell if the list of possible completions is paged.
+    ///
+    /// By default, it is paged.
+    pub fn page_completions(&self) -> bool {
+        self.page_completions
+    }
+
+    /// Number of screen columns used to display possible completions.
+    ///
+    /// By default, the terminal width is used.
+    pub fn completion_display_width(&self) -> Option<usize> {
+        self.completion_display_width
+    }
+
+    /// Tell if possible completions are sorted horizontally.
+    ///
+    /// By default, they are sorted vertically.
+    pub fn print_completions_horizontally(&self) -> bool {
+        self.print_completions_horizontally
+    }
+
+    /// Tell if directory names are marked with a path separator when listing
+    /// possible completions.
+    ///
+    /// By default, they are not.
+    pub fn mark_directories(&self) -> bool {
+        self.mark_directories
+    }
+
+    /// Idle delay (milliseconds) after which the possible completions of the
+    /// word at point are listed under the line (without pressing Tab).
+    ///
+    /// By default, they are listed only on demand.
+    pub fn auto_menu_delay(&self) -> Option<usize> {
+        self.auto_menu_delay
+    }
+
+    /// Tell if the first nine candidates of the completion menu are numbered
+    /// and inserted by pressing their digit.
+    ///
+    /// By default, they are not.
+    pub fn menu_quick_select(&self) -> bool {
+        self.menu_quick_select
+    }
+
+    /// Interval (milliseconds) between the calls of `Helper::on_idle` while
+    /// waiting for input.
+    ///
+    /// By default, `Helper::on_idle` is never called.
+    pub fn idle_tick(&self) -> Option<usize> {
+        self.idle_tick
+    }
+
     pub fn keyseq_timeout(&self) -> i32 {
         self.keyseq_timeout
     }
 
+    /// How the bell is rung (on an impossible completion or movement).
+    ///
+    /// By default, `BellStyle::Audible`.
+    pub fn bell_style(&self) -> BellStyle {
+        self.bell_style
+    }
+
+    /// Two characters typed in sequence to leave the Vi insert mode, like
+    /// Esc.
+    ///
+    /// By default, there are none.
+    pub fn vi_insert_escape(&self) -> Option<(char, char)> {
+        self.vi_insert_escape
+    }
+
+    /// Maximum number of repaints per second while typing.
+    ///
+    /// By default, the line is repainted after each key stroke.
+    pub fn max_refresh_rate(&self) -> Option<usize> {
+        self.max_refresh_rate
+    }
+
+    /// Tell if undecodable input is replaced by U+FFFD.
+    ///
+    /// By default, `readline` fails with a decode error.
+    pub fn replace_invalid_input(&self) -> bool {
+        self.replace_invalid_input
+    }
+
+    /// Tell if the terminal is asked to bracket the pasted text, received by
+    /// the editor as one `Cmd::Paste` (Unix only).
+    ///
+    /// By default, it is.
+    pub fn bracketed_paste(&self) -> bool {
+        self.bracketed_paste
+    }
+
+    /// Consecutive insertions (or deletions) of characters undone at once.
+    ///
+    /// By default, the characters of a word are.
+    pub fn undo_grouping(&self) -> UndoGrouping {
+        self.undo_grouping
+    }
+
     pub fn edit_mode(&self) -> EditMode {
         self.edit_mode
     }
 
+    pub(crate) fn set_edit_mode(&mut self, edit_mode: EditMode) {
+        self.edit_mode = edit_mode;
+        self.keyseq_timeout = match edit_mode {
+            EditMode::Emacs => -1, // no timeout
+            EditMode::Vi => 500,
+        };
+    }
+
     /// Tell if lines are automatically added to the history.
     ///
     /// By default, they are not.
@@ -99,6 +310,96 @@ impl Config {
     pub(crate) fn set_color_mode(&mut self, color_mode: ColorMode) {
         self.color_mode = color_mode;
     }
+
+    /// Tell which output stream should be used: stdout or stderr.
+    ///
+    /// By default, stdout is used.
+    pub fn output_stream(&self) -> OutputStreamType {
+        self.output_stream
+    }
+
+    pub(crate) fn set_output_stream(&mut self, stream: OutputStreamType) {
+        self.output_stream = stream;
+    }
+
+    /// Tell if lines are edited (or read as is from stdin).
+    ///
+    /// By default, they are edited only when stdin is a supported terminal.
+    pub fn behavior(&self) -> Behavior {
+        self.behavior
+    }
+
+    pub(crate) fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Tell how East Asian ambiguous characters (like '±' or box drawings)
+    /// are displayed by the terminal.
+    ///
+    /// By default, they are considered narrow.
+    pub fn ambiguous_width(&self) -> AmbiguousWidth {
+        self.ambiguous_width
+    }
+
+    /// Characters, other than alphanumerics, which are considered part of a
+    /// word by word commands (motions, kills, case changes).
+    ///
+    /// By default, there is none.
+    pub fn word_chars(&self) -> WordChars {
+        self.word_chars
+    }
+
+    /// Tell if word commands use Unicode word boundaries (UAX #29).
+    ///
+    /// By default, they don't.
+    pub fn unicode_words(&self) -> bool {
+        self.unicode_words
+    }
+
+    /// Tell if case commands use the Turkish and Azeri mappings of the dotted
+    /// and dotless `i`.
+    ///
+    /// By default, they don't.
+    pub fn turkic_case(&self) -> bool {
+        self.turkic_case
+    }
+
+    /// Syntax whose words (tokens) are used by word commands.
+    ///
+    /// By default, there is none.
+    pub fn tokenizer(&self) -> Option<Tokenizer> {
+        self.tokenizer
+    }
+
+    /// Styles used by the editor itself (hints, completions, search).
+    ///
+    /// By default, there is no style.
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// Tell if the display is screen-reader friendly.
+    ///
+    /// By default, it isn't.
+    pub fn screen_reader(&self) -> bool {
+        self.screen_reader
+    }
+
+    /// Marker displayed before the prompt while a recalled history entry
+    /// differs from the original one.
+    ///
+    /// By default, there is none.
+    pub fn modified_marker(&self) -> Option<&'static str> {
+        self.modified_marker
+    }
+
+    /// Columns reserved before the prompt for the widget (see
+    /// `Editor::set_prompt_widget`).
+    ///
+    /// By default, none: the widget takes the width of its text.
+    pub fn prompt_widget_width(&self) -> usize {
+        self.prompt_widget_width
+    }
 }
 
 impl Default for Config {
@@ -107,12 +408,40 @@ impl Default for Config {
             max_history_size: 100,
             history_duplicates: HistoryDuplicates::IgnoreConsecutive,
             history_ignore_space: false,
+            history_format: HistoryFormat::Plain,
             completion_type: CompletionType::Circular, // TODO Validate
+            completion_ignore_case: false,
+            fuzzy_completion: false,
+            show_all_if_ambiguous: false,
             completion_prompt_limit: 100,
+            page_completions: true,
+            completion_display_width: None,
+            print_completions_horizontally: false,
+            mark_directories: false,
+            auto_menu_delay: None,
+            menu_quick_select: false,
+            idle_tick: None,
             keyseq_timeout: -1,
+            bell_style: BellStyle::Audible,
+            vi_insert_escape: None,
+            max_refresh_rate: None,
+            replace_invalid_input: false,
+            bracketed_paste: true,
+            undo_grouping: UndoGrouping::Word,
             edit_mode: EditMode::Emacs,
             auto_add_history: false,
             color_mode: ColorMode::Enabled,
+            output_stream: OutputStreamType::Stdout,
+            behavior: Behavior::Auto,
+            ambiguous_width: AmbiguousWidth::Narrow,
+            word_chars: WordChars::default(),
+            unicode_words: false,
+            turkic_case: false,
+            tokenizer: None,
+            theme: Theme::default(),
+            screen_reader: false,
+            modified_marker: None,
+            prompt_widget_width: 0,
         }
     }
 }
@@ -124,6 +453,22 @@ pub enum HistoryDuplicates {
     IgnoreConsecutive,
 }
 
+/// Format of the history file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryFormat {
+    /// One line per entry
+    Plain,
+    /// One line per entry with its metadata (time, duration, exit status and
+    /// session), after a `#V2` header line
+    Extended,
+}
+
+impl Default for HistoryFormat {
+    fn default() -> HistoryFormat {
+        HistoryFormat::Plain
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CompletionType {
     /// Complete the next full match (like in Vim by default)
@@ -132,6 +477,21 @@ pub enum CompletionType {
     /// When more than one match, list all matches
     /// (like in Bash/Readline).
     List,
+    /// Complete the first match and display all the matches in a menu under
+    /// the line, where the selection is moved with Tab, Shift-Tab and the
+    /// arrow keys, and accepted with Enter (like zsh's `menu-select`).
+    Menu,
+}
+
+/// Bell style (readline `bell-style`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BellStyle {
+    /// Ring the terminal bell
+    Audible,
+    /// Never ring the bell
+    None,
+    /// Flash the screen (reverse video) instead of ringing the bell
+    Visible,
 }
 
 /// Style of editing / Standard keymaps
@@ -146,9 +506,216 @@ pub enum EditMode {
 pub enum ColorMode {
     Enabled,
     Forced,
+    /// Like `Enabled` but for a terminal without colors: the styles of the
+    /// `Theme` fall back to text attributes (bold, underline, reverse...).
+    /// Monochrome terminals (like `TERM=vt100`) are detected automatically.
+    Monochrome,
     Disabled,
 }
 
+impl ColorMode {
+    /// Resolve `Enabled` according to the
+    /// [`CLICOLOR_FORCE`, `CLICOLOR`](https://bixense.com/clicolors/) and
+    /// [`NO_COLOR`](https://no-color.org/) environment variables (in that
+    /// order of precedence):
+    /// - `CLICOLOR_FORCE` (not `0`) => `Forced`,
+    /// - `NO_COLOR` (not empty) => `Monochrome` (text attributes only),
+    /// - `CLICOLOR=0` => `Disabled`.
+    ///
+    /// Otherwise (and for the other modes), the mode is unchanged: colors are
+    /// then enabled only when stdout is a tty.
+    pub fn resolve(self) -> ColorMode {
+        self.resolve_with(|name| env::var(name).ok())
+    }
+
+    fn resolve_with<F>(self, var: F) -> ColorMode
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        if self != ColorMode::Enabled {
+            return self;
+        }
+        if var("CLICOLOR_FORCE").map_or(false, |v| v != "0") {
+            ColorMode::Forced
+        } else if var("NO_COLOR").map_or(false, |v| !v.is_empty()) {
+            ColorMode::Monochrome
+        } else if var("CLICOLOR").map_or(false, |v| v == "0") {
+            ColorMode::Disabled
+        } else {
+            ColorMode::Enabled
+        }
+    }
+}
+
+/// Should the editor use stdout or stderr
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStreamType {
+    Stderr,
+    Stdout,
+}
+
+/// Interactive (line editing) or non-interactive behavior
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Behavior {
+    /// Edit the lines when stdin is a terminal (with a reduced display on
+    /// unsupported terminals like `TERM=dumb`: no hint, no highlighting and
+    /// a single row scrolled horizontally), read them as is otherwise.
+    Auto,
+    /// Always edit the lines, even on an unsupported terminal (`readline`
+    /// then fails if stdin is not a terminal).
+    Interactive,
+    /// Never edit the lines: they are read from stdin without any prompt and
+    /// returned without their line terminator (`Eof` is returned at the end
+    /// of the input), like when stdin is a file or a pipe.
+    NonInteractive,
+}
+
+/// Display width of East Asian ambiguous characters
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmbiguousWidth {
+    /// Width 2 if the locale is Chinese, Japanese or Korean, 1 otherwise
+    Auto,
+    /// Width 1
+    Narrow,
+    /// Width 2
+    Wide,
+}
+
+/// Consecutive insertions (or deletions) of characters undone at once (the
+/// text inserted or killed by a single command always is)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UndoGrouping {
+    /// Each character is undone alone
+    Char,
+    /// The alphanumeric characters of a word are undone at once
+    Word,
+    /// The characters typed (or deleted) without a pause longer than the
+    /// duration (in milliseconds) are undone at once
+    Pause(usize),
+}
+
+/// Set of ASCII characters considered as word constituents in addition to
+/// alphanumeric characters (like zsh `WORDCHARS`).
+///
+/// Non-ASCII characters are ignored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WordChars(u128);
+
+impl WordChars {
+    pub fn new(chars: &str) -> WordChars {
+        let mut set = 0;
+        for c in chars.chars().filter(|c| c.is_ascii()) {
+            set |= 1 << (c as u32);
+        }
+        WordChars(set)
+    }
+
+    /// Tell if `c` belongs to this set.
+    pub fn contains(&self, c: char) -> bool {
+        c.is_ascii() && self.0 & (1 << (c as u32)) != 0
+    }
+}
+
+/// Per-call overrides of the `Editor` configuration.
+///
+/// Used with `Editor::readline_with` for one-off prompts (e.g. "Enter token:")
+/// without mutating the long-lived configuration of the editor.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReadlineOptions {
+    auto_add_history: Option<bool>,
+    history: Option<bool>,
+    hints: Option<bool>,
+    completion: Option<bool>,
+    edit_mode: Option<EditMode>,
+    secret: Option<bool>,
+}
+
+impl ReadlineOptions {
+    pub fn new() -> ReadlineOptions {
+        ReadlineOptions::default()
+    }
+
+    /// Tell if the accepted line is automatically added to the history.
+    ///
+    /// By default, `Config::auto_add_history` is used.
+    pub fn auto_add_history(mut self, yes: bool) -> ReadlineOptions {
+        self.auto_add_history = Some(yes);
+        self
+    }
+
+    /// Tell if history browsing and searching are allowed.
+    ///
+    /// By default, they are.
+    pub fn history(mut self, yes: bool) -> ReadlineOptions {
+        self.history = Some(yes);
+        self
+    }
+
+    /// Tell if hints are displayed.
+    ///
+    /// By default, they are (when a `Hinter` is registered).
+    pub fn hints(mut self, yes: bool) -> ReadlineOptions {
+        self.hints = Some(yes);
+        self
+    }
+
+    /// Tell if tab-completion is enabled.
+    ///
+    /// By default, it is (when a `Completer` is registered).
+    pub fn completion(mut self, yes: bool) -> ReadlineOptions {
+        self.completion = Some(yes);
+        self
+    }
+
+    /// Choose between Emacs or Vi mode.
+    ///
+    /// By default, `Config::edit_mode` is used.
+    pub fn edit_mode(mut self, edit_mode: EditMode) -> ReadlineOptions {
+        self.edit_mode = Some(edit_mode);
+        self
+    }
+
+    /// Tell if the edited line is hidden (like a password). A secret line is
+    /// also never completed, hinted nor added to the history.
+    ///
+    /// By default, it is not.
+    pub fn secret(mut self, yes: bool) -> ReadlineOptions {
+        self.secret = Some(yes);
+        self
+    }
+
+    pub(crate) fn history_enabled(&self) -> bool {
+        self.history.unwrap_or(true) && !self.secret_enabled()
+    }
+
+    pub(crate) fn hints_enabled(&self) -> bool {
+        self.hints.unwrap_or(true) && !self.secret_enabled()
+    }
+
+    pub(crate) fn completion_enabled(&self) -> bool {
+        self.completion.unwrap_or(true) && !self.secret_enabled()
+    }
+
+    pub(crate) fn secret_enabled(&self) -> bool {
+        self.secret.unwrap_or(false)
+    }
+
+    /// Return a copy of `config` with these overrides applied.
+    pub(crate) fn apply(&self, config: &Config) -> Config {
+        let mut builder = Builder { p: *config };
+        if let Some(edit_mode) = self.edit_mode {
+            builder.set_edit_mode(edit_mode);
+        }
+        if let Some(yes) = self.auto_add_history {
+            builder.set_auto_add_history(yes);
+        }
+        if self.secret_enabled() {
+            builder.set_auto_add_history(false);
+        }
+        builder.build()
+    }
+}
+
 /// Configuration builder
 #[derive(Debug, Default)]
 pub struct Builder {
@@ -186,6 +753,15 @@ impl Builder {
         self
     }
 
+    /// Format of the history file written by `History::save` (both formats
+    /// are read by `History::load`).
+    ///
+    /// By default, `HistoryFormat::Plain`.
+    pub fn history_format(mut self, format: HistoryFormat) -> Builder {
+        self.set_history_format(format);
+        self
+    }
+
     /// Set `completion_type`.
     pub fn completion_type(mut self, completion_type: CompletionType) -> Builder {
         self.set_completion_type(completion_type);
@@ -199,9 +775,101 @@ impl Builder {
         self
     }
 
+    /// Pause with a `--More--` prompt when the list of possible completions
+    /// does not fit on the screen.
+    ///
+    /// By default, it is paged.
+    pub fn page_completions(mut self, yes: bool) -> Builder {
+        self.set_page_completions(yes);
+        self
+    }
+
+    /// Number of screen columns used to display possible completions.
+    /// `Some(0)` means one completion per line.
+    ///
+    /// By default, the terminal width is used.
+    pub fn completion_display_width(mut self, width: Option<usize>) -> Builder {
+        self.set_completion_display_width(width);
+        self
+    }
+
+    /// Display possible completions sorted horizontally (instead of
+    /// vertically).
+    ///
+    /// By default, they are sorted vertically.
+    pub fn print_completions_horizontally(mut self, yes: bool) -> Builder {
+        self.set_print_completions_horizontally(yes);
+        self
+    }
+
+    /// Match the completions without regard to case.
+    ///
+    /// By default, the case matters.
+    pub fn completion_ignore_case(mut self, yes: bool) -> Builder {
+        self.set_completion_ignore_case(yes);
+        self
+    }
+
+    /// Match the completions by subsequence (like fzf), sorted by score.
+    ///
+    /// By default, only the candidates starting with the word are completed.
+    pub fn fuzzy_completion(mut self, yes: bool) -> Builder {
+        self.set_fuzzy_completion(yes);
+        self
+    }
+
+    /// List ambiguous completions on the first Tab (with
+    /// `CompletionType::List`).
+    ///
+    /// By default, a second Tab is needed.
+    pub fn show_all_if_ambiguous(mut self, yes: bool) -> Builder {
+        self.set_show_all_if_ambiguous(yes);
+        self
+    }
+
+    /// Append a path separator to directory names when listing possible
+    /// completions.
+    ///
+    /// By default, they are not marked.
+    pub fn mark_directories(mut self, yes: bool) -> Builder {
+        self.set_mark_directories(yes);
+        self
+    }
+
+    /// List the possible completions of the word at point under the line
+    /// when no key has been pressed for `delay_ms` milliseconds, and refresh
+    /// the list as typing continues.
+    ///
+    /// By default, they are listed only on demand.
+    pub fn auto_menu_delay(mut self, delay_ms: Option<usize>) -> Builder {
+        self.set_auto_menu_delay(delay_ms);
+        self
+    }
+
+    /// Number the first nine candidates of the completion menu (see
+    /// `auto_menu_delay`): pressing `1` to `9` inserts the matching candidate
+    /// instead of the digit.
+    ///
+    /// By default, digits are inserted as typed.
+    pub fn menu_quick_select(mut self, yes: bool) -> Builder {
+        self.set_menu_quick_select(yes);
+        self
+    }
+
+    /// Call `Helper::on_idle` every `tick_ms` milliseconds while waiting for
+    /// input (to animate a spinner, to display a "connection lost" banner
+    /// or to expire a hint).
+    ///
+    /// By default, `Helper::on_idle` is never called.
+    pub fn idle_tick(mut self, tick_ms: Option<usize>) -> Builder {
+        self.set_idle_tick(tick_ms);
+        self
+    }
+
     /// Timeout for ambiguous key sequences in milliseconds.
     /// Currently, it is used only to distinguish a single ESC from an ESC
-    /// sequence.
+    /// sequence (or the first character of `vi_insert_escape` from the
+    /// sequence).
     /// After seeing an ESC key, wait at most `keyseq_timeout_ms` for another
     /// byte.
     pub fn keyseq_timeout(mut self, keyseq_timeout_ms: i32) -> Builder {
@@ -209,6 +877,63 @@ impl Builder {
         self
     }
 
+    /// How the bell is rung.
+    ///
+    /// By default, `BellStyle::Audible`.
+    pub fn bell_style(mut self, bell_style: BellStyle) -> Builder {
+        self.set_bell_style(bell_style);
+        self
+    }
+
+    /// Leave the Vi insert mode when `first` then `second` are typed (like
+    /// `('j', 'j')`).
+    ///
+    /// `first` is inserted only when it is not followed by `second` within
+    /// `keyseq_timeout`.
+    pub fn vi_insert_escape(mut self, escape: Option<(char, char)>) -> Builder {
+        self.set_vi_insert_escape(escape);
+        self
+    }
+
+    /// Repaint the line at most `rate` times per second while typing
+    /// (useful on slow or high-latency terminals).
+    /// Keys received in between are batched and the final state is always
+    /// displayed.
+    ///
+    /// By default, the line is repainted after each key stroke.
+    pub fn max_refresh_rate(mut self, rate: Option<usize>) -> Builder {
+        self.set_max_refresh_rate(rate);
+        self
+    }
+
+    /// Replace undecodable input (invalid UTF-8 or UTF-16) with U+FFFD and
+    /// keep editing, instead of aborting the whole `readline` call.
+    ///
+    /// By default, `readline` fails with a decode error.
+    pub fn replace_invalid_input(mut self, yes: bool) -> Builder {
+        self.set_replace_invalid_input(yes);
+        self
+    }
+
+    /// Ask the terminal to bracket the pasted text, so that it is inserted
+    /// at once (given to the `PasteHandler` of the editor) instead of being
+    /// interpreted as typed keys (Unix only).
+    ///
+    /// By default, it is.
+    pub fn bracketed_paste(mut self, yes: bool) -> Builder {
+        self.set_bracketed_paste(yes);
+        self
+    }
+
+    /// Choose the consecutive insertions (or deletions) of characters undone
+    /// (and redone) at once.
+    ///
+    /// By default, the characters of a word are.
+    pub fn undo_grouping(mut self, grouping: UndoGrouping) -> Builder {
+        self.set_undo_grouping(grouping);
+        self
+    }
+
     /// Choose between Emacs or Vi mode.
     pub fn edit_mode(mut self, edit_mode: EditMode) -> Builder {
         self.set_edit_mode(edit_mode);
@@ -231,6 +956,111 @@ impl Builder {
         self
     }
 
+    /// Whether to use stdout or stderr (to keep stdout for the program
+    /// output, when it is piped).
+    ///
+    /// By default, stdout is used.
+    pub fn output_stream(mut self, stream: OutputStreamType) -> Builder {
+        self.set_output_stream(stream);
+        self
+    }
+
+    /// Force the interactive (line editing) or non-interactive behavior,
+    /// regardless of stdin being a terminal (for test harnesses or
+    /// `tool < script.txt`).
+    ///
+    /// By default, the behavior is detected.
+    pub fn behavior(mut self, behavior: Behavior) -> Builder {
+        self.set_behavior(behavior);
+        self
+    }
+
+    /// Tell how East Asian ambiguous characters (like '±' or box drawings)
+    /// are displayed by the terminal, so that cursor positioning stays in
+    /// sync.
+    ///
+    /// By default, they are considered narrow.
+    pub fn ambiguous_width(mut self, ambiguous_width: AmbiguousWidth) -> Builder {
+        self.set_ambiguous_width(ambiguous_width);
+        self
+    }
+
+    /// Characters, other than alphanumerics, which are considered part of a
+    /// word by word commands (e.g. `"-_./"`).
+    ///
+    /// By default, there is none.
+    pub fn word_chars(mut self, chars: &str) -> Builder {
+        self.set_word_chars(chars);
+        self
+    }
+
+    /// Use Unicode word boundaries (UAX #29) for word motions and kills
+    /// (better with CJK and mixed-script text).
+    ///
+    /// By default, a word is a sequence of alphanumeric characters.
+    pub fn unicode_words(mut self, yes: bool) -> Builder {
+        self.set_unicode_words(yes);
+        self
+    }
+
+    /// Uppercase `i` to `İ` and lowercase `I` to `ı` (Turkish and Azeri) in
+    /// case commands.
+    ///
+    /// By default, the language-independent mappings are used.
+    pub fn turkic_case(mut self, yes: bool) -> Builder {
+        self.set_turkic_case(yes);
+        self
+    }
+
+    /// Use the words (tokens) of the application syntax for word motions
+    /// and kills (so that a quoted string is a single word).
+    ///
+    /// By default, there is none.
+    pub fn tokenizer(mut self, tokenizer: Option<Tokenizer>) -> Builder {
+        self.set_tokenizer(tokenizer);
+        self
+    }
+
+    /// Styles used by the editor itself (hints, completions, search).
+    ///
+    /// By default, there is no style.
+    pub fn theme(mut self, theme: Theme) -> Builder {
+        self.set_theme(theme);
+        self
+    }
+
+    /// Minimize the cursor movements and repaints so that screen readers and
+    /// braille displays can track the edited line: typed or deleted
+    /// characters at the end of the line are only echoed (or erased with
+    /// backspaces), hints are not displayed and the line is repainted only
+    /// when it is edited elsewhere.
+    ///
+    /// By default, the line is repainted after each modification.
+    pub fn screen_reader(mut self, yes: bool) -> Builder {
+        self.set_screen_reader(yes);
+        self
+    }
+
+    /// Display `marker` (like `"*"`) before the prompt while a recalled
+    /// history entry has been edited: the edits are kept until the line is
+    /// accepted, unless `Cmd::RevertLine` is used to restore the entry.
+    ///
+    /// By default, there is no marker.
+    pub fn modified_marker(mut self, marker: Option<&'static str>) -> Builder {
+        self.set_modified_marker(marker);
+        self
+    }
+
+    /// Reserve `width` columns before the prompt for the widget (see
+    /// `Editor::set_prompt_widget`): its text is truncated or padded so
+    /// that the prompt does not move.
+    ///
+    /// By default (0), the widget takes the width of its text.
+    pub fn prompt_widget_width(mut self, width: usize) -> Builder {
+        self.set_prompt_widget_width(width);
+        self
+    }
+
     pub fn build(self) -> Config {
         self.p
     }
@@ -265,6 +1095,14 @@ pub trait Configurer {
     fn set_history_ignore_space(&mut self, yes: bool) {
         self.config_mut().set_history_ignore_space(yes);
     }
+
+    /// Format of the history file written by `History::save` (both formats
+    /// are read by `History::load`).
+    ///
+    /// By default, `HistoryFormat::Plain`.
+    fn set_history_format(&mut self, format: HistoryFormat) {
+        self.config_mut().set_history_format(format);
+    }
     /// Set `completion_type`.
     fn set_completion_type(&mut self, completion_type: CompletionType) {
         self.config_mut().completion_type = completion_type;
@@ -276,18 +1114,101 @@ pub trait Configurer {
         self.config_mut().completion_prompt_limit = completion_prompt_limit;
     }
 
+    /// Pause with a `--More--` prompt when the list of possible completions
+    /// does not fit on the screen.
+    fn set_page_completions(&mut self, yes: bool) {
+        self.config_mut().page_completions = yes;
+    }
+
+    /// Number of screen columns used to display possible completions.
+    fn set_completion_display_width(&mut self, width: Option<usize>) {
+        self.config_mut().completion_display_width = width;
+    }
+
+    /// Display possible completions sorted horizontally.
+    fn set_print_completions_horizontally(&mut self, yes: bool) {
+        self.config_mut().print_completions_horizontally = yes;
+    }
+
+    /// Match the completions without regard to case.
+    fn set_completion_ignore_case(&mut self, yes: bool) {
+        self.config_mut().completion_ignore_case = yes;
+    }
+
+    /// Match the completions by subsequence, sorted by score.
+    fn set_fuzzy_completion(&mut self, yes: bool) {
+        self.config_mut().fuzzy_completion = yes;
+    }
+
+    /// List ambiguous completions on the first Tab.
+    fn set_show_all_if_ambiguous(&mut self, yes: bool) {
+        self.config_mut().show_all_if_ambiguous = yes;
+    }
+
+    /// Append a path separator to directory names when listing possible
+    /// completions.
+    fn set_mark_directories(&mut self, yes: bool) {
+        self.config_mut().mark_directories = yes;
+    }
+
+    /// Idle delay (milliseconds) after which possible completions are
+    /// listed automatically.
+    fn set_auto_menu_delay(&mut self, delay_ms: Option<usize>) {
+        self.config_mut().auto_menu_delay = delay_ms;
+    }
+
+    /// Number the first nine candidates of the completion menu.
+    fn set_menu_quick_select(&mut self, yes: bool) {
+        self.config_mut().menu_quick_select = yes;
+    }
+
+    /// Interval (milliseconds) between the calls of `Helper::on_idle`.
+    fn set_idle_tick(&mut self, tick_ms: Option<usize>) {
+        self.config_mut().idle_tick = tick_ms;
+    }
+
     /// Timeout for ambiguous key sequences in milliseconds.
     fn set_keyseq_timeout(&mut self, keyseq_timeout_ms: i32) {
         self.config_mut().keyseq_timeout = keyseq_timeout_ms;
     }
 
+    /// How the bell is rung.
+    fn set_bell_style(&mut self, bell_style: BellStyle) {
+        self.config_mut().bell_style = bell_style;
+    }
+
+    /// Two characters leaving the Vi insert mode.
+    fn set_vi_insert_escape(&mut self, escape: Option<(char, char)>) {
+        self.config_mut().vi_insert_escape = escape;
+    }
+
+    /// Maximum number of repaints per second while typing.
+    fn set_max_refresh_rate(&mut self, rate: Option<usize>) {
+        self.config_mut().max_refresh_rate = rate;
+    }
+
+    /// Replace undecodable input with U+FFFD instead of failing.
+    fn set_replace_invalid_input(&mut self, yes: bool) {
+        self.config_mut().replace_invalid_input = yes;
+    }
+
+    /// Ask the terminal to bracket the pasted text.
+    fn set_bracketed_paste(&mut self, yes: bool) {
+        self.config_mut().bracketed_paste = yes;
+    }
+
+    /// Consecutive changes undone at once.
+    fn set_undo_grouping(&mut self, grouping: UndoGrouping) {
+        self.config_mut().undo_grouping = grouping;
+    }
+
     /// Choose between Emacs or Vi mode.
+    ///
+    /// On an `Editor`, it can be changed between two `readline` calls (the
+    /// history and kill ring are kept), or while editing with
+    /// `Cmd::SetEditMode`.
     fn set_edit_mode(&mut self, edit_mode: EditMode) {
-        self.config_mut().edit_mode = edit_mode;
-        match edit_mode {
-            EditMode::Emacs => self.set_keyseq_timeout(-1), // no timeout
-            EditMode::Vi => self.set_keyseq_timeout(500),
-        }
+        self.config_mut().set_edit_mode(edit_mode);
     }
 
     /// Tell if lines are automatically added to the history.
@@ -303,4 +1224,96 @@ pub trait Configurer {
     fn set_color_mode(&mut self, color_mode: ColorMode) {
         self.config_mut().set_color_mode(color_mode);
     }
+
+    /// Whether to use stdout or stderr.
+    ///
+    /// By default, stdout is used.
+    fn set_output_stream(&mut self, stream: OutputStreamType) {
+        self.config_mut().set_output_stream(stream);
+    }
+
+    /// Force the interactive or non-interactive behavior.
+    ///
+    /// By default, the behavior is detected.
+    fn set_behavior(&mut self, behavior: Behavior) {
+        self.config_mut().behavior = behavior;
+    }
+
+    /// Tell how East Asian ambiguous characters are displayed by the terminal.
+    fn set_ambiguous_width(&mut self, ambiguous_width: AmbiguousWidth) {
+        self.config_mut().ambiguous_width = ambiguous_width;
+    }
+
+    /// Characters, other than alphanumerics, which are considered part of a
+    /// word by word commands.
+    ///
+    /// By default, there is none.
+    fn set_word_chars(&mut self, chars: &str) {
+        self.config_mut().word_chars = WordChars::new(chars);
+    }
+
+    /// Use Unicode word boundaries (UAX #29) for word commands.
+    fn set_unicode_words(&mut self, yes: bool) {
+        self.config_mut().unicode_words = yes;
+    }
+
+    /// Use the Turkish and Azeri mappings of `i` for case commands.
+    fn set_turkic_case(&mut self, yes: bool) {
+        self.config_mut().turkic_case = yes;
+    }
+
+    /// Use the words of the application syntax for word commands.
+    fn set_tokenizer(&mut self, tokenizer: Option<Tokenizer>) {
+        self.config_mut().tokenizer = tokenizer;
+    }
+
+    /// Styles used by the editor itself (hints, completions, search).
+    fn set_theme(&mut self, theme: Theme) {
+        self.config_mut().theme = theme;
+    }
+
+    /// Screen-reader friendly display (echo-only updates).
+    fn set_screen_reader(&mut self, yes: bool) {
+        self.config_mut().screen_reader = yes;
+    }
+
+    /// Marker displayed before the prompt of an edited history entry.
+    fn set_modified_marker(&mut self, marker: Option<&'static str>) {
+        self.config_mut().modified_marker = marker;
+    }
+
+    /// Columns reserved before the prompt for the widget.
+    fn set_prompt_widget_width(&mut self, width: usize) {
+        self.config_mut().prompt_widget_width = width;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ColorMode;
+
+    #[test]
+    fn resolve_color_mode() {
+        let resolve = |vars: &[(&str, &str)]| {
+            ColorMode::Enabled.resolve_with(|name| {
+                vars.iter()
+                    .find(|&&(n, _)| n == name)
+                    .map(|&(_, v)| v.to_owned())
+            })
+        };
+        assert_eq!(ColorMode::Enabled, resolve(&[]));
+        assert_eq!(ColorMode::Monochrome, resolve(&[("NO_COLOR", "1")]));
+        assert_eq!(ColorMode::Enabled, resolve(&[("NO_COLOR", "")]));
+        assert_eq!(ColorMode::Disabled, resolve(&[("CLICOLOR", "0")]));
+        assert_eq!(ColorMode::Enabled, resolve(&[("CLICOLOR", "1")]));
+        assert_eq!(
+            ColorMode::Forced,
+            resolve(&[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")])
+        );
+        assert_eq!(ColorMode::Enabled, resolve(&[("CLICOLOR_FORCE", "0")]));
+        assert_eq!(
+            ColorMode::Disabled,
+            ColorMode::Disabled.resolve_with(|_| Some("1".to_owned()))
+        );
+    }
 }
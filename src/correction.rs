@@ -0,0 +1,79 @@
+//! "Did you mean" correction of the first word of an accepted line.
+use std::cmp;
+
+/// Spell-correction provider
+pub trait Corrector {
+    /// Tells if the first `word` of the accepted line is unknown (and should
+    /// be corrected).
+    fn is_unknown(&self, word: &str) -> bool;
+    /// Known words the corrections are chosen from (in addition to the first
+    /// words of the history entries).
+    fn words(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Levenshtein distance between `a` and `b` (in chars).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + if ca == *cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = cmp::min(substitution, cmp::min(row[j], row[j + 1]) + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Return at most `max` `candidates` close to `word` (nearest first).
+///
+/// A candidate is close when at most a third of its chars (at least one)
+/// must be edited.
+pub fn suggestions<'c, I>(word: &str, candidates: I, max: usize) -> Vec<String>
+where
+    I: IntoIterator<Item = &'c str>,
+{
+    let limit = cmp::max(1, word.chars().count() / 3);
+    let mut found: Vec<(usize, &str)> = Vec::new();
+    for candidate in candidates {
+        if candidate == word || found.iter().any(|&(_, c)| c == candidate) {
+            continue;
+        }
+        let distance = edit_distance(word, candidate);
+        if distance <= limit {
+            found.push((distance, candidate));
+        }
+    }
+    found.sort_by_key(|&(distance, _)| distance);
+    found
+        .into_iter()
+        .take(max)
+        .map(|(_, candidate)| candidate.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    pub fn edit_distance() {
+        assert_eq!(0, super::edit_distance("git", "git"));
+        assert_eq!(2, super::edit_distance("gti", "git"));
+        assert_eq!(1, super::edit_distance("gi", "git"));
+        assert_eq!(3, super::edit_distance("", "git"));
+        assert_eq!(3, super::edit_distance("kitten", "sitting"));
+    }
+
+    #[test]
+    pub fn suggestions() {
+        let words = ["git", "grep", "gist", "ls", "git"];
+        assert_eq!(
+            vec!["git".to_owned(), "gist".to_owned()],
+            super::suggestions("gitt", words.iter().cloned(), 5)
+        );
+        assert!(super::suggestions("cargo", words.iter().cloned(), 5).is_empty());
+    }
+}
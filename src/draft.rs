@@ -0,0 +1,82 @@
+//! Draft of the edited line, saved in a file while editing so that it can be
+//! restored after a crash (see `Editor::set_draft_file`).
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+
+use history::create_private;
+
+pub(crate) struct Draft {
+    path: PathBuf,
+    /// Content of the file
+    saved: String,
+    /// `true` when the file must survive the end of the current edit
+    kept: bool,
+}
+
+impl Draft {
+    pub fn new(path: PathBuf) -> Draft {
+        Draft {
+            path,
+            saved: String::new(),
+            kept: false,
+        }
+    }
+
+    /// Draft left by a previous process, if any.
+    pub fn load(&mut self) -> Option<String> {
+        let mut draft = String::new();
+        match File::open(&self.path).and_then(|mut file| file.read_to_string(&mut draft)) {
+            Ok(_) if !draft.is_empty() => {
+                self.saved = draft.clone();
+                Some(draft)
+            }
+            _ => None,
+        }
+    }
+
+    /// Tell if `line` has not been saved yet.
+    pub fn is_stale(&self, line: &str) -> bool {
+        self.saved != line
+    }
+
+    /// Save `line` (an empty line removes the file).
+    pub fn save(&mut self, line: &str) -> io::Result<()> {
+        if !self.is_stale(line) {
+            return Ok(());
+        }
+        if line.is_empty() {
+            return self.clear();
+        }
+        let mut file = try!(create_private(&self.path));
+        try!(file.write_all(line.as_bytes()));
+        self.saved = line.to_owned();
+        Ok(())
+    }
+
+    /// Save `line` and keep it when the edit ends (like when the terminal
+    /// has been detached).
+    pub fn keep(&mut self, line: &str) -> io::Result<()> {
+        self.kept = true;
+        self.save(line)
+    }
+
+    /// Remove the file at the end of an edit (accepted, interrupted or
+    /// ended), unless it must be kept.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.kept {
+            self.kept = false;
+            Ok(())
+        } else {
+            self.clear()
+        }
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.saved.clear();
+        match fs::remove_file(&self.path) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            rc => rc,
+        }
+    }
+}
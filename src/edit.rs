@@ -1,20 +1,31 @@
 //! Command processor
 
 use std::cell::RefCell;
-use std::fmt;
+use std::cmp;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::ops::Range;
 use std::rc::Rc;
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthChar;
 
 use super::Result;
-use highlight::Highlighter;
+use compose;
+use config::BellStyle;
+use highlight::{Highlighter, Selected};
 use hint::Hinter;
 use history::{Direction, History};
 use keymap::{Anchor, At, CharSearch, Cmd, Movement, RepeatCount, Word};
 use keymap::{InputState, Refresher};
+use keys::KeyPress;
 use line_buffer::{LineBuffer, WordAction, MAX_LINE};
-use tty::{Position, RawReader, Renderer};
+use prompt::PromptTemplate;
+use trace::{Trace, Traced};
+use tty::{self, char_width, str_width, Position, RawReader, Renderer};
 use undo::Changeset;
+use HelperContext;
+
+/// Called with the line and the cursor position after its modifications
+pub type OnEdit<'h> = Fn(&str, usize) + 'h;
 
 /// Represent the state during line editing.
 /// Implement rendering.
@@ -28,11 +39,34 @@ pub struct State<'out, 'prompt> {
     pub old_rows: usize, // Number of rows used so far (from start of prompt to end of input)
     history_index: usize, // The history index we are currently editing
     saved_line_for_history: LineBuffer, // Current edited line before history browsing
+    edited_entries: HashMap<usize, String>, // Edits of the recalled history entries
     byte_buffer: [u8; 4],
     pub changes: Rc<RefCell<Changeset>>, // changes to line, for undo/redo
     pub hinter: Option<&'out Hinter>,
     pub highlighter: Option<&'out Highlighter>,
-    no_hint: bool, // `false` if an hint has been displayed
+    no_hint: bool,                // `false` if an hint has been displayed
+    pub selection: Option<usize>, // Anchor of the selected region
+    pub selection_style: Option<&'static str>,
+    pub search_match: Option<Range<usize>>, // Text matched by the incremental search
+    pub search_match_style: Option<&'static str>,
+    pub menu: Option<String>, // Rows displayed under the line (possible completions)
+    pub screen_reader: bool,  // Echo-only updates at the end of the line
+    pub bell: Option<&'out Fn()>, // Called instead of ringing the terminal bell
+    pub bell_style: BellStyle, // None, audible or visible
+    pub completion_ignore_case: bool, // Given to the completer
+    pub fuzzy_completion: bool, // Given to the completer
+    pub hint_markers: Option<(&'static str, &'static str)>, // Around unstyled hints
+    pub history: Option<&'out History>, // Given to the completer and hinter
+    pub secret: bool,         // Line not displayed (password)
+    pub prompt_template: Option<&'out PromptTemplate>, // Rendered on each repaint
+    rendered_prompt: Option<String>, // Last rendering of `prompt_template`
+    pub modified_marker: Option<&'static str>, // Before the prompt of an edited history entry
+    marked: bool,             // `true` if the marker is displayed
+    pub widget: Option<(usize, &'out Fn() -> String)>, // Before the prompt, with its reserved width
+    pub trace: Option<&'out Trace>, // Records keys, commands and refresh decisions
+    other_prompt: bool,       // `true` if another prompt is displayed (search, argument)
+    pub on_edit: Option<&'out OnEdit<'out>>, // `Helper::update_after_edit`
+    edit_revision: Option<usize>, // Line revision given to `on_edit`
 }
 
 impl<'out, 'prompt> State<'out, 'prompt> {
@@ -50,15 +84,39 @@ impl<'out, 'prompt> State<'out, 'prompt> {
             prompt,
             prompt_size,
             line: LineBuffer::with_capacity(capacity),
-            cursor: prompt_size,
+            // the prompt (which may wrap) is displayed by the first refresh
+            cursor: Position::default(),
             old_rows: 0,
             history_index,
             saved_line_for_history: LineBuffer::with_capacity(capacity),
+            edited_entries: HashMap::new(),
             byte_buffer: [0; 4],
             changes: Rc::new(RefCell::new(Changeset::new())),
             hinter,
             highlighter,
             no_hint: true,
+            selection: None,
+            selection_style: None,
+            search_match: None,
+            search_match_style: None,
+            menu: None,
+            screen_reader: false,
+            bell: None,
+            bell_style: BellStyle::Audible,
+            completion_ignore_case: false,
+            fuzzy_completion: false,
+            hint_markers: None,
+            history: None,
+            secret: false,
+            prompt_template: None,
+            rendered_prompt: None,
+            modified_marker: None,
+            marked: false,
+            widget: None,
+            trace: None,
+            other_prompt: false,
+            on_edit: None,
+            edit_revision: None,
         }
     }
 
@@ -69,15 +127,37 @@ impl<'out, 'prompt> State<'out, 'prompt> {
         single_esc_abort: bool,
     ) -> Result<Cmd> {
         loop {
-            let rc = input_state.next_cmd(rdr, self, single_esc_abort);
+            let rc = match self.trace {
+                Some(trace) => {
+                    let mut rdr = Traced {
+                        rdr,
+                        trace,
+                        secret: self.secret,
+                    };
+                    input_state.next_cmd(&mut rdr, self, single_esc_abort)
+                }
+                None => input_state.next_cmd(rdr, self, single_esc_abort),
+            };
+            if tty::take_forced_refresh() {
+                // requested by `force_refresh`
+                try!(self.refresh_line());
+                if rc.is_err() {
+                    continue;
+                }
+            }
             if rc.is_err() && self.out.sigwinch() {
                 self.out.update_size();
+                // the prompt may wrap differently
+                self.update_prompt_size();
                 try!(self.refresh_line());
                 continue;
             }
             if let Ok(Cmd::Replace(_, _)) = rc {
                 self.changes.borrow_mut().begin();
             }
+            if let (Some(trace), Ok(ref cmd)) = (self.trace, &rc) {
+                trace.cmd(cmd, self.secret);
+            }
             return rc;
         }
     }
@@ -94,48 +174,300 @@ impl<'out, 'prompt> State<'out, 'prompt> {
         );
     }
 
+    /// Position of the cursor, computed from the memoized grapheme widths
+    /// instead of segmenting the line again.
+    fn cursor_position(&self) -> Position {
+        if self.secret {
+            return self.prompt_size;
+        }
+        let cols = self.out.get_columns();
+        self.line.position(self.line.pos(), self.prompt_size, cols)
+    }
+
     pub fn move_cursor(&mut self) -> Result<()> {
         // calculate the desired position of the cursor
-        let cursor = self
-            .out
-            .calculate_position(&self.line[..self.line.pos()], self.prompt_size);
+        let cursor = self.cursor_position();
+        if self.out.is_dumb() {
+            // the cursor can only be moved by repainting the line
+            return self.refresh_line();
+        }
+        if !self.out.is_row_visible(cursor.row) {
+            // scroll the rows of a line taller than the screen
+            return self.refresh_line();
+        }
         if self.cursor == cursor {
             return Ok(());
         }
-        if self.highlighter.map_or(false, |h| {
-            self.line
-                .grapheme_at_cursor()
-                .map_or(false, |s| h.highlight_char(s))
-        }) {
-            let prompt_size = self.prompt_size;
-            try!(self.refresh(self.prompt, prompt_size, None));
+        if !self.screen_reader
+            && self.highlighter.map_or(false, |h| {
+                self.line
+                    .grapheme_at_cursor()
+                    .map_or(false, |s| h.highlight_char(s))
+            })
+        {
+            try!(self.refresh_current(None));
         } else {
+            self.trace_refresh("move");
             try!(self.out.move_cursor(self.cursor, cursor));
         }
         self.cursor = cursor;
         Ok(())
     }
 
+    /// Repaint after the current prompt (the last rendering of the template,
+    /// if any).
+    fn refresh_current(&mut self, hint: Option<String>) -> Result<()> {
+        self.other_prompt = false;
+        let marker_changed = self.marker_changed();
+        if marker_changed {
+            self.marked = !self.marked;
+        }
+        if let Some(prefix) = self.prompt_prefix() {
+            let prompt = format!(
+                "{}{}",
+                prefix,
+                self.rendered_prompt
+                    .as_ref()
+                    .map_or(self.prompt, String::as_str)
+            );
+            // the widget width may change
+            self.prompt_size = self.out.calculate_position(&prompt, Position::default());
+            let prompt_size = self.prompt_size;
+            return self.refresh(&prompt, prompt_size, hint);
+        }
+        if marker_changed {
+            self.update_prompt_size();
+        }
+        let prompt_size = self.prompt_size;
+        match self.rendered_prompt.take() {
+            Some(prompt) => {
+                let rc = self.refresh(&prompt, prompt_size, hint);
+                self.rendered_prompt = Some(prompt);
+                rc
+            }
+            None => {
+                let prompt = self.prompt;
+                self.refresh(prompt, prompt_size, hint)
+            }
+        }
+    }
+
+    /// Render the prompt template again (the providers may return new
+    /// values).
+    fn render_prompt(&mut self) {
+        if let Some(template) = self.prompt_template {
+            let prompt = template.render();
+            if self.rendered_prompt.as_ref() != Some(&prompt) {
+                self.rendered_prompt = Some(prompt);
+                self.update_prompt_size();
+            }
+        }
+    }
+
+    /// Compute the size of the displayed prompt (with the rendering of the
+    /// template, the modified entry marker and the widget, if any).
+    fn update_prompt_size(&mut self) {
+        let prompt = self
+            .rendered_prompt
+            .as_ref()
+            .map_or(self.prompt, String::as_str);
+        self.prompt_size = match self.prompt_prefix() {
+            Some(prefix) => self
+                .out
+                .calculate_position(&format!("{}{}", prefix, prompt), Position::default()),
+            None => self.out.calculate_position(prompt, Position::default()),
+        };
+    }
+
+    /// Displayed before the prompt: the modified entry marker and the widget
+    /// (called on each repaint and fitted to its reserved width, if any).
+    fn prompt_prefix(&self) -> Option<String> {
+        let marker = self.modified_marker.filter(|_| self.marked);
+        if marker.is_none() && self.widget.is_none() {
+            return None;
+        }
+        let mut prefix = marker.unwrap_or("").to_owned();
+        if let Some((width, widget)) = self.widget {
+            let segment = widget();
+            if width == 0 {
+                prefix.push_str(&segment);
+            } else {
+                let wide = self.out.is_wide_ambiguous();
+                let segment = tty::truncate(&segment, 0, width, wide);
+                prefix.push_str(segment);
+                for _ in str_width(segment, wide)..width {
+                    prefix.push(' ');
+                }
+            }
+        }
+        Some(prefix)
+    }
+
+    /// Tell if the edited line is a recalled history entry which has been
+    /// modified.
+    pub fn is_modified_entry(&self) -> bool {
+        self.history
+            .and_then(|history| history.get(self.history_index))
+            .map_or(false, |entry| entry != self.line.as_str())
+    }
+
+    /// Record the kind of refresh (`decision`) in the trace, if any.
+    pub fn trace_refresh(&self, decision: &str) {
+        if let Some(trace) = self.trace {
+            trace.refresh(decision);
+        }
+    }
+
+    /// Tell if the modified entry marker must be displayed or hidden.
+    fn marker_changed(&self) -> bool {
+        self.modified_marker.is_some() && self.marked != self.is_modified_entry()
+    }
+
     fn refresh(&mut self, prompt: &str, prompt_size: Position, hint: Option<String>) -> Result<()> {
+        let selected = match (self.selection_range(), self.selection_style) {
+            (Some(range), Some(style)) => Some((range, style)),
+            _ => match (self.search_match.clone(), self.search_match_style) {
+                (Some(range), Some(style)) => Some((range, style)),
+                _ => None,
+            },
+        }
+        .map(|(range, style)| Selected {
+            highlighter: self.highlighter,
+            range,
+            style,
+        });
+        let highlighter = match selected {
+            Some(ref selected) => Some(selected as &Highlighter),
+            None => self.highlighter,
+        };
+        self.line.take_dirty();
+        let hidden;
+        let line = if self.secret {
+            hidden = LineBuffer::with_capacity(0);
+            &hidden
+        } else {
+            &self.line
+        };
+        self.trace_refresh("repaint");
         let (cursor, end_pos) = try!(self.out.refresh_line(
             prompt,
             prompt_size,
-            &self.line,
+            line,
             hint,
             self.cursor.row,
             self.old_rows,
-            self.highlighter,
+            highlighter,
         ));
 
         self.cursor = cursor;
         self.old_rows = end_pos.row;
+        let menu = self.menu.as_ref().filter(|menu| {
+            // not under a line taller than the screen
+            !self.out.is_dumb() && end_pos.row + menu.lines().count() < self.out.get_rows()
+        });
+        if let Some(menu) = menu {
+            // display the menu under the line and go back to the cursor
+            let rows = menu.lines().count();
+            // the menu may be styled (selected candidate)
+            let last = Position {
+                col: menu.lines().last().map_or(0, |line| {
+                    self.out.calculate_position(line, Position::default()).col
+                }),
+                row: end_pos.row + rows,
+            };
+            try!(self.out.move_cursor(cursor, end_pos));
+            try!(self.out.write_and_flush(format!("\n{}", menu).as_bytes()));
+            try!(self.out.move_cursor(last, cursor));
+            self.old_rows = last.row;
+        }
         Ok(())
     }
 
+    /// Repaint only the text modified by the last edits when possible (see
+    /// `Renderer::refresh_span`), the whole line otherwise.
+    fn refresh_edit(&mut self) -> Result<()> {
+        let from = self.line.take_dirty();
+        let from = match from {
+            Some(from)
+                if !self.other_prompt
+                    && !self.secret
+                    && self.highlighter.is_none()
+                    && self.selection.is_none()
+                    && self.menu.is_none()
+                    && self.prompt_template.is_none()
+                    && self.widget.is_none()
+                    && !self.marker_changed() =>
+            {
+                from
+            }
+            _ => return self.refresh_line(),
+        };
+        let hint = self.hint();
+        let span = try!(self.out.refresh_span(
+            self.prompt_size,
+            &self.line,
+            from,
+            hint.clone(),
+            self.cursor
+        ));
+        match span {
+            Some((cursor, end_pos)) => {
+                self.trace_refresh("span");
+                self.cursor = cursor;
+                self.old_rows = end_pos.row;
+                Ok(())
+            }
+            None => self.refresh_current(hint),
+        }
+    }
+
+    /// Ring the bell (or call the bell callback) according to the
+    /// `BellStyle`.
+    pub fn beep(&mut self) -> Result<()> {
+        match (self.bell, self.bell_style) {
+            (_, BellStyle::None) => Ok(()),
+            (Some(bell), _) => {
+                bell();
+                Ok(())
+            }
+            (None, BellStyle::Visible) => self.out.flash(),
+            (None, BellStyle::Audible) => self.out.beep(),
+        }
+    }
+
+    /// Context given to the completer and hinter
+    pub fn helper_context(&self) -> HelperContext<'_> {
+        HelperContext::new(self.history, self.history_index)
+            .ignore_case(self.completion_ignore_case)
+            .fuzzy(self.fuzzy_completion)
+            .with_word_chars(self.line.word_chars())
+    }
+
+    /// Notify the helper of the modifications of the line since the last
+    /// call (before the hint, the highlighting or the completion).
+    pub fn update_after_edit(&mut self) {
+        let revision = Some(self.line.revision());
+        if let Some(on_edit) = self.on_edit.filter(|_| self.edit_revision != revision) {
+            self.edit_revision = revision;
+            on_edit(self.line.as_str(), self.line.pos());
+        }
+    }
+
     fn hint(&mut self) -> Option<String> {
+        self.update_after_edit();
         if let Some(hinter) = self.hinter {
             self.no_hint = false;
-            hinter.hint(self.line.as_str(), self.line.pos())
+            let hint = hinter.hint_with_context(
+                self.line.as_str(),
+                self.line.pos(),
+                &self.helper_context(),
+            );
+            match self.hint_markers {
+                Some((prefix, suffix)) => hint
+                    .filter(|hint| !hint.is_empty())
+                    .map(|hint| format!("{}{}{}", prefix, hint, suffix)),
+                None => hint,
+            }
         } else {
             self.no_hint = true;
             None
@@ -145,14 +477,15 @@ impl<'out, 'prompt> State<'out, 'prompt> {
 
 impl<'out, 'prompt> Refresher for State<'out, 'prompt> {
     fn refresh_line(&mut self) -> Result<()> {
-        let prompt_size = self.prompt_size;
+        self.render_prompt();
         let hint = self.hint();
-        self.refresh(self.prompt, prompt_size, hint)
+        self.refresh_current(hint)
     }
 
     fn refresh_prompt_and_line(&mut self, prompt: &str) -> Result<()> {
         let prompt_size = self.out.calculate_position(prompt, Position::default());
         let hint = self.hint();
+        self.other_prompt = true;
         self.refresh(prompt, prompt_size, hint)
     }
 
@@ -189,27 +522,30 @@ impl<'out, 'prompt> State<'out, 'prompt> {
     pub fn edit_insert(&mut self, ch: char, n: RepeatCount) -> Result<()> {
         if let Some(push) = self.line.insert(ch, n) {
             if push {
-                let prompt_size = self.prompt_size;
                 let no_previous_hint = self.no_hint;
                 let hint = self.hint();
+                let width = char_width(ch, self.out.is_wide_ambiguous()).unwrap_or(0);
                 if n == 1
-                    && self.cursor.col + ch.width().unwrap_or(0) < self.out.get_columns()
+                    && !self.secret
+                    && !self.marker_changed()
+                    && self.cursor.col + width < self.out.get_columns()
                     && (hint.is_none() && no_previous_hint) // TODO refresh only current line
-                    && !self.highlighter.map_or(true, |h| h.highlight_char(ch.encode_utf8(&mut self.byte_buffer)))
+                    && (self.screen_reader
+                        || !self.highlighter.map_or(true, |h| h.highlight_char(ch.encode_utf8(&mut self.byte_buffer))))
                 {
                     // Avoid a full update of the line in the trivial case.
-                    let cursor = self
-                        .out
-                        .calculate_position(&self.line[..self.line.pos()], self.prompt_size);
+                    self.trace_refresh("echo");
+                    self.line.take_dirty();
+                    let cursor = self.cursor_position();
                     self.cursor = cursor;
                     let bits = ch.encode_utf8(&mut self.byte_buffer);
                     let bits = bits.as_bytes();
                     self.out.write_and_flush(bits)
                 } else {
-                    self.refresh(self.prompt, prompt_size, hint)
+                    self.refresh_current(hint)
                 }
             } else {
-                self.refresh_line()
+                self.refresh_edit()
             }
         } else {
             Ok(())
@@ -243,7 +579,7 @@ impl<'out, 'prompt> State<'out, 'prompt> {
                 let start = self.line.pos();
                 self.line.replace(start..end, text);
             }
-            self.refresh_line()
+            self.refresh_edit()
         } else {
             Ok(())
         }
@@ -264,12 +600,22 @@ impl<'out, 'prompt> State<'out, 'prompt> {
             if !input_state.is_emacs_mode() {
                 self.line.move_backward(1);
             }
-            self.refresh_line()
+            self.refresh_edit()
         } else {
             Ok(())
         }
     }
 
+    /// Insert the characters received in a burst (like a paste) at once: one
+    /// undo record and one repaint.
+    pub fn edit_insert_bulk(&mut self, text: &str) -> Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.line.insert_bulk(text);
+        self.refresh_edit()
+    }
+
     // Delete previously yanked text and yank/paste `text` at current position.
     pub fn edit_yank_pop(&mut self, yank_size: usize, text: &str) -> Result<()> {
         self.changes.borrow_mut().begin();
@@ -318,26 +664,115 @@ impl<'out, 'prompt> State<'out, 'prompt> {
         }
     }
 
+    /// Selected region, between the anchor and the cursor.
+    pub fn selection_range(&self) -> Option<Range<usize>> {
+        let pos = self.line.pos();
+        self.selection.and_then(|anchor| {
+            if anchor < pos {
+                Some(anchor..pos)
+            } else if pos < anchor {
+                Some(pos..anchor)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Extend the selection up to `mvt`.
+    ///
+    /// The cursor is only moved when the selection cannot be displayed (on a
+    /// dumb terminal).
+    pub fn edit_select(&mut self, mvt: &Movement) -> Result<()> {
+        if self.selection.is_none() && self.selection_style.is_some() {
+            self.selection = Some(self.line.pos());
+        }
+        let moved = match *mvt {
+            Movement::BackwardChar(n) => self.line.move_backward(n),
+            Movement::ForwardChar(n) => self.line.move_forward(n),
+            Movement::BeginningOfLine => self.line.move_home(),
+            Movement::EndOfLine => self.line.move_end(),
+            Movement::BackwardWord(n, word_def) => self.line.move_to_prev_word(word_def, n),
+            Movement::ForwardWord(n, at, word_def) => self.line.move_to_next_word(at, word_def, n),
+            _ => false,
+        };
+        if moved {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Delete the selected region (into the kill ring when `kill` is `true`).
+    pub fn edit_delete_selection(&mut self, kill: bool) -> Result<()> {
+        let range = self.selection_range();
+        self.selection = None;
+        if let Some(range) = range {
+            if kill {
+                self.line.kill_range(range);
+            } else {
+                self.line.delete_range(range);
+            }
+        }
+        self.refresh_line()
+    }
+
     pub fn edit_kill(&mut self, mvt: &Movement) -> Result<()> {
+        let end = if self.screen_reader && self.line.pos() == self.line.len() {
+            Some(self.line.as_str().to_owned())
+        } else {
+            None
+        };
         if self.line.kill(mvt) {
-            self.refresh_line()
+            if let Some(end) = end {
+                if try!(self.erase_end(&end[self.line.len()..])) {
+                    return Ok(());
+                }
+            }
+            self.refresh_edit()
         } else {
             Ok(())
         }
     }
 
+    /// Erase the `removed` characters before the cursor with backspaces (like
+    /// the tty echo does) instead of repainting the line.
+    /// Return `false` when this is not possible.
+    fn erase_end(&mut self, removed: &str) -> Result<bool> {
+        let width = str_width(removed, self.out.is_wide_ambiguous());
+        if self.line.pos() != self.line.len()
+            || removed.is_empty()
+            || removed.contains('\n')
+            || removed.graphemes(true).count() != width
+            || self.cursor.col < width
+            || !self.no_hint
+            || self.menu.is_some()
+            || self.selection.is_some()
+            || self.secret
+            || self.marker_changed()
+        {
+            return Ok(false);
+        }
+        self.trace_refresh("erase");
+        self.line.take_dirty();
+        try!(self
+            .out
+            .write_and_flush("\x08 \x08".repeat(width).as_bytes()));
+        self.cursor.col -= width;
+        Ok(true)
+    }
+
     pub fn edit_insert_text(&mut self, text: &str) -> Result<()> {
         if text.is_empty() {
             return Ok(());
         }
         let cursor = self.line.pos();
         self.line.insert_str(cursor, text);
-        self.refresh_line()
+        self.refresh_edit()
     }
 
     pub fn edit_delete(&mut self, n: RepeatCount) -> Result<()> {
         if self.line.delete(n).is_some() {
-            self.refresh_line()
+            self.refresh_edit()
         } else {
             Ok(())
         }
@@ -349,7 +784,7 @@ impl<'out, 'prompt> State<'out, 'prompt> {
         let succeed = self.line.transpose_chars();
         self.changes.borrow_mut().end();
         if succeed {
-            self.refresh_line()
+            self.refresh_edit()
         } else {
             Ok(())
         }
@@ -379,12 +814,29 @@ impl<'out, 'prompt> State<'out, 'prompt> {
         }
     }
 
+    pub fn edit_toggle_case(&mut self) -> Result<()> {
+        let range = self.selection_range();
+        let selected = range.is_some();
+        self.selection = None;
+        self.changes.borrow_mut().begin();
+        let succeed = match range {
+            Some(range) => self.line.edit_case(range, WordAction::TOGGLE),
+            None => self.line.edit_word(WordAction::TOGGLE),
+        };
+        self.changes.borrow_mut().end();
+        if succeed || selected {
+            self.refresh_line()
+        } else {
+            Ok(())
+        }
+    }
+
     pub fn edit_word(&mut self, a: WordAction) -> Result<()> {
         self.changes.borrow_mut().begin();
         let succeed = self.line.edit_word(a);
         self.changes.borrow_mut().end();
         if succeed {
-            self.refresh_line()
+            self.refresh_edit()
         } else {
             Ok(())
         }
@@ -395,73 +847,125 @@ impl<'out, 'prompt> State<'out, 'prompt> {
         let succeed = self.line.transpose_words(n);
         self.changes.borrow_mut().end();
         if succeed {
-            self.refresh_line()
+            self.refresh_edit()
         } else {
             Ok(())
         }
     }
 
+    pub fn edit_drag_word(&mut self, forward: bool, n: RepeatCount) -> Result<()> {
+        self.changes.borrow_mut().begin();
+        let succeed = self.line.drag_word(forward, n);
+        self.changes.borrow_mut().end();
+        if succeed {
+            self.refresh_edit()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The history index we are currently editing (`history.len()` for a new
+    /// line).
+    pub fn history_index(&self) -> usize {
+        self.history_index
+    }
+
+    /// Edit `line` as if it has been recalled from the history entry at
+    /// `index`.
+    pub fn edit_history_entry(&mut self, line: &str, index: usize) {
+        self.backup();
+        self.history_index = index;
+        self.line.update(line, line.len());
+    }
+
+    /// Restore the recalled history entry (discarding its edits).
+    pub fn edit_revert_line(&mut self, history: &History) -> Result<()> {
+        match history.get(self.history_index) {
+            Some(entry) if entry != self.line.as_str() => {
+                self.changes.borrow_mut().begin();
+                self.line.update(entry, entry.len());
+                self.changes.borrow_mut().end();
+                self.refresh_line()
+            }
+            _ => self.beep(),
+        }
+    }
+
+    /// Move to the history entry at `index` (`history.len()` for the new
+    /// line), keeping the edits of the current entry until the line is
+    /// accepted (like bash).
+    fn recall(&mut self, history: &History, index: usize) {
+        match history.get(self.history_index) {
+            Some(entry) if entry != self.line.as_str() => {
+                self.edited_entries
+                    .insert(self.history_index, self.line.as_str().to_owned());
+            }
+            Some(_) => {
+                self.edited_entries.remove(&self.history_index);
+            }
+            // Save the current edited line before overwriting it
+            None => self.backup(),
+        }
+        self.history_index = index;
+        match history.get(index) {
+            Some(entry) => {
+                let buf = self.edited_entries.get(&index).unwrap_or(entry);
+                self.changes.borrow_mut().begin();
+                self.line.update(buf, buf.len());
+                self.changes.borrow_mut().end();
+            }
+            // Restore current edited line
+            None => self.restore(),
+        }
+    }
+
     /// Substitute the currently edited line with the next or previous history
     /// entry.
     pub fn edit_history_next(&mut self, history: &History, prev: bool) -> Result<()> {
         if history.is_empty() {
             return Ok(());
         }
-        if self.history_index == history.len() {
-            if prev {
-                // Save the current edited line before overwriting it
-                self.backup();
-            } else {
+        let index = if prev {
+            if self.history_index == 0 {
                 return Ok(());
             }
-        } else if self.history_index == 0 && prev {
-            return Ok(());
-        }
-        if prev {
-            self.history_index -= 1;
-        } else {
-            self.history_index += 1;
-        }
-        if self.history_index < history.len() {
-            let buf = history.get(self.history_index).unwrap();
-            self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
-            self.changes.borrow_mut().end();
+            self.history_index - 1
         } else {
-            // Restore current edited line
-            self.restore();
-        }
+            if self.history_index == history.len() {
+                return Ok(());
+            }
+            self.history_index + 1
+        };
+        self.recall(history, index);
         self.refresh_line()
     }
 
     // Non-incremental, anchored search
     pub fn edit_history_search(&mut self, history: &History, dir: Direction) -> Result<()> {
         if history.is_empty() {
-            return self.out.beep();
+            return self.beep();
         }
         if self.history_index == history.len() && dir == Direction::Forward
             || self.history_index == 0 && dir == Direction::Reverse
         {
-            return self.out.beep();
+            return self.beep();
         }
-        if dir == Direction::Reverse {
-            self.history_index -= 1;
+        let start = if dir == Direction::Reverse {
+            self.history_index - 1
         } else {
-            self.history_index += 1;
-        }
-        if let Some(history_index) = history.starts_with(
-            &self.line.as_str()[..self.line.pos()],
-            self.history_index,
-            dir,
-        ) {
-            self.history_index = history_index;
-            let buf = history.get(history_index).unwrap();
-            self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
-            self.changes.borrow_mut().end();
+            self.history_index + 1
+        };
+        let pos = self.line.pos();
+        let prefix = self.line.as_str()[..pos].to_owned();
+        if let Some(history_index) = history.starts_with(&prefix, start, dir) {
+            self.recall(history, history_index);
+            // the cursor stays after the prefix, to search it again
+            if self.line.as_str().starts_with(&prefix) {
+                self.line.set_pos(pos);
+            }
             self.refresh_line()
         } else {
-            self.out.beep()
+            self.beep()
         }
     }
 
@@ -470,29 +974,263 @@ impl<'out, 'prompt> State<'out, 'prompt> {
         if history.is_empty() {
             return Ok(());
         }
-        if self.history_index == history.len() {
-            if first {
-                // Save the current edited line before overwriting it
-                self.backup();
-            } else {
-                return Ok(());
-            }
-        } else if self.history_index == 0 && first {
+        let index = if first { 0 } else { history.len() };
+        if self.history_index == index {
             return Ok(());
         }
-        if first {
-            self.history_index = 0;
-            let buf = history.get(self.history_index).unwrap();
-            self.changes.borrow_mut().begin();
-            self.line.update(buf, buf.len());
-            self.changes.borrow_mut().end();
-        } else {
-            self.history_index = history.len();
-            // Restore current edited line
-            self.restore();
+        self.recall(history, index);
+        self.refresh_line()
+    }
+
+    /// Read a line after `prompt` on the rows below the edited line, then
+    /// resume editing. Return `None` when aborted.
+    pub fn read_input<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        input_state: &mut InputState,
+        prompt: &str,
+    ) -> Result<Option<String>> {
+        let (menu, row) = try!(self.open_row_below());
+        let (input, cursor_row, rows) = {
+            let mut nested = State::new(&mut *self.out, prompt, 0, None, None);
+            try!(nested.refresh_line());
+            let input = loop {
+                match try!(nested.next_cmd(input_state, rdr, true)) {
+                    Cmd::SelfInsert(n, c) => try!(nested.edit_insert(c, n)),
+                    Cmd::Insert(_, ref text) => try!(nested.edit_insert_text(text)),
+                    Cmd::Kill(ref mvt) => try!(nested.edit_kill(mvt)),
+                    Cmd::Move(Movement::BackwardChar(n)) => try!(nested.edit_move_backward(n)),
+                    Cmd::Move(Movement::ForwardChar(n)) => try!(nested.edit_move_forward(n)),
+                    Cmd::Move(Movement::BeginningOfLine) => try!(nested.edit_move_home()),
+                    Cmd::Move(Movement::EndOfLine) => try!(nested.edit_move_end()),
+                    Cmd::AcceptLine => break Some(nested.line.as_str().to_owned()),
+                    Cmd::Abort | Cmd::Interrupt | Cmd::EndOfFile => break None,
+                    _ => {}
+                }
+            };
+            (input, nested.cursor.row, nested.old_rows)
+        };
+        try!(self.close_row_below(menu, row, cursor_row, rows));
+        Ok(input)
+    }
+
+    /// Ask the `msg` question (y/n) on the rows below the edited line, then
+    /// resume editing.
+    pub fn confirm<R: RawReader>(&mut self, rdr: &mut R, msg: &str) -> Result<bool> {
+        let (menu, row) = try!(self.open_row_below());
+        let question = format!("{} (y/n) ", msg);
+        try!(self.out.write_and_flush(question.as_bytes()));
+        let end = self.out.calculate_position(&question, Position::default());
+        let yes = try!(read_yes_no(rdr));
+        try!(self.close_row_below(menu, row, end.row, end.row));
+        Ok(yes)
+    }
+
+    /// Write `text` where the prompt is, and repaint the prompt and the
+    /// edited line under it.
+    pub fn print_above(&mut self, text: &str) -> Result<()> {
+        // erase the prompt, the line and the menu
+        let empty = LineBuffer::with_capacity(0);
+        let (cursor, _) = try!(self.out.refresh_line(
+            "",
+            Position::default(),
+            &empty,
+            None,
+            self.cursor.row,
+            self.old_rows,
+            None,
+        ));
+        self.cursor = cursor;
+        self.old_rows = cursor.row;
+        try!(self.out.write_and_flush(text.as_bytes()));
+        if !text.ends_with('\n') {
+            try!(self.out.write_and_flush(b"\n"));
         }
         self.refresh_line()
     }
+
+    /// Read the hexadecimal code point of a character (terminated by Enter or
+    /// Space), showing the typed digits under the line.
+    /// Return `None` when aborted or invalid.
+    pub fn read_code_point<R: RawReader>(&mut self, rdr: &mut R) -> Result<Option<char>> {
+        let menu = self.menu.take();
+        let mut digits = String::new();
+        let c = loop {
+            self.menu = Some(format!("U+{}", digits));
+            try!(self.refresh_line());
+            match try!(rdr.next_key(true)) {
+                KeyPress::Char(c) if c.is_ascii_hexdigit() && digits.len() < 6 => digits.push(c),
+                KeyPress::Backspace | KeyPress::Ctrl('H') => {
+                    digits.pop();
+                }
+                KeyPress::Enter | KeyPress::Char(' ') => {
+                    let c = u32::from_str_radix(&digits, 16)
+                        .ok()
+                        .and_then(::std::char::from_u32);
+                    if c.is_none() {
+                        try!(self.beep());
+                    }
+                    break c;
+                }
+                KeyPress::Esc | KeyPress::Ctrl('G') | KeyPress::Ctrl('C') => break None,
+                _ => try!(self.beep()),
+            }
+        };
+        self.menu = menu;
+        try!(self.refresh_line());
+        Ok(c)
+    }
+
+    /// Read the two characters of a compose sequence, showing them under the
+    /// line.
+    /// Return `None` when aborted or unknown.
+    pub fn read_compose<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        table: &HashMap<(char, char), char>,
+    ) -> Result<Option<char>> {
+        let menu = self.menu.take();
+        let mut chars = String::new();
+        let c = loop {
+            self.menu = Some(format!("Compose: {}", chars));
+            try!(self.refresh_line());
+            match try!(rdr.next_key(true)) {
+                KeyPress::Char(c) => match chars.pop() {
+                    Some(first) => {
+                        let c = compose::compose(table, first, c);
+                        if c.is_none() {
+                            try!(self.beep());
+                        }
+                        break c;
+                    }
+                    None => chars.push(c),
+                },
+                KeyPress::Backspace | KeyPress::Ctrl('H') if !chars.is_empty() => {
+                    chars.pop();
+                }
+                _ => break None,
+            }
+        };
+        self.menu = menu;
+        try!(self.refresh_line());
+        Ok(c)
+    }
+
+    /// List the most recent history entries containing the typed filter, one
+    /// page at a time (numbered, the most recent first), under the line.
+    /// Return the selected entry (`None` when aborted).
+    pub fn read_history_entry<R: RawReader>(
+        &mut self,
+        rdr: &mut R,
+        history: &History,
+    ) -> Result<Option<String>> {
+        let menu = self.menu.take();
+        let cols = self.out.get_columns();
+        let wide = self.out.is_wide_ambiguous();
+        // rows left for the entries (under the line, the filter and the page)
+        let page_size = cmp::max(self.out.get_rows().saturating_sub(self.old_rows + 3), 1);
+        let mut filter = String::new();
+        let mut selected = 0;
+        let entry = loop {
+            let matches: Vec<&String> = history
+                .iter()
+                .rev()
+                .filter(|entry| entry.contains(filter.as_str()))
+                .collect();
+            selected = cmp::min(selected, matches.len().saturating_sub(1));
+            let page = selected / page_size;
+            let mut list = format!("Filter: {}", filter);
+            for (i, entry) in matches
+                .iter()
+                .enumerate()
+                .skip(page * page_size)
+                .take(page_size)
+            {
+                let marker = if i == selected { '>' } else { ' ' };
+                let row = format!(
+                    "{}{:3} {}",
+                    marker,
+                    i + 1,
+                    entry.lines().next().unwrap_or("")
+                );
+                write!(list, "\n{}", tty::truncate(&row, 0, cols, wide)).unwrap();
+            }
+            if matches.len() > page_size {
+                let pages = (matches.len() + page_size - 1) / page_size;
+                write!(list, "\n-- {}/{} --", page + 1, pages).unwrap();
+            }
+            self.menu = Some(list);
+            try!(self.refresh_line());
+            match try!(rdr.next_key(true)) {
+                KeyPress::Char(c) => {
+                    filter.push(c);
+                    selected = 0;
+                }
+                KeyPress::Backspace | KeyPress::Ctrl('H') => {
+                    filter.pop();
+                    selected = 0;
+                }
+                KeyPress::Up | KeyPress::Ctrl('P') => selected = selected.saturating_sub(1),
+                KeyPress::Down | KeyPress::Ctrl('N') => selected += 1,
+                KeyPress::PageUp => selected = selected.saturating_sub(page_size),
+                KeyPress::PageDown => selected += page_size,
+                KeyPress::Enter => break matches.get(selected).map(|entry| (*entry).clone()),
+                KeyPress::Esc | KeyPress::Ctrl('G') | KeyPress::Ctrl('C') => break None,
+                _ => try!(self.beep()),
+            }
+        };
+        self.menu = menu;
+        try!(self.refresh_line());
+        Ok(entry)
+    }
+
+    /// Hide the menu and go to the start of the row below the edited line.
+    fn open_row_below(&mut self) -> Result<(Option<String>, usize)> {
+        let menu = self.menu.take();
+        if menu.is_some() {
+            try!(self.refresh_line());
+        }
+        let end = Position {
+            col: 0,
+            row: self.old_rows,
+        };
+        try!(self.out.move_cursor(self.cursor, end));
+        try!(self.out.write_and_flush(b"\n"));
+        Ok((menu, end.row + 1))
+    }
+
+    /// Erase the `rows` displayed from `row` and repaint the edited line.
+    fn close_row_below(
+        &mut self,
+        menu: Option<String>,
+        row: usize,
+        cursor_row: usize,
+        rows: usize,
+    ) -> Result<()> {
+        // the rows below are erased by the next refresh
+        self.cursor = Position {
+            col: 0,
+            row: row + cursor_row,
+        };
+        self.old_rows = row + rows;
+        self.menu = menu;
+        self.refresh_line()
+    }
+}
+
+/// Wait for a y/n answer (Esc, Ctrl-G and Ctrl-C mean no).
+pub fn read_yes_no<R: RawReader>(rdr: &mut R) -> Result<bool> {
+    loop {
+        match try!(rdr.next_key(true)) {
+            KeyPress::Char('y') | KeyPress::Char('Y') => return Ok(true),
+            KeyPress::Char('n')
+            | KeyPress::Char('N')
+            | KeyPress::Esc
+            | KeyPress::Ctrl('G')
+            | KeyPress::Ctrl('C') => return Ok(false),
+            _ => {}
+        }
+    }
 }
 
 #[cfg(test)]
@@ -506,11 +1244,34 @@ pub fn init_state<'out>(out: &'out mut Renderer, line: &str, pos: usize) -> Stat
         old_rows: 0,
         history_index: 0,
         saved_line_for_history: LineBuffer::with_capacity(100),
+        edited_entries: HashMap::new(),
         byte_buffer: [0; 4],
         changes: Rc::new(RefCell::new(Changeset::new())),
         hinter: None,
         highlighter: None,
         no_hint: true,
+        selection: None,
+        selection_style: None,
+        search_match: None,
+        search_match_style: None,
+        menu: None,
+        screen_reader: false,
+        bell: None,
+        bell_style: BellStyle::Audible,
+        completion_ignore_case: false,
+        fuzzy_completion: false,
+        hint_markers: None,
+        history: None,
+        secret: false,
+        prompt_template: None,
+        rendered_prompt: None,
+        modified_marker: None,
+        marked: false,
+        widget: None,
+        trace: None,
+        other_prompt: false,
+        on_edit: None,
+        edit_revision: None,
     }
 }
 
@@ -518,6 +1279,8 @@ pub fn init_state<'out>(out: &'out mut Renderer, line: &str, pos: usize) -> Stat
 mod test {
     use super::init_state;
     use history::History;
+    use keymap::Refresher;
+    use std::cell::Cell;
     use tty::Sink;
 
     #[test]
@@ -557,4 +1320,76 @@ mod test {
         assert_eq!(2, s.history_index);
         assert_eq!(line, s.line.as_str());
     }
+
+    #[test]
+    fn edited_entries() {
+        let mut history = History::new();
+        history.add("line0");
+        history.add("line1");
+        let mut out = Sink::new();
+        let mut s = init_state(&mut out, "new", 3);
+        s.history_index = history.len();
+
+        s.edit_history_next(&history, true).unwrap();
+        s.edit_insert('!', 1).unwrap();
+        s.edit_history_next(&history, true).unwrap();
+        assert_eq!("line0", s.line.as_str());
+        s.edit_history(&history, false).unwrap();
+        assert_eq!("new", s.line.as_str());
+        s.edit_history_next(&history, true).unwrap();
+        assert_eq!("line1!", s.line.as_str());
+        assert_eq!("line1", &history[1]);
+
+        // unmodified again
+        s.edit_revert_line(&history).unwrap();
+        s.edit_history_next(&history, true).unwrap();
+        s.edit_history_next(&history, false).unwrap();
+        assert_eq!("line1", s.line.as_str());
+        assert!(s.edited_entries.is_empty());
+    }
+
+    #[test]
+    fn widget() {
+        let calls = Cell::new(0);
+        let widget = || {
+            calls.set(calls.get() + 1);
+            "[2 jobs]".to_owned()
+        };
+        let mut out = Sink::new();
+        let mut s = init_state(&mut out, "", 0);
+        s.widget = Some((4, &widget));
+        s.refresh_line().unwrap();
+        assert_eq!(1, calls.get());
+        assert_eq!(4, s.prompt_size.col);
+        assert_eq!(Some("[2 j".to_owned()), s.prompt_prefix());
+
+        s.widget = Some((0, &widget));
+        s.refresh_line().unwrap();
+        assert_eq!(8, s.prompt_size.col);
+    }
+
+    #[test]
+    fn modified_marker() {
+        let mut history = History::new();
+        history.add("line0");
+        let mut out = Sink::new();
+        let mut s = init_state(&mut out, "", 0);
+        s.history = Some(&history);
+        s.history_index = history.len();
+        s.modified_marker = Some("*");
+
+        s.edit_history_next(&history, true).unwrap();
+        assert!(!s.is_modified_entry());
+        assert_eq!(0, s.prompt_size.col);
+        s.edit_insert('!', 1).unwrap();
+        assert!(s.is_modified_entry());
+        assert_eq!(1, s.prompt_size.col);
+        assert_eq!(7, s.cursor.col);
+
+        s.edit_revert_line(&history).unwrap();
+        assert_eq!("line0", s.line.as_str());
+        assert_eq!(0, s.prompt_size.col);
+        s.edit_history_next(&history, false).unwrap();
+        assert!(!s.is_modified_entry());
+    }
 }
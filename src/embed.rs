@@ -0,0 +1,806 @@
+//! Embedding of the editor in a GUI text widget (egui, iced...)
+//!
+//! The keys are sent by the GUI event loop (see `KeySource`) and the edit
+//! is drawn on a cell grid (see `Grid`) instead of a terminal: the widget
+//! only has to paint the cells, with their style, and the cursor. Keymaps,
+//! kill ring, history, completion, hints and highlighting work as in a
+//! terminal.
+//!
+//! `Editor::readline` is blocking: run it with `Widget::readline` in a
+//! worker thread, the GUI thread sending the keys through a channel and
+//! repainting the widget with the grids given to its `Surface` (see the
+//! `embed` example).
+//!
+//! ```
+//! use rustyline::embed::{Grid, Surface, Widget};
+//! use rustyline::{Editor, KeyPress};
+//! use std::sync::mpsc;
+//!
+//! struct Repaint;
+//!
+//! impl Surface for Repaint {
+//!     fn size(&self) -> (usize, usize) {
+//!         (40, 10)
+//!     }
+//!
+//!     fn draw(&mut self, grid: &Grid) {
+//!         // paint `grid.cell(col, row)` and `grid.cursor()`
+//!     }
+//! }
+//!
+//! let (keys, mut events) = mpsc::channel();
+//! for key in &[KeyPress::Char('h'), KeyPress::Char('i'), KeyPress::Enter] {
+//!     keys.send(*key).unwrap(); // from the GUI thread
+//! }
+//! let mut editor = Editor::<()>::new();
+//! let mut widget = Widget::new(Repaint);
+//! assert_eq!("hi", widget.readline(&mut editor, "> ", &mut events).unwrap());
+//! assert_eq!("> hi", widget.grid().text(0));
+//! ```
+use std::cmp;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{readline_edit, Editor, Helper, ReadlineOptions, Result};
+use error::ReadlineError;
+use highlight::Highlighter;
+use keys::KeyPress;
+use line_buffer::LineBuffer;
+use tty::{truncate, width, Position, RawReader, Renderer, View};
+
+/// Cell grid on which the edit is drawn, sized by `Surface::size`
+pub trait Surface {
+    /// Number of columns and rows of the widget
+    fn size(&self) -> (usize, usize);
+
+    /// Repaint the widget with `grid` (called after each change).
+    fn draw(&mut self, grid: &Grid);
+
+    /// Signal an error (instead of ringing the terminal bell).
+    fn beep(&mut self) {}
+}
+
+/// Keys pressed in the widget
+pub trait KeySource {
+    /// Next key, waiting at most `timeout` (or indefinitely when `None`).
+    ///
+    /// Return `None` on timeout or when the widget is closed (the edit then
+    /// ends with `ReadlineError::Eof`).
+    fn next_key(&mut self, timeout: Option<Duration>) -> Option<KeyPress>;
+}
+
+/// Keys sent by the GUI thread
+impl KeySource for Receiver<KeyPress> {
+    fn next_key(&mut self, timeout: Option<Duration>) -> Option<KeyPress> {
+        match timeout {
+            Some(timeout) => match self.recv_timeout(timeout) {
+                Ok(key) => Some(key),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+            },
+            None => self.recv().ok(),
+        }
+    }
+}
+
+/// Editor output on a `Surface`, kept between `readline` calls (like the
+/// contents of a terminal)
+pub struct Widget<S: Surface> {
+    display: Display,
+    surface: S,
+}
+
+impl<S: Surface> Widget<S> {
+    pub fn new(surface: S) -> Widget<S> {
+        let (cols, rows) = surface.size();
+        Widget {
+            display: Display::new(cols, rows),
+            surface,
+        }
+    }
+
+    /// Like `Editor::readline` but keys are read from `keys` and the edit is
+    /// drawn on the surface.
+    ///
+    /// # Errors
+    /// Will return `ReadlineError::Eof` when `keys` is closed before the line
+    /// is accepted.
+    pub fn readline<H: Helper>(
+        &mut self,
+        editor: &mut Editor<H>,
+        prompt: &str,
+        keys: &mut KeySource,
+    ) -> Result<String> {
+        self.readline_with(editor, prompt, keys, &ReadlineOptions::default())
+    }
+
+    /// Like `Editor::readline_with` but keys are read from `keys` and the
+    /// edit is drawn on the surface.
+    pub fn readline_with<H: Helper>(
+        &mut self,
+        editor: &mut Editor<H>,
+        prompt: &str,
+        keys: &mut KeySource,
+        options: &ReadlineOptions,
+    ) -> Result<String> {
+        let mut rdr = Keys {
+            source: keys,
+            pending: None,
+        };
+        let user_input = {
+            let mut out = Drawn {
+                display: &mut self.display,
+                surface: &mut self.surface,
+            };
+            out.update_size();
+            readline_edit(prompt, None, editor, options, &mut out, &mut rdr, None)
+        };
+        if options.apply(&editor.config).auto_add_history() {
+            if let Ok(ref line) = user_input {
+                editor.add_history_entry(line.as_str());
+            }
+        }
+        self.print("\n");
+        user_input
+    }
+
+    /// Write some application output (like the result of a command) on the
+    /// grid.
+    pub fn print(&mut self, text: &str) {
+        self.display.screen.put(text);
+        self.surface.draw(&self.display.screen);
+    }
+
+    /// Current grid
+    pub fn grid(&self) -> &Grid {
+        &self.display.screen
+    }
+
+    pub fn surface(&self) -> &S {
+        &self.surface
+    }
+
+    pub fn surface_mut(&mut self) -> &mut S {
+        &mut self.surface
+    }
+}
+
+/// Reader of the keys from a `KeySource`
+struct Keys<'k> {
+    source: &'k mut KeySource,
+    /// Key received while waiting for some input
+    pending: Option<KeyPress>,
+}
+
+impl<'k> RawReader for Keys<'k> {
+    fn next_key(&mut self, _: bool) -> Result<KeyPress> {
+        match self.pending.take().or_else(|| self.source.next_key(None)) {
+            Some(key) => Ok(key),
+            None => Err(ReadlineError::Eof),
+        }
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        match try!(self.next_key(false)) {
+            KeyPress::Char(c) => Ok(c),
+            KeyPress::Tab => Ok('\t'),
+            KeyPress::Enter => Ok('\r'),
+            KeyPress::Esc => Ok('\x1b'),
+            KeyPress::Ctrl(c) => Ok(((c as u8) & 0x1f) as char),
+            _ => Ok('\u{fffd}'),
+        }
+    }
+
+    fn has_pending_input(&mut self) -> bool {
+        self.wait_for_input(Duration::from_millis(0))
+    }
+
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        if self.pending.is_none() {
+            self.pending = self.source.next_key(Some(timeout));
+        }
+        self.pending.is_some()
+    }
+}
+
+/// Renderer drawing the grid on a `Surface` after each change
+struct Drawn<'d> {
+    display: &'d mut Display,
+    surface: &'d mut Surface,
+}
+
+impl<'d> Drawn<'d> {
+    fn draw<T>(&mut self, rc: Result<T>) -> Result<T> {
+        self.surface.draw(&self.display.screen);
+        rc
+    }
+}
+
+impl<'d> Renderer for Drawn<'d> {
+    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        let rc = self.display.move_cursor(old, new);
+        self.draw(rc)
+    }
+
+    fn refresh_line(
+        &mut self,
+        prompt: &str,
+        prompt_size: Position,
+        line: &LineBuffer,
+        hint: Option<String>,
+        current_row: usize,
+        old_rows: usize,
+        highlighter: Option<&Highlighter>,
+    ) -> Result<(Position, Position)> {
+        let rc = self.display.refresh_line(
+            prompt,
+            prompt_size,
+            line,
+            hint,
+            current_row,
+            old_rows,
+            highlighter,
+        );
+        self.draw(rc)
+    }
+
+    fn refresh_span(
+        &mut self,
+        prompt_size: Position,
+        line: &LineBuffer,
+        from: usize,
+        hint: Option<String>,
+        current: Position,
+    ) -> Result<Option<(Position, Position)>> {
+        let rc = self
+            .display
+            .refresh_span(prompt_size, line, from, hint, current);
+        self.draw(rc)
+    }
+
+    fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        self.display.calculate_position(s, orig)
+    }
+
+    fn write_and_flush(&mut self, buf: &[u8]) -> Result<()> {
+        let rc = self.display.write_and_flush(buf);
+        self.draw(rc)
+    }
+
+    fn beep(&mut self) -> Result<()> {
+        self.surface.beep();
+        Ok(())
+    }
+
+    fn clear_screen(&mut self) -> Result<()> {
+        let rc = self.display.clear_screen();
+        self.draw(rc)
+    }
+
+    fn sigwinch(&self) -> bool {
+        let (cols, rows) = self.surface.size();
+        cols != self.display.screen.cols || rows != self.display.screen.rows
+    }
+
+    fn update_size(&mut self) {
+        let (cols, rows) = self.surface.size();
+        self.display.screen.resize(cols, rows);
+    }
+
+    fn get_columns(&self) -> usize {
+        self.display.screen.cols
+    }
+
+    fn get_rows(&self) -> usize {
+        self.display.screen.rows
+    }
+
+    fn is_row_visible(&self, row: usize) -> bool {
+        self.display.is_row_visible(row)
+    }
+
+    fn set_wide_ambiguous(&mut self, wide: bool) {
+        self.display.set_wide_ambiguous(wide)
+    }
+
+    fn is_wide_ambiguous(&self) -> bool {
+        self.display.is_wide_ambiguous()
+    }
+}
+
+/// Renderer emulating a terminal screen (shared with `testing`)
+#[derive(Clone, Debug)]
+pub(crate) struct Display {
+    pub output: String,
+    pub line: String,
+    prompt_len: usize, // Length of the prompt displayed at the start of `line`
+    view: View,        // Displayed rows of the line
+    pub cursor: Position,
+    pub beeps: usize,
+    pub screen: Grid,
+}
+
+impl Display {
+    pub fn new(cols: usize, rows: usize) -> Display {
+        Display {
+            output: String::new(),
+            line: String::new(),
+            prompt_len: 0,
+            view: View::default(),
+            cursor: Position::default(),
+            beeps: 0,
+            screen: Grid {
+                cols,
+                rows,
+                cells: vec![Vec::new()],
+                pos: Position::default(),
+                style: String::new(),
+                wide_ambiguous: false,
+            },
+        }
+    }
+}
+
+/// Cell of a `Grid`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cell {
+    /// Grapheme (empty after a wide one)
+    pub text: String,
+    /// SGR parameters of the ANSI style given by the highlighter (like
+    /// `"1;31"` for bold red), empty for the default style
+    pub style: String,
+}
+
+/// Screen contents, drawn like a terminal would do (with auto-wrap and
+/// scrolling)
+#[derive(Clone, Debug)]
+pub struct Grid {
+    pub(crate) cols: usize,
+    pub(crate) rows: usize,
+    /// Cells by row (a wide grapheme is followed by an empty cell).
+    pub(crate) cells: Vec<Vec<Cell>>,
+    /// Cursor position.
+    pub(crate) pos: Position,
+    /// Style of the next cells written.
+    style: String,
+    /// East Asian ambiguous characters are 2 cells wide.
+    wide_ambiguous: bool,
+}
+
+impl Grid {
+    /// Number of columns and rows
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// Cell at `col` in `row` (`None` after the end of the row)
+    pub fn cell(&self, col: usize, row: usize) -> Option<&Cell> {
+        self.cells.get(row).and_then(|cells| cells.get(col))
+    }
+
+    /// Text of `row`, without styles nor trailing spaces
+    pub fn text(&self, row: usize) -> String {
+        self.cells.get(row).map_or_else(String::new, |cells| {
+            let text: String = cells.iter().map(|cell| cell.text.as_str()).collect();
+            text.trim_end().to_owned()
+        })
+    }
+
+    /// Cursor position (column, row)
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.pos.col, self.pos.row)
+    }
+
+    /// Change the size (the rows which do not fit anymore scroll out).
+    pub(crate) fn resize(&mut self, cols: usize, rows: usize) {
+        self.cols = cols;
+        self.rows = cmp::max(rows, 1);
+        while self.cells.len() > self.rows {
+            self.cells.remove(0);
+            self.pos.row = self.pos.row.saturating_sub(1);
+        }
+    }
+
+    /// Write `s` on the screen at the current position like a terminal
+    /// would do (with auto-wrap and scrolling).
+    pub(crate) fn put(&mut self, s: &str) {
+        let mut esc_seq = 0;
+        let mut sequence: Option<String> = None;
+        for g in s.graphemes(true) {
+            if let Some(mut params) = sequence.take() {
+                if g.len() == 1 && g.as_bytes()[0].is_ascii_alphabetic() {
+                    if g == "m" && params.starts_with('[') {
+                        select_graphic_rendition(&mut self.style, &params[1..]);
+                    }
+                } else {
+                    params.push_str(g);
+                    sequence = Some(params);
+                }
+                continue;
+            } else if g == "\x1b" {
+                sequence = Some(String::new());
+                continue;
+            }
+            let w = width(g, &mut esc_seq, self.wide_ambiguous);
+            if g == "\n" || g == "\r\n" {
+                self.pos.col = 0;
+                self.line_feed();
+            } else if g == "\r" {
+                self.pos.col = 0;
+            } else if g == "\x08" {
+                self.pos.col = self.pos.col.saturating_sub(1);
+            } else if w > 0 {
+                if self.pos.col + w > self.cols {
+                    self.pos.col = 0;
+                    self.line_feed();
+                }
+                let row = &mut self.cells[self.pos.row];
+                while row.len() < self.pos.col + w {
+                    row.push(Cell {
+                        text: " ".to_owned(),
+                        style: String::new(),
+                    });
+                }
+                row[self.pos.col] = Cell {
+                    text: g.to_owned(),
+                    style: self.style.clone(),
+                };
+                for cell in row.iter_mut().skip(self.pos.col + 1).take(w - 1) {
+                    cell.text.clear();
+                    cell.style.clone_from(&self.style);
+                }
+                self.pos.col += w;
+            }
+        }
+    }
+
+    fn line_feed(&mut self) {
+        self.pos.row += 1;
+        if self.pos.row == self.rows {
+            self.cells.remove(0);
+            self.pos.row -= 1;
+        }
+        while self.cells.len() <= self.pos.row {
+            self.cells.push(Vec::new());
+        }
+    }
+
+    /// Move the cursor `n` rows down (without scrolling).
+    fn move_down(&mut self, n: usize) {
+        self.pos.row = cmp::min(self.pos.row + n, self.rows - 1);
+        while self.cells.len() <= self.pos.row {
+            self.cells.push(Vec::new());
+        }
+    }
+
+    /// Clear from the cursor to the end of the row.
+    fn clear_eol(&mut self) {
+        self.cells[self.pos.row].truncate(self.pos.col);
+    }
+
+    /// Clear from the cursor to the end of the screen.
+    fn clear_eos(&mut self) {
+        self.clear_eol();
+        self.cells.truncate(self.pos.row + 1);
+    }
+}
+
+impl Renderer for Display {
+    fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        self.cursor = new;
+        let (old, new) = (self.view.screen_pos(old), self.view.screen_pos(new));
+        if new.row > old.row {
+            self.screen.move_down(new.row - old.row);
+        } else {
+            self.screen.pos.row -= cmp::min(self.screen.pos.row, old.row - new.row);
+        }
+        self.screen.pos.col = new.col;
+        Ok(())
+    }
+
+    fn refresh_line(
+        &mut self,
+        prompt: &str,
+        prompt_size: Position,
+        line: &LineBuffer,
+        hint: Option<String>,
+        current_row: usize,
+        old_rows: usize,
+        highlighter: Option<&Highlighter>,
+    ) -> Result<(Position, Position)> {
+        let end_pos = line.position(line.len(), prompt_size, self.screen.cols);
+        let cursor = line.position(line.pos(), prompt_size, self.screen.cols);
+        let old_view = self.view;
+        let size = (self.screen.cols, self.screen.rows);
+        let view = old_view.scroll(line, prompt_size, size, (cursor, end_pos));
+        self.view = view;
+
+        // clear old rows (like the unix renderer)
+        let current_row = old_view.screen_row(current_row);
+        let old_rows = old_view.screen_row(old_rows);
+        self.screen.move_down(old_rows.saturating_sub(current_row));
+        for _ in 0..old_rows {
+            self.screen.pos.col = 0;
+            self.screen.clear_eol();
+            self.screen.pos.row = self.screen.pos.row.saturating_sub(1);
+        }
+        self.screen.pos.col = 0;
+        self.screen.clear_eol();
+        let start_row = self.screen.pos.row;
+
+        self.line.clear();
+        if view.rows > 0 {
+            // the rows of the view only, not highlighted
+            if view.top == 0 {
+                match highlighter {
+                    Some(highlighter) => self.line.push_str(&highlighter.highlight_prompt(prompt)),
+                    None => self.line.push_str(prompt),
+                }
+            }
+            self.prompt_len = self.line.len();
+            let text = &line[view.start..view.end];
+            if view.end < line.len() && text.ends_with('\n') {
+                self.line.push_str(&text[..text.len() - 1]);
+            } else {
+                self.line.push_str(text);
+            }
+        } else if let Some(highlighter) = highlighter {
+            self.line.push_str(&highlighter.highlight_prompt(prompt));
+            self.prompt_len = self.line.len();
+            self.line.push_str(&highlighter.highlight(line, line.pos()));
+        } else {
+            self.line.push_str(prompt);
+            self.prompt_len = self.line.len();
+            self.line.push_str(line);
+        }
+        let end_visible = view.is_visible(end_pos.row);
+        if let Some(hint) = hint.filter(|_| end_visible) {
+            let truncate = truncate(
+                &hint,
+                end_pos.col,
+                self.screen.cols,
+                self.screen.wide_ambiguous,
+            );
+            if let Some(highlighter) = highlighter {
+                self.line.push_str(&highlighter.highlight_hint(truncate));
+            } else {
+                self.line.push_str(truncate);
+            }
+        }
+        self.screen.put(&self.line);
+        if end_visible && end_pos.col == 0 && end_pos.row > 0 && self.screen.pos.col != 0 {
+            self.screen.put("\n");
+        }
+        // position the cursor (the screen may have scrolled)
+        let end_row = self.screen.pos.row;
+        let end_screen_row = view.screen_row(end_pos.row);
+        let start_row = cmp::min(start_row, end_row.saturating_sub(end_screen_row));
+        self.screen.pos.row = start_row + view.screen_row(cursor.row);
+        self.screen.pos.col = cursor.col;
+        self.cursor = cursor;
+        Ok((cursor, end_pos))
+    }
+
+    fn refresh_span(
+        &mut self,
+        prompt_size: Position,
+        line: &LineBuffer,
+        from: usize,
+        hint: Option<String>,
+        current: Position,
+    ) -> Result<Option<(Position, Position)>> {
+        let start = line.position(from, prompt_size, self.screen.cols);
+        let end_pos = line.position(line.len(), prompt_size, self.screen.cols);
+        let cursor = line.position(line.pos(), prompt_size, self.screen.cols);
+        if self.view.rows > 0 || end_pos.row >= self.screen.rows {
+            // the rows of the line are not all displayed
+            return Ok(None);
+        }
+
+        try!(self.move_cursor(current, start));
+        self.screen.clear_eos();
+        // the prompt and the text before `from` are unchanged
+        let span = self.prompt_len + from;
+        self.line.truncate(span);
+        self.line.push_str(&line[from..]);
+        if let Some(hint) = hint {
+            self.line.push_str(truncate(
+                &hint,
+                end_pos.col,
+                self.screen.cols,
+                self.screen.wide_ambiguous,
+            ));
+        }
+        self.screen.put(&self.line[span..]);
+        if end_pos.col == 0 && end_pos.row > 0 && self.screen.pos.col != 0 {
+            self.screen.put("\n");
+        }
+        // position the cursor (the screen may have scrolled)
+        let start_row = self.screen.pos.row.saturating_sub(end_pos.row);
+        self.screen.pos.row = start_row + cursor.row;
+        self.screen.pos.col = cursor.col;
+        self.cursor = cursor;
+        Ok(Some((cursor, end_pos)))
+    }
+
+    fn calculate_position(&self, s: &str, orig: Position) -> Position {
+        let mut pos = orig;
+        let mut esc_seq = 0;
+        for c in s.graphemes(true) {
+            if c == "\n" {
+                pos.row += 1;
+                pos.col = 0;
+                continue;
+            }
+            let cw = width(c, &mut esc_seq, self.screen.wide_ambiguous);
+            pos.col += cw;
+            if pos.col > self.screen.cols {
+                pos.row += 1;
+                pos.col = cw;
+            }
+        }
+        if pos.col == self.screen.cols {
+            pos.col = 0;
+            pos.row += 1;
+        }
+        pos
+    }
+
+    fn write_and_flush(&mut self, buf: &[u8]) -> Result<()> {
+        let text = String::from_utf8_lossy(buf);
+        self.output.push_str(&text);
+        self.screen.put(&text);
+        Ok(())
+    }
+
+    fn beep(&mut self) -> Result<()> {
+        self.beeps += 1;
+        Ok(())
+    }
+
+    fn clear_screen(&mut self) -> Result<()> {
+        self.output.clear();
+        self.screen.cells = vec![Vec::new()];
+        self.screen.style.clear();
+        self.screen.pos = Position::default();
+        Ok(())
+    }
+
+    fn sigwinch(&self) -> bool {
+        false
+    }
+
+    fn update_size(&mut self) {}
+
+    fn get_columns(&self) -> usize {
+        self.screen.cols
+    }
+
+    fn get_rows(&self) -> usize {
+        self.screen.rows
+    }
+
+    fn is_row_visible(&self, row: usize) -> bool {
+        self.view.is_visible(row)
+    }
+
+    fn set_wide_ambiguous(&mut self, wide: bool) {
+        self.screen.wide_ambiguous = wide;
+    }
+
+    fn is_wide_ambiguous(&self) -> bool {
+        self.screen.wide_ambiguous
+    }
+}
+
+/// Apply the SGR `params` (like `"1;31"`) to `style`.
+fn select_graphic_rendition(style: &mut String, params: &str) {
+    if params.is_empty() || params == "0" {
+        style.clear();
+    } else if params.starts_with("0;") {
+        style.clear();
+        style.push_str(params.trim_start_matches("0;"));
+    } else {
+        if !style.is_empty() {
+            style.push(';');
+        }
+        style.push_str(params);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow::{self, Owned};
+    use std::sync::mpsc;
+
+    use super::{Cell, Grid, Surface, Widget};
+    use completion::Completer;
+    use highlight::Highlighter;
+    use hint::Hinter;
+    use keys::KeyPress;
+    use {Editor, Helper, Result};
+
+    struct Recorder {
+        size: (usize, usize),
+        draws: usize,
+        beeps: usize,
+    }
+
+    impl Surface for Recorder {
+        fn size(&self) -> (usize, usize) {
+            self.size
+        }
+
+        fn draw(&mut self, _: &Grid) {
+            self.draws += 1;
+        }
+
+        fn beep(&mut self) {
+            self.beeps += 1;
+        }
+    }
+
+    struct GreenPrompt;
+
+    impl Completer for GreenPrompt {
+        type Candidate = String;
+
+        fn complete(&self, _: &str, _: usize) -> Result<(usize, Vec<String>)> {
+            Ok((0, vec![]))
+        }
+    }
+    impl Hinter for GreenPrompt {
+        fn hint(&self, _: &str, _: usize) -> Option<String> {
+            None
+        }
+    }
+    impl Highlighter for GreenPrompt {
+        fn highlight_prompt<'p>(&self, prompt: &'p str) -> Cow<'p, str> {
+            Owned(format!("\x1b[1;32m{}\x1b[0m", prompt))
+        }
+    }
+    impl Helper for GreenPrompt {}
+
+    #[test]
+    fn widget() {
+        let mut editor = Editor::new();
+        editor.set_helper(Some(GreenPrompt));
+        let mut widget = Widget::new(Recorder {
+            size: (10, 3),
+            draws: 0,
+            beeps: 0,
+        });
+        let (keys, mut events) = mpsc::channel();
+        for key in &[KeyPress::Char('a'), KeyPress::Backspace, KeyPress::Tab] {
+            keys.send(*key).unwrap();
+        }
+        for c in "0123456789ab".chars() {
+            keys.send(KeyPress::Char(c)).unwrap();
+        }
+        keys.send(KeyPress::Enter).unwrap();
+        let line = widget.readline(&mut editor, "> ", &mut events).unwrap();
+        assert_eq!("0123456789ab", line);
+        assert!(widget.surface().draws > 0);
+        assert_eq!(1, widget.surface().beeps);
+        let grid = widget.grid();
+        assert_eq!("> 01234567", grid.text(0));
+        assert_eq!("89ab", grid.text(1));
+        assert_eq!((0, 2), grid.cursor());
+        assert_eq!(
+            Some(&Cell {
+                text: ">".to_owned(),
+                style: "1;32".to_owned(),
+            }),
+            grid.cell(0, 0)
+        );
+        assert_eq!("", grid.cell(2, 0).unwrap().style);
+
+        widget.print("done\n");
+        assert_eq!("89ab", widget.grid().text(0)); // scrolled
+        widget.surface_mut().size = (10, 2);
+        drop(keys);
+        assert!(widget.readline(&mut editor, "> ", &mut events).is_err());
+        assert_eq!((10, 2), widget.grid().size());
+    }
+}
@@ -31,6 +31,42 @@ pub enum ReadlineError {
     Decode(char::DecodeUtf16Error),
 }
 
+/// Broad category of a `ReadlineError`, independent of the platform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// End of input (Ctrl-D or closed stdin)
+    Eof,
+    /// Canceled by the user (Ctrl-C)
+    Interrupted,
+    /// Input bytes or code units that cannot be decoded
+    Decode,
+    /// Terminal setup or query (raw mode, size) failed
+    Terminal,
+    /// Read or write on the backend failed
+    Io,
+    /// Terminal resized while reading (Windows)
+    WindowResize,
+}
+
+impl ReadlineError {
+    /// Tell the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match *self {
+            ReadlineError::Io(_) => ErrorKind::Io,
+            ReadlineError::Eof => ErrorKind::Eof,
+            ReadlineError::Interrupted => ErrorKind::Interrupted,
+            #[cfg(unix)]
+            ReadlineError::Utf8Error => ErrorKind::Decode,
+            #[cfg(unix)]
+            ReadlineError::Errno(_) => ErrorKind::Terminal,
+            #[cfg(windows)]
+            ReadlineError::WindowResize => ErrorKind::WindowResize,
+            #[cfg(windows)]
+            ReadlineError::Decode(_) => ErrorKind::Decode,
+        }
+    }
+}
+
 impl fmt::Display for ReadlineError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
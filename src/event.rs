@@ -0,0 +1,64 @@
+//! Events pushed into the loop of `readline` from other threads (like the
+//! messages received by a chat client), with the `EventSender` returned by
+//! `Editor::event_sender`.
+//!
+//! The events are handled while waiting for the next key (the reader is
+//! polled every 50 ms while there is a sender), or on the next `readline`
+//! call when none is in progress.
+//!
+//! ```no_run
+//! use rustyline::{Cmd, Editor};
+//! use std::thread;
+//! let mut editor = Editor::<()>::new();
+//! let events = editor.event_sender();
+//! thread::spawn(move || {
+//!     events.print("<bob> hello");
+//!     // pre-fill the edited line
+//!     events.send_cmd(Cmd::Insert(1, "hi bob".to_owned()));
+//! });
+//! let line = editor.readline("> ");
+//! ```
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+use keymap::Cmd;
+
+/// Interval of the polling of the events while waiting for a key
+pub(crate) const EVENT_POLL: Duration = Duration::from_millis(50);
+
+pub(crate) enum Event {
+    Print(String),
+    Cmd(Cmd),
+    Refresh,
+}
+
+/// Handle to push events into the editor loop (see the module
+/// documentation), which can be cloned and sent to other threads.
+///
+/// Each method returns `false` when the editor has been dropped.
+#[derive(Clone)]
+pub struct EventSender(pub(crate) Sender<Event>);
+
+impl EventSender {
+    /// Write `text` (followed by a new line if it has none) above the
+    /// prompt, and repaint the edited line under it.
+    pub fn print(&self, text: &str) -> bool {
+        self.send(Event::Print(text.to_owned()))
+    }
+
+    /// Execute `cmd` as if it was bound to the next key pressed (like
+    /// `Cmd::Insert` to pre-fill the line, `Cmd::Kill(Movement::WholeLine)`
+    /// to clear it or `Cmd::AcceptLine` to submit it).
+    pub fn send_cmd(&self, cmd: Cmd) -> bool {
+        self.send(Event::Cmd(cmd))
+    }
+
+    /// Repaint the prompt (rendered again) and the edited line.
+    pub fn refresh(&self) -> bool {
+        self.send(Event::Refresh)
+    }
+
+    fn send(&self, event: Event) -> bool {
+        self.0.send(event).is_ok()
+    }
+}
@@ -0,0 +1,176 @@
+//! Multi-field prompts (wizard), each field being read by `Editor::readline_with`
+use std::io::Write;
+use std::result;
+use std::slice;
+
+use config::ReadlineOptions;
+use {output_stream, Editor, Helper, Result};
+
+/// Check a field value: the error message is displayed and the field is read
+/// again.
+pub type Validator = Box<Fn(&str) -> result::Result<(), String>>;
+
+struct Field<H: Helper> {
+    name: String,
+    prompt: String,
+    secret: bool,
+    default: Option<String>,
+    helper: Option<H>,
+    validator: Option<Validator>,
+}
+
+/// Fields prompted in sequence, created by `Editor::form`.
+///
+/// The `prompt`, `default`, `helper` and `validator` methods apply to the
+/// last added field.
+///
+/// ```no_run
+/// use rustyline::Editor;
+/// let mut editor = Editor::<()>::new();
+/// let answers = editor
+///     .form()
+///     .field("host")
+///     .default("localhost")
+///     .validator(|host| if host.is_empty() { Err("empty host".to_owned()) } else { Ok(()) })
+///     .field_secret("password")
+///     .run()
+///     .unwrap();
+/// println!("{:?}", answers.get("host"));
+/// ```
+pub struct Form<'e, H: Helper + 'e> {
+    editor: &'e mut Editor<H>,
+    fields: Vec<Field<H>>,
+}
+
+impl<'e, H: Helper> Form<'e, H> {
+    pub(crate) fn new(editor: &'e mut Editor<H>) -> Form<'e, H> {
+        Form {
+            editor,
+            fields: Vec::new(),
+        }
+    }
+
+    /// Add a field prompted with "`name`: ".
+    pub fn field(self, name: &str) -> Form<'e, H> {
+        self.add(name, false)
+    }
+
+    /// Add a field whose value is hidden (see `ReadlineOptions::secret`).
+    pub fn field_secret(self, name: &str) -> Form<'e, H> {
+        self.add(name, true)
+    }
+
+    fn add(mut self, name: &str, secret: bool) -> Form<'e, H> {
+        self.fields.push(Field {
+            name: name.to_owned(),
+            prompt: format!("{}: ", name),
+            secret,
+            default: None,
+            helper: None,
+            validator: None,
+        });
+        self
+    }
+
+    /// Replace the prompt of the last field.
+    pub fn prompt(mut self, prompt: &str) -> Form<'e, H> {
+        self.last().prompt = prompt.to_owned();
+        self
+    }
+
+    /// Initial value of the last field.
+    pub fn default(mut self, value: &str) -> Form<'e, H> {
+        self.last().default = Some(value.to_owned());
+        self
+    }
+
+    /// Completer, hinter and highlighter of the last field (instead of the
+    /// editor's helper).
+    pub fn helper(mut self, helper: H) -> Form<'e, H> {
+        self.last().helper = Some(helper);
+        self
+    }
+
+    /// Validator of the last field.
+    pub fn validator<F>(mut self, validator: F) -> Form<'e, H>
+    where
+        F: Fn(&str) -> result::Result<(), String> + 'static,
+    {
+        self.last().validator = Some(Box::new(validator));
+        self
+    }
+
+    fn last(&mut self) -> &mut Field<H> {
+        self.fields
+            .last_mut()
+            .expect("no field: `field` must be called first")
+    }
+
+    /// Read the fields in sequence (the answers are not added to the
+    /// history).
+    ///
+    /// Stop at the first error (like `Interrupted` or `Eof`).
+    pub fn run(self) -> Result<Answers> {
+        let editor = self.editor;
+        let mut values = Vec::with_capacity(self.fields.len());
+        for mut field in self.fields {
+            let options = ReadlineOptions::new()
+                .auto_add_history(false)
+                .history(false)
+                .secret(field.secret);
+            let helper = match field.helper.take() {
+                Some(helper) => Some(editor.helper.replace(helper)),
+                None => None,
+            };
+            let value = read_field(editor, &field, &options);
+            if let Some(helper) = helper {
+                editor.helper = helper;
+            }
+            values.push((field.name, try!(value)));
+        }
+        Ok(Answers { values })
+    }
+}
+
+fn read_field<H: Helper>(
+    editor: &mut Editor<H>,
+    field: &Field<H>,
+    options: &ReadlineOptions,
+) -> Result<String> {
+    let initial = field.default.as_ref().map(|value| (value.as_str(), ""));
+    loop {
+        let value = try!(editor.readline_internal(&field.prompt, initial, options));
+        match field.validator {
+            Some(ref validator) => match validator(&value) {
+                Ok(()) => return Ok(value),
+                Err(msg) => {
+                    let mut out = output_stream(editor.config.output_stream());
+                    try!(writeln!(out, "{}", msg));
+                    try!(out.flush());
+                }
+            },
+            None => return Ok(value),
+        }
+    }
+}
+
+/// Field values returned by `Form::run`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Answers {
+    values: Vec<(String, String)>,
+}
+
+impl Answers {
+    /// Value of the field `name`
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .iter()
+            .find(|pair| pair.0 == name)
+            .map(|pair| pair.1.as_str())
+    }
+
+    /// `(name, value)` pairs, in field order
+    pub fn iter(&self) -> slice::Iter<'_, (String, String)> {
+        self.values.iter()
+    }
+}
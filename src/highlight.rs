@@ -1,7 +1,8 @@
 //! Syntax highlighting
 
 use config::CompletionType;
-use std::borrow::Cow::{self, Borrowed};
+use std::borrow::Cow::{self, Borrowed, Owned};
+use std::ops::Range;
 
 /// Syntax highlighter with [ansi color](https://en.wikipedia.org/wiki/ANSI_escape_code#SGR_(Select_Graphic_Rendition)_parameters).
 /// Rustyline will try to handle escape sequence for ansi color on windows
@@ -62,3 +63,560 @@ pub trait Highlighter {
 }
 
 impl Highlighter for () {}
+
+/// Styles used by the editor itself, whatever the `Highlighter` is.
+///
+/// Each style is a list of
+/// [SGR](https://en.wikipedia.org/wiki/ANSI_escape_code#SGR_(Select_Graphic_Rendition)_parameters)
+/// parameters (like `"1;34"` for bold blue).
+///
+/// ```
+/// use rustyline::highlight::Theme;
+/// use rustyline::Config;
+/// let config = Config::builder()
+///     .theme(Theme::new().hint("1;30").candidate_match("1"))
+///     .build();
+/// ```
+///
+/// On a monochrome terminal (see `ColorMode::Monochrome`), a style without
+/// any text attribute (bold, underline, reverse...) falls back to a default
+/// attribute, or to the hint markers for hints.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Theme {
+    hint: Option<&'static str>,
+    hint_markers: Option<(&'static str, &'static str)>,
+    selection: Option<&'static str>,
+    search_match: Option<&'static str>,
+    candidate_match: Option<&'static str>,
+    menu_selection: Option<&'static str>,
+    description: Option<&'static str>,
+}
+
+impl Theme {
+    pub fn new() -> Theme {
+        Self::default()
+    }
+
+    /// Style of hints, used only when `Highlighter::highlight_hint` leaves
+    /// the hint unchanged.
+    pub fn hint(mut self, style: &'static str) -> Theme {
+        self.hint = Some(style);
+        self
+    }
+
+    /// Text displayed around hints when they cannot be styled (like
+    /// `("[", "]")`): when colors are disabled, or on a monochrome terminal
+    /// when the hint style has no attribute.
+    pub fn hint_markers(mut self, prefix: &'static str, suffix: &'static str) -> Theme {
+        self.hint_markers = Some((prefix, suffix));
+        self
+    }
+
+    /// Style of the selected region (reverse video by default).
+    pub fn selection(mut self, style: &'static str) -> Theme {
+        self.selection = Some(style);
+        self
+    }
+
+    /// Style of the searched text in the incremental search prompt.
+    pub fn search_match(mut self, style: &'static str) -> Theme {
+        self.search_match = Some(style);
+        self
+    }
+
+    /// Style of the prefix common to all candidates when listing
+    /// completions (or of the fuzzy matched characters, bold by default),
+    /// used only when `Highlighter::highlight_candidate` leaves the
+    /// candidate unchanged.
+    pub fn candidate_match(mut self, style: &'static str) -> Theme {
+        self.candidate_match = Some(style);
+        self
+    }
+
+    /// Style of the selected candidate of the completion menu (see
+    /// `CompletionType::Menu`), reverse video by default.
+    pub fn menu_selection(mut self, style: &'static str) -> Theme {
+        self.menu_selection = Some(style);
+        self
+    }
+
+    /// Style of the descriptions of the candidates (see
+    /// `Candidate::description`), dim by default.
+    pub fn description(mut self, style: &'static str) -> Theme {
+        self.description = Some(style);
+        self
+    }
+
+    pub fn hint_style(&self) -> Option<&'static str> {
+        self.hint
+    }
+
+    pub fn hint_marker_pair(&self) -> Option<(&'static str, &'static str)> {
+        self.hint_markers
+    }
+
+    pub fn selection_style(&self) -> Option<&'static str> {
+        self.selection
+    }
+
+    pub fn search_match_style(&self) -> Option<&'static str> {
+        self.search_match
+    }
+
+    pub fn candidate_match_style(&self) -> Option<&'static str> {
+        self.candidate_match
+    }
+
+    pub fn menu_selection_style(&self) -> Option<&'static str> {
+        self.menu_selection
+    }
+
+    pub fn description_style(&self) -> Option<&'static str> {
+        self.description
+    }
+
+    /// Tell if no style is defined.
+    pub fn is_empty(&self) -> bool {
+        *self == Theme::default()
+    }
+
+    /// Replace the styles which would be indistinguishable on a monochrome
+    /// terminal (only colors) by text attributes.
+    pub(crate) fn monochrome(&self) -> Theme {
+        let fallback = |style: Option<&'static str>, attribute| match style {
+            Some(style) if has_attribute(style) => Some(style),
+            _ => Some(attribute),
+        };
+        Theme {
+            hint: match self.hint {
+                Some(style) if has_attribute(style) => Some(style),
+                _ if self.hint_markers.is_some() => None,
+                _ => Some("4"), // underline
+            },
+            hint_markers: self.hint_markers,
+            selection: fallback(self.selection, "7"), // reverse
+            search_match: fallback(self.search_match, "4"), // underline
+            candidate_match: fallback(self.candidate_match, "1"), // bold
+            menu_selection: fallback(self.menu_selection, "7"), // reverse
+            description: fallback(self.description, "2"), // dim
+        }
+    }
+}
+
+/// Tell if the SGR `style` contains some text attribute (not only colors).
+fn has_attribute(style: &str) -> bool {
+    let mut params = style.split(';').map(|p| p.parse::<u8>().unwrap_or(0));
+    while let Some(param) = params.next() {
+        match param {
+            1...9 | 21 | 53 => return true,
+            // extended colors: 38;5;n or 38;2;r;g;b
+            38 | 48 | 58 => {
+                let n = if params.next() == Some(5) { 1 } else { 3 };
+                for _ in 0..n {
+                    params.next();
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Wrap `s` with the specified `style`.
+pub(crate) fn paint<'s>(style: Option<&str>, s: &'s str) -> Cow<'s, str> {
+    match style {
+        Some(style) if !s.is_empty() => Owned(format!("\x1b[{}m{}\x1b[0m", style, s)),
+        _ => Borrowed(s),
+    }
+}
+
+/// Highlight the partner of the bracket under the cursor (or before it), like
+/// readline's `blink-matching-paren`.
+///
+/// Optionally, the partner of an XML/HTML tag (`<a>` and `</a>`) or of an
+/// application keyword (like `begin` and `end`) is also highlighted.
+///
+/// ```
+/// use rustyline::highlight::{Highlighter, MatchingBracketHighlighter};
+/// let highlighter = MatchingBracketHighlighter::new()
+///     .tags(true)
+///     .keywords("begin", "end");
+/// assert_eq!(Some(4..8), highlighter.partner("<b>x</b>", 1));
+/// assert_eq!(Some(0..5), highlighter.partner("begin x; end", 10));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatchingBracketHighlighter {
+    style: &'static str,
+    tags: bool,
+    keywords: Vec<(String, String)>,
+}
+
+impl Default for MatchingBracketHighlighter {
+    fn default() -> MatchingBracketHighlighter {
+        MatchingBracketHighlighter {
+            style: "1;34", // bold blue
+            tags: false,
+            keywords: Vec::new(),
+        }
+    }
+}
+
+impl MatchingBracketHighlighter {
+    pub fn new() -> MatchingBracketHighlighter {
+        Self::default()
+    }
+
+    /// Style of the partner (bold blue by default).
+    pub fn style(mut self, style: &'static str) -> MatchingBracketHighlighter {
+        self.style = style;
+        self
+    }
+
+    /// Also match XML/HTML open and close tags (self-closing tags have no
+    /// partner).
+    pub fn tags(mut self, yes: bool) -> MatchingBracketHighlighter {
+        self.tags = yes;
+        self
+    }
+
+    /// Also match the `open` and `close` keywords (matched as whole words).
+    pub fn keywords(mut self, open: &str, close: &str) -> MatchingBracketHighlighter {
+        self.keywords.push((open.to_owned(), close.to_owned()));
+        self
+    }
+
+    /// Byte range of the partner of the construct at (or just before) `pos`.
+    pub fn partner(&self, line: &str, pos: usize) -> Option<Range<usize>> {
+        let mut positions = vec![pos];
+        if let Some(c) = line[..pos].chars().next_back() {
+            positions.push(pos - c.len_utf8());
+        }
+        for &pos in &positions {
+            if let Some(partner) = bracket_partner(line, pos) {
+                return Some(partner);
+            }
+        }
+        if self.tags {
+            if let Some(partner) = tag_partner(line, pos) {
+                return Some(partner);
+            }
+        }
+        self.keywords
+            .iter()
+            .filter_map(|pair| keyword_partner(line, pos, &pair.0, &pair.1))
+            .next()
+    }
+}
+
+impl Highlighter for MatchingBracketHighlighter {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        match self.partner(line, pos) {
+            Some(range) => Owned(format!(
+                "{}{}{}",
+                &line[..range.start],
+                paint(Some(self.style), &line[range.clone()]),
+                &line[range.end..]
+            )),
+            None => Borrowed(line),
+        }
+    }
+
+    fn highlight_char(&self, grapheme: &str) -> bool {
+        // a tag or keyword is detected by any of its characters
+        self.tags || !self.keywords.is_empty() || grapheme.len() == 1 && "()[]{}".contains(grapheme)
+    }
+}
+
+fn bracket_partner(line: &str, pos: usize) -> Option<Range<usize>> {
+    let (open, close, forward) = match line[pos..].chars().next() {
+        Some('(') => ('(', ')', true),
+        Some('[') => ('[', ']', true),
+        Some('{') => ('{', '}', true),
+        Some(')') => ('(', ')', false),
+        Some(']') => ('[', ']', false),
+        Some('}') => ('{', '}', false),
+        _ => return None,
+    };
+    let mut depth = 0;
+    if forward {
+        for (i, c) in line[pos..].char_indices() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(pos + i..pos + i + 1);
+                }
+            }
+        }
+    } else {
+        for (i, c) in line[..=pos].char_indices().rev() {
+            if c == close {
+                depth += 1;
+            } else if c == open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i..i + 1);
+                }
+            }
+        }
+    }
+    None
+}
+
+struct Tag<'l> {
+    span: Range<usize>,
+    name: &'l str,
+    closing: bool,
+    self_closing: bool,
+}
+
+/// Tags (`<...>`) of `line`, in order.
+fn tags(line: &str) -> Vec<Tag<'_>> {
+    let mut tags = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices() {
+        match c {
+            '<' => start = Some(i),
+            '>' => if let Some(start) = start.take() {
+                let content = &line[start + 1..i];
+                let closing = content.starts_with('/');
+                let name = content.trim_start_matches('/');
+                let end = name
+                    .find(|c: char| c.is_whitespace() || c == '/')
+                    .unwrap_or(name.len());
+                if end > 0 {
+                    tags.push(Tag {
+                        span: start..i + 1,
+                        name: &name[..end],
+                        closing,
+                        self_closing: content.ends_with('/'),
+                    });
+                }
+            },
+            _ => {}
+        }
+    }
+    tags
+}
+
+fn tag_partner(line: &str, pos: usize) -> Option<Range<usize>> {
+    let tags = tags(line);
+    let index = match tags
+        .iter()
+        .position(|tag| tag.span.start <= pos && pos < tag.span.end)
+    {
+        Some(index) if !tags[index].self_closing => index,
+        _ => return None,
+    };
+    let tag = &tags[index];
+    let mut depth = 0;
+    let same = |other: &&Tag| other.name == tag.name && !other.self_closing;
+    if tag.closing {
+        for other in tags[..=index].iter().rev().filter(same) {
+            depth += if other.closing { 1 } else { -1 };
+            if depth == 0 {
+                return Some(other.span.clone());
+            }
+        }
+    } else {
+        for other in tags[index..].iter().filter(same) {
+            depth += if other.closing { -1 } else { 1 };
+            if depth == 0 {
+                return Some(other.span.clone());
+            }
+        }
+    }
+    None
+}
+
+fn keyword_partner(line: &str, pos: usize, open: &str, close: &str) -> Option<Range<usize>> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+        match (start, is_word_char(c)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                let word = &line[s..i];
+                if word == open || word == close {
+                    words.push((s..i, word == open));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    let (index, opening) = match words
+        .iter()
+        .position(|word| word.0.start <= pos && pos <= word.0.end)
+    {
+        Some(index) => (index, words[index].1),
+        None => return None,
+    };
+    let mut depth = 0;
+    if opening {
+        for &(ref span, open) in &words[index..] {
+            depth += if open { 1 } else { -1 };
+            if depth == 0 {
+                return Some(span.clone());
+            }
+        }
+    } else {
+        for &(ref span, open) in words[..=index].iter().rev() {
+            depth += if open { -1 } else { 1 };
+            if depth == 0 {
+                return Some(span.clone());
+            }
+        }
+    }
+    None
+}
+
+/// Apply the `Theme` on top of the user's `Highlighter`.
+pub(crate) struct Themed<'h> {
+    pub highlighter: Option<&'h Highlighter>,
+    pub theme: Theme,
+}
+
+impl<'h> Highlighter for Themed<'h> {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        match self.highlighter {
+            Some(h) => h.highlight(line, pos),
+            None => Borrowed(line),
+        }
+    }
+
+    fn highlight_prompt<'p>(&self, prompt: &'p str) -> Cow<'p, str> {
+        match self.highlighter {
+            Some(h) => h.highlight_prompt(prompt),
+            None => Borrowed(prompt),
+        }
+    }
+
+    fn highlight_hint<'h2>(&self, hint: &'h2 str) -> Cow<'h2, str> {
+        match self.highlighter.map(|h| h.highlight_hint(hint)) {
+            Some(Owned(hint)) => Owned(hint),
+            _ => paint(self.theme.hint, hint),
+        }
+    }
+
+    fn highlight_candidate<'c>(
+        &self,
+        candidate: &'c str,
+        completion: CompletionType,
+    ) -> Cow<'c, str> {
+        match self.highlighter {
+            Some(h) => h.highlight_candidate(candidate, completion),
+            None => Borrowed(candidate),
+        }
+    }
+
+    fn highlight_char(&self, grapheme: &str) -> bool {
+        self.highlighter.map_or(false, |h| h.highlight_char(grapheme))
+    }
+}
+
+/// Paint the selected region instead of using the user's `Highlighter` for
+/// the line.
+pub(crate) struct Selected<'h> {
+    pub highlighter: Option<&'h Highlighter>,
+    pub range: Range<usize>,
+    pub style: &'static str,
+}
+
+impl<'h> Highlighter for Selected<'h> {
+    fn highlight<'l>(&self, line: &'l str, _: usize) -> Cow<'l, str> {
+        Owned(format!(
+            "{}{}{}",
+            &line[..self.range.start],
+            paint(Some(self.style), &line[self.range.clone()]),
+            &line[self.range.end..]
+        ))
+    }
+
+    fn highlight_prompt<'p>(&self, prompt: &'p str) -> Cow<'p, str> {
+        match self.highlighter {
+            Some(h) => h.highlight_prompt(prompt),
+            None => Borrowed(prompt),
+        }
+    }
+
+    fn highlight_hint<'h2>(&self, hint: &'h2 str) -> Cow<'h2, str> {
+        match self.highlighter {
+            Some(h) => h.highlight_hint(hint),
+            None => Borrowed(hint),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Highlighter, MatchingBracketHighlighter, Selected, Theme, Themed};
+
+    #[test]
+    fn themed_hint() {
+        let themed = Themed {
+            highlighter: None,
+            theme: Theme::new().hint("1;30"),
+        };
+        assert_eq!("\x1b[1;30mhint\x1b[0m", themed.highlight_hint("hint"));
+        assert_eq!("", themed.highlight_hint(""));
+        assert_eq!("line", themed.highlight("line", 0));
+    }
+
+    #[test]
+    fn monochrome() {
+        let theme = Theme::new()
+            .hint("90")
+            .selection("1;44")
+            .search_match("38;5;4")
+            .monochrome();
+        assert_eq!(Some("4"), theme.hint_style());
+        assert_eq!(Some("1;44"), theme.selection_style());
+        assert_eq!(Some("4"), theme.search_match_style());
+        assert_eq!(Some("1"), theme.candidate_match_style());
+        assert_eq!(Some("7"), theme.menu_selection_style());
+        assert_eq!(Some("2"), theme.description_style());
+
+        let theme = Theme::new().hint_markers("[", "]").monochrome();
+        assert_eq!(None, theme.hint_style());
+        assert_eq!(Some(("[", "]")), theme.hint_marker_pair());
+    }
+
+    #[test]
+    fn selected() {
+        let selected = Selected {
+            highlighter: None,
+            range: 2..4,
+            style: "7",
+        };
+        assert_eq!("li\x1b[7mne\x1b[0m!", selected.highlight("line!", 0));
+    }
+
+    #[test]
+    fn matching_bracket() {
+        let highlighter = MatchingBracketHighlighter::new()
+            .tags(true)
+            .keywords("begin", "end");
+        assert_eq!(Some(6..7), highlighter.partner("(a [b])", 0));
+        assert_eq!(Some(3..4), highlighter.partner("(a [b])", 5));
+        assert_eq!(Some(0..1), highlighter.partner("(a [b])", 7));
+        assert_eq!(None, highlighter.partner("(a [b]", 0));
+        // nested tags
+        let html = "<ul><li>a</li><br/><li>b</li></ul>";
+        assert_eq!(Some(29..34), highlighter.partner(html, 1));
+        assert_eq!(Some(9..14), highlighter.partner(html, 5));
+        assert_eq!(Some(19..23), highlighter.partner(html, 25));
+        assert_eq!(None, highlighter.partner(html, 16));
+        // nested keywords
+        let code = "begin begin end x end";
+        assert_eq!(Some(18..21), highlighter.partner(code, 2));
+        assert_eq!(Some(6..11), highlighter.partner(code, 13));
+        assert_eq!(None, highlighter.partner("x", 0));
+        assert_eq!(
+            "(a\x1b[1;34m)\x1b[0m",
+            MatchingBracketHighlighter::new().highlight("(a)", 0)
+        );
+    }
+}
@@ -1,11 +1,19 @@
 //! Hints (suggestions at the right of the prompt as you type).
 
+use HelperContext;
+
 /// Hints provider
 pub trait Hinter {
     /// Takes the currently edited `line` with the cursor `pos`ition and
     /// returns the string that should be displayed or `None`
     /// if no hint is available for the text the user currently typed.
     fn hint(&self, line: &str, pos: usize) -> Option<String>;
+    /// Like `hint`, with access to the history (`ctx`): this is the method
+    /// called by the editor, which ignores `ctx` by default.
+    fn hint_with_context(&self, line: &str, pos: usize, ctx: &HelperContext) -> Option<String> {
+        let _ = ctx;
+        self.hint(line, pos)
+    }
 }
 
 impl Hinter for () {
@@ -13,3 +21,53 @@ impl Hinter for () {
         None
     }
 }
+
+/// Hint the end of the most recent history entry starting with the line
+/// (when the cursor is at the end of the line).
+pub struct HistoryHinter;
+
+impl Hinter for HistoryHinter {
+    /// No hint without the history.
+    fn hint(&self, _line: &str, _pos: usize) -> Option<String> {
+        None
+    }
+
+    fn hint_with_context(&self, line: &str, pos: usize, ctx: &HelperContext) -> Option<String> {
+        if line.is_empty() || pos < line.len() {
+            return None;
+        }
+        ctx.history().and_then(|history| {
+            history
+                .iter()
+                .rev()
+                .find(|entry| entry.len() > line.len() && entry.starts_with(line))
+                .map(|entry| entry[pos..].to_owned())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hinter, HistoryHinter};
+    use history::History;
+    use HelperContext;
+
+    #[test]
+    fn history_hinter() {
+        let mut history = History::new();
+        history.add("git pull");
+        history.add("git push");
+        history.add("git");
+        let ctx = HelperContext::new(Some(&history), history.len());
+        assert_eq!(
+            Some("sh".to_owned()),
+            HistoryHinter.hint_with_context("git pu", 6, &ctx)
+        );
+        assert_eq!(None, HistoryHinter.hint_with_context("git pu", 5, &ctx));
+        assert_eq!(
+            None,
+            HistoryHinter.hint_with_context("git", 3, &HelperContext::new(None, 0))
+        );
+        assert_eq!(None, HistoryHinter.hint_with_context("ls", 2, &ctx));
+    }
+}
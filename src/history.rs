@@ -1,16 +1,45 @@
 //! History API
+//!
+//! With `HistoryFormat::Extended`, the history file records the metadata of
+//! each entry (`EntryInfo`) on its line, after a `#V2` header line:
+//!
+//! ```text
+//! #V2
+//! 1546300800\t1520\t0\tsession1\tcargo build
+//! 1546300805\t\t\t\techo "a\\tb"
+//! ```
+//!
+//! The fields are separated by tabs: the time (in seconds since the Unix
+//! epoch), the duration (in milliseconds), the exit status, the session and
+//! the entry (with its tabs, new lines and `\\` escaped). The unknown ones
+//! are empty. Files without the header (`HistoryFormat::Plain`) hold one
+//! entry per line and are still loaded.
 
 #[cfg(unix)]
 use libc;
+use std::borrow::Borrow;
+use std::cell::RefCell;
 use std::collections::vec_deque;
-use std::collections::VecDeque;
+use std::collections::Bound::{Included, Unbounded};
+use std::collections::{BTreeMap, VecDeque};
 use std::fs::File;
+use std::io;
 use std::iter::DoubleEndedIterator;
+use std::mem;
 use std::ops::Index;
 use std::path::Path;
+use std::result;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use super::Result;
-use config::{Config, HistoryDuplicates};
+use config::{Config, HistoryDuplicates, HistoryFormat};
+use regex::Regex;
+use snapshot::{escape, unescape};
+
+/// First line of the `HistoryFormat::Extended` files
+const EXTENDED_HEADER: &str = "#V2";
 
 /// Search direction
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -19,13 +48,99 @@ pub enum Direction {
     Reverse,
 }
 
+/// Outcome of `History::add`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddResult {
+    /// The entry has been appended.
+    Added,
+    /// The entry has been appended and the oldest one removed (the history
+    /// was full).
+    AddedTrimmingOldest,
+    /// The entry has been ignored because it is empty.
+    IgnoredEmpty,
+    /// The entry has been ignored because it starts with a space
+    /// (`history_ignore_space`).
+    IgnoredSpace,
+    /// The entry has been ignored because it is the same as the previous one
+    /// (`HistoryDuplicates::IgnoreConsecutive`).
+    IgnoredDuplicate,
+    /// The entry has been ignored because the history is disabled
+    /// (`max_history_size` is 0).
+    Disabled,
+}
+
+impl AddResult {
+    /// Tell if the entry has been appended.
+    pub fn is_added(self) -> bool {
+        match self {
+            AddResult::Added | AddResult::AddedTrimmingOldest => true,
+            _ => false,
+        }
+    }
+}
+
+/// Metadata of a history entry (see `History::info`)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntryInfo {
+    /// When the entry has been added (unknown for the entries loaded from a
+    /// plain file)
+    pub time: Option<SystemTime>,
+    /// How long the command took to run
+    pub duration: Option<Duration>,
+    /// Exit status of the command
+    pub exit_status: Option<i32>,
+    /// Session which has added the entry (see `History::set_session`)
+    pub session: Option<Arc<str>>,
+}
+
+/// Memory used by the history entries (see `History::stats`), to tune
+/// `max_history_size`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HistoryStats {
+    /// Number of entries
+    pub entries: usize,
+    /// Number of distinct entries (each text is stored once)
+    pub distinct_entries: usize,
+    /// Size (in bytes) of the distinct texts
+    pub text_bytes: usize,
+    /// Size (in bytes) saved by sharing the text of identical entries
+    pub shared_bytes: usize,
+}
+
+/// Text shared by identical entries, looked up by `str`
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Text(Arc<String>);
+
+impl Borrow<str> for Text {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
 /// Current state of the history.
+///
+/// Identical entries (when duplicates are kept) share their text.
 #[derive(Default)]
 pub struct History {
-    entries: VecDeque<String>,
+    entries: VecDeque<Arc<String>>,
+    /// Metadata of each entry
+    infos: VecDeque<EntryInfo>,
+    /// Distinct texts of the entries, sorted so that the ones starting with a
+    /// prefix are contiguous, with the (ascending) identifiers of the entries
+    /// sharing each of them
+    texts: BTreeMap<Text, Vec<usize>>,
     max_len: usize,
     pub(crate) ignore_space: bool,
     pub(crate) ignore_dups: bool,
+    pub(crate) format: HistoryFormat,
+    /// Session of the entries added
+    session: Option<Arc<str>>,
+    /// Number of entries removed from the front (so that `evicted + index`
+    /// identifies an entry even after older ones have been removed).
+    evicted: usize,
+    /// Last prefix searched and the (ascending) identifiers of the entries
+    /// starting with it (gathered from `texts`).
+    prefix_index: RefCell<Option<(String, Vec<usize>)>>,
 }
 
 impl History {
@@ -36,48 +151,158 @@ impl History {
     pub fn with_config(config: Config) -> History {
         History {
             entries: VecDeque::new(),
+            infos: VecDeque::new(),
+            texts: BTreeMap::new(),
             max_len: config.max_history_size(),
             ignore_space: config.history_ignore_space(),
             ignore_dups: config.history_duplicates() == HistoryDuplicates::IgnoreConsecutive,
+            format: config.history_format(),
+            session: None,
+            evicted: 0,
+            prefix_index: RefCell::new(None),
         }
     }
 
     /// Return the history entry at position `index`, starting from 0.
     pub fn get(&self, index: usize) -> Option<&String> {
-        self.entries.get(index)
+        self.entries.get(index).map(|entry| &**entry)
     }
 
     /// Return the last history entry (i.e. previous command)
     pub fn last(&self) -> Option<&String> {
-        self.entries.back()
+        self.entries.back().map(|entry| &**entry)
+    }
+
+    /// Return the metadata of the entry at position `index`.
+    pub fn info(&self, index: usize) -> Option<&EntryInfo> {
+        self.infos.get(index)
+    }
+
+    /// Return the metadata of the entry at position `index`, to record the
+    /// duration and exit status of the command once it has run.
+    pub fn info_mut(&mut self, index: usize) -> Option<&mut EntryInfo> {
+        self.infos.get_mut(index)
+    }
+
+    /// Set the session recorded with the next entries added (like an
+    /// identifier of the process, to tell concurrent shells apart).
+    pub fn set_session(&mut self, session: Option<&str>) {
+        self.session = session.map(Arc::from);
+    }
+
+    /// Return the positions of the entries added between `start`
+    /// (inclusive) and `end` (exclusive), in ascending order.
+    ///
+    /// The entries without time (loaded from a plain file) are skipped.
+    pub fn between(&self, start: SystemTime, end: SystemTime) -> Vec<usize> {
+        self.infos
+            .iter()
+            .enumerate()
+            .filter(|&(_, info)| info.time.map_or(false, |time| time >= start && time < end))
+            .map(|(i, _)| i)
+            .collect()
     }
 
     /// Add a new entry in the history.
-    pub fn add<S: AsRef<str> + Into<String>>(&mut self, line: S) -> bool {
+    pub fn add<S: AsRef<str> + Into<String>>(&mut self, line: S) -> AddResult {
+        let info = EntryInfo {
+            time: Some(SystemTime::now()),
+            session: self.session.clone(),
+            ..EntryInfo::default()
+        };
+        self.add_with_info(line.as_ref(), info)
+    }
+
+    fn add_with_info(&mut self, line: &str, info: EntryInfo) -> AddResult {
         if self.max_len == 0 {
-            return false;
-        }
-        if line.as_ref().is_empty()
-            || (self.ignore_space && line
-                .as_ref()
-                .chars()
-                .next()
-                .map_or(true, |c| c.is_whitespace()))
-        {
-            return false;
+            return AddResult::Disabled;
+        }
+        if line.is_empty() {
+            return AddResult::IgnoredEmpty;
+        }
+        if self.ignore_space && line.chars().next().map_or(true, |c| c.is_whitespace()) {
+            return AddResult::IgnoredSpace;
         }
         if self.ignore_dups {
             if let Some(s) = self.entries.back() {
-                if s == line.as_ref() {
-                    return false;
+                if **s == *line {
+                    return AddResult::IgnoredDuplicate;
                 }
             }
         }
+        let mut result = AddResult::Added;
         if self.entries.len() == self.max_len {
-            self.entries.pop_front();
+            self.pop_front();
+            result = AddResult::AddedTrimmingOldest;
+        }
+        let id = self.evicted + self.entries.len();
+        if let Some((ref prefix, ref mut ids)) = *self.prefix_index.borrow_mut() {
+            if line.starts_with(prefix.as_str()) {
+                ids.push(id);
+            }
+        }
+        let text = self.intern(line, id);
+        self.entries.push_back(text);
+        self.infos.push_back(info);
+        result
+    }
+
+    /// Return the text shared by the entries identical to `line`.
+    /// Record the entry `id` with it.
+    fn intern(&mut self, line: &str, id: usize) -> Arc<String> {
+        if let Some((text, ids)) = self
+            .texts
+            .range_mut::<str, _>((Included(line), Included(line)))
+            .next()
+        {
+            if let Err(i) = ids.binary_search(&id) {
+                ids.insert(i, id);
+            }
+            return Arc::clone(&text.0);
+        }
+        let text = Arc::new(line.to_owned());
+        self.texts.insert(Text(Arc::clone(&text)), vec![id]);
+        text
+    }
+
+    /// Forget the entry `id` and its text if no other entry shares it.
+    fn release(texts: &mut BTreeMap<Text, Vec<usize>>, entry: &str, id: usize) {
+        let unused = match texts.get_mut(entry) {
+            Some(ids) => {
+                if let Ok(i) = ids.binary_search(&id) {
+                    ids.remove(i);
+                }
+                ids.is_empty()
+            }
+            None => false,
+        };
+        if unused {
+            texts.remove(entry);
+        }
+    }
+
+    /// Rebuild `texts` after the entries have moved.
+    fn reindex(&mut self) {
+        self.texts.clear();
+        for (i, entry) in self.entries.iter().enumerate() {
+            self.texts
+                .entry(Text(Arc::clone(entry)))
+                .or_default()
+                .push(self.evicted + i);
+        }
+        *self.prefix_index.borrow_mut() = None;
+    }
+
+    /// Memory used by the entries
+    pub fn stats(&self) -> HistoryStats {
+        let text_bytes = self.texts.keys().map(|text| text.0.len()).sum();
+        let total_bytes: usize = self.entries.iter().map(|entry| entry.len()).sum();
+        HistoryStats {
+            entries: self.entries.len(),
+            distinct_entries: self.texts.len(),
+            text_bytes,
+            shared_bytes: total_bytes - text_bytes,
         }
-        self.entries.push_back(line.into());
-        true
     }
 
     /// Return the number of entries in the history.
@@ -100,18 +325,32 @@ impl History {
     pub fn set_max_len(&mut self, len: usize) {
         self.max_len = len;
         if len == 0 {
-            self.entries.clear();
+            self.clear();
             return;
         }
         loop {
             if self.entries.len() <= len {
                 break;
             }
-            self.entries.pop_front();
+            self.pop_front();
+        }
+    }
+
+    fn pop_front(&mut self) {
+        if let Some(entry) = self.entries.pop_front() {
+            let id = self.evicted;
+            History::release(&mut self.texts, &entry, id);
+            if let Some((_, ref mut ids)) = *self.prefix_index.borrow_mut() {
+                if ids.first() == Some(&id) {
+                    ids.remove(0);
+                }
+            }
+            self.infos.pop_front();
+            self.evicted += 1;
         }
     }
 
-    /// Save the history in the specified file.
+    /// Save the history in the specified file (in the `history_format`).
     // TODO append_history
     // http://cnswww.cns.cwru.edu/php/chet/readline/history.html#IDX30
     // TODO history_truncate_file
@@ -119,25 +358,29 @@ impl History {
     pub fn save<P: AsRef<Path> + ?Sized>(&self, path: &P) -> Result<()> {
         use std::io::{BufWriter, Write};
 
-        if self.is_empty() {
+        // an emptied history must still overwrite the previous entries
+        if self.is_empty() && !path.as_ref().exists() {
             return Ok(());
         }
-        let old_umask = umask();
-        let f = File::create(path);
-        restore_umask(old_umask);
-        let file = try!(f);
-        fix_perm(&file);
+        let file = try!(create_private(path.as_ref()));
         let mut wtr = BufWriter::new(file);
-        for entry in &self.entries {
-            try!(wtr.write_all(entry.as_bytes()));
-            try!(wtr.write_all(b"\n"));
+        if self.format == HistoryFormat::Extended {
+            try!(writeln!(wtr, "{}", EXTENDED_HEADER));
+            for (entry, info) in self.entries.iter().zip(&self.infos) {
+                try!(writeln!(wtr, "{}\t{}", format_info(info), escape(entry)));
+            }
+        } else {
+            for entry in &self.entries {
+                try!(wtr.write_all(entry.as_bytes()));
+                try!(wtr.write_all(b"\n"));
+            }
         }
         // https://github.com/rust-lang/rust/issues/32677#issuecomment-204833485
         try!(wtr.flush());
         Ok(())
     }
 
-    /// Load the history from the specified file.
+    /// Load the history from the specified file (in either format).
     ///
     /// # Errors
     /// Will return `Err` if path does not already exist or could not be read.
@@ -146,15 +389,88 @@ impl History {
 
         let file = try!(File::open(&path));
         let rdr = BufReader::new(file);
-        for line in rdr.lines() {
-            self.add(try!(line).as_ref()); // TODO truncate to MAX_LINE
+        let mut extended = false;
+        for (i, line) in rdr.lines().enumerate() {
+            let line = try!(line); // TODO truncate to MAX_LINE
+            if i == 0 && line == EXTENDED_HEADER {
+                extended = true;
+                continue;
+            }
+            if !extended {
+                self.add_with_info(&line, EntryInfo::default());
+                continue;
+            }
+            match parse_entry(&line, self.infos.back()) {
+                Some((entry, info)) => {
+                    self.add_with_info(&entry, info);
+                }
+                None => debug!(target: "rustyline", "invalid history line: {:?}", line),
+            }
         }
         Ok(())
     }
 
     /// Clear history
     pub fn clear(&mut self) {
-        self.entries.clear()
+        self.entries.clear();
+        self.infos.clear();
+        self.texts.clear();
+        self.evicted = 0;
+        *self.prefix_index.borrow_mut() = None;
+    }
+
+    /// Remove the entry at position `index` (like a mistyped secret) and
+    /// return it.
+    ///
+    /// The history file is only updated by the next `save`.
+    pub fn remove(&mut self, index: usize) -> Option<String> {
+        let entry = self.entries.remove(index);
+        if let Some(ref entry) = entry {
+            self.infos.remove(index);
+            let id = self.evicted + index;
+            History::release(&mut self.texts, entry, id);
+            // the following entries have moved
+            for ids in self.texts.values_mut() {
+                renumber(ids, id);
+            }
+            if let Some((_, ref mut ids)) = *self.prefix_index.borrow_mut() {
+                if let Ok(i) = ids.binary_search(&id) {
+                    ids.remove(i);
+                }
+                renumber(ids, id);
+            }
+        }
+        entry.map(|entry| entry.to_string())
+    }
+
+    /// Remove all the entries matching `predicate` and return how many have
+    /// been removed.
+    pub fn remove_matching<F: FnMut(&str) -> bool>(&mut self, mut predicate: F) -> usize {
+        let len = self.entries.len();
+        let kept: Vec<bool> = self.entries.iter().map(|entry| !predicate(entry)).collect();
+        let mut kept_infos = kept.iter();
+        self.infos.retain(|_| *kept_infos.next().unwrap());
+        let mut kept_entries = kept.iter();
+        self.entries.retain(|_| *kept_entries.next().unwrap());
+        let removed = len - self.entries.len();
+        if removed > 0 {
+            self.reindex();
+        }
+        removed
+    }
+
+    /// Replace the entry at position `index` by `line` and return the
+    /// previous one (`None` when `index` is out of bounds).
+    pub fn replace<S: Into<String>>(&mut self, index: usize, line: S) -> Option<String> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        let id = self.evicted + index;
+        let text = self.intern(&line.into(), id);
+        let entry = mem::replace(&mut self.entries[index], text);
+        History::release(&mut self.texts, &entry, id);
+        *self.prefix_index.borrow_mut() = None;
+        Some(entry.to_string())
     }
 
     /// Search history (start position inclusive [0, len-1]).
@@ -165,22 +481,86 @@ impl History {
     /// Return None if no entry contains `term` between [start, len -1] for
     /// forward search
     /// or between [0, start] for reverse search.
+    ///
+    /// The entries are scanned (see `starts_with` for the indexed search).
     pub fn search(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
-        let test = |entry: &String| entry.contains(term);
-        self.search_match(term, start, dir, test)
+        if term.is_empty() {
+            return None;
+        }
+        let test = |entry: &str| entry.contains(term);
+        self.search_match(start, dir, test)
+    }
+
+    /// Search history for an entry matching `regex`
+    pub(crate) fn search_regex(
+        &self,
+        regex: &Regex,
+        start: usize,
+        dir: Direction,
+    ) -> Option<usize> {
+        let test = |entry: &str| regex.is_match(entry);
+        self.search_match(start, dir, test)
     }
 
     /// Anchored search
+    ///
+    /// The distinct texts are sorted so that only the ones starting with
+    /// `term` are visited, and the entries found are kept for the last
+    /// searched `term` so that repeated searches (for example, successive
+    /// `history-search-backward`) are a binary search.
     pub fn starts_with(&self, term: &str, start: usize, dir: Direction) -> Option<usize> {
-        let test = |entry: &String| entry.starts_with(term);
-        self.search_match(term, start, dir, test)
+        if term.is_empty() || start >= self.len() {
+            return None;
+        }
+        let mut prefix_index = self.prefix_index.borrow_mut();
+        if prefix_index
+            .as_ref()
+            .map_or(true, |cached| cached.0 != term)
+        {
+            let mut ids: Vec<usize> = self
+                .texts
+                .range::<str, _>((Included(term), Unbounded))
+                .take_while(|&(text, _)| text.0.starts_with(term))
+                .flat_map(|(_, ids)| ids.iter().cloned())
+                .collect();
+            ids.sort();
+            *prefix_index = Some((term.to_owned(), ids));
+        }
+        let ids = &prefix_index.as_ref().unwrap().1;
+        let start = self.evicted + start;
+        let id = match dir {
+            Direction::Reverse => {
+                let i = match ids.binary_search(&start) {
+                    Ok(i) => i + 1,
+                    Err(i) => i,
+                };
+                if i == 0 {
+                    return None;
+                }
+                ids[i - 1]
+            }
+            Direction::Forward => {
+                let i = match ids.binary_search(&start) {
+                    Ok(i) | Err(i) => i,
+                };
+                if i == ids.len() {
+                    return None;
+                }
+                ids[i]
+            }
+        };
+        if id < self.evicted {
+            None
+        } else {
+            Some(id - self.evicted)
+        }
     }
 
-    fn search_match<F>(&self, term: &str, start: usize, dir: Direction, test: F) -> Option<usize>
+    fn search_match<F>(&self, start: usize, dir: Direction, test: F) -> Option<usize>
     where
-        F: Fn(&String) -> bool,
+        F: Fn(&str) -> bool,
     {
-        if term.is_empty() || start >= self.len() {
+        if start >= self.len() {
             return None;
         }
         match dir {
@@ -190,11 +570,15 @@ impl History {
                     .iter()
                     .rev()
                     .skip(self.entries.len() - 1 - start)
-                    .position(test);
+                    .position(|entry| test(entry));
                 index.and_then(|index| Some(start - index))
             }
             Direction::Forward => {
-                let index = self.entries.iter().skip(start).position(test);
+                let index = self
+                    .entries
+                    .iter()
+                    .skip(start)
+                    .position(|entry| test(entry));
                 index.and_then(|index| Some(index + start))
             }
         }
@@ -224,13 +608,13 @@ impl<'a> IntoIterator for &'a History {
 }
 
 /// History iterator.
-pub struct Iter<'a>(vec_deque::Iter<'a, String>);
+pub struct Iter<'a>(vec_deque::Iter<'a, Arc<String>>);
 
 impl<'a> Iterator for Iter<'a> {
     type Item = &'a String;
 
     fn next(&mut self) -> Option<&'a String> {
-        self.0.next()
+        self.0.next().map(|entry| &**entry)
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -240,10 +624,87 @@ impl<'a> Iterator for Iter<'a> {
 
 impl<'a> DoubleEndedIterator for Iter<'a> {
     fn next_back(&mut self) -> Option<&'a String> {
-        self.0.next_back()
+        self.0.next_back().map(|entry| &**entry)
+    }
+}
+
+/// Format the metadata fields of a `HistoryFormat::Extended` line.
+fn format_info(info: &EntryInfo) -> String {
+    let time = info
+        .time
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(String::new(), |time| time.as_secs().to_string());
+    let duration = info.duration.map_or(String::new(), |duration| {
+        (duration.as_secs() * 1000 + u64::from(duration.subsec_millis())).to_string()
+    });
+    let exit_status = info
+        .exit_status
+        .map_or(String::new(), |status| status.to_string());
+    let session = info.session.as_ref().map_or("", |session| &**session);
+    format!(
+        "{}\t{}\t{}\t{}",
+        time,
+        duration,
+        exit_status,
+        escape(session)
+    )
+}
+
+/// Parse a `HistoryFormat::Extended` line (the session of the `previous`
+/// entry is shared when it is the same).
+fn parse_entry(line: &str, previous: Option<&EntryInfo>) -> Option<(String, EntryInfo)> {
+    let fields: Vec<&str> = line.splitn(5, '\t').collect();
+    if fields.len() != 5 {
+        return None;
+    }
+    let (secs, millis, exit_status) = match (
+        parse_field::<u64>(fields[0]),
+        parse_field::<u64>(fields[1]),
+        parse_field(fields[2]),
+    ) {
+        (Ok(secs), Ok(millis), Ok(exit_status)) => (secs, millis, exit_status),
+        _ => return None,
+    };
+    let (session, entry) = match (unescape(fields[3]), unescape(fields[4])) {
+        (Some(session), Some(entry)) => (session, entry),
+        _ => return None,
+    };
+    let session = if session.is_empty() {
+        None
+    } else {
+        match previous.and_then(|previous| previous.session.as_ref()) {
+            Some(previous) if **previous == *session => Some(Arc::clone(previous)),
+            _ => Some(Arc::from(session)),
+        }
+    };
+    let info = EntryInfo {
+        time: secs.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+        duration: millis.map(Duration::from_millis),
+        exit_status,
+        session,
+    };
+    Some((entry, info))
+}
+
+/// Parse an optional (empty when unknown) field.
+fn parse_field<T: FromStr>(field: &str) -> result::Result<Option<T>, T::Err> {
+    if field.is_empty() {
+        Ok(None)
+    } else {
+        field.parse().map(Some)
     }
 }
 
+/// Create (or truncate) a file only readable and writable by the user.
+pub(crate) fn create_private(path: &Path) -> io::Result<File> {
+    let old_umask = umask();
+    let f = File::create(path);
+    restore_umask(old_umask);
+    let file = try!(f);
+    fix_perm(&file);
+    Ok(file)
+}
+
 #[cfg(windows)]
 fn umask() -> u16 {
     0
@@ -261,6 +722,13 @@ fn restore_umask(old_umask: libc::mode_t) {
     }
 }
 
+/// Shift the (ascending) identifiers following the `removed` one.
+fn renumber(ids: &mut [usize], removed: usize) {
+    for id in ids.iter_mut().filter(|id| **id > removed) {
+        *id -= 1;
+    }
+}
+
 #[cfg(windows)]
 fn fix_perm(_: &File) {}
 #[cfg(unix)]
@@ -274,15 +742,18 @@ fn fix_perm(file: &File) {
 #[cfg(test)]
 mod tests {
     extern crate tempdir;
-    use super::{Direction, History};
-    use config::Config;
+    use super::{AddResult, Direction, EntryInfo, History, HistoryStats};
+    use config::{Config, HistoryFormat};
+    use std::fs;
     use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
     fn init() -> History {
         let mut history = History::new();
-        assert!(history.add("line1"));
-        assert!(history.add("line2"));
-        assert!(history.add("line3"));
+        assert!(history.add("line1").is_added());
+        assert!(history.add("line2").is_added());
+        assert!(history.add("line3").is_added());
         history
     }
 
@@ -297,11 +768,70 @@ mod tests {
         let config = Config::builder().history_ignore_space(true).build();
         let mut history = History::with_config(config);
         assert_eq!(config.max_history_size(), history.max_len);
-        assert!(history.add("line1"));
-        assert!(history.add("line2"));
-        assert!(!history.add("line2"));
-        assert!(!history.add(""));
-        assert!(!history.add(" line3"));
+        assert_eq!(AddResult::Added, history.add("line1"));
+        assert_eq!(AddResult::Added, history.add("line2"));
+        assert_eq!(AddResult::IgnoredDuplicate, history.add("line2"));
+        assert_eq!(AddResult::IgnoredEmpty, history.add(""));
+        assert_eq!(AddResult::IgnoredSpace, history.add(" line3"));
+        history.set_max_len(2);
+        assert_eq!(AddResult::AddedTrimmingOldest, history.add("line3"));
+        assert_eq!(Some(&"line2".to_owned()), history.get(0));
+        history.set_max_len(0);
+        assert_eq!(AddResult::Disabled, history.add("line4"));
+    }
+
+    #[test]
+    fn remove() {
+        let mut history = init();
+        assert_eq!(Some("line2".to_owned()), history.remove(1));
+        assert_eq!(None, history.remove(2));
+        assert_eq!(Some(&"line3".to_owned()), history.get(1));
+        assert_eq!(Some(1), history.starts_with("line3", 1, Direction::Reverse));
+
+        assert_eq!(Some("line3".to_owned()), history.replace(1, "line4"));
+        assert_eq!(None, history.replace(2, "line5"));
+        assert_eq!(Some(1), history.starts_with("line4", 1, Direction::Reverse));
+
+        history.add("secret");
+        assert_eq!(
+            2,
+            history.remove_matching(|entry| entry == "secret" || entry == "line1")
+        );
+        assert_eq!(1, history.len());
+        assert_eq!(0, history.remove_matching(|entry| entry.is_empty()));
+    }
+
+    #[test]
+    fn stats() {
+        let mut history = History::new();
+        history.ignore_dups = false;
+        for line in &["ls", "make", "ls", "ls"] {
+            history.add(*line);
+        }
+        let stats = history.stats();
+        assert_eq!(4, stats.entries);
+        assert_eq!(2, stats.distinct_entries);
+        assert_eq!(6, stats.text_bytes);
+        assert_eq!(4, stats.shared_bytes);
+
+        history.remove(0);
+        history.replace(0, "ls");
+        history.remove_matching(|entry| entry == "ls");
+        assert_eq!(HistoryStats::default(), history.stats());
+    }
+
+    #[test]
+    fn save_emptied() {
+        let mut history = init();
+        let td = tempdir::TempDir::new_in(Path::new("."), "histo").unwrap();
+        let history_path = td.path().join(".history");
+
+        history.save(&history_path).unwrap();
+        history.remove_matching(|_| true);
+        history.save(&history_path).unwrap();
+        history.load(&history_path).unwrap();
+        assert!(history.is_empty());
+        td.close().unwrap();
     }
 
     #[test]
@@ -323,6 +853,79 @@ mod tests {
         td.close().unwrap();
     }
 
+    #[test]
+    fn save_extended() {
+        let config = Config::builder()
+            .history_format(HistoryFormat::Extended)
+            .build();
+        let mut history = History::with_config(config);
+        history.set_session(Some("s1"));
+        history.add("cargo build");
+        history.add("echo \"a\tb\"\nls");
+        {
+            let info = history.info_mut(0).unwrap();
+            info.time = Some(UNIX_EPOCH + Duration::from_secs(1_546_300_800));
+            info.duration = Some(Duration::from_millis(1520));
+            info.exit_status = Some(0);
+        }
+        history.info_mut(1).unwrap().time = None;
+        history.set_session(None);
+        history.add("make");
+        history.info_mut(2).unwrap().time = None;
+        let td = tempdir::TempDir::new_in(Path::new("."), "histo").unwrap();
+        let history_path = td.path().join(".history");
+
+        history.save(&history_path).unwrap();
+        assert_eq!(
+            "#V2\n1546300800\t1520\t0\ts1\tcargo build\n\
+             \t\t\ts1\techo \"a\\tb\"\\nls\n\
+             \t\t\t\tmake\n",
+            fs::read_to_string(&history_path).unwrap()
+        );
+        let mut loaded = History::new();
+        loaded.load(&history_path).unwrap();
+        assert_eq!(Some(&"echo \"a\tb\"\nls".to_owned()), loaded.get(1));
+        assert_eq!(history.info(0), loaded.info(0));
+        assert_eq!(Some(Arc::from("s1")), loaded.info(1).unwrap().session);
+        assert_eq!(Some(&EntryInfo::default()), loaded.info(2));
+
+        // plain file
+        loaded.save(&history_path).unwrap();
+        history.clear();
+        history.load(&history_path).unwrap();
+        // the new line splits the second entry
+        assert_eq!(4, history.len());
+        assert_eq!(Some(&EntryInfo::default()), history.info(0));
+        td.close().unwrap();
+    }
+
+    #[test]
+    fn between() {
+        let mut history = init();
+        let start = UNIX_EPOCH + Duration::from_secs(100);
+        for i in 0..3 {
+            history.info_mut(i).unwrap().time = Some(start + Duration::from_secs(i as u64));
+        }
+        history.info_mut(1).unwrap().time = None;
+        assert_eq!(
+            vec![0, 2],
+            history.between(start, start + Duration::from_secs(3))
+        );
+        assert_eq!(
+            vec![2],
+            history.between(
+                start + Duration::from_secs(1),
+                start + Duration::from_secs(3)
+            )
+        );
+        history.remove(0);
+        assert_eq!(vec![1], history.between(start, SystemTime::now()));
+        history.remove_matching(|entry| entry == "line2");
+        assert_eq!(vec![0], history.between(start, SystemTime::now()));
+        assert!(history.add("line4").is_added());
+        assert_eq!(vec![0, 1], history.between(start, SystemTime::now()));
+    }
+
     #[test]
     fn search() {
         let history = init();
@@ -346,4 +949,82 @@ mod tests {
         assert_eq!(Some(1), history.search("line", 1, Direction::Reverse));
         assert_eq!(Some(0), history.search("line1", 1, Direction::Reverse));
     }
+
+    #[test]
+    fn starts_with() {
+        let history = init();
+        assert_eq!(None, history.starts_with("", 2, Direction::Reverse));
+        assert_eq!(None, history.starts_with("ine", 2, Direction::Reverse));
+        assert_eq!(Some(2), history.starts_with("line", 2, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("line", 1, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("line", 1, Direction::Forward));
+        assert_eq!(Some(0), history.starts_with("line1", 2, Direction::Reverse));
+        assert_eq!(None, history.starts_with("line1", 1, Direction::Forward));
+
+        // index maintained on add and eviction
+        let mut history = History::new();
+        history.add("a1");
+        history.add("b");
+        history.add("a2");
+        history.set_max_len(3);
+        assert_eq!(Some(2), history.starts_with("a", 2, Direction::Reverse));
+        assert!(history.add("c").is_added());
+        assert_eq!(None, history.starts_with("a", 0, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("a", 0, Direction::Forward));
+        assert!(history.add("a3").is_added());
+        assert_eq!(Some(2), history.starts_with("a", 2, Direction::Reverse));
+        assert_eq!(Some(0), history.starts_with("a", 1, Direction::Reverse));
+        history.clear();
+        assert_eq!(None, history.starts_with("line", 0, Direction::Reverse));
+    }
+
+    #[test]
+    fn starts_with_after_eviction() {
+        let mut history = History::new();
+        for i in 0..10 {
+            history.add(format!("a{}", i));
+            history.add("b");
+        }
+        // cached before the evictions
+        assert_eq!(Some(18), history.starts_with("a", 19, Direction::Reverse));
+        // b, a8, b, a9, b
+        history.set_max_len(5);
+        assert_eq!(2, history.prefix_index.borrow().as_ref().unwrap().1.len());
+        assert_eq!(Some(3), history.starts_with("a", 4, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("a", 0, Direction::Forward));
+        // a8, b, a9, b, c
+        assert!(history.add("c").is_added());
+        assert_eq!(Some(0), history.starts_with("a", 0, Direction::Forward));
+        assert_eq!(None, history.starts_with("a", 3, Direction::Forward));
+        assert_eq!(4, history.stats().distinct_entries);
+        // not cached
+        assert_eq!(Some(3), history.starts_with("b", 4, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("b", 0, Direction::Forward));
+        assert_eq!(None, history.starts_with("b", 0, Direction::Reverse));
+    }
+
+    #[test]
+    fn starts_with_after_remove() {
+        let mut history = History::new();
+        history.add("a1");
+        history.add("b1");
+        history.add("a2");
+        history.add("b2");
+        history.add("a3");
+        assert_eq!(Some(4), history.starts_with("a", 4, Direction::Reverse));
+        assert_eq!(Some("b1".to_owned()), history.remove(1));
+        assert_eq!(Some(3), history.starts_with("a", 3, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("a", 2, Direction::Reverse));
+        assert_eq!(Some(2), history.starts_with("b", 3, Direction::Reverse));
+        assert_eq!(None, history.starts_with("b", 1, Direction::Reverse));
+
+        assert_eq!(Some("a2".to_owned()), history.replace(1, "b3"));
+        assert_eq!(Some(0), history.starts_with("a", 2, Direction::Reverse));
+        assert_eq!(Some(1), history.starts_with("b", 1, Direction::Reverse));
+
+        assert_eq!(2, history.remove_matching(|entry| entry.starts_with('b')));
+        assert_eq!(Some(1), history.starts_with("a", 1, Direction::Reverse));
+        assert_eq!(None, history.starts_with("b", 1, Direction::Reverse));
+        assert_eq!(2, history.stats().distinct_entries);
+    }
 }
@@ -0,0 +1,662 @@
+//! Readline init file (`~/.inputrc`), read by `Editor::read_inputrc` so
+//! that the configuration of bash users applies to the application.
+//!
+//! Supported directives:
+//! - `set` variables: `bell-style`, `completion-display-width`,
+//!   `completion-ignore-case`, `completion-query-items`, `editing-mode`,
+//!   `enable-bracketed-paste`, `history-size`, `keymap`, `keyseq-timeout`,
+//!   `mark-directories`, `page-completions`, `print-completions-horizontally`
+//!   and `show-all-if-ambiguous` (which also selects
+//!   `CompletionType::List`); the others are ignored,
+//! - `$if` (`mode=emacs`, `mode=vi`, `term=...` or the application name),
+//!   `$else` and `$endif`,
+//! - `$include`,
+//! - key bindings, to a readline function (like `"\e[A":
+//!   history-search-backward` or `Control-u: unix-line-discard`) or to a
+//!   macro (`"\C-xd": "date\n"`, inserted as is). The functions which are
+//!   not readline ones are bound to `Cmd::Custom`, so that the commands
+//!   registered with `Editor::add_command` can be bound too.
+//!
+//! Only the bindings of the keymap of the edit mode (`emacs` or `vi-insert`)
+//! are applied, and only to a single key (the sequences of several keys, like
+//! `"\C-x\C-r"`, are ignored).
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::Result;
+use config::{BellStyle, CompletionType, Configurer, EditMode};
+use error::ReadlineError;
+use keymap::{Anchor, At, Cmd, Movement, Word};
+use keys::{char_to_key_press, KeyPress};
+use tty::vt;
+
+/// Maximum depth of the `$include` directives
+const MAX_INCLUDES: usize = 10;
+
+/// Path of the init file: `$INPUTRC`, `~/.inputrc` or `/etc/inputrc`
+/// (the first one found).
+pub(crate) fn path() -> Option<PathBuf> {
+    use dirs::home_dir;
+
+    let mut paths = Vec::new();
+    if let Some(path) = env::var_os("INPUTRC") {
+        paths.push(PathBuf::from(path));
+    }
+    if let Some(home) = home_dir() {
+        paths.push(home.join(".inputrc"));
+    }
+    paths.push(PathBuf::from("/etc/inputrc"));
+    paths.into_iter().find(|path| path.is_file())
+}
+
+/// Keymaps of readline
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Keymap {
+    Emacs,
+    ViCommand,
+    ViInsert,
+}
+
+/// Apply the variables of the `path` file to `config` and return the
+/// bindings of the keymap of the resulting edit mode.
+pub(crate) fn read(
+    config: &mut Configurer,
+    path: &Path,
+    app: &str,
+) -> Result<Vec<(KeyPress, Cmd)>> {
+    let text = try!(fs::read_to_string(path));
+    let mut parser = Parser::new(config, app, env::var("TERM").ok());
+    try!(parser.parse(&text, path.parent(), 0));
+    Ok(parser.finish())
+}
+
+struct Parser<'c> {
+    config: &'c mut Configurer,
+    app: &'c str,
+    term: Option<String>,
+    keymap: Keymap,
+    keyseq_timeout: Option<i32>,
+    /// For each nested `$if`: whether its lines are read or skipped
+    conditions: Vec<bool>,
+    bindings: Vec<(Keymap, KeyPress, Cmd)>,
+}
+
+impl<'c> Parser<'c> {
+    fn new(config: &'c mut Configurer, app: &'c str, term: Option<String>) -> Parser<'c> {
+        let keymap = match config.config_mut().edit_mode() {
+            EditMode::Emacs => Keymap::Emacs,
+            EditMode::Vi => Keymap::ViInsert,
+        };
+        Parser {
+            config,
+            app,
+            term,
+            keymap,
+            keyseq_timeout: None,
+            conditions: Vec::new(),
+            bindings: Vec::new(),
+        }
+    }
+
+    fn parse(&mut self, text: &str, dir: Option<&Path>, depth: usize) -> Result<()> {
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('$') {
+                try!(self.directive(&line[1..], dir, depth));
+            } else if self.conditions.iter().all(|&read| read) {
+                self.line(line);
+            }
+        }
+        Ok(())
+    }
+
+    fn directive(&mut self, line: &str, dir: Option<&Path>, depth: usize) -> Result<()> {
+        let (name, arg) = split_word(line);
+        match name {
+            "if" => {
+                let test = self.test(arg);
+                self.conditions.push(test);
+            }
+            "else" => match self.conditions.last_mut() {
+                Some(read) => *read = !*read,
+                None => debug!(target: "rustyline", "inputrc: $else without $if"),
+            },
+            "endif" => {
+                if self.conditions.pop().is_none() {
+                    debug!(target: "rustyline", "inputrc: $endif without $if");
+                }
+            }
+            "include" if self.conditions.iter().all(|&read| read) => {
+                if depth >= MAX_INCLUDES {
+                    debug!(target: "rustyline", "inputrc: too many nested $include");
+                    return Ok(());
+                }
+                let path = include_path(arg, dir);
+                match fs::read_to_string(&path) {
+                    Ok(text) => try!(self.parse(&text, path.parent(), depth + 1)),
+                    // ignored, like by readline
+                    Err(err) => debug!(
+                        target: "rustyline",
+                        "inputrc: cannot read {}: {}",
+                        path.display(),
+                        err
+                    ),
+                }
+            }
+            "include" => {}
+            _ => debug!(target: "rustyline", "inputrc: unknown directive: {}", name),
+        }
+        Ok(())
+    }
+
+    /// Evaluate the test of an `$if`.
+    fn test(&mut self, test: &str) -> bool {
+        if test.starts_with("mode=") {
+            let mode = match self.config.config_mut().edit_mode() {
+                EditMode::Emacs => "emacs",
+                EditMode::Vi => "vi",
+            };
+            test[5..].trim() == mode
+        } else if test.starts_with("term=") {
+            let name = test[5..].trim();
+            self.term.as_ref().map_or(false, |term| {
+                term == name || term.split('-').next() == Some(name)
+            })
+        } else {
+            test.eq_ignore_ascii_case(self.app)
+        }
+    }
+
+    fn line(&mut self, line: &str) {
+        if line.starts_with("set") && line[3..].starts_with(char::is_whitespace) {
+            let (name, value) = split_word(line[3..].trim_start());
+            self.set(&name.to_ascii_lowercase(), value);
+            return;
+        }
+        match parse_binding(line) {
+            Some((key, cmd)) => self.bindings.push((self.keymap, key, cmd)),
+            None => debug!(target: "rustyline", "inputrc: unsupported line: {}", line),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: &str) {
+        let value = value.split_whitespace().next().unwrap_or("");
+        let on = value.eq_ignore_ascii_case("on") || value == "1";
+        let number = value.parse::<i32>().ok();
+        let config = &mut *self.config;
+        match name {
+            "bell-style" => match &*value.to_ascii_lowercase() {
+                "none" => config.set_bell_style(BellStyle::None),
+                "visible" => config.set_bell_style(BellStyle::Visible),
+                _ => config.set_bell_style(BellStyle::Audible),
+            },
+            "completion-display-width" => {
+                config.set_completion_display_width(number.and_then(positive))
+            }
+            "completion-ignore-case" => config.set_completion_ignore_case(on),
+            "completion-query-items" => {
+                if let Some(limit) = number.and_then(positive) {
+                    config.set_completion_prompt_limit(limit);
+                }
+            }
+            "editing-mode" => match value {
+                "emacs" => {
+                    config.set_edit_mode(EditMode::Emacs);
+                    self.keymap = Keymap::Emacs;
+                }
+                "vi" => {
+                    config.set_edit_mode(EditMode::Vi);
+                    self.keymap = Keymap::ViInsert;
+                }
+                _ => debug!(target: "rustyline", "inputrc: unknown editing-mode: {}", value),
+            },
+            "enable-bracketed-paste" => config.set_bracketed_paste(on),
+            "history-size" => {
+                if let Some(size) = number.and_then(positive) {
+                    config.set_max_history_size(size);
+                }
+            }
+            "keymap" => match value {
+                "emacs" | "emacs-standard" | "emacs-meta" | "emacs-ctlx" => {
+                    self.keymap = Keymap::Emacs
+                }
+                "vi" | "vi-command" | "vi-move" => self.keymap = Keymap::ViCommand,
+                "vi-insert" => self.keymap = Keymap::ViInsert,
+                _ => debug!(target: "rustyline", "inputrc: unknown keymap: {}", value),
+            },
+            "keyseq-timeout" => {
+                if let Some(timeout) = number {
+                    // applied last, not to be reset by `editing-mode`
+                    self.keyseq_timeout = Some(timeout);
+                }
+            }
+            "mark-directories" => config.set_mark_directories(on),
+            "page-completions" => config.set_page_completions(on),
+            "print-completions-horizontally" => config.set_print_completions_horizontally(on),
+            "show-all-if-ambiguous" => {
+                config.set_show_all_if_ambiguous(on);
+                if on {
+                    config.set_completion_type(CompletionType::List);
+                }
+            }
+            _ => debug!(target: "rustyline", "inputrc: ignored variable: {}", name),
+        }
+    }
+
+    fn finish(self) -> Vec<(KeyPress, Cmd)> {
+        if let Some(timeout) = self.keyseq_timeout {
+            self.config.set_keyseq_timeout(timeout);
+        }
+        let keymap = match self.config.config_mut().edit_mode() {
+            EditMode::Emacs => Keymap::Emacs,
+            EditMode::Vi => Keymap::ViInsert,
+        };
+        self.bindings
+            .into_iter()
+            .filter(|&(k, _, _)| k == keymap)
+            .map(|(_, key, cmd)| (key, cmd))
+            .collect()
+    }
+}
+
+fn positive(n: i32) -> Option<usize> {
+    if n > 0 {
+        Some(n as usize)
+    } else {
+        None
+    }
+}
+
+/// Split `line` at the first blank.
+fn split_word(line: &str) -> (&str, &str) {
+    match line.find(char::is_whitespace) {
+        Some(i) => (&line[..i], line[i..].trim()),
+        None => (line, ""),
+    }
+}
+
+/// Path of an `$include`d file (`~` being the home directory, and a relative
+/// path being relative to the including file).
+fn include_path(arg: &str, dir: Option<&Path>) -> PathBuf {
+    use dirs::home_dir;
+
+    if arg.starts_with("~/") {
+        if let Some(home) = home_dir() {
+            return home.join(&arg[2..]);
+        }
+    }
+    match dir {
+        Some(dir) => dir.join(arg),
+        None => PathBuf::from(arg),
+    }
+}
+
+/// Parse a `key: function` or `"keyseq": function` line.
+fn parse_binding(line: &str) -> Option<(KeyPress, Cmd)> {
+    let (seq, value) = if line.starts_with('"') {
+        match quoted_end(line, '"') {
+            Some(end) if line[end + 1..].trim_start().starts_with(':') => (
+                translate(&line[1..end]),
+                line[end + 1..].trim_start()[1..].trim(),
+            ),
+            _ => return None,
+        }
+    } else {
+        match line.find(':') {
+            Some(colon) => (key_name(line[..colon].trim()), line[colon + 1..].trim()),
+            None => return None,
+        }
+    };
+    seq.as_ref()
+        .and_then(|seq| key_press(seq))
+        .and_then(|key| parse_command(key, value).map(|cmd| (key, cmd)))
+}
+
+/// Parse the macro or the function bound to `key`.
+fn parse_command(key: KeyPress, value: &str) -> Option<Cmd> {
+    if value.starts_with('"') || value.starts_with('\'') {
+        let quote = value.chars().next().unwrap();
+        let end = quoted_end(value, quote).unwrap_or(value.len());
+        return translate(&value[1..end]).map(|text| Cmd::Insert(1, text.into_iter().collect()));
+    }
+    let name = value.split_whitespace().next().unwrap_or("");
+    match function(&name.to_ascii_lowercase()) {
+        Some(cmd) => Some(cmd),
+        None if name == "self-insert" => match key {
+            KeyPress::Char(c) => Some(Cmd::SelfInsert(1, c)),
+            _ => None,
+        },
+        None if name.is_empty() => None,
+        None => Some(Cmd::Custom(name.to_owned())),
+    }
+}
+
+/// Index of the quote closing the string starting at the beginning of `s`.
+fn quoted_end(s: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == quote {
+            return Some(i);
+        }
+    }
+    None
+}
+
+fn control(c: char) -> char {
+    if c == '?' {
+        '\x7f'
+    } else {
+        ((c.to_ascii_uppercase() as u8) & 0x1f) as char
+    }
+}
+
+/// Characters of a quoted key sequence or macro (with the escapes `\C-`,
+/// `\M-`, `\e`, `\\`, `\"`, `\'`, `\a`, `\b`, `\d`, `\f`, `\n`, `\r`, `\t`,
+/// `\v`, `\nnn` and `\xHH`).
+fn translate(s: &str) -> Option<Vec<char>> {
+    let mut chars = s.chars().peekable();
+    let mut seq = Vec::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            seq.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(c @ 'C') | Some(c @ 'M') if chars.peek() == Some(&'-') => {
+                chars.next();
+                if c == 'M' {
+                    // the next character, preceded by ESC
+                    seq.push('\x1b');
+                    continue;
+                }
+                let c = match chars.next() {
+                    Some('\\') => match chars.next() {
+                        Some('e') => '\x1b',
+                        Some(c) => c,
+                        None => return None,
+                    },
+                    Some(c) => c,
+                    None => return None,
+                };
+                seq.push(control(c));
+            }
+            Some('e') => seq.push('\x1b'),
+            Some('a') => seq.push('\x07'),
+            Some('b') => seq.push('\x08'),
+            Some('d') => seq.push('\x7f'),
+            Some('f') => seq.push('\x0c'),
+            Some('n') => seq.push('\n'),
+            Some('r') => seq.push('\r'),
+            Some('t') => seq.push('\t'),
+            Some('v') => seq.push('\x0b'),
+            Some(c @ '0'...'7') | Some(c @ 'x') => {
+                let (radix, max, mut code) = if c == 'x' {
+                    (16, 2, 0)
+                } else {
+                    (8, 2, c.to_digit(8).unwrap())
+                };
+                for _ in 0..max {
+                    match chars.peek().and_then(|c| c.to_digit(radix)) {
+                        Some(digit) => code = code * radix + digit,
+                        None => break,
+                    }
+                    chars.next();
+                }
+                match ::std::char::from_u32(code) {
+                    Some(c) => seq.push(c),
+                    None => return None,
+                }
+            }
+            Some(c) => seq.push(c),
+            None => return None,
+        }
+    }
+    Some(seq)
+}
+
+/// Characters of a key name (like `Control-u`, `M-DEL` or `TAB`).
+fn key_name(name: &str) -> Option<Vec<char>> {
+    let mut name = name;
+    let (mut ctrl, mut meta) = (false, false);
+    loop {
+        let lower = name.to_ascii_lowercase();
+        if lower.starts_with("control-") {
+            ctrl = true;
+            name = &name[8..];
+        } else if lower.starts_with("meta-") {
+            meta = true;
+            name = &name[5..];
+        } else if lower.starts_with("c-") {
+            ctrl = true;
+            name = &name[2..];
+        } else if lower.starts_with("m-") {
+            meta = true;
+            name = &name[2..];
+        } else {
+            break;
+        }
+    }
+    let c = match &*name.to_ascii_lowercase() {
+        "del" | "rubout" => '\x7f',
+        "esc" | "escape" => '\x1b',
+        "lfd" | "newline" => '\n',
+        "ret" | "return" => '\r',
+        "space" | "spc" => ' ',
+        "tab" => '\t',
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return None,
+            }
+        }
+    };
+    let c = if ctrl { control(c) } else { c };
+    Some(if meta { vec!['\x1b', c] } else { vec![c] })
+}
+
+/// Key of a sequence of characters (`None` if there are several keys).
+fn key_press(seq: &[char]) -> Option<KeyPress> {
+    if seq.len() == 1 {
+        return Some(char_to_key_press(seq[0]));
+    } else if seq.len() == 2 && seq[0] == '\x1b' {
+        return Some(KeyPress::Meta(seq[1]));
+    } else if seq.len() < 2 || seq[0] != '\x1b' {
+        return None;
+    }
+    let mut chars = seq[1..].iter();
+    let key = vt::escape_sequence(&mut || chars.next().cloned().ok_or(ReadlineError::Eof));
+    match key {
+        Ok(KeyPress::UnknownEscSeq) | Err(_) => None,
+        // several keys
+        Ok(_) if chars.next().is_some() => None,
+        Ok(key) => Some(key),
+    }
+}
+
+/// Command of the readline function `name` (like `kill-whole-line`), with
+/// a repeat count of 1.
+///
+/// `self-insert` and the functions without equivalent return `None`.
+pub fn function(name: &str) -> Option<Cmd> {
+    let cmd = match name {
+        "abort" => Cmd::Abort,
+        "accept-line" => Cmd::AcceptLine,
+        "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+        "backward-delete-char" => Cmd::Kill(Movement::BackwardChar(1)),
+        "backward-kill-line" | "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+        "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+        "backward-word" => Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+        "beginning-of-history" => Cmd::BeginningOfHistory,
+        "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+        "capitalize-word" => Cmd::CapitalizeWord,
+        "clear-screen" => Cmd::ClearScreen,
+        "complete" | "menu-complete" => Cmd::Complete,
+        "menu-complete-backward" => Cmd::CompleteBackward,
+        "copy-region-as-kill" => Cmd::CopySelection,
+        "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+        "do-nothing" => Cmd::Noop,
+        "downcase-word" => Cmd::DowncaseWord,
+        "emacs-editing-mode" => Cmd::SetEditMode(EditMode::Emacs),
+        "end-of-history" => Cmd::EndOfHistory,
+        "end-of-line" => Cmd::Move(Movement::EndOfLine),
+        "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+        "forward-search-history" => Cmd::ForwardSearchHistory,
+        "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "history-search-backward" => Cmd::HistorySearchBackward,
+        "history-search-forward" => Cmd::HistorySearchForward,
+        "kill-line" => Cmd::Kill(Movement::EndOfLine),
+        "kill-region" => Cmd::KillSelection,
+        "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+        "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+        "next-history" => Cmd::NextHistory,
+        "operate-and-get-next" => Cmd::AcceptLineAndDownHistory,
+        "previous-history" => Cmd::PreviousHistory,
+        "quoted-insert" => Cmd::QuotedInsert,
+        "reverse-search-history" => Cmd::ReverseSearchHistory,
+        "revert-line" => Cmd::RevertLine,
+        "tab-insert" => Cmd::Insert(1, "\t".to_owned()),
+        "transpose-chars" => Cmd::TransposeChars,
+        "transpose-words" => Cmd::TransposeWords(1),
+        "undo" => Cmd::Undo(1),
+        "unix-word-rubout" => Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+        "upcase-word" => Cmd::UpcaseWord,
+        "vi-editing-mode" => Cmd::SetEditMode(EditMode::Vi),
+        "yank" => Cmd::Yank(1, Anchor::Before),
+        "yank-pop" => Cmd::YankPop,
+        _ => return None,
+    };
+    Some(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempdir;
+    use std::fs;
+    use std::path::Path;
+
+    use super::{key_name, key_press, translate, Parser};
+    use config::{BellStyle, CompletionType, Config, EditMode};
+    use keymap::{Cmd, Movement};
+    use keys::KeyPress;
+    use Editor;
+
+    fn key(seq: &str) -> Option<KeyPress> {
+        let seq = if seq.starts_with('"') {
+            translate(&seq[1..seq.len() - 1])
+        } else {
+            key_name(seq)
+        };
+        seq.and_then(|seq| key_press(&seq))
+    }
+
+    #[test]
+    fn keys() {
+        assert_eq!(Some(KeyPress::Up), key(r#""\e[A""#));
+        assert_eq!(Some(KeyPress::Up), key(r#""\eOA""#));
+        assert_eq!(Some(KeyPress::ControlRight), key(r#""\e[1;5C""#));
+        assert_eq!(Some(KeyPress::Ctrl('U')), key(r#""\C-u""#));
+        assert_eq!(Some(KeyPress::Meta('f')), key(r#""\M-f""#));
+        assert_eq!(Some(KeyPress::Meta('\x08')), key(r#""\M-\C-h""#));
+        assert_eq!(Some(KeyPress::Char('\\')), key(r#""\\""#));
+        assert_eq!(Some(KeyPress::Backspace), key(r#""\177""#));
+        assert_eq!(None, key(r#""\C-x\C-r""#));
+        assert_eq!(Some(KeyPress::Ctrl('U')), key("Control-u"));
+        assert_eq!(Some(KeyPress::Meta('\x7f')), key("Meta-Rubout"));
+        assert_eq!(Some(KeyPress::Meta('\x08')), key("M-C-h"));
+        assert_eq!(Some(KeyPress::Tab), key("TAB"));
+        assert_eq!(Some(KeyPress::Char('a')), key("a"));
+        assert_eq!(None, key("Foo"));
+    }
+
+    const INPUTRC: &str = r#"
+# comment
+set keyseq-timeout 200
+set editing-mode vi
+set bell-style none
+set completion-ignore-case On
+set show-all-if-ambiguous on
+set unknown-variable on
+$if mode=vi
+  set keymap vi-command
+  "k": history-search-backward
+  set keymap vi-insert
+  "\e[A": history-search-backward
+  "\C-xd": "date\n"
+$else
+  "\e[A": previous-history
+$endif
+$if term=xterm
+  Control-t: insert-timestamp
+$endif
+$if Other
+  Control-l: clear-screen
+$else
+  $if MyApp
+    M-DEL: backward-kill-word
+  $endif
+$endif
+"#;
+
+    #[test]
+    fn parse() {
+        let mut builder = Config::builder();
+        let bindings = {
+            let term = Some("xterm-256color".to_owned());
+            let mut parser = Parser::new(&mut builder, "myapp", term);
+            parser.parse(INPUTRC, None, 0).unwrap();
+            parser.finish()
+        };
+        let config = builder.build();
+        assert_eq!(EditMode::Vi, config.edit_mode());
+        assert_eq!(200, config.keyseq_timeout());
+        assert_eq!(BellStyle::None, config.bell_style());
+        assert!(config.completion_ignore_case());
+        assert!(config.show_all_if_ambiguous());
+        assert_eq!(CompletionType::List, config.completion_type());
+        assert_eq!(
+            vec![
+                (KeyPress::Up, Cmd::HistorySearchBackward),
+                (
+                    KeyPress::Ctrl('T'),
+                    Cmd::Custom("insert-timestamp".to_owned())
+                ),
+                (
+                    KeyPress::Meta('\x7f'),
+                    Cmd::Kill(Movement::BackwardWord(1, ::keymap::Word::Emacs))
+                ),
+            ],
+            bindings
+        );
+    }
+
+    #[test]
+    fn read_inputrc_file() {
+        let td = tempdir::TempDir::new_in(Path::new("."), "inputrc").unwrap();
+        fs::write(td.path().join("bindings"), "\"\\C-o\": \"ls\"\n").unwrap();
+        let path = td.path().join("inputrc");
+        fs::write(&path, "set history-size 10\n$include bindings\n").unwrap();
+
+        let mut editor = Editor::<()>::new();
+        editor.read_inputrc_file(&path, "app").unwrap();
+        assert_eq!(10, editor.config.max_history_size());
+        assert_eq!(
+            Some(Cmd::Insert(1, "ls".to_owned())),
+            editor.unbind_sequence(KeyPress::Ctrl('O'))
+        );
+
+        // a missing included file is ignored
+        fs::write(&path, "$include missing\nset history-size 20\n").unwrap();
+        editor.read_inputrc_file(&path, "app").unwrap();
+        assert_eq!(20, editor.config.max_history_size());
+        td.close().unwrap();
+    }
+}
@@ -1,6 +1,7 @@
 //! Bindings from keys to command for Emacs and Vi modes
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use std::time::Duration;
 
 use super::Result;
 use config::Config;
@@ -17,8 +18,12 @@ pub type RepeatCount = usize;
 pub enum Cmd {
     /// abort
     Abort, // Miscellaneous Command
+    /// accept-and-hold
+    AcceptAndHold,
     /// accept-line
     AcceptLine,
+    /// accept-line-and-down-history, operate-and-get-next
+    AcceptLineAndDownHistory,
     /// beginning-of-history
     BeginningOfHistory,
     /// capitalize-word
@@ -27,24 +32,53 @@ pub enum Cmd {
     ClearScreen,
     /// complete
     Complete,
+    /// menu-complete-backward (the previous candidate of a completion menu)
+    CompleteBackward,
+    /// copy-region-as-kill, copy-region
+    CopySelection,
+    /// user-defined command (see `Editor::add_command`)
+    Custom(String),
     /// downcase-word
     DowncaseWord,
+    /// Swap the word under the cursor with the previous word (keeping the
+    /// separators), the cursor following the dragged word
+    DragWordBackward(RepeatCount),
+    /// Swap the word under the cursor with the next word (keeping the
+    /// separators), the cursor following the dragged word
+    DragWordForward(RepeatCount),
     /// vi-eof-maybe
     EndOfFile,
     /// end-of-history
     EndOfHistory,
+    /// execute-named-command: read the name of a command (registered with
+    /// `Editor::add_command`, or a readline function like `kill-whole-line`)
+    /// to execute it
+    ExecuteNamedCommand,
     /// forward-search-history
     ForwardSearchHistory,
-    /// history-search-backward
+    /// history-search-backward: previous entry starting with the text before
+    /// the cursor (unbound by default, like
+    /// `editor.bind_sequence(KeyPress::Up, Cmd::HistorySearchBackward)`)
     HistorySearchBackward,
-    /// history-search-forward
+    /// history-search-forward: next entry starting with the text before the
+    /// cursor (unbound by default)
     HistorySearchForward,
+    /// list the recent history entries under the line (filtered by the typed
+    /// text) to insert the selected one
+    HistoryList,
+    /// insert the character composed from the next two keys (see
+    /// `Editor::add_compose_sequence`)
+    Compose,
     Insert(RepeatCount, String),
+    /// insert the character whose hexadecimal code point is typed next
+    InsertCodePoint,
     Interrupt,
     /// backward-delete-char, backward-kill-line, backward-kill-word
-    /// delete-char, kill-line, kill-word, unix-line-discard, unix-word-rubout,
-    /// vi-delete, vi-delete-to, vi-rubout
+    /// delete-char, kill-line, kill-whole-line, kill-word, unix-line-discard,
+    /// unix-word-rubout, vi-delete, vi-delete-to, vi-rubout
     Kill(Movement),
+    /// kill-region
+    KillSelection,
     /// backward-char, backward-word, beginning-of-line, end-of-line,
     /// forward-char, forward-word, vi-char-search, vi-end-word, vi-next-word,
     /// vi-prev-word
@@ -54,19 +88,45 @@ pub enum Cmd {
     Noop,
     /// vi-replace
     Overwrite(char),
+    /// text pasted in the terminal (see `Config::bracketed_paste`), inserted
+    /// as is after the `PasteHandler` of the editor
+    Paste(String),
+    /// paste the system clipboard (queried with OSC 52)
+    PasteClipboard,
+    /// insert the string returned by the `Picker` (see `Editor::set_picker`)
+    Pick,
     /// previous-history
     PreviousHistory,
+    /// push-line
+    PushLine,
     /// quoted-insert
     QuotedInsert,
+    /// redo (of the changes undone by the last `Undo` commands)
+    Redo(RepeatCount),
     /// vi-change-char
     ReplaceChar(RepeatCount, char),
     /// vi-change-to, vi-substitute
     Replace(Movement, Option<String>),
+    /// emacs-editing-mode, vi-editing-mode (kept for the next lines)
+    SetEditMode(EditMode),
+    /// Restore the last line discarded by `Interrupt`, a whole line kill or
+    /// an aborted incremental search (the current line is kept in its place)
+    RecoverLine,
     /// reverse-search-history
     ReverseSearchHistory,
+    /// revert-line: restore the recalled history entry, discarding its edits
+    RevertLine,
+    /// extend the selection (Shift+Left, Shift+Right, Shift+Home, Shift+End)
+    Select(Movement),
     /// self-insert
     SelfInsert(RepeatCount, char),
     Suspend,
+    /// toggle-case: swap the case of the selection, or of the word after
+    /// point (not bound by default)
+    ToggleCase,
+    /// toggle the regex interpretation of the incremental search string (M-r
+    /// while searching)
+    ToggleRegexSearch,
     /// transpose-chars
     TransposeChars,
     /// transpose-words
@@ -76,9 +136,12 @@ pub enum Cmd {
     Unknown,
     /// upcase-word
     UpcaseWord,
-    /// vi-yank-to
+    /// vi-yank-to, copy-backward-word, copy-forward-word
+    ///
+    /// Put the text up to the `Movement` position on the kill ring without
+    /// modifying the line.
     ViYankTo(Movement),
-    /// yank, vi-put
+    /// yank, vi-put (of the nth most recent kill)
     Yank(RepeatCount, Anchor),
     /// yank-pop
     YankPop,
@@ -90,6 +153,7 @@ impl Cmd {
             Cmd::Kill(Movement::BackwardChar(_)) | Cmd::Kill(Movement::ForwardChar(_)) => true,
             Cmd::ClearScreen
             | Cmd::Kill(_)
+            | Cmd::KillSelection
             | Cmd::Replace(_, _)
             | Cmd::Noop
             | Cmd::Suspend
@@ -220,6 +284,7 @@ impl CharSearch {
 /// Where to move
 #[derive(Debug, Clone, PartialEq)]
 pub enum Movement {
+    /// kill-whole-line
     WholeLine, // not really a movement
     /// beginning-of-line
     BeginningOfLine,
@@ -262,8 +327,8 @@ impl Movement {
     }
 }
 
-#[derive(PartialEq)]
-enum InputMode {
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum InputMode {
     /// Vi Command/Alternate
     Command,
     /// Insert/Input mode
@@ -281,6 +346,10 @@ pub struct InputState {
     num_args: i16,
     last_cmd: Cmd,                        // vi only
     last_char_search: Option<CharSearch>, // vi only
+    vi_insert_escape: Option<(char, char)>,
+    keyseq_timeout: i32,
+    pending_key: Option<KeyPress>, // read after the first char of `vi_insert_escape`
+    searching: bool,               // in the incremental search
 }
 
 pub trait Refresher {
@@ -309,6 +378,10 @@ impl InputState {
             num_args: 0,
             last_cmd: Cmd::Noop,
             last_char_search: None,
+            vi_insert_escape: config.vi_insert_escape(),
+            keyseq_timeout: config.keyseq_timeout(),
+            pending_key: None,
+            searching: false,
         }
     }
 
@@ -316,6 +389,28 @@ impl InputState {
         self.mode == EditMode::Emacs
     }
 
+    /// Vi input mode (saved in a `Snapshot`)
+    pub(crate) fn input_mode(&self) -> InputMode {
+        self.input_mode
+    }
+
+    pub(crate) fn set_input_mode(&mut self, input_mode: InputMode) {
+        self.input_mode = input_mode;
+    }
+
+    /// Tell if the keys are read by the incremental search (where M-r
+    /// toggles the regex mode).
+    pub(crate) fn set_searching(&mut self, yes: bool) {
+        self.searching = yes;
+    }
+
+    /// Switch the keymap (in insert mode for Vi).
+    pub(crate) fn set_edit_mode(&mut self, mode: EditMode) {
+        self.mode = mode;
+        self.input_mode = InputMode::Insert;
+        self.num_args = 0;
+    }
+
     /// Parse user input into one command
     /// `single_esc_abort` is used in emacs mode on unix platform when a single
     /// esc key is expected to abort current action.
@@ -421,6 +516,8 @@ impl InputState {
                 Cmd::Kill(Movement::ForwardChar(n))
             },
             KeyPress::Tab => Cmd::Complete,
+            KeyPress::BackTab => Cmd::CompleteBackward,
+            KeyPress::BracketedPasteStart => Cmd::Paste(try!(rdr.read_pasted_text())),
             KeyPress::Ctrl('K') => if positive {
                 Cmd::Kill(Movement::EndOfLine)
             } else {
@@ -433,7 +530,8 @@ impl InputState {
                 let snd_key = try!(rdr.next_key(true));
                 match snd_key {
                     KeyPress::Ctrl('G') | KeyPress::Esc => Cmd::Abort,
-                    KeyPress::Ctrl('U') => Cmd::Undo(n),
+                    KeyPress::Ctrl('U') | KeyPress::Char('u') => Cmd::Undo(n),
+                    KeyPress::Char('8') => Cmd::InsertCodePoint,
                     _ => Cmd::Unknown,
                 }
             }
@@ -442,6 +540,9 @@ impl InputState {
             } else {
                 Cmd::Kill(Movement::ForwardWord(n, At::AfterEnd, Word::Emacs))
             },
+            KeyPress::Ctrl('O') => Cmd::AcceptLineAndDownHistory,
+            KeyPress::Meta('A') | KeyPress::Meta('a') => Cmd::AcceptAndHold,
+            KeyPress::Meta('Q') | KeyPress::Meta('q') => Cmd::PushLine,
             KeyPress::Meta('<') => Cmd::BeginningOfHistory,
             KeyPress::Meta('>') => Cmd::EndOfHistory,
             KeyPress::Meta('B') | KeyPress::Meta('b') => if positive {
@@ -461,9 +562,13 @@ impl InputState {
                 Cmd::Move(Movement::BackwardWord(n, Word::Emacs))
             },
             KeyPress::Meta('L') | KeyPress::Meta('l') => Cmd::DowncaseWord,
+            KeyPress::Meta('R') | KeyPress::Meta('r') if !self.searching => Cmd::RevertLine,
             KeyPress::Meta('T') | KeyPress::Meta('t') => Cmd::TransposeWords(n),
             KeyPress::Meta('U') | KeyPress::Meta('u') => Cmd::UpcaseWord,
+            KeyPress::Meta('W') | KeyPress::Meta('w') => Cmd::CopySelection,
+            KeyPress::Meta('X') | KeyPress::Meta('x') => Cmd::ExecuteNamedCommand,
             KeyPress::Meta('Y') | KeyPress::Meta('y') => Cmd::YankPop,
+            KeyPress::Meta('\x1f') => Cmd::Redo(n), // C-M-_
             _ => self.common(key, n, positive),
         };
         debug!(target: "rustyline", "Emacs command: {:?}", cmd);
@@ -650,15 +755,13 @@ impl InputState {
             KeyPress::Char('-') |
             KeyPress::Char('k') | // TODO: move to the start of the line.
             KeyPress::Ctrl('P') => Cmd::PreviousHistory,
-            KeyPress::Ctrl('R') => {
-                self.input_mode = InputMode::Insert; // TODO Validate
-                Cmd::ReverseSearchHistory
-            }
+            KeyPress::Ctrl('R') => Cmd::Redo(n),
             KeyPress::Ctrl('S') => {
                 self.input_mode = InputMode::Insert; // TODO Validate
                 Cmd::ForwardSearchHistory
             }
             KeyPress::Esc => Cmd::Noop,
+            KeyPress::BracketedPasteStart => Cmd::Paste(try!(rdr.read_pasted_text())),
             _ => self.common(key, n, true),
         };
         debug!(target: "rustyline", "Vi command: {:?}", cmd);
@@ -669,7 +772,15 @@ impl InputState {
     }
 
     fn vi_insert<R: RawReader>(&mut self, rdr: &mut R, wrt: &mut Refresher) -> Result<Cmd> {
-        let key = try!(rdr.next_key(false));
+        let key = match self.pending_key.take() {
+            Some(key) => key,
+            None => try!(rdr.next_key(false)),
+        };
+        let key = if try!(self.is_vi_insert_escape(rdr, key)) {
+            KeyPress::Esc
+        } else {
+            key
+        };
         {
             let bindings = self.custom_bindings.read().unwrap();
             if let Some(cmd) = bindings.get(&key) {
@@ -689,6 +800,8 @@ impl InputState {
             },
             KeyPress::Ctrl('H') | KeyPress::Backspace => Cmd::Kill(Movement::BackwardChar(1)),
             KeyPress::Tab => Cmd::Complete,
+            KeyPress::BackTab => Cmd::CompleteBackward,
+            KeyPress::BracketedPasteStart => Cmd::Paste(try!(rdr.read_pasted_text())),
             KeyPress::Esc => {
                 // vi-movement-mode/vi-command-mode
                 self.input_mode = InputMode::Command;
@@ -710,6 +823,30 @@ impl InputState {
         Ok(cmd)
     }
 
+    /// Tell if `key` is followed by the second character of
+    /// `Config::vi_insert_escape` (otherwise, the next key is kept for the
+    /// next command).
+    fn is_vi_insert_escape<R: RawReader>(&mut self, rdr: &mut R, key: KeyPress) -> Result<bool> {
+        let (first, second) = match self.vi_insert_escape {
+            Some(escape) => escape,
+            None => return Ok(false),
+        };
+        if key != KeyPress::Char(first) {
+            return Ok(false);
+        }
+        if self.keyseq_timeout >= 0
+            && !rdr.wait_for_input(Duration::from_millis(self.keyseq_timeout as u64))
+        {
+            return Ok(false);
+        }
+        let next = try!(rdr.next_key(false));
+        if next == KeyPress::Char(second) {
+            return Ok(true);
+        }
+        self.pending_key = Some(next);
+        Ok(false)
+    }
+
     fn vi_cmd_motion<R: RawReader>(
         &mut self,
         rdr: &mut R,
@@ -816,6 +953,24 @@ impl InputState {
                 }
             }
             KeyPress::End => Cmd::Move(Movement::EndOfLine),
+            KeyPress::ShiftLeft => Cmd::Select(Movement::BackwardChar(n)),
+            KeyPress::ShiftRight => Cmd::Select(Movement::ForwardChar(n)),
+            KeyPress::ShiftHome => Cmd::Select(Movement::BeginningOfLine),
+            KeyPress::ShiftEnd => Cmd::Select(Movement::EndOfLine),
+            KeyPress::MetaShiftLeft => {
+                if positive {
+                    Cmd::DragWordBackward(n)
+                } else {
+                    Cmd::DragWordForward(n)
+                }
+            }
+            KeyPress::MetaShiftRight => {
+                if positive {
+                    Cmd::DragWordForward(n)
+                } else {
+                    Cmd::DragWordBackward(n)
+                }
+            }
             KeyPress::Right => {
                 if positive {
                     Cmd::Move(Movement::ForwardChar(n))
@@ -829,6 +984,7 @@ impl InputState {
             KeyPress::Up => Cmd::PreviousHistory,
             KeyPress::Ctrl('R') => Cmd::ReverseSearchHistory,
             KeyPress::Ctrl('S') => Cmd::ForwardSearchHistory, // most terminals override Ctrl+S to suspend execution
+            KeyPress::Meta('R') | KeyPress::Meta('r') if self.searching => Cmd::ToggleRegexSearch,
             KeyPress::Ctrl('T') => Cmd::TransposeChars,
             KeyPress::Ctrl('U') => {
                 if positive {
@@ -1,4 +1,8 @@
 //! Key constants
+#[cfg(any(windows, test))]
+use std::char::{decode_utf16, DecodeUtf16Error};
+#[cfg(any(windows, test))]
+use std::result;
 
 /// #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -6,6 +10,10 @@ pub enum KeyPress {
     UnknownEscSeq,
     Backspace, // Ctrl('H')
     BackTab,
+    /// End of a bracketed paste (`ESC [ 201 ~`)
+    BracketedPasteEnd,
+    /// Start of a bracketed paste (`ESC [ 200 ~`), followed by the pasted text
+    BracketedPasteStart,
     Char(char),
     ControlDown,
     ControlLeft,
@@ -22,11 +30,15 @@ pub enum KeyPress {
     Insert,
     Left,
     Meta(char),
+    MetaShiftLeft,
+    MetaShiftRight,
     Null,
     PageDown,
     PageUp,
     Right,
     ShiftDown,
+    ShiftEnd,
+    ShiftHome,
     ShiftLeft,
     ShiftRight,
     ShiftUp,
@@ -34,6 +46,34 @@ pub enum KeyPress {
     Up,
 }
 
+/// Rewriting of each key pressed before its dispatch, whatever the edit mode
+/// is (to swap some keys or to map the keys of another keyboard layout to
+/// the command keys).
+///
+/// ```
+/// use rustyline::{Editor, KeyPress};
+/// let mut editor = Editor::<()>::new();
+/// // Russian layout: 'и' and 'а' are on the 'b' and 'f' keys
+/// editor.set_key_remapper(Some(Box::new(|key| match key {
+///     KeyPress::Meta('и') => KeyPress::Meta('b'),
+///     KeyPress::Meta('а') => KeyPress::Meta('f'),
+///     key => key,
+/// })));
+/// ```
+pub trait KeyRemapper {
+    /// Return the key to dispatch instead of `key`.
+    fn remap(&self, key: KeyPress) -> KeyPress;
+}
+
+impl<F> KeyRemapper for F
+where
+    F: Fn(KeyPress) -> KeyPress,
+{
+    fn remap(&self, key: KeyPress) -> KeyPress {
+        self(key)
+    }
+}
+
 //#[allow(clippy::match_same_arms)]
 pub fn char_to_key_press(c: char) -> KeyPress {
     if !c.is_control() {
@@ -76,12 +116,91 @@ pub fn char_to_key_press(c: char) -> KeyPress {
     }
 }
 
+/// Reassembly of the UTF-16 code units received one at a time: the Windows
+/// console splits each character outside the BMP (like an emoji) across two
+/// input events (a high and a low surrogate).
+#[cfg(any(windows, test))]
+#[derive(Debug, Default)]
+pub struct Utf16Decoder {
+    high: Option<u16>,
+    retry: Option<u16>,
+}
+
+#[cfg(any(windows, test))]
+impl Utf16Decoder {
+    /// Decode the next `unit`: `None` for a high surrogate (until the low
+    /// one is received), `Err` for an unpaired surrogate.
+    pub fn decode(&mut self, unit: u16) -> Option<result::Result<char, DecodeUtf16Error>> {
+        let high = self.high.take();
+        match unit {
+            0xD800...0xDBFF => {
+                self.high = Some(unit);
+                high.map(decode_unit)
+            }
+            0xDC00...0xDFFF => Some(match high {
+                Some(high) => decode_utf16([high, unit].iter().cloned()).next().unwrap(),
+                None => decode_unit(unit),
+            }),
+            _ => Some(match high {
+                Some(high) => {
+                    // decoded on the next call
+                    self.retry = Some(unit);
+                    decode_unit(high)
+                }
+                None => decode_unit(unit),
+            }),
+        }
+    }
+
+    /// Unit received just after an unpaired high surrogate, still to be
+    /// decoded.
+    pub fn take_retry(&mut self) -> Option<u16> {
+        self.retry.take()
+    }
+}
+
+#[cfg(any(windows, test))]
+fn decode_unit(unit: u16) -> result::Result<char, DecodeUtf16Error> {
+    decode_utf16(Some(unit)).next().unwrap()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{char_to_key_press, KeyPress};
+    use super::{char_to_key_press, KeyPress, Utf16Decoder};
 
     #[test]
     fn char_to_key() {
         assert_eq!(KeyPress::Esc, char_to_key_press('\x1b'));
     }
+
+    #[test]
+    fn utf16_decoder() {
+        let mut decoder = Utf16Decoder::default();
+        assert_eq!(Some('a'), decoder.decode(0x61).and_then(|r| r.ok()));
+        // U+1F389
+        assert!(decoder.decode(0xD83C).is_none());
+        assert_eq!(
+            Some('\u{1f389}'),
+            decoder.decode(0xDF89).and_then(|r| r.ok())
+        );
+        // unpaired low surrogate
+        assert_eq!(
+            Some(0xDF89),
+            decoder
+                .decode(0xDF89)
+                .and_then(|r| r.err())
+                .map(|e| e.unpaired_surrogate())
+        );
+        // unpaired high surrogate
+        assert!(decoder.decode(0xD83C).is_none());
+        assert_eq!(
+            Some(0xD83C),
+            decoder
+                .decode(0x62)
+                .and_then(|r| r.err())
+                .map(|e| e.unpaired_surrogate())
+        );
+        assert_eq!(Some(0x62), decoder.take_retry());
+        assert_eq!(None, decoder.take_retry());
+    }
 }
@@ -1,4 +1,5 @@
 //! Kill Ring management
+use keymap::RepeatCount;
 use line_buffer::{DeleteListener, Direction};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -34,6 +35,21 @@ impl KillRing {
         }
     }
 
+    /// Index of the most recent slot and the slots (for `Snapshot`)
+    pub(crate) fn slots(&self) -> (usize, Vec<String>) {
+        (self.index, self.slots.clone())
+    }
+
+    /// Restore (and take) the slots saved in a `Snapshot` (as much as the size
+    /// permits).
+    pub(crate) fn restore(&mut self, index: usize, slots: &mut Vec<String>) {
+        slots.truncate(self.slots.capacity());
+        self.index = if index < slots.len() { index } else { 0 };
+        self.slots.clear();
+        self.slots.append(slots);
+        self.last_action = Action::Other;
+    }
+
     /// Reset `last_action` state.
     pub fn reset(&mut self) {
         self.last_action = Action::Other;
@@ -81,6 +97,18 @@ impl KillRing {
         }
     }
 
+    /// Yank the `n`th most recently killed text (`1` for the last one).
+    /// Return `None` when kill-ring is empty.
+    pub fn yank_nth(&mut self, n: RepeatCount) -> Option<&String> {
+        if self.slots.is_empty() {
+            return None;
+        }
+        let len = self.slots.len();
+        let back = n.saturating_sub(1) % len;
+        self.index = (self.index + len - back) % len;
+        self.yank()
+    }
+
     /// Yank killed text stored in previous slot.
     /// Return `None` when the previous command was not a yank.
     pub fn yank_pop(&mut self) -> Option<(usize, &String)> {
@@ -227,4 +255,23 @@ mod tests {
         assert_eq!(Some((5, &"longword2".to_owned())), kill_ring.yank_pop());
         assert_eq!(Some((9, &"word1".to_owned())), kill_ring.yank_pop());
     }
+
+    #[test]
+    fn yank_nth() {
+        let mut kill_ring = KillRing::new(3);
+        kill_ring.kill("word1", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word2", Mode::Append);
+        kill_ring.reset();
+        kill_ring.kill("word3", Mode::Append);
+
+        assert_eq!(Some(&"word3".to_owned()), kill_ring.yank_nth(1));
+        assert_eq!(Some(&"word1".to_owned()), kill_ring.yank_nth(3));
+        assert_eq!(
+            Some(&"word3".to_owned()),
+            kill_ring.yank_pop().map(|(_, t)| t)
+        );
+        assert_eq!(Some(&"word2".to_owned()), kill_ring.yank_nth(5));
+        assert_eq!(Action::Yank(5), kill_ring.last_action);
+    }
 }
@@ -33,46 +33,145 @@ extern crate utf8parse;
 #[cfg(windows)]
 extern crate winapi;
 
+#[macro_use]
+mod macros;
+
+#[cfg(feature = "capi")]
+pub mod capi;
+mod clipboard;
+pub mod command;
 pub mod completion;
+mod compose;
 pub mod config;
+pub mod correction;
+mod draft;
 mod edit;
+pub mod embed;
 pub mod error;
+pub mod event;
+pub mod form;
 pub mod highlight;
 pub mod hint;
 pub mod history;
+pub mod inputrc;
 mod keymap;
 mod keys;
 mod kill_ring;
 pub mod line_buffer;
+pub mod prompt;
+pub mod quote;
+mod regex;
+pub mod snapshot;
+pub mod testing;
+pub mod tokenizer;
+pub mod trace;
 mod undo;
+pub mod validate;
 
 mod tty;
 
+use std::borrow::Cow::{self, Borrowed, Owned};
+use std::cell::RefCell;
+use std::cmp;
 use std::collections::HashMap;
 use std::fmt;
 use std::io::{self, Write};
-use std::path::Path;
+use std::iter;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 use std::result;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex, RwLock};
-use unicode_width::UnicodeWidthStr;
-
-use tty::{RawMode, RawReader, Renderer, Term, Terminal};
-
-use completion::{longest_common_prefix, Candidate, Completer};
-pub use config::{ColorMode, CompletionType, Config, EditMode, HistoryDuplicates};
-use edit::State;
-use highlight::Highlighter;
+use std::time::{Duration, Instant};
+
+use tty::{RawMode, RawReader, Remapped, Renderer, Term, Terminal};
+
+use command::{CommandHandler, Context, PasteHandler, Picker};
+use completion::{fuzzy_sort, longest_common_prefix, Candidate, CandidateStream, Completer};
+use correction::Corrector;
+use draft::Draft;
+pub use config::{
+    AmbiguousWidth, Behavior, BellStyle, ColorMode, CompletionType, Config, EditMode,
+    HistoryDuplicates, HistoryFormat, OutputStreamType, ReadlineOptions, UndoGrouping,
+};
+use config::WordChars;
+use edit::{OnEdit, State};
+use event::{Event, EventSender, EVENT_POLL};
+use form::Form;
+use highlight::{paint, Highlighter, Theme, Themed};
 use hint::Hinter;
-use history::{Direction, History};
+use history::{AddResult, Direction, History};
+use regex::Regex;
 pub use keymap::{Anchor, At, CharSearch, Cmd, Movement, RepeatCount, Word};
 use keymap::{InputState, Refresher};
-pub use keys::KeyPress;
+pub use keys::{KeyPress, KeyRemapper};
 use kill_ring::{KillRing, Mode};
-use line_buffer::WordAction;
+use line_buffer::{ChangeListener, SharedListener, WordAction};
+use prompt::PromptTemplate;
+use snapshot::Snapshot;
+use trace::Trace;
+use undo::Changeset;
+use validate::{ValidationResult, Validator};
 
 /// The error type for I/O and Linux Syscalls (Errno)
 pub type Result<T> = result::Result<T, error::ReadlineError>;
 
+/// Idle delay after which the draft of the edited line is saved
+const DRAFT_DELAY: Duration = Duration::from_millis(500);
+
+/// Interval of the polling of the keys while receiving streamed candidates
+const STREAM_POLL: Duration = Duration::from_millis(50);
+
+/// Receive the candidates of the `stream` (see `Completer::complete_stream`),
+/// listed under the line with a spinner as they arrive. Return `None` when
+/// a key is pressed before the end of the stream (the key is then handled
+/// by the main loop).
+fn receive_candidates<R: RawReader, C: Candidate>(
+    rdr: &mut R,
+    s: &mut State,
+    stream: CandidateStream<C>,
+) -> Result<Option<Vec<C>>> {
+    let frames = ['|', '/', '-', '\\'];
+    let menu = s.menu.take();
+    let mut candidates = Vec::new();
+    let mut tick = 0;
+    loop {
+        match stream.recv_timeout(STREAM_POLL) {
+            Ok(batch) => {
+                candidates.extend(batch);
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if rdr.wait_for_input(Duration::from_millis(0)) {
+            // the user keeps typing: drop (cancel) the stream
+            s.menu = menu;
+            try!(s.refresh_line());
+            return Ok(None);
+        }
+        let msg = &tty::SPINNER_MSG[..tty::SPINNER_MSG.len() - 1];
+        let mut pending = format!("{}{}", msg, frames[tick % frames.len()]);
+        if !candidates.is_empty() {
+            let max_rows = cmp::max(1, s.out.get_rows() / 3);
+            pending.push('\n');
+            pending.push_str(&completion_menu(
+                &candidates,
+                s.out.get_columns(),
+                max_rows,
+                false,
+                s.out.is_wide_ambiguous(),
+            ));
+        }
+        s.menu = Some(pending);
+        try!(s.refresh_line());
+        tick += 1;
+    }
+    s.menu = menu;
+    Ok(Some(candidates))
+}
+
 /// Completes the line/word
 fn complete_line<R: RawReader, C: Completer>(
     rdr: &mut R,
@@ -82,22 +181,54 @@ fn complete_line<R: RawReader, C: Completer>(
     highlighter: Option<&Highlighter>,
     config: &Config,
 ) -> Result<Option<Cmd>> {
-    // get a list of completions
-    let (start, candidates) = try!(completer.complete(&s.line, s.line.pos()));
+    s.update_after_edit();
+    let stream = completer.complete_stream(&s.line, s.line.pos(), &s.helper_context());
+    let (start, candidates) = if let Some((start, stream)) = stream {
+        match try!(receive_candidates(rdr, s, stream)) {
+            Some(candidates) => (start, candidates),
+            None => return Ok(None), // cancelled
+        }
+    } else {
+        // get a list of completions, with a spinner if it is slow
+        let spinner = match s.out.shared_writer() {
+            Some(out) if s.cursor.col + tty::SPINNER_MSG.len() < s.out.get_columns() => {
+                Some(tty::Spinner::start(Duration::from_millis(100), out))
+            }
+            _ => None,
+        };
+        let completions =
+            completer.complete_with_context(&s.line, s.line.pos(), &s.helper_context());
+        if spinner.map_or(false, |spinner| spinner.stop()) {
+            try!(s.refresh_line());
+        }
+        try!(completions)
+    };
+    // positions of the fuzzy matched characters in the displays
+    let (candidates, matches) = if config.fuzzy_completion() {
+        let pattern = s.line.as_str().get(start..s.line.pos()).unwrap_or("");
+        fuzzy_sort(pattern, candidates, config.completion_ignore_case())
+    } else {
+        (candidates, Vec::new())
+    };
     // if no completions, we are done
     if candidates.is_empty() {
-        try!(s.out.beep());
+        try!(s.beep());
         Ok(None)
     } else if CompletionType::Circular == config.completion_type() {
         let mark = s.changes.borrow_mut().begin();
         // Save the current edited line before overwriting it
         let backup = s.line.as_str().to_owned();
         let backup_pos = s.line.pos();
+        let menu = s.menu.take();
+        let wide = s.out.is_wide_ambiguous();
         let mut cmd;
         let mut i = 0;
         loop {
             // Show completion or original buffer
             if i < candidates.len() {
+                s.menu = completer
+                    .candidate_doc(&candidates[i])
+                    .map(|doc| doc_pane(&doc, s.out.get_columns(), s.out.get_rows(), wide));
                 let candidate = candidates[i].replacement();
                 // TODO we can't highlight the line buffer directly
                 /*let candidate = if let Some(highlighter) = s.highlighter {
@@ -109,6 +240,7 @@ fn complete_line<R: RawReader, C: Completer>(
                 try!(s.refresh_line());
             } else {
                 // Restore current edited line
+                s.menu = None;
                 s.line.update(&backup, backup_pos);
                 try!(s.refresh_line());
             }
@@ -118,15 +250,22 @@ fn complete_line<R: RawReader, C: Completer>(
                 Cmd::Complete => {
                     i = (i + 1) % (candidates.len() + 1); // Circular
                     if i == candidates.len() {
-                        try!(s.out.beep());
+                        try!(s.beep());
+                    }
+                }
+                Cmd::CompleteBackward => {
+                    i = (i + candidates.len()) % (candidates.len() + 1);
+                    if i == candidates.len() {
+                        try!(s.beep());
                     }
                 }
                 Cmd::Abort => {
                     // Re-show original buffer
+                    s.menu = menu;
                     if i < candidates.len() {
                         s.line.update(&backup, backup_pos);
-                        try!(s.refresh_line());
                     }
+                    try!(s.refresh_line());
                     s.changes.borrow_mut().truncate(mark);
                     return Ok(None);
                 }
@@ -136,6 +275,9 @@ fn complete_line<R: RawReader, C: Completer>(
                 }
             }
         }
+        // Hide the documentation
+        s.menu = menu;
+        try!(s.refresh_line());
         Ok(Some(cmd))
     } else if CompletionType::List == config.completion_type() {
         if let Some(lcp) = longest_common_prefix(&candidates) {
@@ -145,17 +287,19 @@ fn complete_line<R: RawReader, C: Completer>(
                 try!(s.refresh_line());
             }
         }
-        // beep if ambiguous
-        if candidates.len() > 1 {
-            try!(s.out.beep());
-        } else {
+        if candidates.len() <= 1 {
             return Ok(None);
         }
-        // we can't complete any further, wait for second tab
-        let mut cmd = try!(s.next_cmd(input_state, rdr, true));
-        // if any character other than tab, pass it to the main loop
-        if cmd != Cmd::Complete {
-            return Ok(Some(cmd));
+        let mut cmd = Cmd::Complete;
+        if !config.show_all_if_ambiguous() {
+            // beep if ambiguous
+            try!(s.beep());
+            // we can't complete any further, wait for second tab
+            cmd = try!(s.next_cmd(input_state, rdr, true));
+            // if any character other than tab, pass it to the main loop
+            if cmd != Cmd::Complete {
+                return Ok(Some(cmd));
+            }
         }
         // move cursor to EOL to avoid overwriting the command line
         let save_pos = s.line.pos();
@@ -182,11 +326,81 @@ fn complete_line<R: RawReader, C: Completer>(
             true
         };
         if show_completions {
-            page_completions(rdr, s, input_state, highlighter, &candidates)
+            page_completions(rdr, s, input_state, highlighter, &candidates, &matches, config)
         } else {
             try!(s.refresh_line());
             Ok(None)
         }
+    } else if CompletionType::Menu == config.completion_type() {
+        if candidates.len() == 1 {
+            completer.update(&mut s.line, start, candidates[0].replacement());
+            try!(s.refresh_line());
+            return Ok(None);
+        }
+        // the selection is highlighted only when colors are enabled
+        let theme = highlighter.map(|_| config.theme());
+        let mark = s.changes.borrow_mut().begin();
+        // Save the current edited line before overwriting it
+        let backup = s.line.as_str().to_owned();
+        let backup_pos = s.line.pos();
+        let menu = s.menu.take();
+        let cols = s.out.get_columns();
+        let wide = s.out.is_wide_ambiguous();
+        let num_cols = menu_columns(&candidates, cols, wide);
+        let max_rows = cmp::max(1, s.out.get_rows() / 3);
+        let len = candidates.len();
+        let mut i = 0;
+        let cmd = loop {
+            s.menu = Some(selection_menu(
+                &candidates,
+                &matches,
+                i,
+                cols,
+                (num_cols, max_rows),
+                theme,
+                wide,
+            ));
+            completer.update(&mut s.line, start, candidates[i].replacement());
+            try!(s.refresh_line());
+
+            let cmd = try!(s.next_cmd(input_state, rdr, true));
+            i = match cmd {
+                Cmd::Complete | Cmd::Move(Movement::ForwardChar(_)) => (i + 1) % len,
+                Cmd::CompleteBackward | Cmd::Move(Movement::BackwardChar(_)) => (i + len - 1) % len,
+                Cmd::NextHistory | Cmd::HistorySearchForward => {
+                    if i + num_cols < len {
+                        i + num_cols
+                    } else {
+                        // first row
+                        i % num_cols
+                    }
+                }
+                Cmd::PreviousHistory | Cmd::HistorySearchBackward => {
+                    if i >= num_cols {
+                        i - num_cols
+                    } else {
+                        // last row
+                        i + (len - 1 - i) / num_cols * num_cols
+                    }
+                }
+                Cmd::Abort => {
+                    // Re-show original buffer
+                    s.menu = menu;
+                    s.line.update(&backup, backup_pos);
+                    try!(s.refresh_line());
+                    s.changes.borrow_mut().truncate(mark);
+                    return Ok(None);
+                }
+                // Accept the selection without submitting the line
+                Cmd::AcceptLine => break None,
+                cmd => break Some(cmd),
+            };
+        };
+        s.changes.borrow_mut().end();
+        // Hide the menu
+        s.menu = menu;
+        try!(s.refresh_line());
+        Ok(cmd)
     } else {
         Ok(None)
     }
@@ -198,27 +412,61 @@ fn page_completions<R: RawReader, C: Candidate>(
     input_state: &mut InputState,
     highlighter: Option<&Highlighter>,
     candidates: &[C],
+    matches: &[Vec<usize>],
+    config: &Config,
 ) -> Result<Option<Cmd>> {
-    use std::cmp;
+    use std::path::MAIN_SEPARATOR;
+
+    let displays: Vec<Cow<str>> = candidates
+        .iter()
+        .map(|c| {
+            let display = c.display();
+            if config.mark_directories()
+                && c.replacement().ends_with(MAIN_SEPARATOR)
+                && !display.ends_with(MAIN_SEPARATOR)
+            {
+                Owned(format!("{}{}", display, MAIN_SEPARATOR))
+            } else {
+                Borrowed(display)
+            }
+        }).collect();
+
+    let prefix_len = if highlighter.is_some() && config.theme().candidate_match_style().is_some() {
+        displays.iter().skip(1).fold(displays[0].len(), |len, d| {
+            displays[0][..len]
+                .char_indices()
+                .zip(d.chars())
+                .take_while(|&((_, c1), c2)| c1 == c2)
+                .last()
+                .map_or(0, |((i, c), _)| i + c.len_utf8())
+        })
+    } else {
+        0
+    };
 
     let min_col_pad = 2;
-    let cols = s.out.get_columns();
+    let cols = match config.completion_display_width() {
+        Some(width) => cmp::min(width, s.out.get_columns()),
+        None => s.out.get_columns(),
+    };
+    let wide = s.out.is_wide_ambiguous();
     let max_width = cmp::min(
         cols,
-        candidates
-            .into_iter()
-            .map(|s| s.display().width())
-            .max()
-            .unwrap()
-            + min_col_pad,
+        displays.iter().map(|d| tty::str_width(d, wide)).max().unwrap() + min_col_pad,
     );
-    let num_cols = cols / max_width;
+    let described = candidates.iter().any(|c| c.description().is_some());
+    let num_cols = if described {
+        1
+    } else {
+        cmp::max(1, cols / cmp::max(1, max_width))
+    };
+    let description_style = highlighter.map(|_| config.theme().description_style().unwrap_or("2"));
 
     let mut pause_row = s.out.get_rows() - 1;
     let num_rows = (candidates.len() + num_cols - 1) / num_cols;
     let mut ab = String::new();
     for row in 0..num_rows {
-        if row == pause_row {
+        if config.page_completions() && row == pause_row {
             try!(s.out.write_and_flush(b"\n--More--"));
             let mut cmd = Cmd::Noop;
             while cmd != Cmd::SelfInsert(1, 'y')
@@ -248,16 +496,37 @@ fn page_completions<R: RawReader, C: Candidate>(
         }
         ab.clear();
         for col in 0..num_cols {
-            let i = (col * num_rows) + row;
-            if i < candidates.len() {
-                let candidate = &candidates[i].display();
-                let width = candidate.width();
+            let (i, next) = if config.print_completions_horizontally() {
+                let i = (row * num_cols) + col;
+                (i, if col + 1 < num_cols { i + 1 } else { usize::MAX })
+            } else {
+                (
+                    (col * num_rows) + row,
+                    ((col + 1) * num_rows) + row,
+                )
+            };
+            if i < displays.len() {
+                let candidate = &displays[i];
+                let width = tty::str_width(candidate, wide);
                 if let Some(highlighter) = highlighter {
-                    ab.push_str(&highlighter.highlight_candidate(candidate, CompletionType::List));
+                    match highlighter.highlight_candidate(candidate, CompletionType::List) {
+                        Borrowed(_) if !matches.is_empty() => {
+                            let style = config.theme().candidate_match_style().unwrap_or("1");
+                            ab.push_str(&paint_matches(candidate, &matches[i], style));
+                        }
+                        Borrowed(_) if prefix_len > 0 => {
+                            let (prefix, suffix) = candidate.split_at(prefix_len);
+                            ab.push_str(&paint(config.theme().candidate_match_style(), prefix));
+                            ab.push_str(suffix);
+                        }
+                        highlighted => ab.push_str(&highlighted),
+                    }
                 } else {
                     ab.push_str(candidate);
                 }
-                if ((col + 1) * num_rows) + row < candidates.len() {
+                if let Some(description) = candidates[i].description() {
+                    push_description(&mut ab, width, description, cols, description_style, wide);
+                } else if next < displays.len() {
                     for _ in width..max_width {
                         ab.push(' ');
                     }
@@ -271,12 +540,406 @@ fn page_completions<R: RawReader, C: Candidate>(
     Ok(None)
 }
 
+/// Numbered candidates of the completion menu (see
+/// `Config::menu_quick_select`)
+#[derive(Default)]
+struct QuickSelect {
+    menu: String,
+    start: usize,
+    replacements: Vec<String>,
+}
+
+impl QuickSelect {
+    /// Replacement selected by `digit` while `menu` is displayed
+    fn choice(&self, menu: Option<&String>, digit: char) -> Option<&str> {
+        if menu != Some(&self.menu) {
+            return None;
+        }
+        digit
+            .to_digit(10)
+            .and_then(|n| self.replacements.get((n as usize).wrapping_sub(1)))
+            .map(String::as_str)
+    }
+}
+
+/// List the possible completions of the word at point under the line.
+fn update_menu<C: Completer>(
+    s: &mut State,
+    completer: &C,
+    quick_select: &mut Option<QuickSelect>,
+) -> Result<()> {
+    let menu = if s.line.is_empty() {
+        None
+    } else {
+        s.update_after_edit();
+        let (start, candidates) =
+            try!(completer.complete_with_context(&s.line, s.line.pos(), &s.helper_context()));
+        if candidates.is_empty() {
+            None
+        } else {
+            let max_rows = cmp::max(1, s.out.get_rows() / 3);
+            let numbered = quick_select.is_some();
+            let menu = completion_menu(
+                &candidates,
+                s.out.get_columns(),
+                max_rows,
+                numbered,
+                s.out.is_wide_ambiguous(),
+            );
+            if let Some(ref mut quick_select) = *quick_select {
+                quick_select.menu = menu.clone();
+                quick_select.start = start;
+                quick_select.replacements = candidates
+                    .iter()
+                    .take(9)
+                    .map(|c| c.replacement().to_owned())
+                    .collect();
+            }
+            Some(menu)
+        }
+    };
+    if menu.is_some() || s.menu.is_some() {
+        s.menu = menu;
+        try!(s.refresh_line());
+    }
+    Ok(())
+}
+
+/// Truncate each line of the candidate `doc` to the screen width and keep
+/// at most a third of the screen rows.
+fn doc_pane(doc: &str, cols: usize, rows: usize, wide: bool) -> String {
+    let cols = cmp::max(1, cols.saturating_sub(1));
+    doc.lines()
+        .take(cmp::max(1, rows / 3))
+        .map(|line| tty::truncate(line, 0, cols, wide))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Lay out the `candidates` horizontally on at most `max_rows` rows (the
+/// first nine prefixed by their number when `numbered`).
+fn completion_menu<C: Candidate>(
+    candidates: &[C],
+    cols: usize,
+    max_rows: usize,
+    numbered: bool,
+    wide: bool,
+) -> String {
+    // avoid writing on the last column (deferred wrap)
+    let cols = cmp::max(1, cols.saturating_sub(1));
+    let displays: Vec<Cow<str>> = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            if numbered && i < 9 {
+                let display = format!("{} {}", i + 1, c.display());
+                Owned(tty::truncate(&display, 0, cols, wide).to_owned())
+            } else {
+                Borrowed(tty::truncate(c.display(), 0, cols, wide))
+            }
+        })
+        .collect();
+    let min_col_pad = 2;
+    let max_width = cmp::min(
+        cols,
+        displays.iter().map(|d| tty::str_width(d, wide)).max().unwrap_or(0) + min_col_pad,
+    );
+    let num_cols = cmp::max(1, cols / cmp::max(1, max_width));
+    let mut menu = String::new();
+    for (i, row) in displays.chunks(num_cols).take(max_rows).enumerate() {
+        if i > 0 {
+            menu.push('\n');
+        }
+        for (j, display) in row.iter().enumerate() {
+            menu.push_str(display);
+            if j + 1 < row.len() {
+                for _ in tty::str_width(display, wide)..max_width {
+                    menu.push(' ');
+                }
+            }
+        }
+    }
+    menu
+}
+
+/// Number of columns of the completion menu of the `candidates` (one when
+/// they have a description)
+fn menu_columns<C: Candidate>(candidates: &[C], cols: usize, wide: bool) -> usize {
+    if candidates.iter().any(|c| c.description().is_some()) {
+        return 1;
+    }
+    // avoid writing on the last column (deferred wrap)
+    let cols = cmp::max(1, cols.saturating_sub(1));
+    // each candidate is surrounded by a space or by the selection brackets
+    let max_width = cmp::min(
+        cols,
+        candidates
+            .iter()
+            .map(|c| tty::str_width(c.display(), wide))
+            .max()
+            .unwrap_or(0)
+            + 2,
+    );
+    cmp::max(1, cols / max_width)
+}
+
+/// Lay out the `candidates` on `num_cols` columns and at most `max_rows`
+/// rows (the page of the `selected` one), with the `selected` one painted
+/// with the `theme` (and the fuzzy `matches` of the others), or between
+/// brackets when there is no theme (colors disabled).
+fn selection_menu<C: Candidate>(
+    candidates: &[C],
+    matches: &[Vec<usize>],
+    selected: usize,
+    cols: usize,
+    (num_cols, max_rows): (usize, usize),
+    theme: Option<Theme>,
+    wide: bool,
+) -> String {
+    let style = theme.map(|theme| theme.menu_selection_style().unwrap_or("7"));
+    let match_style = theme.map(|theme| theme.candidate_match_style().unwrap_or("1"));
+    let description_style = theme.map(|theme| theme.description_style().unwrap_or("2"));
+    let cols = cmp::max(1, cols.saturating_sub(1));
+    let width = cols / num_cols;
+    let displays: Vec<&str> = candidates
+        .iter()
+        .map(|c| tty::truncate(c.display(), 0, width.saturating_sub(2), wide))
+        .collect();
+    let first_row = selected / num_cols / max_rows * max_rows;
+    let mut menu = String::new();
+    for (i, row) in displays
+        .chunks(num_cols)
+        .enumerate()
+        .skip(first_row)
+        .take(max_rows)
+    {
+        if i > first_row {
+            menu.push('\n');
+        }
+        for (j, display) in row.iter().enumerate() {
+            let k = i * num_cols + j;
+            if k != selected {
+                menu.push(' ');
+                match (match_style, matches.get(k)) {
+                    (Some(style), Some(indices)) => {
+                        menu.push_str(&paint_matches(display, indices, style))
+                    }
+                    _ => menu.push_str(display),
+                }
+                menu.push(' ');
+            } else if style.is_some() {
+                menu.push(' ');
+                menu.push_str(&paint(style, display));
+                menu.push(' ');
+            } else {
+                menu.push('[');
+                menu.push_str(display);
+                menu.push(']');
+            }
+            if let Some(description) = candidates[k].description() {
+                let width = tty::str_width(display, wide) + 2;
+                push_description(&mut menu, width, description, cols + 1, description_style, wide);
+            } else if j + 1 < row.len() {
+                for _ in tty::str_width(display, wide) + 2..width {
+                    menu.push(' ');
+                }
+            }
+        }
+    }
+    menu
+}
+
+/// Append to the `row` (`width` columns wide) the first line of the
+/// `description` of its candidate, right-aligned on `cols` columns (but the
+/// last one) and truncated if needed.
+fn push_description(
+    row: &mut String,
+    width: usize,
+    description: &str,
+    cols: usize,
+    style: Option<&str>,
+    wide: bool,
+) {
+    let min_pad = 2;
+    let cols = cols.saturating_sub(1);
+    if width + min_pad >= cols {
+        return;
+    }
+    let description = description.lines().next().unwrap_or("");
+    let description = tty::truncate(description, 0, cols - width - min_pad, wide);
+    for _ in width + tty::str_width(description, wide)..cols {
+        row.push(' ');
+    }
+    row.push_str(&paint(style, description));
+}
+
+/// Paint the characters of `display` at the byte positions `indices` (fuzzy
+/// matched) with `style`.
+fn paint_matches(display: &str, indices: &[usize], style: &str) -> String {
+    let mut painted = String::with_capacity(display.len());
+    let mut run = String::new();
+    for (i, c) in display.char_indices() {
+        if indices.contains(&i) {
+            run.push(c);
+        } else {
+            if !run.is_empty() {
+                painted.push_str(&paint(Some(style), &run));
+                run.clear();
+            }
+            painted.push(c);
+        }
+    }
+    painted.push_str(&paint(Some(style), &run));
+    painted
+}
+
+/// Handle the events received from the `EventSender`s, until a command to
+/// execute.
+fn handle_events(s: &mut State, events: &Receiver<Event>) -> Result<Option<Cmd>> {
+    for event in events.try_iter() {
+        match event {
+            Event::Print(text) => try!(s.print_above(&text)),
+            Event::Cmd(cmd) => return Ok(Some(cmd)),
+            Event::Refresh => try!(s.refresh_line()),
+        }
+    }
+    Ok(None)
+}
+
+/// Expand the word before the cursor if it is an abbreviation.
+/// Return `None` if it is not, the tab stops (distance from the end of the
+/// line) following the one where the cursor is otherwise.
+fn expand_abbreviation(
+    s: &mut State,
+    abbreviations: &HashMap<String, String>,
+) -> Result<Option<Vec<usize>>> {
+    if abbreviations.is_empty() {
+        return Ok(None);
+    }
+    let pos = s.line.pos();
+    if s.line[pos..].chars().next().map_or(false, |c| !c.is_whitespace()) {
+        return Ok(None);
+    }
+    let start = s.line[..pos]
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| c.is_whitespace())
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    let (text, offsets) = match abbreviations.get(&s.line[start..pos]) {
+        Some(expansion) => snippet(expansion),
+        None => return Ok(None),
+    };
+    s.line.replace(start..pos, &text);
+    let len = s.line.len();
+    let mut stops = offsets
+        .into_iter()
+        .map(|offset| len - (start + offset))
+        .collect::<Vec<_>>();
+    if !stops.is_empty() {
+        let first = stops.remove(0);
+        s.line.set_pos(len - first);
+    }
+    try!(s.refresh_line());
+    Ok(Some(stops))
+}
+
+/// Remove `${n}` tab stops from `expansion`.
+/// Return the text and the offsets of the tab stops, sorted by `n`.
+fn snippet(expansion: &str) -> (String, Vec<usize>) {
+    let mut text = String::with_capacity(expansion.len());
+    let mut stops = Vec::new();
+    let mut rest = expansion;
+    while let Some(i) = rest.find("${") {
+        let (before, after) = rest.split_at(i);
+        text.push_str(before);
+        let tab_stop = after[2..]
+            .find('}')
+            .and_then(|j| after[2..2 + j].parse::<usize>().ok().map(|n| (n, j)));
+        match tab_stop {
+            Some((n, j)) => {
+                stops.push((n, text.len()));
+                rest = &after[3 + j..];
+            }
+            None => {
+                text.push_str("${");
+                rest = &after[2..];
+            }
+        }
+    }
+    text.push_str(rest);
+    stops.sort_by_key(|&(n, _)| n);
+    (text, stops.into_iter().map(|(_, offset)| offset).collect())
+}
+
+/// Suggest corrections of the first word if it is unknown.
+/// Return `false` if the user wants to go on editing the line.
+fn correct_first_word<R: RawReader>(
+    rdr: &mut R,
+    s: &mut State,
+    input_state: &mut InputState,
+    corrector: &Corrector,
+    history: &History,
+) -> Result<bool> {
+    let (start, end) = {
+        let line = s.line.as_str();
+        let start = line
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(line.len());
+        let end = line[start..]
+            .find(char::is_whitespace)
+            .map_or(line.len(), |i| start + i);
+        (start, end)
+    };
+    if start == end || !corrector.is_unknown(&s.line[start..end]) {
+        return Ok(true);
+    }
+    let suggestions = {
+        let words = corrector.words();
+        let first_words = history
+            .iter()
+            .filter_map(|entry| entry.split_whitespace().next());
+        correction::suggestions(
+            &s.line[start..end],
+            words.iter().map(|w| w.as_str()).chain(first_words),
+            9,
+        )
+    };
+    if suggestions.is_empty() {
+        return Ok(true);
+    }
+    try!(s.edit_move_end());
+    let mut msg = String::from("\nDid you mean:");
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        msg.push_str(&format!(" {}) {}", i + 1, suggestion));
+    }
+    try!(s.out.write_and_flush(msg.as_bytes()));
+    s.old_rows += 1;
+    let cmd = try!(s.next_cmd(input_state, rdr, false));
+    let accept = match cmd {
+        Cmd::SelfInsert(1, c) => match c.to_digit(10) {
+            Some(n) if n >= 1 && n as usize <= suggestions.len() => {
+                s.line
+                    .replace(start..end, &suggestions[n as usize - 1]);
+                true
+            }
+            _ => false,
+        },
+        Cmd::AcceptLine => true,
+        _ => false,
+    };
+    try!(s.refresh_line());
+    Ok(accept)
+}
+
 /// Incremental search
-fn reverse_incremental_search<R: RawReader>(
+fn incremental_search<R: RawReader>(
     rdr: &mut R,
     s: &mut State,
     input_state: &mut InputState,
     history: &History,
+    config: &Config,
+    mut direction: Direction,
+    discarded_line: &mut Option<String>,
 ) -> Result<Option<Cmd>> {
     if history.is_empty() {
         return Ok(None);
@@ -287,17 +950,39 @@ fn reverse_incremental_search<R: RawReader>(
     let backup_pos = s.line.pos();
 
     let mut search_buf = String::new();
-    let mut history_idx = history.len() - 1;
-    let mut direction = Direction::Reverse;
+    let mut history_idx = match direction {
+        Direction::Reverse => history.len() - 1,
+        // from the browsed entry or from the oldest one
+        Direction::Forward if s.history_index() < history.len() => s.history_index(),
+        Direction::Forward => 0,
+    };
     let mut success = true;
+    let mut wrapped = false;
+    let mut regex_mode = false;
+    let mut invalid = None; // regex error
 
     let mut cmd;
     // Display the reverse-i-search prompt and process chars
     loop {
-        let prompt = if success {
-            format!("(reverse-i-search)`{}': ", search_buf)
+        let term = if s.highlighter.is_some() {
+            paint(config.theme().search_match_style(), &search_buf)
         } else {
-            format!("(failed reverse-i-search)`{}': ", search_buf)
+            Borrowed(search_buf.as_str())
+        };
+        let prompt = match invalid {
+            Some(ref err) => format!("(invalid regex: {})`{}': ", err, term),
+            None => format!(
+                "({}{}{}{}i-search)`{}': ",
+                if success { "" } else { "failed " },
+                if wrapped { "wrapped " } else { "" },
+                if regex_mode { "regex " } else { "" },
+                if direction == Direction::Reverse {
+                    "reverse-"
+                } else {
+                    ""
+                },
+                term
+            ),
         };
         try!(s.refresh_prompt_and_line(&prompt));
 
@@ -308,49 +993,93 @@ fn reverse_incremental_search<R: RawReader>(
             match cmd {
                 Cmd::Kill(Movement::BackwardChar(_)) => {
                     search_buf.pop();
+                    if regex_mode {
+                        invalid = Regex::new(&search_buf).err();
+                    }
                     continue;
                 }
+                Cmd::ToggleRegexSearch => {
+                    regex_mode = !regex_mode;
+                    invalid = None;
+                }
                 Cmd::ReverseSearchHistory => {
-                    direction = Direction::Reverse;
-                    if history_idx > 0 {
-                        history_idx -= 1;
+                    if history_idx == 0 || (!success && direction == Direction::Reverse) {
+                        // wrap to the newest entry
+                        history_idx = history.len() - 1;
+                        wrapped = true;
                     } else {
-                        success = false;
-                        continue;
+                        history_idx -= 1;
                     }
+                    direction = Direction::Reverse;
                 }
                 Cmd::ForwardSearchHistory => {
-                    direction = Direction::Forward;
-                    if history_idx < history.len() - 1 {
-                        history_idx += 1;
+                    if history_idx == history.len() - 1
+                        || (!success && direction == Direction::Forward)
+                    {
+                        // wrap to the oldest entry
+                        history_idx = 0;
+                        wrapped = true;
                     } else {
-                        success = false;
-                        continue;
+                        history_idx += 1;
                     }
+                    direction = Direction::Forward;
                 }
                 Cmd::Abort => {
+                    if s.line.as_str() != backup {
+                        *discarded_line = Some(s.line.as_str().to_owned());
+                    }
                     // Restore current edited line (before search)
+                    s.search_match = None;
                     s.line.update(&backup, backup_pos);
                     try!(s.refresh_line());
                     s.changes.borrow_mut().truncate(mark);
                     return Ok(None);
                 }
                 Cmd::Move(_) => {
+                    s.search_match = None;
                     try!(s.refresh_line()); // restore prompt
                     break;
                 }
-                _ => break,
+                _ => {
+                    if s.search_match.take().is_some() {
+                        try!(s.refresh_line()); // restore prompt, without the match
+                    }
+                    break;
+                }
             }
         }
-        success = match history.search(&search_buf, history_idx, direction) {
-            Some(idx) => {
+        let found = if !regex_mode {
+            history.search(&search_buf, history_idx, direction).map(|idx| {
+                let start = history[idx].find(&search_buf).unwrap();
+                (idx, start..start + search_buf.len())
+            })
+        } else if search_buf.is_empty() {
+            None
+        } else {
+            match Regex::new(&search_buf) {
+                Ok(regex) => {
+                    invalid = None;
+                    history
+                        .search_regex(&regex, history_idx, direction)
+                        .map(|idx| (idx, regex.find(&history[idx]).unwrap()))
+                }
+                Err(err) => {
+                    invalid = Some(err);
+                    continue;
+                }
+            }
+        };
+        success = match found {
+            Some((idx, range)) => {
                 history_idx = idx;
-                let entry = history.get(idx).unwrap();
-                let pos = entry.find(&search_buf).unwrap();
-                s.line.update(entry, pos);
+                s.line.update(&history[idx], range.start);
+                s.search_match = Some(range).filter(|range| !range.is_empty());
                 true
             }
-            _ => false,
+            _ => {
+                s.search_match = None;
+                false
+            }
         };
     }
     s.changes.borrow_mut().end();
@@ -360,61 +1089,402 @@ fn reverse_incremental_search<R: RawReader>(
 /// Handles reading and editting the readline buffer.
 /// It will also handle special inputs in an appropriate fashion
 /// (e.g., C-c will exit readline)
-fn readline_edit<H: Helper>(
+///
+/// `original_mode` is `None` when keys are not read from the terminal (see
+/// `testing`).
+fn readline_edit<H: Helper, R: RawReader>(
     prompt: &str,
     initial: Option<(&str, &str)>,
     editor: &mut Editor<H>,
-    original_mode: &tty::Mode,
+    options: &ReadlineOptions,
+    out: &mut Renderer,
+    rdr: &mut R,
+    original_mode: Option<&tty::Mode>,
 ) -> Result<String> {
-    let completer = editor.helper.as_ref();
-    let hinter = editor.helper.as_ref().map(|h| h as &Hinter);
-    let highlighter = if editor.term.colors_enabled() {
-        editor.helper.as_ref().map(|h| h as &Highlighter)
+    if let Some(remapper) = editor.key_remapper.take() {
+        let rc = readline_edit(
+            prompt,
+            initial,
+            editor,
+            options,
+            out,
+            &mut Remapped {
+                rdr,
+                remapper: &*remapper,
+            },
+            original_mode,
+        );
+        editor.key_remapper = Some(remapper);
+        return rc;
+    }
+    let mut config = options.apply(&editor.config);
+    let wide_ambiguous = tty::is_wide_ambiguous(config.ambiguous_width());
+    out.set_wide_ambiguous(wide_ambiguous);
+    let colors_enabled = match original_mode {
+        Some(_) => editor.term.colors_enabled(),
+        None => config.color_mode() != ColorMode::Disabled,
+    };
+    let monochrome = colors_enabled && match original_mode {
+        Some(_) => editor.term.is_monochrome(),
+        None => config.color_mode() == ColorMode::Monochrome,
+    };
+    if monochrome {
+        let theme = config.theme().monochrome();
+        config.set_theme(theme);
+    }
+    let completer = if options.completion_enabled() {
+        editor.helper.as_ref()
+    } else {
+        None
+    };
+    let hinter = if options.hints_enabled() && !config.screen_reader() {
+        editor.helper.as_ref().map(|h| h as &Hinter)
+    } else {
+        None
+    };
+    let no_history = History::with_config(config);
+    let history = if options.history_enabled() {
+        &editor.history
     } else {
+        &no_history
+    };
+    let themed;
+    let highlighter = if !colors_enabled {
         None
+    } else if config.theme().is_empty() {
+        editor.helper.as_ref().map(|h| h as &Highlighter)
+    } else {
+        themed = Themed {
+            highlighter: editor.helper.as_ref().map(|h| h as &Highlighter),
+            theme: config.theme(),
+        };
+        Some(&themed as &Highlighter)
     };
 
-    let mut stdout = editor.term.create_writer();
+    let on_edit = editor
+        .helper
+        .as_ref()
+        .map(|helper| move |line: &str, pos: usize| helper.update_after_edit(line, pos));
 
     editor.reset_kill_ring(); // TODO recreate a new kill ring vs Arc<Mutex<KillRing>>
     let mut s = State::new(
-        &mut stdout,
+        out,
         prompt,
-        editor.history.len(),
+        history.len(),
         hinter,
         highlighter,
     );
-    let mut input_state = InputState::new(&editor.config, Arc::clone(&editor.custom_bindings));
-
-    s.line.set_delete_listener(editor.kill_ring.clone());
-    s.line.set_change_listener(s.changes.clone());
+    let mut input_state = InputState::new(&config, Arc::clone(&editor.custom_bindings));
+
+    s.line.set_word_chars(config.word_chars());
+    s.line.set_unicode_words(config.unicode_words());
+    s.line.set_turkic_case(config.turkic_case());
+    s.line.set_wide_ambiguous(wide_ambiguous);
+    s.line.set_tokenizer(config.tokenizer());
+    s.line.add_delete_listener(editor.kill_ring.clone());
+    let mut changes_listener = s.line.add_change_listener(s.changes.clone());
+    for listener in &editor.change_listeners {
+        let listener = SharedListener(Arc::clone(listener));
+        s.line.add_change_listener(Rc::new(RefCell::new(listener)));
+    }
+    if colors_enabled {
+        s.selection_style = Some(config.theme().selection_style().unwrap_or("7"));
+        s.search_match_style = Some(config.theme().search_match_style().unwrap_or("4"));
+    } else if !s.out.is_dumb() {
+        // without colors, the selection is still shown in reverse video
+        s.selection_style = Some("7");
+    }
+    if !colors_enabled || monochrome && config.theme().hint_style().is_none() {
+        s.hint_markers = config.theme().hint_marker_pair();
+    }
+    s.screen_reader = config.screen_reader();
+    s.bell = editor.bell.as_ref().map(|bell| bell.as_ref() as &Fn());
+    s.bell_style = config.bell_style();
+    s.completion_ignore_case = config.completion_ignore_case();
+    s.fuzzy_completion = config.fuzzy_completion();
+    s.on_edit = on_edit.as_ref().map(|on_edit| on_edit as &OnEdit);
+    s.history = Some(history);
+    s.secret = options.secret_enabled();
+    s.modified_marker = config.modified_marker();
+    s.widget = editor
+        .prompt_widget
+        .as_ref()
+        .map(|widget| (config.prompt_widget_width(), widget.as_ref() as &Fn() -> String));
+    s.trace = editor.trace.as_ref();
+    if editor.templated {
+        s.prompt_template = editor.prompt_template.as_ref();
+    }
 
-    if let Some((left, right)) = initial {
+    if let Some(snapshot) = editor.restored.take() {
+        // after `restore_snapshot` (the whole line, even beyond `MAX_LINE`, for
+        // its undo history)
+        s.line.update("", 0);
+        s.line.insert_bulk(&snapshot.line);
+        s.line.set_pos(snapshot.pos);
+        *s.changes.borrow_mut() = snapshot.changes;
+        input_state.set_input_mode(snapshot.input_mode);
+    } else if let Some((left, right)) = initial {
         s.line
             .update((left.to_owned() + right).as_ref(), left.len());
+    } else if let Some((line, index)) = editor.held_line.take() {
+        // after accept-and-hold or accept-line-and-down-history
+        // (indexes are shifted when the oldest entry has been removed)
+        match index.and_then(|index| {
+            (index.saturating_sub(1)..index + 1)
+                .rev()
+                .find(|&i| history.get(i) == Some(&line))
+        }) {
+            Some(index) => s.edit_history_entry(&line, index),
+            None => s.line.update(&line, line.len()),
+        }
+    } else if let Some(line) = editor.pushed_lines.pop() {
+        // after push-line
+        s.line.update(&line, line.len());
+    } else if !s.secret && editor.recovered_draft.is_some() {
+        // left by a process which died while editing
+        let draft = editor.recovered_draft.take().unwrap();
+        let first_line = draft.lines().next().unwrap_or("");
+        let question = format!(
+            "Restore the unsaved draft `{}`? (y/n) ",
+            tty::truncate(first_line, 0, 40, s.out.is_wide_ambiguous())
+        );
+        try!(s.out.write_and_flush(question.as_bytes()));
+        let yes = try!(edit::read_yes_no(rdr));
+        try!(s.out.write_and_flush(b"\n"));
+        if yes {
+            s.line.update(&draft, draft.len());
+        }
     }
+    s.changes
+        .borrow_mut()
+        .set_grouping(config.undo_grouping());
 
     try!(s.refresh_line());
 
-    let mut rdr = try!(editor.term.create_reader(&editor.config));
-
+    // characters received in a burst, inserted (and displayed) at once
+    let mut pending_text = String::new();
+    let frame = config
+        .max_refresh_rate()
+        .map(|rate| Duration::from_millis(1000 / cmp::max(1, rate) as u64));
+    let mut last_refresh = Instant::now();
+    let mut tab_stops = Vec::new();
+    let auto_menu_delay = config
+        .auto_menu_delay()
+        .map(|delay| Duration::from_millis(delay as u64));
+    let mut menu_line = String::new();
+    let mut quick_select = if config.menu_quick_select() {
+        Some(QuickSelect::default())
+    } else {
+        None
+    };
+    let idle_tick = config
+        .idle_tick()
+        .map(|tick| Duration::from_millis(tick as u64));
+    let mut hold;
+    // command read by `Cmd::ExecuteNamedCommand`
+    let mut dispatched = None;
     loop {
-        let rc = s.next_cmd(&mut input_state, &mut rdr, false);
-        let mut cmd = try!(rc);
+        if let (Some(delay), Some(completer)) = (auto_menu_delay, completer) {
+            // list the completions once the user stops typing
+            if s.line.as_str() != menu_line && !rdr.wait_for_input(delay) {
+                menu_line = s.line.as_str().to_owned();
+                try!(update_menu(&mut s, completer, &mut quick_select));
+            }
+        }
+        if let Some(draft) = editor.draft.as_mut().filter(|_| !s.secret) {
+            // save the draft once the user stops typing
+            if draft.is_stale(s.line.as_str()) && !rdr.wait_for_input(DRAFT_DELAY) {
+                if let Err(err) = draft.save(s.line.as_str()) {
+                    debug!(target: "rustyline", "cannot save the draft: {}", err);
+                }
+            }
+        }
+        let idle = idle_tick.and_then(|tick| editor.helper.as_ref().map(|helper| (tick, helper)));
+        let mut injected = dispatched.take();
+        let last_key = Instant::now();
+        let mut last_tick = last_key;
+        while injected.is_none() {
+            if let Some(events) = editor.events.as_ref().map(|events| &events.1) {
+                injected = try!(handle_events(&mut s, events));
+                if injected.is_some() {
+                    break;
+                }
+            }
+            let timeout = match (idle, editor.events.is_some()) {
+                (Some((tick, _)), true) => cmp::min(tick, EVENT_POLL),
+                (Some((tick, _)), false) => tick,
+                (None, true) => EVENT_POLL,
+                (None, false) => break,
+            };
+            if rdr.wait_for_input(timeout) {
+                break;
+            }
+            if tty::take_forced_refresh() {
+                // requested by `force_refresh` from another thread
+                try!(s.refresh_line());
+            }
+            // a whole tick waited, or several polls of the events
+            let ticked = |&(tick, _): &(Duration, &H)| timeout == tick || last_tick.elapsed() >= tick;
+            if let Some((_, helper)) = idle.filter(ticked) {
+                last_tick = Instant::now();
+                let refresh = {
+                    let mut idle = Idle {
+                        line: s.line.as_str(),
+                        pos: s.line.pos(),
+                        elapsed: last_key.elapsed(),
+                        refresh: false,
+                    };
+                    helper.on_idle(&mut idle);
+                    idle.refresh
+                };
+                if refresh {
+                    try!(s.refresh_line());
+                }
+            }
+        }
+
+        let rc = match injected {
+            Some(cmd) => Ok(cmd),
+            None => s.next_cmd(&mut input_state, rdr, false),
+        };
+        let mut cmd = match rc {
+            Ok(cmd) => cmd,
+            Err(err) => {
+                // the terminal may have been detached: keep the edit so that it can be
+                // resumed by another `readline` call
+                if !pending_text.is_empty() {
+                    s.line.insert_bulk(&pending_text);
+                }
+                if let Some(draft) = editor.draft.as_mut().filter(|_| !s.secret) {
+                    if let Err(err) = draft.keep(s.line.as_str()) {
+                        debug!(target: "rustyline", "cannot save the draft: {}", err);
+                    }
+                }
+                let changes = mem::replace(&mut *s.changes.borrow_mut(), Changeset::new());
+                editor.snapshot = Some(Snapshot {
+                    line: s.line.as_str().to_owned(),
+                    pos: s.line.pos(),
+                    input_mode: input_state.input_mode(),
+                    changes,
+                    kill_ring: editor.kill_ring.lock().unwrap().slots(),
+                });
+                return Err(err);
+            }
+        };
+
+        hold = match cmd {
+            Cmd::AcceptAndHold | Cmd::AcceptLineAndDownHistory => Some(cmd.clone()),
+            _ => None,
+        };
+        if hold.is_some() {
+            cmd = Cmd::AcceptLine;
+        }
+
+        if s.menu.is_some() {
+            match cmd {
+                Cmd::AcceptLine | Cmd::Interrupt | Cmd::EndOfFile => {
+                    // leave the screen clean
+                    s.menu = None;
+                    menu_line.clear();
+                    try!(s.refresh_line());
+                }
+                Cmd::SelfInsert(1, digit) => {
+                    let choice = match (completer, quick_select.as_ref()) {
+                        (Some(completer), Some(quick_select)) => {
+                            match quick_select.choice(s.menu.as_ref(), digit) {
+                                Some(replacement) => {
+                                    s.changes.borrow_mut().begin();
+                                    completer.update(&mut s.line, quick_select.start, replacement);
+                                    s.changes.borrow_mut().end();
+                                    true
+                                }
+                                None => false,
+                            }
+                        }
+                        _ => false,
+                    };
+                    if choice {
+                        // the menu is listed again for the completed line
+                        s.menu = None;
+                        try!(s.refresh_line());
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+        }
 
         if cmd.should_reset_kill_ring() {
             editor.reset_kill_ring();
         }
 
+        if s.selection.is_some() {
+            match cmd {
+                Cmd::Select(_) | Cmd::CopySelection | Cmd::KillSelection => {}
+                Cmd::Kill(Movement::BackwardChar(_)) | Cmd::Kill(Movement::ForwardChar(_)) => {
+                    // Backspace or Delete
+                    try!(s.edit_delete_selection(false));
+                    continue;
+                }
+                _ => {
+                    s.selection = None;
+                    try!(s.refresh_line());
+                }
+            }
+        }
+
+        if let Cmd::SelfInsert(n, c) = cmd {
+            // more input to come (paste, key repeat) or last repaint too
+            // recent: repaint only once (but a screen reader expects each
+            // character to be echoed)
+            let pending = !config.screen_reader()
+                && (rdr.has_pending_input() || frame.map_or(false, |frame| {
+                    let elapsed = last_refresh.elapsed();
+                    elapsed < frame && rdr.wait_for_input(frame - elapsed)
+                }));
+            // a space may expand an abbreviation
+            if pending && (c != ' ' || editor.abbreviations.is_empty()) {
+                s.trace_refresh("defer");
+                pending_text.extend(iter::repeat(c).take(n));
+                continue;
+            }
+        }
+        if !pending_text.is_empty() {
+            try!(s.edit_insert_bulk(&pending_text));
+            pending_text.clear();
+        }
+        last_refresh = Instant::now();
+
+        if cmd == Cmd::Complete && !tab_stops.is_empty() {
+            // jump to the next tab stop of the last expanded abbreviation
+            let dist = tab_stops.remove(0);
+            if dist <= s.line.len() && s.line.is_char_boundary(s.line.len() - dist) {
+                let pos = s.line.len() - dist;
+                s.line.set_pos(pos);
+                try!(s.move_cursor());
+            }
+            continue;
+        }
+
+        if cmd == Cmd::SelfInsert(1, ' ') || cmd == Cmd::AcceptLine {
+            if let Some(stops) = try!(expand_abbreviation(&mut s, &editor.abbreviations)) {
+                if cmd != Cmd::AcceptLine && !stops.is_empty() {
+                    // the cursor is at the first tab stop
+                    tab_stops = stops;
+                    continue;
+                }
+            }
+        }
+
         // autocomplete
         if cmd == Cmd::Complete && completer.is_some() {
             let next = try!(complete_line(
-                &mut rdr,
+                rdr,
                 &mut s,
                 &mut input_state,
                 completer.unwrap(),
                 highlighter,
-                &editor.config,
+                &config,
             ));
             if next.is_some() {
                 cmd = next.unwrap();
@@ -431,14 +1501,25 @@ fn readline_edit<H: Helper>(
             continue;
         }
 
-        if cmd == Cmd::ReverseSearchHistory {
-            // Search history backward
-            let next = try!(reverse_incremental_search(
-                &mut rdr,
+        if cmd == Cmd::ReverseSearchHistory || cmd == Cmd::ForwardSearchHistory {
+            // Search history backward or forward
+            let direction = if cmd == Cmd::ReverseSearchHistory {
+                Direction::Reverse
+            } else {
+                Direction::Forward
+            };
+            input_state.set_searching(true);
+            let next = incremental_search(
+                rdr,
                 &mut s,
                 &mut input_state,
-                &editor.history,
-            ));
+                history,
+                &config,
+                direction,
+                &mut editor.discarded_line,
+            );
+            input_state.set_searching(false);
+            let next = try!(next);
             if next.is_some() {
                 cmd = next.unwrap();
             } else {
@@ -446,6 +1527,18 @@ fn readline_edit<H: Helper>(
             }
         }
 
+        match cmd {
+            Cmd::Kill(Movement::WholeLine)
+            | Cmd::Replace(Movement::WholeLine, _)
+            | Cmd::Interrupt
+                if !s.line.is_empty() =>
+            {
+                // kept for `recover-line`
+                editor.discarded_line = Some(s.line.as_str().to_owned());
+            }
+            _ => {}
+        }
+
         match cmd {
             Cmd::Move(Movement::BeginningOfLine) => {
                 // Move to the beginning of line.
@@ -492,17 +1585,21 @@ fn readline_edit<H: Helper>(
             }
             Cmd::NextHistory => {
                 // Fetch the next command from the history list.
-                try!(s.edit_history_next(&editor.history, false))
+                try!(s.edit_history_next(history, false))
+            }
+            Cmd::RevertLine => {
+                // Discard the edits of the recalled history entry.
+                try!(s.edit_revert_line(history))
             }
             Cmd::PreviousHistory => {
                 // Fetch the previous command from the history list.
-                try!(s.edit_history_next(&editor.history, true))
+                try!(s.edit_history_next(history, true))
             }
             Cmd::HistorySearchBackward => {
-                try!(s.edit_history_search(&editor.history, Direction::Reverse))
+                try!(s.edit_history_search(history, Direction::Reverse))
             }
             Cmd::HistorySearchForward => {
-                try!(s.edit_history_search(&editor.history, Direction::Forward))
+                try!(s.edit_history_search(history, Direction::Forward))
             }
             Cmd::TransposeChars => {
                 // Exchange the char before cursor with the character at cursor.
@@ -515,10 +1612,10 @@ fn readline_edit<H: Helper>(
                 try!(s.edit_insert(c, 1)) // FIXME
             }
             Cmd::Yank(n, anchor) => {
-                // retrieve (yank) last item killed
+                // retrieve (yank) the nth last item killed
                 let mut kill_ring = editor.kill_ring.lock().unwrap();
-                if let Some(text) = kill_ring.yank() {
-                    try!(s.edit_yank(&input_state, text, anchor, n))
+                if let Some(text) = kill_ring.yank_nth(n) {
+                    try!(s.edit_yank(&input_state, text, anchor, 1))
                 }
             }
             Cmd::ViYankTo(ref mvt) => if let Some(text) = s.line.copy(mvt) {
@@ -527,6 +1624,36 @@ fn readline_edit<H: Helper>(
             },
             // TODO CTRL-_ // undo
             Cmd::AcceptLine => {
+                if let Some(ref corrector) = editor.corrector {
+                    if !try!(correct_first_word(
+                        rdr,
+                        &mut s,
+                        &mut input_state,
+                        corrector.as_ref(),
+                        history
+                    )) {
+                        continue;
+                    }
+                }
+                if let Some(ref validator) = editor.validator {
+                    match validator.validate(s.line.as_str()) {
+                        ValidationResult::Valid => {}
+                        ValidationResult::Incomplete(prefix) => {
+                            // a single undo unit
+                            s.changes.borrow_mut().begin();
+                            s.line.move_end();
+                            s.line.insert_bulk(&format!("\n{}", prefix));
+                            s.changes.borrow_mut().end();
+                            try!(s.refresh_line());
+                            continue;
+                        }
+                        ValidationResult::Invalid(msg) => {
+                            s.menu = msg;
+                            try!(s.refresh_line());
+                            continue;
+                        }
+                    }
+                }
                 #[cfg(test)]
                 {
                     editor.term.cursor = s.cursor.col;
@@ -543,11 +1670,11 @@ fn readline_edit<H: Helper>(
             }
             Cmd::BeginningOfHistory => {
                 // move to first entry in history
-                try!(s.edit_history(&editor.history, true))
+                try!(s.edit_history(history, true))
             }
             Cmd::EndOfHistory => {
                 // move to last entry in history
-                try!(s.edit_history(&editor.history, false))
+                try!(s.edit_history(history, false))
             }
             Cmd::Move(Movement::BackwardWord(n, word_def)) => {
                 // move backwards one word
@@ -560,6 +1687,23 @@ fn readline_edit<H: Helper>(
             Cmd::Kill(ref mvt) => {
                 try!(s.edit_kill(mvt));
             }
+            Cmd::Select(ref mvt) => {
+                // extend the selection
+                try!(s.edit_select(mvt))
+            }
+            Cmd::CopySelection => {
+                if let Some(range) = s.selection_range() {
+                    let mut kill_ring = editor.kill_ring.lock().unwrap();
+                    // not appended to the previous kill
+                    kill_ring.reset();
+                    kill_ring.kill(&s.line[range], Mode::Append);
+                }
+                s.selection = None;
+                try!(s.refresh_line());
+            }
+            Cmd::KillSelection => {
+                try!(s.edit_delete_selection(true));
+            }
             Cmd::Move(Movement::ForwardWord(n, at, word_def)) => {
                 // move forwards one word
                 try!(s.edit_move_to_next_word(at, word_def, n))
@@ -572,10 +1716,22 @@ fn readline_edit<H: Helper>(
                 // transpose words
                 try!(s.edit_transpose_words(n))
             }
+            Cmd::DragWordBackward(n) => {
+                // move the word under the cursor to the left
+                try!(s.edit_drag_word(false, n))
+            }
+            Cmd::DragWordForward(n) => {
+                // move the word under the cursor to the right
+                try!(s.edit_drag_word(true, n))
+            }
             Cmd::UpcaseWord => {
                 // uppercase word after point
                 try!(s.edit_word(WordAction::UPPERCASE))
             }
+            Cmd::ToggleCase => {
+                // swap the case of the selection or of the word after point
+                try!(s.edit_toggle_case())
+            }
             Cmd::YankPop => {
                 // yank-pop
                 let mut kill_ring = editor.kill_ring.lock().unwrap();
@@ -585,21 +1741,127 @@ fn readline_edit<H: Helper>(
             }
             Cmd::Move(Movement::ViCharSearch(n, cs)) => try!(s.edit_move_to(cs, n)),
             Cmd::Undo(n) => {
-                s.line.remove_change_listener();
+                s.line.remove_listener(changes_listener);
                 if s.changes.borrow_mut().undo(&mut s.line, n) {
                     try!(s.refresh_line());
                 }
-                s.line.set_change_listener(s.changes.clone());
+                changes_listener = s.line.add_change_listener(s.changes.clone());
+            }
+            Cmd::Redo(n) => {
+                s.line.remove_listener(changes_listener);
+                if s.changes.borrow_mut().redo(&mut s.line, n) {
+                    try!(s.refresh_line());
+                }
+                changes_listener = s.line.add_change_listener(s.changes.clone());
+            }
+            Cmd::Custom(ref name) => if let Some(handler) = editor.commands.get(name) {
+                try!(handler.handle(&mut Context::new(&mut s, rdr, &config)));
+                try!(s.refresh_line());
+            },
+            Cmd::ExecuteNamedCommand => {
+                let mut input_state = InputState::new(&config, Arc::new(RwLock::new(HashMap::new())));
+                if let Some(name) = try!(s.read_input(rdr, &mut input_state, "Command: ")) {
+                    let name = name.trim();
+                    dispatched = if editor.commands.contains_key(name) {
+                        Some(Cmd::Custom(name.to_owned()))
+                    } else {
+                        inputrc::function(name)
+                    };
+                    if dispatched.is_none() {
+                        try!(s.beep());
+                    }
+                }
+            }
+            Cmd::InsertCodePoint => if let Some(c) = try!(s.read_code_point(rdr)) {
+                try!(s.edit_insert(c, 1))
+            },
+            Cmd::Compose => if let Some(c) = try!(s.read_compose(rdr, &editor.compose_table)) {
+                try!(s.edit_insert(c, 1))
+            },
+            Cmd::SetEditMode(mode) => {
+                input_state.set_edit_mode(mode);
+                editor.config.set_edit_mode(mode);
+            }
+            Cmd::HistoryList => if let Some(entry) = try!(s.read_history_entry(rdr, history)) {
+                // a single undo unit
+                s.changes.borrow_mut().begin();
+                s.line.insert_bulk(&entry);
+                s.changes.borrow_mut().end();
+                try!(s.refresh_line());
+            },
+            Cmd::Pick => if let Some(ref picker) = editor.picker {
+                let menu = s.menu.clone();
+                let picked = try!(picker.pick(&mut Context::new(&mut s, rdr, &config)));
+                s.menu = menu;
+                if let Some(text) = picked {
+                    // a single undo unit
+                    s.changes.borrow_mut().begin();
+                    s.line.insert_bulk(&text);
+                    s.changes.borrow_mut().end();
+                }
+                try!(s.refresh_line());
+            },
+            Cmd::Paste(text) => {
+                let text = match editor.paste_handler {
+                    Some(ref handler) => {
+                        let menu = s.menu.clone();
+                        let text = try!(handler.paste(&text, &mut Context::new(&mut s, rdr, &config)));
+                        s.menu = menu;
+                        text
+                    }
+                    None => Some(text),
+                };
+                if let Some(text) = text {
+                    // a single undo unit, with its new lines (the line is not accepted)
+                    s.changes.borrow_mut().begin();
+                    s.line.insert_bulk(&text);
+                    s.changes.borrow_mut().end();
+                }
+                try!(s.refresh_line());
+            }
+            Cmd::PasteClipboard => {
+                try!(s.out.write_and_flush(clipboard::QUERY.as_bytes()));
+                let reply = try!(rdr.read_osc_reply(clipboard::REPLY_PREFIX, Duration::from_millis(500)));
+                match reply.as_ref().and_then(|reply| clipboard::parse_reply(reply)) {
+                    Some(text) => {
+                        // a single undo unit
+                        s.changes.borrow_mut().begin();
+                        s.line.insert_bulk(&text);
+                        s.changes.borrow_mut().end();
+                        try!(s.refresh_line());
+                    }
+                    None => try!(s.beep()),
+                }
+            }
+            Cmd::PushLine => {
+                // stash the line until the next prompt
+                editor.pushed_lines.push(s.line.as_str().to_owned());
+                s.line.update("", 0);
+                try!(s.refresh_line());
             }
             Cmd::Interrupt => {
                 return Err(error::ReadlineError::Interrupted);
             }
+            Cmd::RecoverLine => match editor.discarded_line.take() {
+                Some(line) => {
+                    if !s.line.is_empty() {
+                        editor.discarded_line = Some(s.line.as_str().to_owned());
+                    }
+                    s.changes.borrow_mut().begin();
+                    s.line.update(&line, line.len());
+                    s.changes.borrow_mut().end();
+                    try!(s.refresh_line());
+                }
+                None => try!(s.beep()),
+            },
             #[cfg(unix)]
             Cmd::Suspend => {
-                try!(original_mode.disable_raw_mode());
-                try!(tty::suspend());
-                try!(editor.term.enable_raw_mode()); // TODO original_mode may have changed
-                try!(s.refresh_line());
+                if let Some(original_mode) = original_mode {
+                    try!(original_mode.disable_raw_mode());
+                    try!(tty::suspend());
+                    try!(editor.term.enable_raw_mode()); // TODO original_mode may have changed
+                    try!(s.refresh_line());
+                }
                 continue;
             }
             Cmd::Noop => {}
@@ -611,6 +1873,14 @@ fn readline_edit<H: Helper>(
     if cfg!(windows) {
         let _ = original_mode; // silent warning
     }
+    editor.held_line = match hold {
+        Some(Cmd::AcceptAndHold) => Some((s.line.as_str().to_owned(), None)),
+        Some(_) => {
+            let next = s.history_index() + 1;
+            history.get(next).map(|entry| (entry.clone(), Some(next)))
+        }
+        None => None,
+    };
     Ok(s.line.into_string())
 }
 
@@ -630,23 +1900,67 @@ fn readline_raw<H: Helper>(
     prompt: &str,
     initial: Option<(&str, &str)>,
     editor: &mut Editor<H>,
+    options: &ReadlineOptions,
 ) -> Result<String> {
     let original_mode = try!(editor.term.enable_raw_mode());
     let guard = Guard(&original_mode);
-    let user_input = readline_edit(prompt, initial, editor, &original_mode);
-    if editor.config.auto_add_history() {
+    let mut stdout = editor.term.create_writer();
+    let bracketed_paste = options.apply(&editor.config).bracketed_paste();
+    if bracketed_paste {
+        try!(stdout.set_bracketed_paste(true));
+    }
+    let mut rdr = try!(editor.term.create_reader(&options.apply(&editor.config)));
+    let user_input = readline_edit(
+        prompt,
+        initial,
+        editor,
+        options,
+        &mut stdout,
+        &mut rdr,
+        Some(&original_mode),
+    );
+    if let Some(ref mut draft) = editor.draft {
+        if let Err(err) = draft.finish() {
+            debug!(target: "rustyline", "cannot remove the draft: {}", err);
+        }
+    }
+    if options.apply(&editor.config).auto_add_history() {
         if let Ok(ref line) = user_input {
-            editor.add_history_entry(line.as_ref());
+            editor.add_history_entry::<&str>(line.as_ref());
         }
     }
+    if bracketed_paste {
+        try!(stdout.set_bracketed_paste(false));
+    }
     drop(guard); // try!(disable_raw_mode(original_mode));
-    println!();
+    try!(stdout.write_and_flush(b"\n"));
     user_input
 }
 
+fn output_stream(stream: OutputStreamType) -> Box<Write> {
+    match stream {
+        OutputStreamType::Stdout => Box::new(io::stdout()),
+        OutputStreamType::Stderr => Box::new(io::stderr()),
+    }
+}
+
 fn readline_direct() -> Result<String> {
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    read_line(&mut stdin)
+}
+
+/// Read the next line (without its line terminator) from a non-interactive
+/// input, or `Eof`.
+fn read_line<R: io::BufRead>(input: &mut R) -> Result<String> {
     let mut line = String::new();
-    if try!(io::stdin().read_line(&mut line)) > 0 {
+    if try!(input.read_line(&mut line)) > 0 {
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
         Ok(line)
     } else {
         Err(error::ReadlineError::Eof)
@@ -655,18 +1969,148 @@ fn readline_direct() -> Result<String> {
 
 /// Syntax specific helper.
 ///
-/// TODO Tokenizer/parser used for both completion, suggestion, highlighting.
-/// (parse current line once)
+/// The line can be parsed once after each edit (see `update_after_edit`)
+/// for the completion, the hint and the highlighting.
 pub trait Helper
 where
     Self: Completer,
     Self: Hinter,
     Self: Highlighter,
 {
+    /// Called with the line, once after each of its modifications (and for
+    /// the initial line) before any hint, highlighting or completion of it:
+    /// the result of its parsing can be kept (in a `RefCell`) for them.
+    fn update_after_edit(&self, line: &str, pos: usize) {
+        let _ = (line, pos);
+    }
+
+    /// Called periodically while waiting for input (see
+    /// `Config::idle_tick`): call `Idle::refresh` to repaint the line with
+    /// an updated hint or (highlighted) prompt.
+    fn on_idle(&self, idle: &mut Idle) {
+        let _ = idle;
+    }
+}
+
+/// Context given to `Helper::on_idle`.
+pub struct Idle<'l> {
+    line: &'l str,
+    pos: usize,
+    elapsed: Duration,
+    refresh: bool,
+}
+
+impl<'l> Idle<'l> {
+    /// Currently edited line
+    pub fn line(&self) -> &str {
+        self.line
+    }
+
+    /// Cursor position (in bytes)
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Time elapsed since the last key was pressed
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Request a repaint of the line (prompt, line and hint).
+    pub fn refresh(&mut self) {
+        self.refresh = true;
+    }
 }
 
 impl Helper for () {}
 
+/// Context given to `Completer::complete` and `Hinter::hint`.
+pub struct HelperContext<'h> {
+    history: Option<&'h History>,
+    history_index: usize,
+    completion_ignore_case: bool,
+    fuzzy_completion: bool,
+    word_chars: WordChars,
+}
+
+impl<'h> HelperContext<'h> {
+    /// Context with the (optional) `history` and the index of the entry being
+    /// edited (`history.len()` for a new line).
+    pub fn new(history: Option<&'h History>, history_index: usize) -> HelperContext<'h> {
+        HelperContext {
+            history,
+            history_index,
+            completion_ignore_case: false,
+            fuzzy_completion: false,
+            word_chars: WordChars::default(),
+        }
+    }
+
+    /// Match the completions without regard to case
+    /// (`Config::completion_ignore_case`).
+    pub fn ignore_case(mut self, yes: bool) -> HelperContext<'h> {
+        self.completion_ignore_case = yes;
+        self
+    }
+
+    /// Tell if the completions are matched without regard to case.
+    pub fn completion_ignore_case(&self) -> bool {
+        self.completion_ignore_case
+    }
+
+    /// Return all the candidates which fuzzy match the word to complete
+    /// (`Config::fuzzy_completion`).
+    pub fn fuzzy(mut self, yes: bool) -> HelperContext<'h> {
+        self.fuzzy_completion = yes;
+        self
+    }
+
+    /// Tell if the completions are matched by subsequence (see
+    /// `completion::fuzzy_match`): the candidates returned are then filtered
+    /// and sorted by the editor, so they don't need to start with the word.
+    pub fn fuzzy_completion(&self) -> bool {
+        self.fuzzy_completion
+    }
+
+    /// Characters, other than alphanumerics, which are part of the word to
+    /// complete (`Config::word_chars`).
+    pub fn with_word_chars(mut self, word_chars: WordChars) -> HelperContext<'h> {
+        self.word_chars = word_chars;
+        self
+    }
+
+    /// Characters, other than alphanumerics, which don't end the word to
+    /// complete.
+    pub fn word_chars(&self) -> WordChars {
+        self.word_chars
+    }
+
+    /// History of the edited lines (`None` when not available, like in a
+    /// nested prompt)
+    pub fn history(&self) -> Option<&History> {
+        self.history
+    }
+
+    /// Index of the history entry being edited (`history.len()` for a new
+    /// line)
+    pub fn history_index(&self) -> usize {
+        self.history_index
+    }
+}
+
+/// Request a full repaint of the prompt and of the line being edited (to
+/// recover from an external program which wrote on the screen), without
+/// clearing the screen like Ctrl-L.
+///
+/// Only a flag is set, so this can be called from a signal handler (the
+/// signal interrupts the wait for the next key on Unix) or from another
+/// thread. A request from another thread does not interrupt the read: the
+/// repaint is only done on the next key pressed, or on the next
+/// `Config::idle_tick` if any.
+pub fn force_refresh() {
+    tty::force_refresh();
+}
+
 /// Line editor
 pub struct Editor<H: Helper> {
     term: Terminal,
@@ -675,6 +2119,37 @@ pub struct Editor<H: Helper> {
     kill_ring: Arc<Mutex<KillRing>>,
     config: Config,
     custom_bindings: Arc<RwLock<HashMap<KeyPress, Cmd>>>,
+    abbreviations: HashMap<String, String>,
+    corrector: Option<Box<Corrector + Send>>,
+    validator: Option<Box<Validator + Send>>,
+    change_listeners: Vec<Arc<Mutex<ChangeListener + Send>>>,
+    /// Line (and its history index) to edit on the next `readline` call
+    held_line: Option<(String, Option<usize>)>,
+    /// Lines stashed by `push-line`, restored by the next `readline` calls
+    pushed_lines: Vec<String>,
+    commands: HashMap<String, Box<CommandHandler + Send>>,
+    picker: Option<Box<Picker + Send>>,
+    paste_handler: Option<Box<PasteHandler + Send>>,
+    bell: Option<Box<Fn() + Send>>,
+    key_remapper: Option<Box<KeyRemapper + Send>>,
+    compose_table: HashMap<(char, char), char>,
+    /// Edit interrupted by the last failure to read a key
+    snapshot: Option<Snapshot>,
+    /// Edit resumed by the next `readline` call
+    restored: Option<Snapshot>,
+    /// Line restored by `recover-line`
+    discarded_line: Option<String>,
+    /// File where the edited line is saved
+    draft: Option<Draft>,
+    /// Draft offered to be restored by the next `readline` call
+    recovered_draft: Option<String>,
+    prompt_widget: Option<Box<Fn() -> String + Send>>,
+    trace: Option<Trace>,
+    prompt_template: Option<PromptTemplate>,
+    /// `true` during `readline_template`
+    templated: bool,
+    /// Channel of the `EventSender`s
+    events: Option<(Sender<Event>, Receiver<Event>)>,
 }
 
 //#[allow(clippy::new_without_default)]
@@ -686,7 +2161,7 @@ impl<H: Helper> Editor<H> {
 
     /// Create an editor with a specific configuration.
     pub fn with_config(config: Config) -> Editor<H> {
-        let term = Terminal::new(config.color_mode());
+        let term = Terminal::new(config.color_mode(), config.output_stream());
         Editor {
             term,
             history: History::with_config(config),
@@ -694,6 +2169,28 @@ impl<H: Helper> Editor<H> {
             kill_ring: Arc::new(Mutex::new(KillRing::new(60))),
             config,
             custom_bindings: Arc::new(RwLock::new(HashMap::new())),
+            abbreviations: HashMap::new(),
+            corrector: None,
+            validator: None,
+            change_listeners: Vec::new(),
+            held_line: None,
+            pushed_lines: Vec::new(),
+            commands: HashMap::new(),
+            picker: None,
+            paste_handler: None,
+            bell: None,
+            key_remapper: None,
+            compose_table: compose::default_table(),
+            snapshot: None,
+            restored: None,
+            discarded_line: None,
+            draft: None,
+            recovered_draft: None,
+            prompt_widget: None,
+            trace: None,
+            prompt_template: None,
+            templated: false,
+            events: None,
         }
     }
 
@@ -704,7 +2201,7 @@ impl<H: Helper> Editor<H> {
     /// Otherwise (e.g., if `stdin` is a pipe or the terminal is not supported),
     /// it uses file-style interaction.
     pub fn readline(&mut self, prompt: &str) -> Result<String> {
-        self.readline_with(prompt, None)
+        self.readline_internal(prompt, None, &ReadlineOptions::default())
     }
 
     /// This function behaves in the exact same manner as `readline`, except
@@ -715,16 +2212,62 @@ impl<H: Helper> Editor<H> {
     /// the cursor and the string on the right is what will appear to the
     /// right of the cursor.
     pub fn readline_with_initial(&mut self, prompt: &str, initial: (&str, &str)) -> Result<String> {
-        self.readline_with(prompt, Some(initial))
+        self.readline_internal(prompt, Some(initial), &ReadlineOptions::default())
     }
 
-    fn readline_with(&mut self, prompt: &str, initial: Option<(&str, &str)>) -> Result<String> {
-        if self.term.is_unsupported() {
+    /// This function behaves in the exact same manner as `readline`, except
+    /// that the specified `options` temporarily override the editor
+    /// configuration (only for this call).
+    ///
+    /// ```
+    /// use rustyline::{Editor, ReadlineOptions};
+    /// let mut rl = Editor::<()>::new();
+    /// let options = ReadlineOptions::new().auto_add_history(false).history(false).hints(false);
+    /// let token = rl.readline_with("Enter token: ", &options);
+    /// ```
+    pub fn readline_with(&mut self, prompt: &str, options: &ReadlineOptions) -> Result<String> {
+        self.readline_internal(prompt, None, options)
+    }
+
+    /// This function behaves in the exact same manner as `readline`, except
+    /// that the prompt is rendered from the `set_prompt_template` template,
+    /// again on each repaint (an empty prompt is used when there is none).
+    pub fn readline_template(&mut self) -> Result<String> {
+        let prompt = self
+            .prompt_template
+            .as_ref()
+            .map_or_else(String::new, PromptTemplate::render);
+        self.templated = true;
+        let rc = self.readline_internal(&prompt, None, &ReadlineOptions::default());
+        self.templated = false;
+        rc
+    }
+
+    /// Register the prompt template used by `readline_template`.
+    pub fn set_prompt_template(&mut self, template: Option<PromptTemplate>) {
+        self.prompt_template = template;
+    }
+
+    fn readline_internal(
+        &mut self,
+        prompt: &str,
+        initial: Option<(&str, &str)>,
+        options: &ReadlineOptions,
+    ) -> Result<String> {
+        if self.config.behavior() == Behavior::NonInteractive {
+            readline_direct()
+        } else if self.config.behavior() == Behavior::Interactive {
+            readline_raw(prompt, initial, self, options)
+        } else if self.term.is_unsupported() && self.term.is_stdin_tty() {
             debug!(target: "rustyline", "unsupported terminal");
-            // Write prompt and flush it to stdout
-            let mut stdout = io::stdout();
-            try!(stdout.write_all(prompt.as_bytes()));
-            try!(stdout.flush());
+            // reduced editing: the line is repainted with only CR and spaces
+            readline_raw(prompt, initial, self, options)
+        } else if self.term.is_unsupported() {
+            debug!(target: "rustyline", "unsupported terminal");
+            // Write prompt and flush it to stdout (or stderr)
+            let mut out = output_stream(self.config.output_stream());
+            try!(out.write_all(prompt.as_bytes()));
+            try!(out.flush());
 
             readline_direct()
         } else if !self.term.is_stdin_tty() {
@@ -732,10 +2275,39 @@ impl<H: Helper> Editor<H> {
             // Not a tty: read from file / pipe.
             readline_direct()
         } else {
-            readline_raw(prompt, initial, self)
+            readline_raw(prompt, initial, self, options)
         }
     }
 
+    /// Ask the `msg` question (like "really delete 120 rows?") and wait for a
+    /// y/n answer.
+    ///
+    /// When `stdin` is not a terminal, the answer is read from the next line.
+    pub fn confirm(&mut self, msg: &str) -> Result<bool> {
+        let question = format!("{} (y/n) ", msg);
+        let interactive = match self.config.behavior() {
+            Behavior::Auto => !self.term.is_unsupported() && self.term.is_stdin_tty(),
+            Behavior::Interactive => true,
+            Behavior::NonInteractive => false,
+        };
+        if !interactive {
+            let mut out = output_stream(self.config.output_stream());
+            try!(out.write_all(question.as_bytes()));
+            try!(out.flush());
+            let answer = try!(readline_direct());
+            return Ok(answer.trim().starts_with(&['y', 'Y'][..]));
+        }
+        let mut stdout = self.term.create_writer();
+        let yes = self.term.enable_raw_mode().and_then(|original_mode| {
+            let _guard = Guard(&original_mode);
+            try!(stdout.write_and_flush(question.as_bytes()));
+            let mut rdr = try!(self.term.create_reader(&self.config));
+            edit::read_yes_no(&mut rdr)
+        });
+        try!(stdout.write_and_flush(b"\n"));
+        yes
+    }
+
     /// Load the history from the specified file.
     pub fn load_history<P: AsRef<Path> + ?Sized>(&mut self, path: &P) -> Result<()> {
         self.history.load(path)
@@ -746,8 +2318,9 @@ impl<H: Helper> Editor<H> {
         self.history.save(path)
     }
 
-    /// Add a new entry in the history.
-    pub fn add_history_entry<S: AsRef<str> + Into<String>>(&mut self, line: S) -> bool {
+    /// Add a new entry in the history (and tell if and why it has been
+    /// ignored).
+    pub fn add_history_entry<S: AsRef<str> + Into<String>>(&mut self, line: S) -> AddResult {
         self.history.add(line)
     }
 
@@ -777,18 +2350,235 @@ impl<H: Helper> Editor<H> {
         self.helper.as_ref()
     }
 
+    /// Tell if the line is styled (colors enabled and stdout is a tty, see
+    /// `ColorMode::resolve`), so that the `Helper` can style its output the
+    /// same way.
+    pub fn colors_enabled(&self) -> bool {
+        self.term.colors_enabled()
+    }
+
+    /// Request a full repaint of the line on the next edit (see the free
+    /// function `force_refresh`, to be used from a signal handler or from
+    /// another thread).
+    pub fn force_refresh(&self) {
+        force_refresh();
+    }
+
     /// Bind a sequence to a command.
     pub fn bind_sequence(&mut self, key_seq: KeyPress, cmd: Cmd) -> Option<Cmd> {
         let mut bindings = self.custom_bindings.write().unwrap();
         bindings.insert(key_seq, cmd)
     }
 
+    /// Register a user-defined command, to be bound with
+    /// `Cmd::Custom(name)`.
+    pub fn add_command(
+        &mut self,
+        name: &str,
+        handler: Box<CommandHandler + Send>,
+    ) -> Option<Box<CommandHandler + Send>> {
+        self.commands.insert(name.to_owned(), handler)
+    }
+
+    /// Read the readline init file (`$INPUTRC`, `~/.inputrc` or
+    /// `/etc/inputrc`, see the `inputrc` module) to apply its variables and
+    /// key bindings, `app_name` being tested by its `$if` directives.
+    ///
+    /// Return `false` when there is no init file.
+    pub fn read_inputrc(&mut self, app_name: &str) -> Result<bool> {
+        match inputrc::path() {
+            Some(path) => {
+                try!(self.read_inputrc_file(&path, app_name));
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Read the readline init file at `path` (see `read_inputrc`).
+    pub fn read_inputrc_file<P: AsRef<Path> + ?Sized>(
+        &mut self,
+        path: &P,
+        app_name: &str,
+    ) -> Result<()> {
+        let bindings = try!(inputrc::read(self, path.as_ref(), app_name));
+        for (key, cmd) in bindings {
+            self.bind_sequence(key, cmd);
+        }
+        Ok(())
+    }
+
+    /// Names of the commands registered with `add_command` (sorted), to list
+    /// them in a help screen. They can be executed from another thread with
+    /// `EventSender::send_cmd(Cmd::Custom(name))`.
+    pub fn command_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.commands.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
     /// Remove a binding for the given sequence.
     pub fn unbind_sequence(&mut self, key_seq: KeyPress) -> Option<Cmd> {
         let mut bindings = self.custom_bindings.write().unwrap();
         bindings.remove(&key_seq)
     }
 
+    /// Register an abbreviation, expanded when Space or Enter is pressed just
+    /// after it (like `gco` => `git checkout`).
+    ///
+    /// The `expansion` may contain `${1}`, `${2}`... tab stops: the cursor is
+    /// moved to the first one and Tab jumps to the next ones.
+    /// Use quoted insert (Ctrl-V) to insert a space without expansion.
+    pub fn add_abbreviation<A: Into<String>, E: Into<String>>(
+        &mut self,
+        abbreviation: A,
+        expansion: E,
+    ) -> Option<String> {
+        self.abbreviations
+            .insert(abbreviation.into(), expansion.into())
+    }
+
+    /// Remove the given abbreviation.
+    pub fn remove_abbreviation(&mut self, abbreviation: &str) -> Option<String> {
+        self.abbreviations.remove(abbreviation)
+    }
+
+    /// Register a sequence of two characters composing the `composed` one
+    /// with `Cmd::Compose` (they can be typed in any order).
+    ///
+    /// The most common accented letters (like `' e` => `é` or `, c` => `ç`)
+    /// and symbols (like `o c` => `©` or `= e` => `€`) are known by default.
+    pub fn add_compose_sequence(&mut self, first: char, second: char, composed: char) -> Option<char> {
+        self.compose_table.insert((first, second), composed)
+    }
+
+    /// Register a callback to check the first word of an accepted line.
+    ///
+    /// When it is unknown, the nearest known words (and first words of the
+    /// history entries) are suggested: the user presses a digit to pick one,
+    /// Enter to keep the line unchanged or any other key to go on editing.
+    pub fn set_corrector(&mut self, corrector: Option<Box<Corrector + Send>>) {
+        self.corrector = corrector;
+    }
+
+    /// Set the validator of the accepted input.
+    ///
+    /// An incomplete input goes on with a new line (starting with the text
+    /// returned by the validator), an invalid one is edited again.
+    pub fn set_validator(&mut self, validator: Option<Box<Validator + Send>>) {
+        self.validator = validator;
+    }
+
+    /// Set the picker run by `Cmd::Pick` (an emoji, snippet or file
+    /// picker...).
+    pub fn set_picker(&mut self, picker: Option<Box<Picker + Send>>) {
+        self.picker = picker;
+    }
+
+    /// Set the handler of the text pasted in the terminal, run by
+    /// `Cmd::Paste` before its insertion (to re-indent the pasted block, or
+    /// to confirm a large paste).
+    pub fn set_paste_handler(&mut self, handler: Option<Box<PasteHandler + Send>>) {
+        self.paste_handler = handler;
+    }
+
+    /// Display the prompt and the edited line with `writer` (like a file
+    /// opened on `/dev/tty`) instead of the output stream, while still
+    /// reading from the terminal.
+    ///
+    /// `None` restores the output stream of the configuration.
+    ///
+    /// Unix only: the Windows console is rendered through the handle of the
+    /// output stream (cursor moves and colors are console calls), which a
+    /// writer cannot stand for.
+    #[cfg(any(unix, test))]
+    pub fn set_output_writer(&mut self, writer: Option<Arc<Mutex<Write + Send>>>) {
+        match writer {
+            Some(writer) => self.term.set_output(tty::Output::Writer(writer)),
+            None => self.term.set_output_stream(self.config.output_stream()),
+        }
+    }
+
+    /// Handle to push events (text to print above the prompt, commands,
+    /// repaints) into the editor loop from other threads (see the `event`
+    /// module).
+    pub fn event_sender(&mut self) -> EventSender {
+        let &mut (ref sender, _) = self.events.get_or_insert_with(mpsc::channel);
+        EventSender(sender.clone())
+    }
+
+    /// Set the callback called instead of ringing the terminal bell (to play
+    /// a sound or to give some other audible cue with a screen reader).
+    pub fn set_bell(&mut self, bell: Option<Box<Fn() + Send>>) {
+        self.bell = bell;
+    }
+
+    /// Set the callback rendering a short dynamic segment (a spinner, a
+    /// background job counter...) displayed before the prompt, in the
+    /// columns reserved by `Config::prompt_widget_width`.
+    ///
+    /// It is called on each repaint of the line: from another thread, call
+    /// `force_refresh` (with `Config::idle_tick` for an animation)
+    /// instead of writing to the terminal.
+    pub fn set_prompt_widget(&mut self, widget: Option<Box<Fn() -> String + Send>>) {
+        self.prompt_widget = widget;
+    }
+
+    /// Record the keys (after their rewriting by the `KeyRemapper`),
+    /// commands and refresh decisions of the next edits, to reproduce a
+    /// rendering bug (see `testing::FakeTerminal::from_trace`).
+    pub fn set_trace(&mut self, trace: Option<Trace>) {
+        self.trace = trace;
+    }
+
+    /// Set the rewriting of the keys pressed, applied before their dispatch
+    /// in all edit modes.
+    pub fn set_key_remapper(&mut self, remapper: Option<Box<KeyRemapper + Send>>) {
+        self.key_remapper = remapper;
+    }
+
+    /// Return the edit (line, cursor, undo history, kill ring and vi mode)
+    /// interrupted by the last failure to read a key, like when the terminal
+    /// has been detached.
+    pub fn take_snapshot(&mut self) -> Option<Snapshot> {
+        self.snapshot.take()
+    }
+
+    /// Save the edited line in the `path` file once the user stops typing,
+    /// and remove it when the line is accepted (or discarded).
+    ///
+    /// When the file is left by a previous process (which died while
+    /// editing), the next `readline` call offers to restore its content.
+    pub fn set_draft_file(&mut self, path: Option<PathBuf>) {
+        self.draft = path.map(Draft::new);
+        self.recovered_draft = self.draft.as_mut().and_then(Draft::load);
+    }
+
+    /// Last line discarded by `Interrupt` (Ctrl-C), a whole line kill or an
+    /// aborted incremental search, restored by `Cmd::RecoverLine`.
+    pub fn discarded_line(&self) -> Option<&str> {
+        self.discarded_line.as_ref().map(|line| &line[..])
+    }
+
+    /// Resume the `snapshot` edit in the next `readline` call (the kill ring
+    /// is restored immediately).
+    pub fn restore_snapshot(&mut self, mut snapshot: Snapshot) {
+        let (index, ref mut slots) = snapshot.kill_ring;
+        self.kill_ring.lock().unwrap().restore(index, slots);
+        self.restored = Some(snapshot);
+    }
+
+    /// Register a listener notified of the modifications of the edited line
+    /// (from the next `readline` call).
+    pub fn add_change_listener(&mut self, listener: Arc<Mutex<ChangeListener + Send>>) {
+        self.change_listeners.push(listener);
+    }
+
+    /// Prompt for several fields in sequence (like a login wizard).
+    pub fn form(&mut self) -> Form<'_, H> {
+        Form::new(self)
+    }
+
     /// ```
     /// let mut rl = rustyline::Editor::<()>::new();
     /// for readline in rl.iter("> ") {
@@ -836,10 +2626,20 @@ impl<H: Helper> config::Configurer for Editor<H> {
         self.history.ignore_space = yes;
     }
 
+    fn set_history_format(&mut self, format: HistoryFormat) {
+        self.config_mut().set_history_format(format);
+        self.history.format = format;
+    }
+
     fn set_color_mode(&mut self, color_mode: ColorMode) {
         self.config_mut().set_color_mode(color_mode);
         self.term.color_mode = color_mode;
     }
+
+    fn set_output_stream(&mut self, stream: OutputStreamType) {
+        self.config_mut().set_output_stream(stream);
+        self.term.set_output_stream(stream);
+    }
 }
 
 impl<H: Helper> fmt::Debug for Editor<H> {
@@ -1,12 +1,16 @@
 //! Line buffer with current cursor position
+use config::WordChars;
 use keymap::{At, CharSearch, Movement, RepeatCount, Word};
-use std::cell::RefCell;
+use std::cell::{Cell, Ref, RefCell};
+use std::cmp;
 use std::fmt;
 use std::iter;
 use std::ops::{Deref, Index, Range};
 use std::rc::Rc;
 use std::string::Drain;
 use std::sync::{Arc, Mutex};
+use tokenizer::Tokenizer;
+use tty::{width, Position};
 use unicode_segmentation::UnicodeSegmentation;
 
 /// Maximum buffer size for the line read
@@ -18,11 +22,13 @@ pub enum WordAction {
     CAPITALIZE,
     LOWERCASE,
     UPPERCASE,
+    /// Swap the case of each character
+    TOGGLE,
 }
 
 /// Delete (kill) direction
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum Direction {
+pub enum Direction {
     Forward,
     Backward,
 }
@@ -34,27 +40,150 @@ impl Default for Direction {
 }
 
 /// Listener to be notified when some text is deleted.
-pub(crate) trait DeleteListener {
+pub trait DeleteListener {
+    /// Called before a kill (deletions until `stop_killing` must be saved in
+    /// the kill ring).
     fn start_killing(&mut self);
+    /// Called before `string` at `idx` is deleted.
     fn delete(&mut self, idx: usize, string: &str, dir: Direction);
+    /// Called after a kill.
     fn stop_killing(&mut self);
 }
 
 /// Listener to be notified when the line is modified.
-pub(crate) trait ChangeListener: DeleteListener {
+pub trait ChangeListener: DeleteListener {
+    /// Called before `c` is inserted at `idx`.
     fn insert_char(&mut self, idx: usize, c: char);
+    /// Called before `string` is inserted at `idx`.
     fn insert_str(&mut self, idx: usize, string: &str);
+    /// Called before `old` at `idx` is replaced by `new`.
     fn replace(&mut self, idx: usize, old: &str, new: &str);
 }
 
+/// Forward the buffer events to a listener shared with another thread.
+pub(crate) struct SharedListener(pub Arc<Mutex<ChangeListener + Send>>);
+
+impl DeleteListener for SharedListener {
+    fn start_killing(&mut self) {
+        self.0.lock().unwrap().start_killing();
+    }
+
+    fn delete(&mut self, idx: usize, string: &str, dir: Direction) {
+        self.0.lock().unwrap().delete(idx, string, dir);
+    }
+
+    fn stop_killing(&mut self) {
+        self.0.lock().unwrap().stop_killing();
+    }
+}
+
+impl ChangeListener for SharedListener {
+    fn insert_char(&mut self, idx: usize, c: char) {
+        self.0.lock().unwrap().insert_char(idx, c);
+    }
+
+    fn insert_str(&mut self, idx: usize, string: &str) {
+        self.0.lock().unwrap().insert_str(idx, string);
+    }
+
+    fn replace(&mut self, idx: usize, old: &str, new: &str) {
+        self.0.lock().unwrap().replace(idx, old, new);
+    }
+}
+
+/// Identify a registered listener (to remove it).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ListenerId(usize);
+
+/// Dispatch the buffer events to all the registered listeners.
+#[derive(Default)]
+struct Listeners {
+    next_id: usize,
+    dl: Vec<(ListenerId, Arc<Mutex<DeleteListener>>)>,
+    cl: Vec<(ListenerId, Rc<RefCell<ChangeListener>>)>,
+}
+
+impl Listeners {
+    fn next_id(&mut self) -> ListenerId {
+        self.next_id += 1;
+        ListenerId(self.next_id)
+    }
+
+    fn remove(&mut self, id: ListenerId) -> bool {
+        let len = self.dl.len() + self.cl.len();
+        self.dl.retain(|&(i, _)| i != id);
+        self.cl.retain(|&(i, _)| i != id);
+        len != self.dl.len() + self.cl.len()
+    }
+
+    fn start_killing(&self) {
+        for (_, dl) in &self.dl {
+            dl.lock().unwrap().start_killing();
+        }
+        for (_, cl) in &self.cl {
+            cl.borrow_mut().start_killing();
+        }
+    }
+
+    fn delete(&self, idx: usize, string: &str, dir: Direction) {
+        for (_, dl) in &self.dl {
+            if let Ok(mut dl) = dl.try_lock() {
+                dl.delete(idx, string, dir);
+            }
+        }
+        for (_, cl) in &self.cl {
+            cl.borrow_mut().delete(idx, string, dir);
+        }
+    }
+
+    fn stop_killing(&self) {
+        for (_, dl) in &self.dl {
+            dl.lock().unwrap().stop_killing();
+        }
+        for (_, cl) in &self.cl {
+            cl.borrow_mut().stop_killing();
+        }
+    }
+
+    fn insert_char(&self, idx: usize, c: char) {
+        for (_, cl) in &self.cl {
+            cl.borrow_mut().insert_char(idx, c);
+        }
+    }
+
+    fn insert_str(&self, idx: usize, string: &str) {
+        for (_, cl) in &self.cl {
+            cl.borrow_mut().insert_str(idx, string);
+        }
+    }
+
+    fn replace(&self, idx: usize, old: &str, new: &str) {
+        for (_, cl) in &self.cl {
+            cl.borrow_mut().replace(idx, old, new);
+        }
+    }
+}
+
+/// End offset and display width (`None` for a line feed) of each grapheme.
+pub(crate) type Widths = Vec<(usize, Option<usize>)>;
+
 /// Represent the current input (text and cursor position).
 ///
 /// The methods do text manipulations or/and cursor movements.
 pub struct LineBuffer {
     buf: String, // Edited line buffer (rl_line_buffer)
     pos: usize,  // Current cursor position (byte position) (rl_point)
-    dl: Option<Arc<Mutex<DeleteListener>>>,
-    cl: Option<Rc<RefCell<ChangeListener>>>,
+    word_chars: WordChars,
+    unicode_words: bool,
+    turkic_case: bool,
+    tokenizer: Option<Tokenizer>,
+    wide_ambiguous: bool, // East Asian ambiguous characters are 2 columns wide
+    widths: RefCell<Option<Widths>>, // Memoized grapheme widths
+    // Edit (start, removed and inserted lengths) not yet applied to `widths`
+    widths_edit: Cell<Option<(usize, usize, usize)>>,
+    dirty: Cell<Option<usize>>, // Start of the text modified since the last repaint
+    revision: Cell<usize>,      // Number of modifications
+    listeners: Listeners,
 }
 
 impl fmt::Debug for LineBuffer {
@@ -72,8 +201,16 @@ impl LineBuffer {
         LineBuffer {
             buf: String::with_capacity(capacity),
             pos: 0,
-            dl: None,
-            cl: None,
+            word_chars: WordChars::default(),
+            unicode_words: false,
+            turkic_case: false,
+            tokenizer: None,
+            wide_ambiguous: false,
+            widths: RefCell::new(None),
+            widths_edit: Cell::new(None),
+            dirty: Cell::new(Some(0)),
+            revision: Cell::new(0),
+            listeners: Listeners::default(),
         }
     }
 
@@ -86,20 +223,84 @@ impl LineBuffer {
         let mut lb = Self::with_capacity(MAX_LINE);
         assert!(lb.insert_str(0, line));
         lb.set_pos(pos);
-        lb.cl = cl;
+        if let Some(cl) = cl {
+            lb.add_change_listener(cl);
+        }
         lb
     }
 
-    pub(crate) fn set_delete_listener(&mut self, dl: Arc<Mutex<DeleteListener>>) {
-        self.dl = Some(dl);
+    /// Set the characters, other than alphanumerics, which are considered
+    /// part of a word.
+    pub fn set_word_chars(&mut self, word_chars: WordChars) {
+        self.word_chars = word_chars;
     }
 
-    pub(crate) fn set_change_listener(&mut self, dl: Rc<RefCell<ChangeListener>>) {
-        self.cl = Some(dl);
+    /// Characters, other than alphanumerics, which are considered part of a
+    /// word.
+    pub fn word_chars(&self) -> WordChars {
+        self.word_chars
     }
 
-    pub(crate) fn remove_change_listener(&mut self) {
-        self.cl = None;
+    /// Use Unicode word boundaries (UAX #29) instead of graphemes to find
+    /// words (except for `Word::Big`).
+    pub fn set_unicode_words(&mut self, yes: bool) {
+        self.unicode_words = yes;
+    }
+
+    /// Use the Turkish and Azeri case mappings of the dotted and dotless `i`
+    /// (`i` <-> `İ`, `ı` <-> `I`).
+    pub fn set_turkic_case(&mut self, yes: bool) {
+        self.turkic_case = yes;
+    }
+
+    /// Measure East Asian ambiguous characters as wide (see
+    /// `Config::ambiguous_width`).
+    pub(crate) fn set_wide_ambiguous(&mut self, wide: bool) {
+        if wide != self.wide_ambiguous {
+            self.wide_ambiguous = wide;
+            // measure the line again
+            self.widths.replace(None);
+            self.widths_edit.set(None);
+        }
+    }
+
+    /// Use the tokens of `tokenizer` as words (except for `Word::Big`).
+    pub fn set_tokenizer(&mut self, tokenizer: Option<Tokenizer>) {
+        self.tokenizer = tokenizer;
+    }
+
+    /// Byte spans of the tokens when they are used as `word_def` words.
+    fn token_spans(&self, word_def: Word) -> Option<Vec<Range<usize>>> {
+        match self.tokenizer {
+            Some(ref tokenizer) if word_def != Word::Big => Some(
+                tokenizer
+                    .tokenize(&self.buf)
+                    .into_iter()
+                    .map(|token| token.span)
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    /// Register a listener notified when some text is deleted.
+    pub fn add_delete_listener(&mut self, dl: Arc<Mutex<DeleteListener>>) -> ListenerId {
+        let id = self.listeners.next_id();
+        self.listeners.dl.push((id, dl));
+        id
+    }
+
+    /// Register a listener notified when the line is modified.
+    pub fn add_change_listener(&mut self, cl: Rc<RefCell<ChangeListener>>) -> ListenerId {
+        let id = self.listeners.next_id();
+        self.listeners.cl.push((id, cl));
+        id
+    }
+
+    /// Unregister the specified listener.
+    /// Return `false` if there is no such listener.
+    pub fn remove_listener(&mut self, id: ListenerId) -> bool {
+        self.listeners.remove(id)
     }
 
     /// Extracts a string slice containing the entire buffer.
@@ -188,6 +389,193 @@ impl LineBuffer {
             .map(|(i, _)| i)
     }
 
+    /// End offset and display width (`None` for a line feed) of each
+    /// grapheme. Computed once, and only measured again around the edits
+    /// (so that typing or pasting at the end of a long line does not
+    /// segment the whole line on each key).
+    pub(crate) fn widths(&self) -> Ref<'_, Widths> {
+        if let Some((start, removed, inserted)) = self.widths_edit.take() {
+            let mut widths = self.widths.borrow_mut();
+            let measured = match *widths {
+                Some(ref mut widths) => self.measure_edit(widths, start, removed, inserted),
+                None => false,
+            };
+            if !measured {
+                *widths = None;
+            }
+        }
+        if self.widths.borrow().is_none() {
+            let mut widths = Vec::new();
+            self.measure(0, &mut widths, |_| false);
+            *self.widths.borrow_mut() = Some(widths);
+        }
+        Ref::map(self.widths.borrow(), |w| w.as_ref().unwrap())
+    }
+
+    /// Start of the text modified since the last call (`None` when the line
+    /// is unchanged), the text before being still displayed as is.
+    pub(crate) fn take_dirty(&self) -> Option<usize> {
+        self.dirty.take()
+    }
+
+    /// Number of modifications of the line (to know if it has changed).
+    pub(crate) fn revision(&self) -> usize {
+        self.revision.get()
+    }
+
+    /// Record the replacement of `removed` bytes at `start` by `inserted`
+    /// ones, applied to the memoized widths by the next `widths` call.
+    fn edit_widths(&self, start: usize, removed: usize, inserted: usize) {
+        self.revision.set(self.revision.get().wrapping_add(1));
+        let dirty = self.dirty.get().unwrap_or(start);
+        self.dirty.set(Some(cmp::min(dirty, start)));
+        if self.widths.borrow().is_none() {
+            return;
+        }
+        let pending = self.widths_edit.replace(Some((start, removed, inserted)));
+        if pending.is_some() {
+            // several edits: measure the whole line again
+            self.widths.replace(None);
+            self.widths_edit.set(None);
+        }
+    }
+
+    /// Measure the graphemes from `from` (a grapheme boundary) until `stop`
+    /// returns `true` for the offset of a grapheme start.
+    fn measure<F>(&self, from: usize, widths: &mut Widths, mut stop: F) -> usize
+    where
+        F: FnMut(usize) -> bool,
+    {
+        let mut esc_seq = 0;
+        for (i, g) in self.buf[from..].grapheme_indices(true) {
+            if stop(from + i) {
+                return from + i;
+            }
+            let w = if g == "\n" {
+                None
+            } else {
+                Some(width(g, &mut esc_seq, self.wide_ambiguous))
+            };
+            widths.push((from + i + g.len(), w));
+        }
+        self.buf.len()
+    }
+
+    /// Update `widths` after an edit: the graphemes before the edit are kept,
+    /// the ones after are shifted once the old boundaries are found again.
+    /// Return `false` when the whole line must be measured.
+    fn measure_edit(
+        &self,
+        widths: &mut Widths,
+        start: usize,
+        removed: usize,
+        inserted: usize,
+    ) -> bool {
+        if self.buf.contains('\x1b') {
+            // the width of an escape sequence depends on what precedes it
+            return false;
+        }
+        // the grapheme ending at `start` may be extended (like by a combining
+        // character) and the one before may be paired with it (flags)
+        let keep = widths
+            .iter()
+            .take_while(|&&(end, _)| end < start)
+            .count()
+            .saturating_sub(1);
+        let from = if keep == 0 { 0 } else { widths[keep - 1].0 };
+        // old graphemes ending after the removed text, with their shifted end
+        let old_end = start + removed;
+        let suffix = widths.iter().position(|&(end, _)| end > old_end);
+        let tail: Widths = match suffix {
+            Some(i) if i > 0 && widths[i - 1].0 == old_end => widths[i - 1..].to_vec(),
+            Some(i) => widths[i..].to_vec(),
+            None => Vec::new(),
+        };
+        let shift = |end: usize| end - removed + inserted;
+        let mut measured = Vec::new();
+        let mut next = 0; // first old boundary not before the current one
+        let resync = self.measure(from, &mut measured, |boundary| {
+            if boundary < start + inserted {
+                return false;
+            }
+            while next < tail.len() && shift(tail[next].0) < boundary {
+                next += 1;
+            }
+            next < tail.len() && shift(tail[next].0) == boundary
+        });
+        widths.truncate(keep);
+        widths.extend(measured);
+        widths.extend(
+            tail.into_iter()
+                .map(|(end, w)| (shift(end), w))
+                .filter(|&(end, _)| end > resync),
+        );
+        true
+    }
+
+    /// Position of the byte `end` of the line displayed from `orig` on `cols`
+    /// columns, computed from the memoized grapheme widths.
+    pub(crate) fn position(&self, end: usize, orig: Position, cols: usize) -> Position {
+        let mut pos = orig;
+        for &(grapheme_end, width) in self.widths().iter() {
+            if grapheme_end > end {
+                break;
+            }
+            match width {
+                None => {
+                    pos.row += 1;
+                    pos.col = 0;
+                }
+                Some(cw) => {
+                    pos.col += cw;
+                    if pos.col > cols {
+                        pos.row += 1;
+                        pos.col = cw;
+                    }
+                }
+            }
+        }
+        if pos.col == cols {
+            pos.col = 0;
+            pos.row += 1;
+        }
+        pos
+    }
+
+    /// Offset of the first grapheme displayed on the `row` (the length of the
+    /// line when there is none) of the line displayed from `orig` on `cols`
+    /// columns.
+    pub(crate) fn row_offset(&self, row: usize, orig: Position, cols: usize) -> usize {
+        if row <= orig.row {
+            return 0;
+        }
+        let mut pos = orig;
+        let mut start = 0;
+        for &(grapheme_end, width) in self.widths().iter() {
+            match width {
+                None => {
+                    pos.row += 1;
+                    pos.col = 0;
+                    if pos.row == row {
+                        return grapheme_end;
+                    }
+                }
+                Some(cw) => {
+                    pos.col += cw;
+                    if pos.col > cols {
+                        pos.row += 1;
+                        pos.col = cw;
+                        if pos.row == row {
+                            return start;
+                        }
+                    }
+                }
+            }
+            start = grapheme_end;
+        }
+        self.buf.len()
+    }
+
     /// Insert the character `ch` at current cursor position
     /// and advance cursor position accordingly.
     /// Return `None` when maximum buffer size has been reached,
@@ -199,10 +587,9 @@ impl LineBuffer {
         }
         let push = self.pos == self.buf.len();
         if n == 1 {
+            self.edit_widths(self.pos, 0, ch.len_utf8());
             self.buf.insert(self.pos, ch);
-            for cl in &self.cl {
-                cl.borrow_mut().insert_char(self.pos, ch);
-            }
+            self.listeners.insert_char(self.pos, ch);
         } else {
             let text = iter::repeat(ch).take(n).collect::<String>();
             let pos = self.pos;
@@ -232,6 +619,18 @@ impl LineBuffer {
         Some(push)
     }
 
+    /// Insert a large `text` (like a paste) at current position and advance
+    /// cursor position accordingly, growing the buffer (in one allocation)
+    /// beyond its maximum size when needed.
+    /// Return `true` when the text has been appended to the end of the line.
+    pub fn insert_bulk(&mut self, text: &str) -> bool {
+        self.buf.reserve(text.len());
+        let pos = self.pos;
+        let push = self.insert_str(pos, text);
+        self.pos += text.len();
+        push
+    }
+
     /// Delete previously yanked text and yank/paste `text` at current position.
     pub fn yank_pop(&mut self, yank_size: usize, text: &str) -> Option<bool> {
         let end = self.pos;
@@ -358,17 +757,35 @@ impl LineBuffer {
         if pos == 0 {
             return None;
         }
+        if let Some(spans) = self.token_spans(word_def) {
+            let starts: Vec<usize> = spans
+                .iter()
+                .map(|span| span.start)
+                .filter(|&start| start < pos)
+                .collect();
+            let n = cmp::max(n, 1);
+            return Some(if n <= starts.len() {
+                starts[starts.len() - n]
+            } else {
+                0
+            });
+        }
         let mut sow = 0;
-        let mut gis = self.buf[..pos].grapheme_indices(true).rev();
+        let mut gis = self.segments(&self.buf[..pos], word_def).rev();
+        // adjacent segments may be distinct words
+        let mut carried = None;
         'outer: for _ in 0..n {
             sow = 0;
-            let mut gj = gis.next();
+            let mut gj = carried.take().or_else(|| gis.next());
             'inner: loop {
                 if let Some((j, y)) = gj {
                     let gi = gis.next();
                     if let Some((_, x)) = gi {
-                        if is_start_of_word(word_def, x, y) {
+                        if self.is_start_of_word(word_def, x, y) {
                             sow = j;
+                            if self.unicode_words && word_def != Word::Big {
+                                carried = gi;
+                            }
                             break 'inner;
                         }
                         gj = gi;
@@ -410,29 +827,56 @@ impl LineBuffer {
         if pos == self.buf.len() {
             return None;
         }
+        if let Some(spans) = self.token_spans(word_def) {
+            let positions: Vec<usize> = spans
+                .iter()
+                .map(|span| match at {
+                    At::Start => span.start,
+                    At::AfterEnd => span.end,
+                    At::BeforeEnd => last_grapheme_start(&self.buf[..span.end]),
+                })
+                .filter(|&p| p > pos)
+                .collect();
+            return match positions.get(cmp::max(n, 1) - 1) {
+                Some(&p) => Some(p),
+                None if word_def == Word::Emacs || at == At::AfterEnd => Some(self.buf.len()),
+                None => positions.last().cloned(),
+            };
+        }
         let mut wp = 0;
-        let mut gis = self.buf[pos..].grapheme_indices(true);
-        let mut gi = if at == At::BeforeEnd {
-            // TODO Validate
-            gis.next()
+        // TODO Validate
+        let skip = if at == At::BeforeEnd {
+            self.grapheme_len(pos)
         } else {
-            None
+            0
         };
+        let mut gis = self
+            .segments(&self.buf[pos + skip..], word_def)
+            .map(|(i, s)| (i + skip, s));
+        let mut gi = None;
+        // adjacent segments may be distinct words
+        let mut carried = None;
         'outer: for _ in 0..n {
             wp = 0;
-            gi = gis.next();
+            gi = carried.take().or_else(|| gis.next());
             'inner: loop {
                 if let Some((i, x)) = gi {
                     let gj = gis.next();
                     if let Some((j, y)) = gj {
-                        if at == At::Start && is_start_of_word(word_def, x, y) {
+                        if at == At::Start && self.is_start_of_word(word_def, x, y) {
                             wp = j;
+                            if self.unicode_words && word_def != Word::Big {
+                                carried = gj;
+                            }
                             break 'inner;
-                        } else if at != At::Start && is_end_of_word(word_def, x, y) {
+                        } else if at != At::Start && self.is_end_of_word(word_def, x, y) {
                             if word_def == Word::Emacs || at == At::AfterEnd {
                                 wp = j;
                             } else {
-                                wp = i;
+                                wp = i + last_grapheme_start(x);
+                            }
+                            if self.unicode_words && word_def != Word::Big {
+                                carried = gj;
                             }
                             break 'inner;
                         }
@@ -450,7 +894,9 @@ impl LineBuffer {
                 Some(self.buf.len())
             } else {
                 match gi {
-                    Some((i, _)) if i != 0 => Some(i + pos),
+                    Some((i, x)) if i + last_grapheme_start(x) != 0 => {
+                        Some(i + last_grapheme_start(x) + pos)
+                    }
                     _ => None,
                 }
             }
@@ -459,6 +905,35 @@ impl LineBuffer {
         }
     }
 
+    /// Graphemes of `s` or, with Unicode words, its word boundaries segments.
+    fn segments<'a>(
+        &self,
+        s: &'a str,
+        word_def: Word,
+    ) -> Box<DoubleEndedIterator<Item = (usize, &'a str)> + 'a> {
+        if self.unicode_words && word_def != Word::Big {
+            Box::new(s.split_word_bound_indices())
+        } else {
+            Box::new(s.grapheme_indices(true))
+        }
+    }
+
+    fn is_start_of_word(&self, word_def: Word, previous: &str, segment: &str) -> bool {
+        if self.unicode_words && word_def != Word::Big {
+            is_start_of_segment(word_def, self.word_chars, previous, segment)
+        } else {
+            is_start_of_word(word_def, self.word_chars, previous, segment)
+        }
+    }
+
+    fn is_end_of_word(&self, word_def: Word, segment: &str, next: &str) -> bool {
+        if self.unicode_words && word_def != Word::Big {
+            is_end_of_segment(word_def, self.word_chars, segment, next)
+        } else {
+            is_end_of_word(word_def, self.word_chars, segment, next)
+        }
+    }
+
     /// Moves the cursor to the end of next word.
     pub fn move_to_next_word(&mut self, at: At, word_def: Word, n: RepeatCount) -> bool {
         if let Some(pos) = self.next_word_pos(self.pos, at, word_def, n) {
@@ -469,26 +944,26 @@ impl LineBuffer {
         }
     }
 
+    /// Search the `n`th grapheme starting with `c` (so that a base character
+    /// matches its combined forms).
     fn search_char_pos(&self, cs: CharSearch, n: RepeatCount) -> Option<usize> {
         let mut shift = 0;
         let search_result = match cs {
             CharSearch::Backward(c) | CharSearch::BackwardAfter(c) => self.buf[..self.pos]
-                .char_indices()
+                .grapheme_indices(true)
                 .rev()
-                .filter(|&(_, ch)| ch == c)
+                .filter(|&(_, g)| g.starts_with(c))
                 .take(n)
-                .last()
-                .map(|(i, _)| i),
+                .last(),
             CharSearch::Forward(c) | CharSearch::ForwardBefore(c) => {
                 if let Some(cc) = self.grapheme_at_cursor() {
                     shift = self.pos + cc.len();
                     if shift < self.buf.len() {
                         self.buf[shift..]
-                            .char_indices()
-                            .filter(|&(_, ch)| ch == c)
+                            .grapheme_indices(true)
+                            .filter(|&(_, g)| g.starts_with(c))
                             .take(n)
                             .last()
-                            .map(|(i, _)| i)
                     } else {
                         None
                     }
@@ -497,17 +972,17 @@ impl LineBuffer {
                 }
             }
         };
-        if let Some(pos) = search_result {
+        if let Some((pos, g)) = search_result {
             Some(match cs {
                 CharSearch::Backward(_) => pos,
-                CharSearch::BackwardAfter(c) => pos + c.len_utf8(),
+                CharSearch::BackwardAfter(_) => pos + g.len(),
                 CharSearch::Forward(_) => shift + pos,
                 CharSearch::ForwardBefore(_) => {
                     shift + pos - self.buf[..shift + pos]
-                        .chars()
+                        .graphemes(true)
                         .next_back()
                         .unwrap()
-                        .len_utf8()
+                        .len()
                 }
             })
         } else {
@@ -515,6 +990,11 @@ impl LineBuffer {
         }
     }
 
+    /// Length of the grapheme starting at `pos`.
+    fn grapheme_len(&self, pos: usize) -> usize {
+        self.buf[pos..].graphemes(true).next().map_or(0, str::len)
+    }
+
     /// Move cursor to the matching character position.
     /// Return `true` when the search succeeds.
     pub fn move_to(&mut self, cs: CharSearch, n: RepeatCount) -> bool {
@@ -554,9 +1034,10 @@ impl LineBuffer {
                     let start = self.pos;
                     self.drain(start..pos, Direction::Forward);
                 }
-                CharSearch::Forward(c) => {
+                CharSearch::Forward(_) => {
                     let start = self.pos;
-                    self.drain(start..pos + c.len_utf8(), Direction::Forward);
+                    let end = pos + self.grapheme_len(pos);
+                    self.drain(start..end, Direction::Forward);
                 }
             };
             true
@@ -571,7 +1052,7 @@ impl LineBuffer {
         }
         self.buf[self.pos..]
             .grapheme_indices(true)
-            .filter(|&(_, ch)| ch.chars().all(|c| c.is_alphanumeric()))
+            .filter(|&(_, ch)| is_word_char(Word::Emacs, self.word_chars, ch))
             .map(|(i, _)| i)
             .next()
             .map(|i| i + self.pos)
@@ -584,26 +1065,27 @@ impl LineBuffer {
                 if start == end {
                     return false;
                 }
-                let word = self
-                    .drain(start..end, Direction::default())
-                    .collect::<String>();
-                let result = match a {
-                    WordAction::CAPITALIZE => {
-                        let ch = (&word).graphemes(true).next().unwrap();
-                        let cap = ch.to_uppercase();
-                        cap + &word[ch.len()..].to_lowercase()
-                    }
-                    WordAction::LOWERCASE => word.to_lowercase(),
-                    WordAction::UPPERCASE => word.to_uppercase(),
-                };
-                self.insert_str(start, &result);
-                self.pos = start + result.len();
-                return true;
+                return self.edit_case(start..end, a);
             }
         }
         false
     }
 
+    /// Alter the text in `range` (the cursor is moved after it).
+    ///
+    /// Full Unicode case mappings are used (`ß` is uppercased to `SS`).
+    pub fn edit_case(&mut self, range: Range<usize>, a: WordAction) -> bool {
+        if range.start >= range.end {
+            return false;
+        }
+        let start = range.start;
+        let text = self.drain(range, Direction::default()).collect::<String>();
+        let result = change_case(&text, a, self.turkic_case);
+        self.insert_str(start, &result);
+        self.pos = start + result.len();
+        true
+    }
+
     /// Transpose two words
     pub fn transpose_words(&mut self, n: RepeatCount) -> bool {
         let word_def = Word::Emacs;
@@ -633,14 +1115,64 @@ impl LineBuffer {
         true
     }
 
+    /// Move the word under (or just before) the cursor `n` words to the left
+    /// (or to the right when `forward`), swapping it with its neighbours.
+    pub fn drag_word(&mut self, forward: bool, n: RepeatCount) -> bool {
+        let word_def = Word::Emacs;
+        let (mut start, mut end) = match self.word_at(self.pos) {
+            Some(word) => (word.start, word.end),
+            None => return false,
+        };
+        let offset = self.pos - start;
+        let mut moved = false;
+        for _ in 0..n {
+            let other = if forward {
+                self.next_word_pos(end, At::AfterEnd, word_def, 1)
+                    .and_then(|other_end| self.word_at(other_end))
+            } else {
+                self.prev_word_pos(start, word_def, 1)
+                    .and_then(|other_start| self.word_at(other_start))
+            };
+            let (left, right) = match other {
+                Some(other) if forward && end <= other.start => (start..end, other),
+                Some(other) if !forward && other.end <= start => (other, start..end),
+                _ => break,
+            };
+            let mut text = String::with_capacity(right.end - left.start);
+            text.push_str(&self.buf[right.clone()]);
+            text.push_str(&self.buf[left.end..right.start]);
+            text.push_str(&self.buf[left.clone()]);
+            let len = end - start;
+            self.replace(left.start..right.end, &text);
+            start = if forward { right.end - len } else { left.start };
+            end = start + len;
+            moved = true;
+        }
+        if moved {
+            self.pos = start + offset;
+        }
+        moved
+    }
+
+    /// Range of the word at `pos` (or ending at `pos`).
+    fn word_at(&self, pos: usize) -> Option<Range<usize>> {
+        let word_def = Word::Emacs;
+        if let Some(start) = self.prev_word_pos(pos, word_def, 1) {
+            if self.next_word_pos(start, At::AfterEnd, word_def, 1) == Some(pos) {
+                return Some(start..pos);
+            }
+        }
+        self.next_word_pos(pos, At::AfterEnd, word_def, 1)
+            .and_then(|end| self.prev_word_pos(end, word_def, 1).map(|start| start..end))
+    }
+
     /// Replaces the content between [`start`..`end`] with `text`
     /// and positions the cursor to the end of text.
     pub fn replace(&mut self, range: Range<usize>, text: &str) {
         let start = range.start;
-        for cl in &self.cl {
-            cl.borrow_mut()
-                .replace(start, self.buf.index(range.clone()), text);
-        }
+        self.listeners
+            .replace(start, self.buf.index(range.clone()), text);
+        self.edit_widths(start, range.end - start, text.len());
         self.buf.drain(range);
         if start == self.buf.len() {
             self.buf.push_str(text);
@@ -653,9 +1185,8 @@ impl LineBuffer {
     /// Insert the `s`tring at the specified position.
     /// Return `true` if the text has been inserted at the end of the line.
     pub fn insert_str(&mut self, idx: usize, s: &str) -> bool {
-        for cl in &self.cl {
-            cl.borrow_mut().insert_str(idx, s);
-        }
+        self.listeners.insert_str(idx, s);
+        self.edit_widths(idx, 0, s.len());
         if idx == self.buf.len() {
             self.buf.push_str(s);
             true
@@ -672,16 +1203,9 @@ impl LineBuffer {
     }
 
     fn drain(&mut self, range: Range<usize>, dir: Direction) -> Drain {
-        for dl in &self.dl {
-            let mut lock = dl.try_lock();
-            if let Ok(mut dl) = lock {
-                dl.delete(range.start, &self.buf[range.start..range.end], dir);
-            }
-        }
-        for cl in &self.cl {
-            cl.borrow_mut()
-                .delete(range.start, &self.buf[range.start..range.end], dir);
-        }
+        self.listeners
+            .delete(range.start, &self.buf[range.start..range.end], dir);
+        self.edit_widths(range.start, range.end - range.start, 0);
         self.buf.drain(range)
     }
 
@@ -735,7 +1259,9 @@ impl LineBuffer {
                             self.buf[pos..self.pos].to_owned()
                         }
                         CharSearch::ForwardBefore(_) => self.buf[self.pos..pos].to_owned(),
-                        CharSearch::Forward(c) => self.buf[self.pos..pos + c.len_utf8()].to_owned(),
+                        CharSearch::Forward(_) => {
+                            self.buf[self.pos..pos + self.grapheme_len(pos)].to_owned()
+                        }
                     })
                 } else {
                     None
@@ -754,6 +1280,13 @@ impl LineBuffer {
         }
     }
 
+    /// Kill the specified `range` in the line.
+    pub fn kill_range(&mut self, range: Range<usize>) {
+        self.listeners.start_killing();
+        self.delete_range(range);
+        self.listeners.stop_killing();
+    }
+
     pub fn kill(&mut self, mvt: &Movement) -> bool {
         let notify = match *mvt {
             Movement::ForwardChar(_) => false,
@@ -761,10 +1294,7 @@ impl LineBuffer {
             _ => true,
         };
         if notify {
-            if let Some(dl) = self.dl.as_ref() {
-                let mut dl = dl.lock().unwrap();
-                dl.start_killing()
-            }
+            self.listeners.start_killing();
         }
         let killed = match *mvt {
             Movement::ForwardChar(n) => {
@@ -801,10 +1331,7 @@ impl LineBuffer {
             }
         };
         if notify {
-            if let Some(dl) = self.dl.as_ref() {
-                let mut dl = dl.lock().unwrap();
-                dl.stop_killing()
-            }
+            self.listeners.stop_killing();
         }
         killed
     }
@@ -818,35 +1345,142 @@ impl Deref for LineBuffer {
     }
 }
 
-fn is_start_of_word(word_def: Word, previous: &str, grapheme: &str) -> bool {
-    (!is_word_char(word_def, previous) && is_word_char(word_def, grapheme))
-        || (word_def == Word::Vi && !is_other_char(previous) && is_other_char(grapheme))
+fn is_start_of_word(word_def: Word, wc: WordChars, previous: &str, grapheme: &str) -> bool {
+    (!is_word_char(word_def, wc, previous) && is_word_char(word_def, wc, grapheme))
+        || (word_def == Word::Vi && !is_other_char(wc, previous) && is_other_char(wc, grapheme))
 }
-fn is_end_of_word(word_def: Word, grapheme: &str, next: &str) -> bool {
-    (!is_word_char(word_def, next) && is_word_char(word_def, grapheme))
-        || (word_def == Word::Vi && !is_other_char(next) && is_other_char(grapheme))
+fn is_end_of_word(word_def: Word, wc: WordChars, grapheme: &str, next: &str) -> bool {
+    (!is_word_char(word_def, wc, next) && is_word_char(word_def, wc, grapheme))
+        || (word_def == Word::Vi && !is_other_char(wc, next) && is_other_char(wc, grapheme))
 }
 
-fn is_word_char(word_def: Word, grapheme: &str) -> bool {
+fn is_word_char(word_def: Word, wc: WordChars, grapheme: &str) -> bool {
     match word_def {
-        Word::Emacs => grapheme.chars().all(|c| c.is_alphanumeric()),
-        Word::Vi => is_vi_word_char(grapheme),
+        Word::Emacs => grapheme
+            .chars()
+            .all(|c| c.is_alphanumeric() || wc.contains(c)),
+        Word::Vi => is_vi_word_char(wc, grapheme),
         Word::Big => !grapheme.chars().any(|c| c.is_whitespace()),
     }
 }
-fn is_vi_word_char(grapheme: &str) -> bool {
-    grapheme.chars().all(|c| c.is_alphanumeric()) || grapheme == "_"
+fn is_vi_word_char(wc: WordChars, grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .all(|c| c.is_alphanumeric() || wc.contains(c))
+        || grapheme == "_"
+}
+fn is_other_char(wc: WordChars, grapheme: &str) -> bool {
+    !(grapheme.chars().any(|c| c.is_whitespace()) || is_vi_word_char(wc, grapheme))
+}
+
+// With Unicode words, each segment containing an alphanumeric character is a
+// word on its own (unless glued to another one by `wc` characters).
+fn is_start_of_segment(word_def: Word, wc: WordChars, previous: &str, segment: &str) -> bool {
+    (is_word_segment(word_def, wc, segment) && !is_glued(word_def, wc, previous, segment))
+        || (word_def == Word::Vi && !is_other_segment(wc, previous) && is_other_segment(wc, segment))
 }
-fn is_other_char(grapheme: &str) -> bool {
-    !(grapheme.chars().any(|c| c.is_whitespace()) || is_vi_word_char(grapheme))
+fn is_end_of_segment(word_def: Word, wc: WordChars, segment: &str, next: &str) -> bool {
+    (is_word_segment(word_def, wc, segment) && !is_glued(word_def, wc, segment, next))
+        || (word_def == Word::Vi && !is_other_segment(wc, next) && is_other_segment(wc, segment))
+}
+fn is_glued(word_def: Word, wc: WordChars, left: &str, right: &str) -> bool {
+    is_word_segment(word_def, wc, left)
+        && is_word_segment(word_def, wc, right)
+        && !(left.chars().any(char::is_alphanumeric) && right.chars().any(char::is_alphanumeric))
+}
+fn is_word_segment(word_def: Word, wc: WordChars, segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+        || segment
+            .chars()
+            .all(|c| wc.contains(c) || (word_def == Word::Vi && c == '_'))
+}
+fn is_other_segment(wc: WordChars, segment: &str) -> bool {
+    !(segment.chars().any(char::is_whitespace) || is_word_segment(Word::Vi, wc, segment))
+}
+
+/// Start of the last grapheme of `s`.
+fn last_grapheme_start(s: &str) -> usize {
+    s.grapheme_indices(true).next_back().map_or(0, |(i, _)| i)
+}
+
+fn change_case(s: &str, a: WordAction, turkic: bool) -> String {
+    match a {
+        WordAction::CAPITALIZE => {
+            let first = s.graphemes(true).next().unwrap_or("");
+            let mut chars = first.chars();
+            let mut result = String::with_capacity(s.len());
+            if let Some(c) = chars.next() {
+                push_titlecase(&mut result, c, turkic);
+            }
+            result.push_str(&lowercase(chars.as_str(), turkic));
+            result.push_str(&lowercase(&s[first.len()..], turkic));
+            result
+        }
+        WordAction::LOWERCASE => lowercase(s, turkic),
+        WordAction::UPPERCASE => uppercase(s, turkic),
+        WordAction::TOGGLE => {
+            let mut result = String::with_capacity(s.len());
+            let mut buf = [0; 4];
+            for c in s.chars() {
+                if c.is_uppercase() {
+                    result.push_str(&lowercase(c.encode_utf8(&mut buf), turkic));
+                } else if c.is_lowercase() {
+                    result.push_str(&uppercase(c.encode_utf8(&mut buf), turkic));
+                } else {
+                    result.push(c);
+                }
+            }
+            result
+        }
+    }
+}
+
+fn lowercase(s: &str, turkic: bool) -> String {
+    if turkic {
+        s.replace('I', "ı").replace('İ', "i").to_lowercase()
+    } else {
+        s.to_lowercase()
+    }
+}
+
+fn uppercase(s: &str, turkic: bool) -> String {
+    if turkic {
+        s.replace('i', "İ").to_uppercase()
+    } else {
+        s.to_uppercase()
+    }
+}
+
+/// Title case of `c`: the digraphs have their own, the first letter of an
+/// uppercase expansion is kept (`ß` -> `Ss`, `ﬁ` -> `Fi`).
+fn push_titlecase(result: &mut String, c: char, turkic: bool) {
+    let title = match c {
+        'Ǆ' | 'ǅ' | 'ǆ' => 'ǅ',
+        'Ǉ' | 'ǈ' | 'ǉ' => 'ǈ',
+        'Ǌ' | 'ǋ' | 'ǌ' => 'ǋ',
+        'Ǳ' | 'ǲ' | 'ǳ' => 'ǲ',
+        'i' if turkic => 'İ',
+        _ => {
+            let mut upper = c.to_uppercase();
+            if let Some(first) = upper.next() {
+                result.push(first);
+            }
+            result.extend(upper.flat_map(char::to_lowercase));
+            return;
+        }
+    };
+    result.push(title);
 }
 
 #[cfg(test)]
 mod test {
     use super::{ChangeListener, DeleteListener, Direction, LineBuffer, WordAction, MAX_LINE};
+    use config::WordChars;
     use keymap::{At, CharSearch, Word};
     use std::cell::RefCell;
     use std::rc::Rc;
+    use tokenizer::Tokenizer;
+    use tty::Position;
 
     struct Listener {
         deleted_str: Option<String>,
@@ -944,6 +1578,17 @@ mod test {
         assert_eq!(8, s.pos);
     }
 
+    #[test]
+    fn insert_bulk() {
+        let mut s = LineBuffer::init("αε", 2, None);
+        let text = "ß".repeat(MAX_LINE);
+        assert_eq!(None, s.yank(&text, 1));
+        let push = s.insert_bulk(&text);
+        assert_eq!(false, push);
+        assert_eq!(2 * MAX_LINE + 4, s.buf.len());
+        assert_eq!(2 * MAX_LINE + 2, s.pos);
+    }
+
     #[test]
     fn moves() {
         let mut s = LineBuffer::init("αß", 4, None);
@@ -1271,6 +1916,20 @@ mod test {
         assert!(!ok);
     }
 
+    #[test]
+    fn word_chars() {
+        let mut s = LineBuffer::init("ls /usr/local-bin", 17, None);
+        assert!(s.delete_prev_word(Word::Emacs, 1));
+        assert_eq!("ls /usr/local-", s.buf);
+
+        let mut s = LineBuffer::init("ls /usr/local-bin", 17, None);
+        s.set_word_chars(WordChars::new("-/"));
+        assert!(s.delete_prev_word(Word::Emacs, 1));
+        assert_eq!("ls ", s.buf);
+        assert!(s.move_to_prev_word(Word::Vi, 1));
+        assert_eq!(0, s.pos);
+    }
+
     #[test]
     fn delete_word() {
         let cl = Listener::new();
@@ -1350,7 +2009,16 @@ mod test {
 
         let mut s = LineBuffer::init("a ßETA  c", 1, None);
         assert!(s.edit_word(WordAction::CAPITALIZE));
-        assert_eq!("a SSeta  c", s.buf);
+        assert_eq!("a Sseta  c", s.buf);
+        assert_eq!(7, s.pos);
+
+        let mut s = LineBuffer::init("ǆungla", 0, None);
+        assert!(s.edit_word(WordAction::CAPITALIZE));
+        assert_eq!("ǅungla", s.buf);
+
+        let mut s = LineBuffer::init("Straße aB", 0, None);
+        assert!(s.edit_word(WordAction::TOGGLE));
+        assert_eq!("sTRASSE aB", s.buf);
         assert_eq!(7, s.pos);
 
         let mut s = LineBuffer::init("test", 1, None);
@@ -1359,6 +2027,31 @@ mod test {
         assert_eq!(4, s.pos);
     }
 
+    #[test]
+    fn edit_case() {
+        let mut s = LineBuffer::init("diyarbakır Iğdır", 0, None);
+        assert!(s.edit_case(0..11, WordAction::UPPERCASE));
+        assert_eq!("DIYARBAKIR Iğdır", s.buf);
+        assert_eq!(10, s.pos);
+        assert!(!s.edit_case(3..3, WordAction::TOGGLE));
+
+        s.set_turkic_case(true);
+        assert!(s.edit_case(0..10, WordAction::LOWERCASE));
+        assert_eq!("dıyarbakır Iğdır", s.buf);
+        assert!(s.edit_case(0..12, WordAction::TOGGLE));
+        assert_eq!("DIYARBAKIR Iğdır", s.buf);
+        let len = s.len();
+        assert!(s.edit_case(11..len, WordAction::TOGGLE));
+        assert_eq!("DIYARBAKIR ıĞDIR", s.buf);
+
+        let mut s = LineBuffer::init("istanbul", 0, None);
+        s.set_turkic_case(true);
+        assert!(s.edit_word(WordAction::CAPITALIZE));
+        assert_eq!("İstanbul", s.buf);
+        assert!(s.edit_case(0..9, WordAction::UPPERCASE));
+        assert_eq!("İSTANBUL", s.buf);
+    }
+
     #[test]
     fn transpose_words() {
         let mut s = LineBuffer::init("ßeta / δelta__", 15, None);
@@ -1377,4 +2070,186 @@ mod test {
         let mut s = LineBuffer::init("ßeta / __", 9, None);
         assert!(!s.transpose_words(1));
     }
+
+    #[test]
+    fn drag_word() {
+        let mut s = LineBuffer::init("cp -r src, dst", 7, None); // in 'src'
+        assert!(s.drag_word(true, 1));
+        assert_eq!("cp -r dst, src", s.buf);
+        assert_eq!(12, s.pos);
+        assert!(!s.drag_word(true, 1));
+
+        assert!(s.drag_word(false, 2));
+        assert_eq!("cp -src r, dst", s.buf);
+        assert_eq!(5, s.pos);
+
+        let mut s = LineBuffer::init("a ßeta c", 7, None); // after 'ßeta'
+        assert!(s.drag_word(false, 3));
+        assert_eq!("ßeta a c", s.buf);
+        assert_eq!(5, s.pos);
+
+        let mut s = LineBuffer::init("  ", 1, None);
+        assert!(!s.drag_word(true, 1));
+    }
+
+    #[test]
+    fn row_offset() {
+        // "> abc", "def" and "gh" on 5 columns
+        let s = LineBuffer::init("abcdef\ngh", 0, None);
+        let orig = Position { col: 2, row: 0 };
+        assert_eq!(0, s.row_offset(0, orig, 5));
+        assert_eq!(3, s.row_offset(1, orig, 5));
+        assert_eq!(7, s.row_offset(2, orig, 5));
+        assert_eq!(9, s.row_offset(3, orig, 5));
+        // the line feed after a full row
+        let s = LineBuffer::init("abc\nd", 0, None);
+        assert_eq!(4, s.row_offset(1, orig, 5));
+    }
+
+    #[test]
+    fn take_dirty() {
+        let mut s = LineBuffer::init("αβγδ", 4, None);
+        assert_eq!(Some(0), s.take_dirty());
+        assert_eq!(None, s.take_dirty());
+        assert!(s.insert('x', 1).is_some());
+        s.move_end();
+        assert!(s.backspace(1));
+        assert_eq!(Some(4), s.take_dirty());
+    }
+
+    #[test]
+    fn widths() {
+        let mut s = LineBuffer::init("a\u{301}中\n", 0, None);
+        assert_eq!(vec![(3, Some(1)), (6, Some(2)), (7, None)], *s.widths());
+        s.insert('b', 1);
+        assert_eq!((1, Some(1)), s.widths()[0]);
+        s.delete_range(0..8);
+        assert!(s.widths().is_empty());
+
+        // measured again around the edits only
+        let mut s = LineBuffer::init("ab", 2, None);
+        let check = |s: &LineBuffer| {
+            let fresh = LineBuffer::init(&s.buf, 0, None);
+            assert_eq!(*fresh.widths(), *s.widths(), "{:?}", s.buf);
+        };
+        check(&s);
+        s.insert('\u{301}', 1); // combining the 'b'
+        check(&s);
+        s.insert_str(1, "中\n");
+        check(&s);
+        s.insert_str(s.len(), "\u{1F1EB}");
+        check(&s);
+        s.insert_str(s.len(), "\u{1F1F7}"); // flag
+        check(&s);
+        s.replace(0..1, "xyz");
+        check(&s);
+        s.delete_range(3..7);
+        check(&s);
+        s.insert('\u{1F1EB}', 1);
+        s.insert('c', 1); // two edits
+        check(&s);
+        s.insert_str(0, "\x1b[1m");
+        check(&s);
+    }
+
+    #[test]
+    fn grapheme_clusters() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let accent = "e\u{301}";
+        let line = format!("a{}b{}", family, accent);
+
+        let mut s = LineBuffer::init(&line, 1 + family.len(), None);
+        assert!(s.backspace(1));
+        assert_eq!(format!("ab{}", accent), s.buf);
+        assert_eq!(1, s.pos);
+
+        let mut s = LineBuffer::init(&line, 1, None);
+        assert_eq!(Some(family.to_owned()), s.delete(1));
+        assert_eq!(format!("ab{}", accent), s.buf);
+
+        let mut s = LineBuffer::init(&line, line.len(), None);
+        assert!(s.transpose_chars());
+        assert_eq!(format!("a{}{}b", family, accent), s.buf);
+        assert_eq!(line.len(), s.pos);
+
+        let mut s = LineBuffer::init(&line, 0, None);
+        assert!(s.move_to(CharSearch::Forward('\u{1F468}'), 1));
+        assert_eq!(1, s.pos);
+        assert!(s.move_to(CharSearch::Forward('e'), 1));
+        assert_eq!(2 + family.len(), s.pos);
+        assert!(!s.move_to(CharSearch::Forward('\u{1F469}'), 1));
+
+        let mut s = LineBuffer::init(&line, 0, None);
+        assert!(s.delete_to(CharSearch::Forward('e'), 1));
+        assert_eq!("", s.buf);
+
+        let mut s = LineBuffer::init(&line, line.len(), None);
+        assert!(s.delete_to(CharSearch::BackwardAfter('\u{1F468}'), 1));
+        assert_eq!(format!("a{}", family), s.buf);
+    }
+
+    #[test]
+    fn unicode_words() {
+        let mut s = LineBuffer::init("漢字テスト can't", 0, None);
+        s.set_unicode_words(true);
+        assert!(s.move_to_next_word(At::AfterEnd, Word::Emacs, 1));
+        assert_eq!(3, s.pos);
+        assert!(s.move_to_next_word(At::AfterEnd, Word::Emacs, 2));
+        assert_eq!(15, s.pos);
+        assert!(s.move_to_next_word(At::AfterEnd, Word::Emacs, 1));
+        assert_eq!(s.len(), s.pos);
+        assert!(s.delete_prev_word(Word::Emacs, 1));
+        assert_eq!("漢字テスト ", s.buf);
+        assert!(s.move_to_prev_word(Word::Emacs, 2));
+        assert_eq!(3, s.pos);
+
+        let mut s = LineBuffer::init("foo_bar-baz", 0, None);
+        s.set_unicode_words(true);
+        assert!(s.move_to_next_word(At::BeforeEnd, Word::Vi, 1));
+        assert_eq!(6, s.pos);
+        assert!(s.move_to_next_word(At::BeforeEnd, Word::Vi, 1));
+        assert_eq!(7, s.pos);
+        assert!(s.move_to_next_word(At::Start, Word::Vi, 1));
+        assert_eq!(8, s.pos);
+        assert!(s.move_to_next_word(At::BeforeEnd, Word::Big, 1));
+        assert_eq!(10, s.pos);
+    }
+
+    #[test]
+    fn tokenizer_words() {
+        let mut s = LineBuffer::init("cp 'My Docs'|wc", 0, None);
+        s.set_tokenizer(Some(Tokenizer::new().operators(&["|"])));
+        assert!(s.move_to_next_word(At::AfterEnd, Word::Emacs, 2));
+        assert_eq!(12, s.pos);
+        assert!(s.move_to_next_word(At::Start, Word::Vi, 1));
+        assert_eq!(13, s.pos);
+        assert!(s.move_to_prev_word(Word::Emacs, 2));
+        assert_eq!(3, s.pos);
+        assert!(s.move_to_next_word(At::BeforeEnd, Word::Vi, 1));
+        assert_eq!(11, s.pos);
+        assert!(s.move_to_next_word(At::BeforeEnd, Word::Big, 1));
+        assert_eq!(14, s.pos);
+        s.set_pos(s.len());
+        assert!(s.delete_prev_word(Word::Emacs, 1));
+        assert_eq!("cp 'My Docs'|", s.buf);
+        assert!(s.delete_prev_word(Word::Emacs, 2));
+        assert_eq!("cp ", s.buf);
+    }
+
+    #[test]
+    fn listeners() {
+        let cl1 = Listener::new();
+        let cl2 = Listener::new();
+        let mut s = LineBuffer::init("αß", 2, Some(cl1.clone()));
+        let id = s.add_change_listener(cl2.clone());
+        s.delete(1);
+        cl1.borrow().assert_deleted_str_eq("ß");
+        cl2.borrow().assert_deleted_str_eq("ß");
+
+        assert!(s.remove_listener(id));
+        assert!(!s.remove_listener(id));
+        s.backspace(1);
+        cl1.borrow().assert_deleted_str_eq("α");
+        cl2.borrow().assert_deleted_str_eq("ß");
+    }
 }
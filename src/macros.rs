@@ -0,0 +1,135 @@
+/// Implement one of the `Helper` super traits by delegating all its methods
+/// to a field (there is no proc-macro derive yet).
+///
+/// The field type must be specified for `Completer` (to get its
+/// `Candidate` type).
+///
+/// ```
+/// #[macro_use]
+/// extern crate rustyline;
+///
+/// use rustyline::completion::FilenameCompleter;
+/// use rustyline::highlight::Highlighter;
+/// use rustyline::hint::Hinter;
+/// use rustyline::Helper;
+///
+/// struct MyHinter;
+///
+/// impl Hinter for MyHinter {
+///     fn hint(&self, _line: &str, _pos: usize) -> Option<String> {
+///         None
+///     }
+/// }
+///
+/// struct MyHelper {
+///     completer: FilenameCompleter,
+///     hinter: MyHinter,
+/// }
+///
+/// delegate_helper!(Completer for MyHelper => completer: FilenameCompleter);
+/// delegate_helper!(Hinter for MyHelper => hinter);
+/// impl Highlighter for MyHelper {}
+/// impl Helper for MyHelper {}
+///
+/// fn main() {
+///     let mut rl = rustyline::Editor::new();
+///     rl.set_helper(Some(MyHelper {
+///         completer: FilenameCompleter::new(),
+///         hinter: MyHinter,
+///     }));
+/// }
+/// ```
+#[macro_export]
+macro_rules! delegate_helper {
+    (Completer for $helper:ty => $field:tt : $ty:ty) => {
+        impl $crate::completion::Completer for $helper {
+            type Candidate = <$ty as $crate::completion::Completer>::Candidate;
+
+            fn complete(
+                &self,
+                line: &str,
+                pos: usize,
+            ) -> $crate::Result<(usize, Vec<Self::Candidate>)> {
+                $crate::completion::Completer::complete(&self.$field, line, pos)
+            }
+
+            fn complete_with_context(
+                &self,
+                line: &str,
+                pos: usize,
+                ctx: &$crate::HelperContext,
+            ) -> $crate::Result<(usize, Vec<Self::Candidate>)> {
+                $crate::completion::Completer::complete_with_context(&self.$field, line, pos, ctx)
+            }
+
+            fn complete_stream(
+                &self,
+                line: &str,
+                pos: usize,
+                ctx: &$crate::HelperContext,
+            ) -> Option<(usize, $crate::completion::CandidateStream<Self::Candidate>)> {
+                $crate::completion::Completer::complete_stream(&self.$field, line, pos, ctx)
+            }
+
+            fn update(
+                &self,
+                line: &mut $crate::line_buffer::LineBuffer,
+                start: usize,
+                elected: &str,
+            ) {
+                $crate::completion::Completer::update(&self.$field, line, start, elected)
+            }
+
+            fn candidate_doc(&self, candidate: &Self::Candidate) -> Option<String> {
+                $crate::completion::Completer::candidate_doc(&self.$field, candidate)
+            }
+        }
+    };
+    (Hinter for $helper:ty => $field:tt) => {
+        impl $crate::hint::Hinter for $helper {
+            fn hint(&self, line: &str, pos: usize) -> Option<String> {
+                $crate::hint::Hinter::hint(&self.$field, line, pos)
+            }
+
+            fn hint_with_context(
+                &self,
+                line: &str,
+                pos: usize,
+                ctx: &$crate::HelperContext,
+            ) -> Option<String> {
+                $crate::hint::Hinter::hint_with_context(&self.$field, line, pos, ctx)
+            }
+        }
+    };
+    (Highlighter for $helper:ty => $field:tt) => {
+        impl $crate::highlight::Highlighter for $helper {
+            fn highlight<'l>(&self, line: &'l str, pos: usize) -> ::std::borrow::Cow<'l, str> {
+                $crate::highlight::Highlighter::highlight(&self.$field, line, pos)
+            }
+
+            fn highlight_prompt<'p>(&self, prompt: &'p str) -> ::std::borrow::Cow<'p, str> {
+                $crate::highlight::Highlighter::highlight_prompt(&self.$field, prompt)
+            }
+
+            fn highlight_hint<'h>(&self, hint: &'h str) -> ::std::borrow::Cow<'h, str> {
+                $crate::highlight::Highlighter::highlight_hint(&self.$field, hint)
+            }
+
+            fn highlight_candidate<'c>(
+                &self,
+                candidate: &'c str,
+                completion: $crate::CompletionType,
+            ) -> ::std::borrow::Cow<'c, str> {
+                $crate::highlight::Highlighter::highlight_candidate(
+                    &self.$field,
+                    candidate,
+                    completion,
+                )
+            }
+
+            fn highlight_char(&self, grapheme: &str) -> bool {
+                $crate::highlight::Highlighter::highlight_char(&self.$field, grapheme)
+            }
+        }
+    };
+}
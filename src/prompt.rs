@@ -0,0 +1,184 @@
+//! Prompt templates (like `"{cwd} {git_branch} ❯ "`) whose variables are
+//! replaced by the values of application providers.
+//!
+//! The template is rendered again on each repaint of the line (like after
+//! `rustyline::force_refresh`): only the variables used are evaluated, and the
+//! value of a slow provider can be kept for some time with
+//! `PromptTemplate::cached_provider`.
+use std::cell::RefCell;
+use std::time::{Duration, Instant};
+
+enum Segment {
+    Text(String),
+    Var(String),
+}
+
+struct Provider {
+    name: String,
+    value: Box<Fn() -> String + Send>,
+    max_age: Option<Duration>,
+    cache: RefCell<Option<(Instant, String)>>,
+}
+
+impl Provider {
+    fn value(&self) -> String {
+        let max_age = match self.max_age {
+            Some(max_age) => max_age,
+            None => return (self.value)(),
+        };
+        let mut cache = self.cache.borrow_mut();
+        if let Some((time, ref value)) = *cache {
+            if time.elapsed() < max_age {
+                return value.clone();
+            }
+        }
+        let value = (self.value)();
+        *cache = Some((Instant::now(), value.clone()));
+        value
+    }
+}
+
+/// Prompt with `{name}` variables (`{{` and `}}` for literal braces), used
+/// with `Editor::readline_template`.
+///
+/// An unknown variable is displayed as is.
+///
+/// ```
+/// use rustyline::prompt::PromptTemplate;
+/// use std::time::Duration;
+/// let template = PromptTemplate::new("{user}@{branch} ❯ ")
+///     .provider("user", || "me".to_owned())
+///     .cached_provider("branch", Duration::from_secs(2), || "master".to_owned());
+/// assert_eq!("me@master ❯ ", template.render());
+/// ```
+pub struct PromptTemplate {
+    segments: Vec<Segment>,
+    providers: Vec<Provider>,
+}
+
+impl PromptTemplate {
+    pub fn new(template: &str) -> PromptTemplate {
+        PromptTemplate {
+            segments: parse(template),
+            providers: Vec::new(),
+        }
+    }
+
+    /// Register the provider of the `name` variable, called on each
+    /// repaint.
+    pub fn provider<F>(self, name: &str, value: F) -> PromptTemplate
+    where
+        F: Fn() -> String + Send + 'static,
+    {
+        self.add(name, Box::new(value), None)
+    }
+
+    /// Register the provider of the `name` variable, whose value is kept for
+    /// `max_age` (like the git branch, slow to compute).
+    pub fn cached_provider<F>(self, name: &str, max_age: Duration, value: F) -> PromptTemplate
+    where
+        F: Fn() -> String + Send + 'static,
+    {
+        self.add(name, Box::new(value), Some(max_age))
+    }
+
+    fn add(
+        mut self,
+        name: &str,
+        value: Box<Fn() -> String + Send>,
+        max_age: Option<Duration>,
+    ) -> PromptTemplate {
+        self.providers.retain(|provider| provider.name != name);
+        self.providers.push(Provider {
+            name: name.to_owned(),
+            value,
+            max_age,
+            cache: RefCell::new(None),
+        });
+        self
+    }
+
+    /// Replace the variables by their current value (each provider is called
+    /// at most once).
+    pub fn render(&self) -> String {
+        let mut prompt = String::new();
+        let mut values: Vec<(&str, String)> = Vec::new();
+        for segment in &self.segments {
+            match *segment {
+                Segment::Text(ref text) => prompt.push_str(text),
+                Segment::Var(ref name) => {
+                    if let Some(pair) = values.iter().find(|&&(n, _)| n == name) {
+                        prompt.push_str(&pair.1);
+                        continue;
+                    }
+                    match self.providers.iter().find(|p| p.name == *name) {
+                        Some(provider) => {
+                            let value = provider.value();
+                            prompt.push_str(&value);
+                            values.push((name, value));
+                        }
+                        None => {
+                            prompt.push('{');
+                            prompt.push_str(name);
+                            prompt.push('}');
+                        }
+                    }
+                }
+            }
+        }
+        prompt
+    }
+}
+
+fn parse(template: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                if !text.is_empty() {
+                    segments.push(Segment::Text(text.split_off(0)));
+                }
+                segments.push(Segment::Var(name));
+            }
+            _ => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        segments.push(Segment::Text(text));
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::PromptTemplate;
+
+    #[test]
+    fn render() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let template = PromptTemplate::new("{{{a}}} {b}{a} {c}> ")
+            .provider("a", move || {
+                (counter.fetch_add(1, Ordering::SeqCst) + 1).to_string()
+            })
+            .cached_provider("b", Duration::from_secs(60), || "b".to_owned());
+        assert_eq!("{1} b1 {c}> ", template.render());
+        assert_eq!("{2} b2 {c}> ", template.render());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+}
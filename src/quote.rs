@@ -0,0 +1,649 @@
+//! Quoting utilities for helpers: split a line into words honoring quotes
+//! and escapes, find the word under the cursor and re-quote a candidate.
+use std::borrow::Cow::{self, Borrowed, Owned};
+use std::ops::Range;
+
+use memchr::memchr;
+
+use config::WordChars;
+
+/// Escape character inside double quotes
+pub static DOUBLE_QUOTES_ESCAPE_CHAR: Option<char> = Some('\\');
+
+// rl_basic_word_break_characters, rl_completer_word_break_characters
+#[cfg(unix)]
+pub static DEFAULT_BREAK_CHARS: [u8; 18] = [
+    b' ', b'\t', b'\n', b'"', b'\\', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&',
+    b'{', b'(', b'\0',
+];
+#[cfg(unix)]
+pub static ESCAPE_CHAR: Option<char> = Some('\\');
+// Remove \ to make file completion works on windows
+#[cfg(windows)]
+pub static DEFAULT_BREAK_CHARS: [u8; 17] = [
+    b' ', b'\t', b'\n', b'"', b'\'', b'`', b'@', b'$', b'>', b'<', b'=', b';', b'|', b'&', b'{',
+    b'(', b'\0',
+];
+#[cfg(windows)]
+pub static ESCAPE_CHAR: Option<char> = None;
+
+// In double quotes, not all break_chars need to be escaped
+// https://www.gnu.org/software/bash/manual/html_node/Double-Quotes.html
+#[cfg(unix)]
+pub static DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 4] = [b'"', b'$', b'\\', b'`'];
+#[cfg(windows)]
+pub static DOUBLE_QUOTES_SPECIAL_CHARS: [u8; 1] = [b'"']; // TODO Validate: only '"' ?
+
+/// Quote surrounding a word
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Quote {
+    Double,
+    Single,
+    None,
+}
+
+/// Quoting and escaping rules used to find the word under the cursor and to
+/// quote a candidate: POSIX shell-like by default (cmd-like on windows).
+///
+/// ```
+/// use rustyline::quote::QuoteRules;
+/// // PowerShell-like: backtick escape in double quotes, literal single quotes
+/// let rules = QuoteRules::new()
+///     .escape_char(None)
+///     .double_quotes_escape(Some('`'), b"\"`$")
+///     .needs_quoting(|s| s.contains(' '));
+/// ```
+#[derive(Clone, Copy)]
+pub struct QuoteRules {
+    break_chars: &'static [u8],
+    escape_char: Option<char>,
+    double_quote: Option<char>,
+    single_quote: Option<char>,
+    double_quotes_escape_char: Option<char>,
+    double_quotes_special_chars: &'static [u8],
+    needs_quoting: Option<fn(&str) -> bool>,
+    word_chars: WordChars,
+}
+
+impl Default for QuoteRules {
+    fn default() -> QuoteRules {
+        QuoteRules {
+            break_chars: &DEFAULT_BREAK_CHARS,
+            escape_char: ESCAPE_CHAR,
+            double_quote: Some('"'),
+            single_quote: if cfg!(windows) { None } else { Some('\'') },
+            double_quotes_escape_char: DOUBLE_QUOTES_ESCAPE_CHAR,
+            double_quotes_special_chars: &DOUBLE_QUOTES_SPECIAL_CHARS,
+            needs_quoting: None,
+            word_chars: WordChars::default(),
+        }
+    }
+}
+
+impl QuoteRules {
+    pub fn new() -> QuoteRules {
+        Self::default()
+    }
+
+    /// Characters which end a word (and must be escaped in candidates).
+    pub fn break_chars(mut self, break_chars: &'static [u8]) -> QuoteRules {
+        self.break_chars = break_chars;
+        self
+    }
+
+    /// Escape character outside quotes (`None` when there is none).
+    pub fn escape_char(mut self, esc_char: Option<char>) -> QuoteRules {
+        self.escape_char = esc_char;
+        self
+    }
+
+    /// Characters opening a `Quote::Double` (with escapes) and a
+    /// `Quote::Single` (without escape) string.
+    pub fn quotes(mut self, double_quote: Option<char>, single_quote: Option<char>) -> QuoteRules {
+        self.double_quote = double_quote;
+        self.single_quote = single_quote;
+        self
+    }
+
+    /// Escape character inside double quotes and the characters which must
+    /// be escaped there.
+    pub fn double_quotes_escape(
+        mut self,
+        esc_char: Option<char>,
+        special_chars: &'static [u8],
+    ) -> QuoteRules {
+        self.double_quotes_escape_char = esc_char;
+        self.double_quotes_special_chars = special_chars;
+        self
+    }
+
+    /// Tell if an unquoted candidate must be double quoted instead of
+    /// escaped.
+    pub fn needs_quoting(mut self, predicate: fn(&str) -> bool) -> QuoteRules {
+        self.needs_quoting = Some(predicate);
+        self
+    }
+
+    /// Break characters which are word constituents and don't end a word
+    /// (`Config::word_chars`).
+    pub fn word_chars(mut self, word_chars: WordChars) -> QuoteRules {
+        self.word_chars = word_chars;
+        self
+    }
+
+    /// Try to find an unclosed quote in `s`.
+    pub fn find_unclosed_quote(&self, s: &str) -> Option<(usize, Quote)> {
+        scan_unclosed_quote(
+            s,
+            self.double_quote,
+            self.single_quote,
+            self.escape_char,
+            self.double_quotes_escape_char,
+        )
+    }
+
+    /// Same as `word_at` but with these rules.
+    pub fn word_at<'l>(&self, line: &'l str, pos: usize) -> (usize, Cow<'l, str>, Quote) {
+        if let Some((idx, quote)) = self.find_unclosed_quote(&line[..pos]) {
+            let start = idx + self.quote_char(quote).map_or(1, |c| c.len_utf8());
+            if quote == Quote::Double {
+                (
+                    start,
+                    unescape(&line[start..pos], self.double_quotes_escape_char),
+                    quote,
+                )
+            } else {
+                (start, Borrowed(&line[start..pos]), quote)
+            }
+        } else {
+            let (start, word) = extract_word_with(
+                line,
+                pos,
+                self.escape_char,
+                self.break_chars,
+                self.word_chars,
+            );
+            (start, unescape(word, self.escape_char), Quote::None)
+        }
+    }
+
+    /// Same as `requote` but with these rules.
+    pub fn requote(&self, candidate: String, quote: Quote) -> String {
+        match quote {
+            Quote::Double => escape(
+                candidate,
+                self.double_quotes_escape_char,
+                self.double_quotes_special_chars,
+                quote,
+            ),
+            Quote::Single => candidate,
+            Quote::None => match (self.needs_quoting, self.double_quote) {
+                (Some(needs_quoting), Some(double_quote)) if needs_quoting(&candidate) => {
+                    let mut quoted = String::with_capacity(candidate.len() + 1);
+                    quoted.push(double_quote);
+                    quoted.push_str(&escape(
+                        candidate,
+                        self.double_quotes_escape_char,
+                        self.double_quotes_special_chars,
+                        Quote::Double,
+                    ));
+                    quoted
+                }
+                _ => escape(candidate, self.escape_char, self.break_chars, quote),
+            },
+        }
+    }
+
+    fn quote_char(&self, quote: Quote) -> Option<char> {
+        match quote {
+            Quote::Double => self.double_quote,
+            Quote::Single => self.single_quote,
+            Quote::None => None,
+        }
+    }
+}
+
+/// Remove escape char
+pub fn unescape(input: &str, esc_char: Option<char>) -> Cow<str> {
+    if esc_char.is_none() {
+        return Borrowed(input);
+    }
+    let esc_char = esc_char.unwrap();
+    if !input.chars().any(|c| c == esc_char) {
+        return Borrowed(input);
+    }
+    let mut result = String::with_capacity(input.len());
+    let mut chars = input.chars();
+    while let Some(ch) = chars.next() {
+        if ch == esc_char {
+            if let Some(ch) = chars.next() {
+                if cfg!(windows) && ch != '"' {
+                    // TODO Validate: only '"' ?
+                    result.push(esc_char);
+                }
+                result.push(ch);
+            } else if cfg!(windows) {
+                result.push(ch);
+            }
+        } else {
+            result.push(ch);
+        }
+    }
+    Owned(result)
+}
+
+/// Escape any `break_chars` in `input` string with `esc_char`.
+/// For example, '/User Information' becomes '/User\ Information'
+/// when space is a breaking char and '\\' the escape char.
+pub fn escape(
+    mut input: String,
+    esc_char: Option<char>,
+    break_chars: &[u8],
+    quote: Quote,
+) -> String {
+    if quote == Quote::Single {
+        return input; // no escape in single quotes
+    }
+    let n = input
+        .bytes()
+        .filter(|b| memchr(*b, break_chars).is_some())
+        .count();
+    if n == 0 {
+        return input; // no need to escape
+    }
+    if esc_char.is_none() {
+        if cfg!(windows) && quote == Quote::None {
+            input.insert(0, '"'); // force double quote
+            return input;
+        }
+        return input;
+    }
+    let esc_char = esc_char.unwrap();
+    let mut result = String::with_capacity(input.len() + n);
+
+    for c in input.chars() {
+        if c.is_ascii() && memchr(c as u8, break_chars).is_some() {
+            result.push(esc_char);
+        }
+        result.push(c);
+    }
+    result
+}
+
+/// Given a `line` and a cursor `pos`ition,
+/// try to find backward the start of a word.
+/// Return (0, `line[..pos]`) if no break char has been found.
+/// Return the word and its start position (idx, `line[idx..pos]`) otherwise.
+pub fn extract_word<'l>(
+    line: &'l str,
+    pos: usize,
+    esc_char: Option<char>,
+    break_chars: &[u8],
+) -> (usize, &'l str) {
+    extract_word_with(line, pos, esc_char, break_chars, WordChars::default())
+}
+
+/// Same as `extract_word` but the break chars in `word_chars` are considered
+/// part of the word.
+pub fn extract_word_with<'l>(
+    line: &'l str,
+    pos: usize,
+    esc_char: Option<char>,
+    break_chars: &[u8],
+    word_chars: WordChars,
+) -> (usize, &'l str) {
+    let line = &line[..pos];
+    if line.is_empty() {
+        return (0, line);
+    }
+    let mut start = None;
+    for (i, c) in line.char_indices().rev() {
+        if let (Some(esc_char), Some(_)) = (esc_char, start) {
+            if esc_char == c {
+                // escaped break char
+                start = None;
+                continue;
+            } else {
+                break;
+            }
+        }
+        if c.is_ascii() && memchr(c as u8, break_chars).is_some() && !word_chars.contains(c) {
+            start = Some(i + c.len_utf8());
+            if esc_char.is_none() {
+                break;
+            } // else maybe escaped...
+        }
+    }
+
+    match start {
+        Some(start) => (start, &line[start..]),
+        None => (0, line),
+    }
+}
+
+/// Given a `line` and a cursor `pos`ition, find the word under the cursor
+/// (honoring quotes and escapes).
+/// Return its start position (just after the unclosed quote if any), its
+/// unescaped text up to `pos` and the quote it is in.
+pub fn word_at<'l>(line: &'l str, pos: usize, break_chars: &[u8]) -> (usize, Cow<'l, str>, Quote) {
+    if let Some((idx, quote)) = find_unclosed_quote(&line[..pos]) {
+        let start = idx + 1;
+        if quote == Quote::Double {
+            (
+                start,
+                unescape(&line[start..pos], DOUBLE_QUOTES_ESCAPE_CHAR),
+                quote,
+            )
+        } else {
+            (start, Borrowed(&line[start..pos]), quote)
+        }
+    } else {
+        let (start, word) = extract_word(line, pos, ESCAPE_CHAR, break_chars);
+        (start, unescape(word, ESCAPE_CHAR), Quote::None)
+    }
+}
+
+/// Quote or escape `candidate` so that it can replace a word found by
+/// `word_at` in the `quote` context.
+pub fn requote(candidate: String, break_chars: &[u8], quote: Quote) -> String {
+    match quote {
+        Quote::Double => escape(
+            candidate,
+            DOUBLE_QUOTES_ESCAPE_CHAR,
+            &DOUBLE_QUOTES_SPECIAL_CHARS,
+            quote,
+        ),
+        Quote::Single => candidate,
+        Quote::None => escape(candidate, ESCAPE_CHAR, break_chars, quote),
+    }
+}
+
+/// Split `line` into words separated by unquoted whitespaces, like a shell.
+/// Return the start position and the unquoted text of each word.
+pub fn split_words(line: &str) -> Vec<(usize, String)> {
+    split_spans(line)
+        .into_iter()
+        .map(|(span, text)| (span.start, text))
+        .collect()
+}
+
+/// Words of a line and the one under the cursor (found by `words_at`), so
+/// that the completer of a subcommand-style CLI knows which argument is
+/// completed.
+#[derive(Debug, PartialEq)]
+pub struct LineWords {
+    /// Byte span (quotes included) and unquoted text of each word
+    pub words: Vec<(Range<usize>, String)>,
+    /// Index of the word under the cursor (or of the new word started by the
+    /// cursor, which is inserted before the following ones)
+    pub index: usize,
+    /// Byte span of the word under the cursor (empty when the cursor starts
+    /// a new word)
+    pub span: Range<usize>,
+}
+
+impl LineWords {
+    /// Unquoted text of the word under the cursor (`None` when the cursor
+    /// starts a new word)
+    pub fn current(&self) -> Option<&str> {
+        self.words
+            .get(self.index)
+            .filter(|word| word.0 == self.span)
+            .map(|word| word.1.as_str())
+    }
+}
+
+/// Split `line` like `split_words` and find the word under the cursor
+/// `pos`ition: the one containing or ending at `pos`.
+///
+/// ("git commit -", 12) => words: ["git", "commit", "-"], index: 2, span:
+/// 11..12
+pub fn words_at(line: &str, pos: usize) -> LineWords {
+    let words = split_spans(line);
+    let (index, span) = match words
+        .iter()
+        .position(|word| word.0.start <= pos && pos <= word.0.end)
+    {
+        Some(index) => (index, words[index].0.clone()),
+        None => (
+            words.iter().filter(|word| word.0.end < pos).count(),
+            pos..pos,
+        ),
+    };
+    LineWords { words, index, span }
+}
+
+fn split_spans(line: &str) -> Vec<(Range<usize>, String)> {
+    let mut words = Vec::new();
+    let mut word: Option<(Range<usize>, String)> = None;
+    let mut mode = ScanMode::Normal;
+    for (index, c) in line.char_indices() {
+        if mode == ScanMode::Normal && c.is_whitespace() {
+            if let Some(word) = word.take() {
+                words.push(word);
+            }
+            continue;
+        }
+        let word = word.get_or_insert_with(|| (index..index, String::new()));
+        word.0.end = index + c.len_utf8();
+        let text = &mut word.1;
+        match mode {
+            ScanMode::DoubleQuote => {
+                if c == '"' {
+                    mode = ScanMode::Normal;
+                } else if c == '\\' {
+                    mode = ScanMode::EscapeInDoubleQuote;
+                } else {
+                    text.push(c);
+                }
+            }
+            ScanMode::Escape => {
+                text.push(c);
+                mode = ScanMode::Normal;
+            }
+            ScanMode::EscapeInDoubleQuote => {
+                if !c.is_ascii() || memchr(c as u8, &DOUBLE_QUOTES_SPECIAL_CHARS).is_none() {
+                    text.push('\\');
+                }
+                text.push(c);
+                mode = ScanMode::DoubleQuote;
+            }
+            ScanMode::Normal => {
+                if c == '"' {
+                    mode = ScanMode::DoubleQuote;
+                } else if c == '\\' && cfg!(not(windows)) {
+                    mode = ScanMode::Escape;
+                } else if c == '\'' && cfg!(not(windows)) {
+                    mode = ScanMode::SingleQuote;
+                } else {
+                    text.push(c);
+                }
+            }
+            ScanMode::SingleQuote => {
+                if c == '\'' {
+                    mode = ScanMode::Normal;
+                } else {
+                    text.push(c);
+                } // no escape in single quotes
+            }
+        }
+    }
+    if let Some(word) = word {
+        words.push(word);
+    }
+    words
+}
+
+#[derive(PartialEq)]
+enum ScanMode {
+    DoubleQuote,
+    Escape,
+    EscapeInDoubleQuote,
+    Normal,
+    SingleQuote,
+}
+
+/// try to find an unclosed single/double quote in `s`.
+/// Return `None` if no unclosed quote is found.
+/// Return the unclosed quote position and its kind otherwise.
+pub fn find_unclosed_quote(s: &str) -> Option<(usize, Quote)> {
+    QuoteRules::default().find_unclosed_quote(s)
+}
+
+fn scan_unclosed_quote(
+    s: &str,
+    double_quote: Option<char>,
+    single_quote: Option<char>,
+    esc_char: Option<char>,
+    double_quotes_esc_char: Option<char>,
+) -> Option<(usize, Quote)> {
+    let char_indices = s.char_indices();
+    let mut mode = ScanMode::Normal;
+    let mut quote_index = 0;
+    for (index, char) in char_indices {
+        let char = Some(char);
+        match mode {
+            ScanMode::DoubleQuote => {
+                if char == double_quote {
+                    mode = ScanMode::Normal;
+                } else if char == double_quotes_esc_char {
+                    // both windows and unix support escape in double quote
+                    mode = ScanMode::EscapeInDoubleQuote;
+                }
+            }
+            ScanMode::Escape => {
+                mode = ScanMode::Normal;
+            }
+            ScanMode::EscapeInDoubleQuote => {
+                mode = ScanMode::DoubleQuote;
+            }
+            ScanMode::Normal => {
+                if char == double_quote {
+                    mode = ScanMode::DoubleQuote;
+                    quote_index = index;
+                } else if char == esc_char {
+                    mode = ScanMode::Escape;
+                } else if char == single_quote {
+                    mode = ScanMode::SingleQuote;
+                    quote_index = index;
+                }
+            }
+            ScanMode::SingleQuote => {
+                if char == single_quote {
+                    mode = ScanMode::Normal;
+                } // no escape in single quotes
+            }
+        };
+    }
+    if ScanMode::DoubleQuote == mode || ScanMode::EscapeInDoubleQuote == mode {
+        return Some((quote_index, Quote::Double));
+    } else if ScanMode::SingleQuote == mode {
+        return Some((quote_index, Quote::Single));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Quote;
+
+    #[test]
+    pub fn word_at() {
+        let break_chars: &[u8] = &super::DEFAULT_BREAK_CHARS;
+        let line = "ls \"User Info";
+        assert_eq!(
+            (4, "User Info".into(), Quote::Double),
+            super::word_at(line, line.len(), break_chars)
+        );
+        let line = "ls /etc/pa";
+        assert_eq!(
+            (3, "/etc/pa".into(), Quote::None),
+            super::word_at(line, line.len(), break_chars)
+        );
+    }
+
+    #[test]
+    pub fn requote() {
+        let break_chars: &[u8] = &super::DEFAULT_BREAK_CHARS;
+        assert_eq!(
+            "User Information",
+            super::requote("User Information".to_owned(), break_chars, Quote::Single)
+        );
+        if cfg!(unix) {
+            assert_eq!(
+                "User\\ Information",
+                super::requote("User Information".to_owned(), break_chars, Quote::None)
+            );
+            assert_eq!(
+                "a\\$b c",
+                super::requote("a$b c".to_owned(), break_chars, Quote::Double)
+            );
+        }
+    }
+
+    #[test]
+    pub fn split_words() {
+        assert_eq!(
+            vec![
+                (0, "ls".to_owned()),
+                (4, "a b".to_owned()),
+                (10, "".to_owned())
+            ],
+            super::split_words("ls  \"a b\" \"\"")
+        );
+        if cfg!(unix) {
+            assert_eq!(
+                vec![(0, "a b".to_owned()), (5, "c\\d\"".to_owned())],
+                super::split_words("a\\ b 'c\\d'\\\"")
+            );
+        }
+    }
+
+    #[test]
+    pub fn words_at() {
+        let line = "git commit -";
+        let words = super::words_at(line, line.len());
+        assert_eq!(3, words.words.len());
+        assert_eq!(2, words.index);
+        assert_eq!(11..12, words.span);
+        assert_eq!(Some("-"), words.current());
+
+        let words = super::words_at("git  \"a b\" c", 7);
+        assert_eq!((5..10, "a b".to_owned()), words.words[1]);
+        assert_eq!(1, words.index);
+        assert_eq!(Some("a b"), words.current());
+
+        let words = super::words_at("git  commit", 4);
+        assert_eq!(1, words.index);
+        assert_eq!(2, words.words.len());
+        assert_eq!(4..4, words.span);
+        assert_eq!(None, words.current());
+        assert_eq!(0, super::words_at("", 0).index);
+    }
+
+    #[test]
+    pub fn rules() {
+        let rules = super::QuoteRules::new()
+            .escape_char(None)
+            .quotes(Some('"'), Some('\''))
+            .double_quotes_escape(Some('`'), b"\"`$")
+            .needs_quoting(|s| s.contains(' '));
+        let line = "ls \"a`\"b";
+        assert_eq!(
+            (4, "a\"b".into(), Quote::Double),
+            rules.word_at(line, line.len())
+        );
+        assert_eq!(
+            "\"My Docs",
+            rules.requote("My Docs".to_owned(), Quote::None)
+        );
+        assert_eq!("a`$b", rules.requote("a$b".to_owned(), Quote::Double));
+
+        let sql = super::QuoteRules::new().quotes(None, Some('\''));
+        assert_eq!(
+            Some((7, Quote::Single)),
+            sql.find_unclosed_quote("select 'ab")
+        );
+        assert_eq!(None, sql.find_unclosed_quote("select \"ab"));
+    }
+}
@@ -0,0 +1,424 @@
+//! Minimal regular expressions, used by the incremental search.
+//!
+//! Supported syntax: literals, `.`, `[...]` / `[^...]` classes, `\d`, `\w`,
+//! `\s`, `^`, `$`, groups `(...)`, alternation `|` and the greedy `*`, `+`,
+//! `?` quantifiers.
+use std::iter::Peekable;
+use std::mem;
+use std::ops::Range;
+use std::result;
+use std::str::Chars;
+
+enum Node {
+    Char(char),
+    Any,
+    /// negated, ranges
+    Class(bool, Vec<(char, char)>),
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    /// node, min, max
+    Repeat(Box<Node>, usize, Option<usize>),
+}
+
+/// Compiled pattern
+pub struct Regex {
+    program: Vec<Inst>,
+}
+
+impl Regex {
+    /// Compile `pattern`, returning a short description of the error when
+    /// it is invalid.
+    pub fn new(pattern: &str) -> result::Result<Regex, String> {
+        let mut parser = Parser {
+            chars: pattern.chars().peekable(),
+        };
+        let alternatives = try!(parser.alternatives());
+        if parser.chars.next().is_some() {
+            return Err("unmatched )".to_owned());
+        }
+        let mut program = Vec::new();
+        compile_alternatives(&mut program, &alternatives);
+        program.push(Inst::Match);
+        Ok(Regex { program })
+    }
+
+    /// Leftmost match in `text`
+    ///
+    /// The threads of the program are run in lockstep over the chars (a
+    /// Pike VM), so the search time is linear in the length of `text` (no
+    /// backtracking).
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        let mut threads = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut found = None;
+        let positions = text
+            .char_indices()
+            .map(|(i, c)| (i, Some(c)))
+            .chain(Some((text.len(), None)));
+        for (step, (pos, c)) in positions.enumerate() {
+            if found.is_none() {
+                // lowest priority: a match starting further left wins
+                threads.add(&self.program, step, 0, pos, pos, text.len());
+            }
+            if threads.list.is_empty() {
+                if found.is_some() {
+                    break;
+                }
+                continue;
+            }
+            for &(pc, start) in &threads.list {
+                let accepted = match self.program[pc] {
+                    Inst::Match => {
+                        found = Some(start..pos);
+                        // the remaining threads have a lower priority
+                        break;
+                    }
+                    Inst::Char(expected) => c == Some(expected),
+                    Inst::Any => c.is_some(),
+                    Inst::Class(negated, ref ranges) => match c {
+                        Some(c) => ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != negated,
+                        None => false,
+                    },
+                    _ => unreachable!(),
+                };
+                if let (true, Some(c)) = (accepted, c) {
+                    let after = pos + c.len_utf8();
+                    next.add(&self.program, step + 1, pc + 1, start, after, text.len());
+                }
+            }
+            mem::swap(&mut threads, &mut next);
+            next.list.clear();
+        }
+        found
+    }
+
+    /// Tell if there is a match in `text`.
+    pub fn is_match(&self, text: &str) -> bool {
+        self.find(text).is_some()
+    }
+}
+
+/// Instruction of a compiled pattern
+enum Inst {
+    Char(char),
+    Any,
+    /// negated, ranges
+    Class(bool, Vec<(char, char)>),
+    Start,
+    End,
+    /// Go on at both targets (the first one has the priority).
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+fn compile_alternatives(program: &mut Vec<Inst>, alternatives: &[Vec<Node>]) {
+    let mut jumps = Vec::new();
+    for (i, sequence) in alternatives.iter().enumerate() {
+        if i + 1 == alternatives.len() {
+            compile_sequence(program, sequence);
+            break;
+        }
+        let split = program.len();
+        program.push(Inst::Split(0, 0));
+        compile_sequence(program, sequence);
+        jumps.push(program.len());
+        program.push(Inst::Jump(0));
+        program[split] = Inst::Split(split + 1, program.len());
+    }
+    let end = program.len();
+    for jump in jumps {
+        program[jump] = Inst::Jump(end);
+    }
+}
+
+fn compile_sequence(program: &mut Vec<Inst>, sequence: &[Node]) {
+    for node in sequence {
+        compile_node(program, node);
+    }
+}
+
+fn compile_node(program: &mut Vec<Inst>, node: &Node) {
+    match *node {
+        Node::Char(c) => program.push(Inst::Char(c)),
+        Node::Any => program.push(Inst::Any),
+        Node::Class(negated, ref ranges) => program.push(Inst::Class(negated, ranges.clone())),
+        Node::Start => program.push(Inst::Start),
+        Node::End => program.push(Inst::End),
+        Node::Group(ref alternatives) => compile_alternatives(program, alternatives),
+        Node::Repeat(ref node, min, max) => {
+            // the last mandatory repetition loops back when there is no maximum
+            let copies = match max {
+                None if min > 0 => min - 1,
+                _ => min,
+            };
+            for _ in 0..copies {
+                compile_node(program, node);
+            }
+            match max {
+                // greedy: one more repetition has the priority
+                None if min > 0 => {
+                    let body = program.len();
+                    compile_node(program, node);
+                    let split = program.len();
+                    program.push(Inst::Split(body, split + 1));
+                }
+                None => {
+                    let split = program.len();
+                    program.push(Inst::Split(0, 0));
+                    compile_node(program, node);
+                    program.push(Inst::Jump(split));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in min..max {
+                        splits.push(program.len());
+                        program.push(Inst::Split(0, 0));
+                        compile_node(program, node);
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Inst::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Threads waiting for the char at the same position, by priority
+struct Threads {
+    /// Program counter and start of the match
+    list: Vec<(usize, usize)>,
+    /// Step (plus one) at which each instruction was last added (to add it
+    /// only once: an empty repetition cannot loop)
+    added: Vec<usize>,
+    stack: Vec<usize>,
+}
+
+impl Threads {
+    fn new(len: usize) -> Threads {
+        Threads {
+            list: Vec::new(),
+            added: vec![0; len],
+            stack: Vec::new(),
+        }
+    }
+
+    /// Follow the instructions which do not consume a char from `pc`.
+    fn add(
+        &mut self,
+        program: &[Inst],
+        step: usize,
+        pc: usize,
+        start: usize,
+        pos: usize,
+        len: usize,
+    ) {
+        self.stack.push(pc);
+        while let Some(pc) = self.stack.pop() {
+            if self.added[pc] == step + 1 {
+                continue;
+            }
+            self.added[pc] = step + 1;
+            match program[pc] {
+                Inst::Split(first, second) => {
+                    self.stack.push(second);
+                    self.stack.push(first);
+                }
+                Inst::Jump(target) => self.stack.push(target),
+                Inst::Start => {
+                    if pos == 0 {
+                        self.stack.push(pc + 1);
+                    }
+                }
+                Inst::End => {
+                    if pos == len {
+                        self.stack.push(pc + 1);
+                    }
+                }
+                _ => self.list.push((pc, start)),
+            }
+        }
+    }
+}
+
+struct Parser<'p> {
+    chars: Peekable<Chars<'p>>,
+}
+
+impl<'p> Parser<'p> {
+    fn alternatives(&mut self) -> result::Result<Vec<Vec<Node>>, String> {
+        let mut alternatives = vec![try!(self.sequence())];
+        while self.chars.peek() == Some(&'|') {
+            self.chars.next();
+            alternatives.push(try!(self.sequence()));
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> result::Result<Vec<Node>, String> {
+        let mut nodes = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let node = try!(self.atom());
+            let (min, max) = match self.chars.peek() {
+                Some(&'*') => (0, None),
+                Some(&'+') => (1, None),
+                Some(&'?') => (0, Some(1)),
+                _ => {
+                    nodes.push(node);
+                    continue;
+                }
+            };
+            self.chars.next();
+            nodes.push(Node::Repeat(Box::new(node), min, max));
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> result::Result<Node, String> {
+        Ok(match self.chars.next() {
+            Some('(') => {
+                let alternatives = try!(self.alternatives());
+                if self.chars.next() != Some(')') {
+                    return Err("missing )".to_owned());
+                }
+                Node::Group(alternatives)
+            }
+            Some('[') => try!(self.class()),
+            Some('.') => Node::Any,
+            Some('^') => Node::Start,
+            Some('$') => Node::End,
+            Some('\\') => try!(self.escape()),
+            Some('*') | Some('+') | Some('?') => return Err("nothing to repeat".to_owned()),
+            Some(c) => Node::Char(c),
+            None => unreachable!(),
+        })
+    }
+
+    fn escape(&mut self) -> result::Result<Node, String> {
+        Ok(match self.chars.next() {
+            Some('d') => Node::Class(false, digits()),
+            Some('D') => Node::Class(true, digits()),
+            Some('w') => Node::Class(false, word_chars()),
+            Some('W') => Node::Class(true, word_chars()),
+            Some('s') => Node::Class(false, spaces()),
+            Some('S') => Node::Class(true, spaces()),
+            Some(c) => Node::Char(c),
+            None => return Err("trailing \\".to_owned()),
+        })
+    }
+
+    fn class(&mut self) -> result::Result<Node, String> {
+        let negated = self.chars.peek() == Some(&'^');
+        if negated {
+            self.chars.next();
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.chars.next() {
+                Some(']') if !first => break,
+                Some('\\') => match try!(self.escape()) {
+                    Node::Class(false, class) => {
+                        ranges.extend(class);
+                        first = false;
+                        continue;
+                    }
+                    Node::Char(c) => c,
+                    _ => return Err("negated class in [...]".to_owned()),
+                },
+                Some(c) => c,
+                None => return Err("missing ]".to_owned()),
+            };
+            first = false;
+            if self.chars.peek() == Some(&'-') {
+                self.chars.next();
+                match self.chars.peek().cloned() {
+                    Some(']') | None => {
+                        // literal '-'
+                        ranges.push((c, c));
+                        ranges.push(('-', '-'));
+                    }
+                    Some(end) => {
+                        self.chars.next();
+                        if end < c {
+                            return Err("invalid range".to_owned());
+                        }
+                        ranges.push((c, end));
+                    }
+                }
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        Ok(Node::Class(negated, ranges))
+    }
+}
+
+fn digits() -> Vec<(char, char)> {
+    vec![('0', '9')]
+}
+
+fn word_chars() -> Vec<(char, char)> {
+    vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')]
+}
+
+fn spaces() -> Vec<(char, char)> {
+    vec![(' ', ' '), ('\t', '\r')]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Regex;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::new(pattern)
+            .unwrap()
+            .find(text)
+            .map(|range| (range.start, range.end))
+    }
+
+    #[test]
+    fn matches() {
+        assert_eq!(Some((0, 8)), find("git (pull|fetch)", "git pull origin"));
+        assert_eq!(Some((5, 14)), find("git (pull|fetch)", "sudo git fetch"));
+        assert_eq!(None, find("git (pull|fetch)", "git push"));
+        assert_eq!(Some((0, 2)), find("^ab?", "aba"));
+        assert_eq!(None, find("^b", "ab"));
+        assert_eq!(Some((1, 2)), find("b$", "ab"));
+        assert_eq!(Some((2, 5)), find("\\d+", "ab123c"));
+        assert_eq!(Some((0, 3)), find("[a-c]*", "abcd"));
+        assert_eq!(Some((0, 1)), find("[^b-]", "a-b"));
+        assert_eq!(Some((0, 4)), find("a.*b", "axxbyy"));
+        assert_eq!(Some((0, 0)), find("(a*)+", "b"));
+        assert_eq!(Some((0, 3)), find("é.", "éa"));
+        assert_eq!(Some((1, 3)), find("b|ab", "xab"));
+        assert_eq!(Some((0, 1)), find("a|ab", "ab"));
+        assert_eq!(Some((0, 2)), find("ab?c?", "abd"));
+        assert_eq!(Some((1, 5)), find("(ab)+", "xababa"));
+    }
+
+    #[test]
+    fn no_backtracking() {
+        let line = format!("a{}", "x".repeat(20_000));
+        assert_eq!(None, find("a.*b", &line));
+        let line = "a".repeat(20_000);
+        assert_eq!(None, find("(a*)*b", &line));
+        assert_eq!(Some((0, 20_000)), find("(a|aa)*$", &line));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(Some("missing )".to_owned()), Regex::new("git (").err());
+        assert_eq!(Some("unmatched )".to_owned()), Regex::new("a)").err());
+        assert_eq!(Some("missing ]".to_owned()), Regex::new("[a").err());
+        assert_eq!(Some("nothing to repeat".to_owned()), Regex::new("*").err());
+        assert_eq!(Some("trailing \\".to_owned()), Regex::new("a\\").err());
+    }
+}
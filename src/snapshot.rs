@@ -0,0 +1,177 @@
+//! Snapshot of an in-progress edit, to resume it in another `readline` call
+//! (like when a SSH session reconnects to a server-side REPL).
+use std::fmt;
+
+use keymap::InputMode;
+use undo::Changeset;
+
+/// Edited line, cursor position, undo history, kill ring and vi mode of an
+/// interrupted edit.
+///
+/// It is kept by `Editor` when reading a key fails (see
+/// `Editor::take_snapshot`) and can be saved as text (with `to_string`) and
+/// parsed back (with `Snapshot::parse`).
+pub struct Snapshot {
+    pub(crate) line: String,
+    pub(crate) pos: usize,
+    pub(crate) input_mode: InputMode,
+    pub(crate) changes: Changeset,
+    pub(crate) kill_ring: (usize, Vec<String>),
+}
+
+impl Snapshot {
+    /// Edited line
+    pub fn line(&self) -> &str {
+        &self.line
+    }
+
+    /// Cursor position (in bytes)
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Parse the text produced by `to_string`.
+    pub fn parse(text: &str) -> Option<Snapshot> {
+        let mut snapshot = Snapshot {
+            line: String::new(),
+            pos: 0,
+            input_mode: InputMode::Insert,
+            changes: Changeset::new(),
+            kill_ring: (0, Vec::new()),
+        };
+        for record in text.lines() {
+            let (name, value) = match record.find('\t') {
+                Some(i) => (&record[..i], &record[i + 1..]),
+                None => (record, ""),
+            };
+            let valid = match name {
+                "line" => unescape(value).map(|line| snapshot.line = line).is_some(),
+                "pos" => value.parse().map(|pos| snapshot.pos = pos).is_ok(),
+                "mode" => parse_input_mode(value)
+                    .map(|mode| snapshot.input_mode = mode)
+                    .is_some(),
+                "kill" => value
+                    .parse()
+                    .map(|index| snapshot.kill_ring.0 = index)
+                    .is_ok(),
+                "slot" => unescape(value)
+                    .map(|slot| snapshot.kill_ring.1.push(slot))
+                    .is_some(),
+                "undo" | "redo" => snapshot.changes.push_record(record),
+                _ => false,
+            };
+            if !valid {
+                return None;
+            }
+        }
+        if snapshot.pos > snapshot.line.len() || !snapshot.line.is_char_boundary(snapshot.pos) {
+            return None;
+        }
+        if !snapshot.changes.is_valid_for(&snapshot.line) {
+            return None;
+        }
+        Some(snapshot)
+    }
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(writeln!(f, "line\t{}", escape(&self.line)));
+        try!(writeln!(f, "pos\t{}", self.pos));
+        let mode = match self.input_mode {
+            InputMode::Command => "command",
+            InputMode::Insert => "insert",
+            InputMode::Replace => "replace",
+        };
+        try!(writeln!(f, "mode\t{}", mode));
+        try!(writeln!(f, "kill\t{}", self.kill_ring.0));
+        for slot in &self.kill_ring.1 {
+            try!(writeln!(f, "slot\t{}", escape(slot)));
+        }
+        for record in self.changes.records() {
+            try!(writeln!(f, "{}", record));
+        }
+        Ok(())
+    }
+}
+
+fn parse_input_mode(name: &str) -> Option<InputMode> {
+    match name {
+        "command" => Some(InputMode::Command),
+        "insert" => Some(InputMode::Insert),
+        "replace" => Some(InputMode::Replace),
+        _ => None,
+    }
+}
+
+/// Escape the record separators (tab and new line) and `\`.
+pub(crate) fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn unescape(s: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            unescaped.push(match chars.next() {
+                Some('\\') => '\\',
+                Some('t') => '\t',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                _ => return None,
+            });
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Snapshot;
+    use keymap::InputMode;
+    use undo::Changeset;
+
+    #[test]
+    fn parse() {
+        let mut changes = Changeset::new();
+        changes.insert_str(0, "a\tb");
+        changes.begin();
+        changes.delete(1, "\tb");
+        changes.end();
+        changes.insert_str(1, "\nc\\");
+        let snapshot = Snapshot {
+            line: "a\nc\\".to_owned(),
+            pos: 2,
+            input_mode: InputMode::Command,
+            changes,
+            kill_ring: (1, vec!["x".to_owned(), "y\tz".to_owned()]),
+        };
+        let text = snapshot.to_string();
+        let parsed = Snapshot::parse(&text).unwrap();
+        assert_eq!(text, parsed.to_string());
+        assert_eq!("a\nc\\", parsed.line());
+        assert_eq!(2, parsed.pos());
+        assert!(Snapshot::parse("pos\t9\n").is_none());
+        assert!(Snapshot::parse("line\ta\\x\n").is_none());
+        assert!(Snapshot::parse("undo\tinsert\tx\ty\n").is_none());
+        // records not matching the line
+        assert!(Snapshot::parse("line\tab\nundo\tinsert\t1\tbc\n").is_none());
+        assert!(Snapshot::parse("line\tab\nredo\tdelete\t2\tc\n").is_none());
+        assert!(Snapshot::parse("line\t\u{e9}\nundo\tinsert\t1\tx\n").is_none());
+        assert!(Snapshot::parse("line\tab\nundo\tinsert\t1\tb\nundo\tdelete\t9\tc\n").is_none());
+        assert!(Snapshot::parse("line\tab\nundo\tinsert\t1\tb\n").is_some());
+    }
+}
@@ -399,3 +399,41 @@ fn ctrl__() {
         }
     }
 }
+
+#[test]
+fn shift_selection() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_cursor(
+            *mode,
+            ("Hello world", ""),
+            &[
+                KeyPress::ShiftLeft,
+                KeyPress::ShiftLeft,
+                KeyPress::ShiftLeft,
+                KeyPress::ShiftLeft,
+                KeyPress::ShiftLeft,
+                KeyPress::Backspace,
+                KeyPress::Enter,
+            ],
+            ("Hello ", ""),
+        );
+        assert_cursor(
+            *mode,
+            ("Hello", " world"),
+            &[KeyPress::ShiftEnd, KeyPress::Delete, KeyPress::Enter],
+            ("Hello", ""),
+        );
+        // any other command clears the selection
+        assert_cursor(
+            *mode,
+            ("Hello", ""),
+            &[
+                KeyPress::ShiftHome,
+                KeyPress::Right,
+                KeyPress::Backspace,
+                KeyPress::Enter,
+            ],
+            ("", "ello"),
+        );
+    }
+}
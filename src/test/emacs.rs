@@ -1,6 +1,7 @@
 //! Emacs specific key bindings
-use super::{assert_cursor, assert_history};
+use super::{assert_cursor, assert_history, init_editor};
 use config::EditMode;
+use keymap::{At, Cmd, Movement, Word};
 use keys::KeyPress;
 
 #[test]
@@ -198,6 +199,22 @@ fn ctrl_x_ctrl_u() {
     );
 }
 
+#[test]
+fn ctrl_x_u_ctrl_meta__() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hello, ", "world"),
+        &[
+            KeyPress::Ctrl('W'),
+            KeyPress::Ctrl('X'),
+            KeyPress::Char('u'),
+            KeyPress::Meta('\x1f'), // redo
+            KeyPress::Enter,
+        ],
+        ("", "world"),
+    );
+}
+
 #[test]
 fn meta_b() {
     assert_cursor(
@@ -357,6 +374,23 @@ fn meta_y() {
     );
 }
 
+#[test]
+fn meta_digit_ctrl_y() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hello, world", "!"),
+        &[
+            KeyPress::Ctrl('W'),
+            KeyPress::Left,
+            KeyPress::Ctrl('W'),
+            KeyPress::Meta('2'),
+            KeyPress::Ctrl('Y'),
+            KeyPress::Enter,
+        ],
+        ("world", " !"),
+    );
+}
+
 #[test]
 fn meta_backspace() {
     assert_cursor(
@@ -376,3 +410,98 @@ fn meta_digit() {
         ("hhh", ""),
     );
 }
+
+#[test]
+fn meta_w() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hello", ""),
+        &[
+            KeyPress::ShiftHome,
+            KeyPress::Meta('w'),
+            KeyPress::End,
+            KeyPress::Ctrl('Y'),
+            KeyPress::Enter,
+        ],
+        ("HelloHello", ""),
+    );
+    // in a new kill slot
+    assert_cursor(
+        EditMode::Emacs,
+        ("Hello", " world"),
+        &[
+            KeyPress::Ctrl('K'),
+            KeyPress::ShiftHome,
+            KeyPress::Meta('w'),
+            KeyPress::End,
+            KeyPress::Ctrl('Y'),
+            KeyPress::Enter,
+        ],
+        ("HelloHello", ""),
+    );
+}
+
+#[test]
+fn kill_whole_line_and_copy() {
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[
+            KeyPress::F(1),
+            KeyPress::F(2),
+            KeyPress::F(3), // appended to the previous copy
+            KeyPress::Ctrl('Y'),
+            KeyPress::Enter,
+        ],
+    );
+    editor.bind_sequence(
+        KeyPress::F(1),
+        Cmd::ViYankTo(Movement::BackwardWord(1, Word::Emacs)),
+    );
+    editor.bind_sequence(
+        KeyPress::F(2),
+        Cmd::ViYankTo(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+    );
+    editor.bind_sequence(KeyPress::F(3), Cmd::Kill(Movement::WholeLine));
+    let line = editor
+        .readline_with_initial("", ("Hello, ", "world!"))
+        .unwrap();
+    assert_eq!("worldHello, world!", line);
+
+    editor.term.keys.insert(4, KeyPress::Meta('Y'));
+    let line = editor
+        .readline_with_initial("", ("Hello, ", "world!"))
+        .unwrap();
+    assert_eq!("Hello, ", line);
+}
+
+#[test]
+fn ctrl_x_8() {
+    assert_cursor(
+        EditMode::Emacs,
+        ("a", "b"),
+        &[
+            KeyPress::Ctrl('X'),
+            KeyPress::Char('8'),
+            KeyPress::Char('e'),
+            KeyPress::Char('9'),
+            KeyPress::Char('x'), // ignored
+            KeyPress::Char('1'),
+            KeyPress::Backspace,
+            KeyPress::Enter,
+            KeyPress::Enter,
+        ],
+        ("a\u{e9}", "b"),
+    );
+    assert_cursor(
+        EditMode::Emacs,
+        ("a", "b"),
+        &[
+            KeyPress::Ctrl('X'),
+            KeyPress::Char('8'),
+            KeyPress::Char('1'),
+            KeyPress::Esc,
+            KeyPress::Enter,
+        ],
+        ("a", "b"),
+    );
+}
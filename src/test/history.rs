@@ -1,6 +1,7 @@
 //! History related commands tests
-use super::assert_history;
+use super::{assert_history, init_editor};
 use config::EditMode;
+use keymap::Cmd;
 use keys::KeyPress;
 
 #[test]
@@ -162,6 +163,105 @@ fn ctrl_s() {
     }
 }
 
+#[test]
+fn forward_search() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        // from the oldest entry
+        assert_history(
+            *mode,
+            &["cargo build", "rustc", "cargo test"],
+            &[
+                KeyPress::Ctrl('S'),
+                KeyPress::Char('c'),
+                KeyPress::Right, // just to assert cursor pos
+                KeyPress::Enter,
+            ],
+            ("c", "argo build"),
+        );
+        // from the browsed entry
+        assert_history(
+            *mode,
+            &["cargo build", "rustc", "cargo test"],
+            &[
+                KeyPress::Up,
+                KeyPress::Up,
+                KeyPress::Ctrl('S'),
+                KeyPress::Char('c'),
+                KeyPress::Char('a'),
+                KeyPress::Right, // just to assert cursor pos
+                KeyPress::Enter,
+            ],
+            ("c", "argo test"),
+        );
+    }
+}
+
+#[test]
+fn search_wraparound() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        assert_history(
+            *mode,
+            &["rustc", "cargo", "rustup"],
+            &[
+                KeyPress::Ctrl('R'),
+                KeyPress::Char('r'),
+                KeyPress::Ctrl('R'),
+                KeyPress::Ctrl('R'),
+                KeyPress::Ctrl('R'), // wrapped
+                KeyPress::Right,     // just to assert cursor pos
+                KeyPress::Enter,
+            ],
+            ("r", "ustup"),
+        );
+        assert_history(
+            *mode,
+            &["rustc", "cargo", "rustup"],
+            &[
+                KeyPress::Ctrl('S'),
+                KeyPress::Char('c'),
+                KeyPress::Ctrl('S'),
+                KeyPress::Ctrl('S'), // no match
+                KeyPress::Ctrl('S'), // wrapped
+                KeyPress::Right,     // just to assert cursor pos
+                KeyPress::Enter,
+            ],
+            ("rustc", ""),
+        );
+    }
+}
+
+#[test]
+fn regex_search() {
+    let entries = &["git pull", "git status", "git fetch origin", "ls"];
+    let mut keys = vec![KeyPress::Ctrl('R'), KeyPress::Meta('r')];
+    keys.extend("fetch|(pux".chars().map(KeyPress::Char));
+    keys.push(KeyPress::Ctrl('H'));
+    keys.extend("l)".chars().map(KeyPress::Char));
+    keys.push(KeyPress::Right); // just to assert cursor pos
+    keys.push(KeyPress::Enter);
+    assert_history(EditMode::Emacs, entries, &keys, ("git f", "etch origin"));
+    // repeated search
+    let len = keys.len();
+    keys.insert(len - 2, KeyPress::Ctrl('R'));
+    assert_history(EditMode::Emacs, entries, &keys, ("git p", "ull"));
+}
+
+#[test]
+fn meta_r() {
+    // revert-line outside of the search
+    assert_history(
+        EditMode::Emacs,
+        &["rustc", "cargo"],
+        &[
+            KeyPress::Up,
+            KeyPress::Backspace,
+            KeyPress::Meta('r'),
+            KeyPress::Enter,
+        ],
+        ("cargo", ""),
+    );
+}
+
 #[test]
 fn meta_lt() {
     assert_history(
@@ -204,3 +304,80 @@ fn meta_gt() {
         ("a", ""),
     );
 }
+
+#[test]
+fn ctrl_o() {
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[
+            KeyPress::Up,
+            KeyPress::Up,
+            KeyPress::Up,
+            KeyPress::Ctrl('O'),
+        ],
+    );
+    for entry in &["line1", "line2", "line3"] {
+        editor.history.add(*entry);
+    }
+    assert_eq!("line1", editor.readline("").unwrap());
+    editor.term.keys = vec![KeyPress::Ctrl('O')];
+    assert_eq!("line2", editor.readline("").unwrap());
+    editor.term.keys = vec![KeyPress::Enter];
+    assert_eq!("line3", editor.readline("").unwrap());
+    assert_eq!("", editor.readline("").unwrap());
+}
+
+#[test]
+fn meta_a() {
+    let mut editor = init_editor(EditMode::Emacs, &[KeyPress::Char('a'), KeyPress::Meta('a')]);
+    assert_eq!("a", editor.readline("").unwrap());
+    editor.term.keys = vec![KeyPress::Char('b'), KeyPress::Enter];
+    assert_eq!("ab", editor.readline("").unwrap());
+}
+
+#[test]
+fn meta_q() {
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[
+            KeyPress::Char('a'),
+            KeyPress::Meta('q'),
+            KeyPress::Char('b'),
+            KeyPress::Enter,
+        ],
+    );
+    assert_eq!("b", editor.readline("").unwrap());
+    editor.term.keys = vec![KeyPress::Char('c'), KeyPress::Enter];
+    assert_eq!("ac", editor.readline("").unwrap());
+    assert_eq!("c", editor.readline("").unwrap());
+}
+
+#[test]
+fn history_search_backward() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        let mut editor = init_editor(*mode, &[]);
+        editor.bind_sequence(KeyPress::Up, Cmd::HistorySearchBackward);
+        editor.bind_sequence(KeyPress::Down, Cmd::HistorySearchForward);
+        for entry in &["git log", "ls", "git status", "make"] {
+            editor.history.add(*entry);
+        }
+        editor.term.keys = "git ".chars().map(KeyPress::Char).collect();
+        editor
+            .term
+            .keys
+            .extend(&[KeyPress::Up, KeyPress::Up, KeyPress::Enter]);
+        assert_eq!("git log", editor.readline("").unwrap());
+        assert_eq!(4, editor.term.cursor);
+
+        editor.term.keys = "git ".chars().map(KeyPress::Char).collect();
+        editor.term.keys.extend(&[
+            KeyPress::Up,
+            KeyPress::Up,
+            KeyPress::Up, // no older entry
+            KeyPress::Down,
+            KeyPress::Enter,
+        ]);
+        assert_eq!("git status", editor.readline("").unwrap());
+        assert_eq!(4, editor.term.cursor);
+    }
+}
@@ -1,13 +1,29 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::process;
+use std::thread;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
 
-use super::{Editor, Result};
-use completion::Completer;
-use config::{Config, EditMode};
+use super::{Editor, Helper, HelperContext, Idle, ReadlineOptions, Result};
+use command::Context;
+use completion::{candidate_stream, CandidateSender, CandidateStream, Completer, Described};
+use config::{CompletionType, Config, EditMode};
+use correction::Corrector;
+use highlight::{Highlighter, Theme};
+use hint::Hinter;
 use edit::init_state;
+use error::{ErrorKind, ReadlineError};
 use keymap::{Cmd, InputState};
 use keys::KeyPress;
-use tty::Sink;
+use prompt::PromptTemplate;
+use snapshot::Snapshot;
+use tty::{RawReader, Sink};
+use validate::ValidationResult;
 
 mod common;
 mod emacs;
@@ -53,6 +69,144 @@ fn complete_line() {
     assert_eq!(4, s.line.pos());
 }
 
+struct DocCompleter(RefCell<Vec<String>>);
+impl Completer for DocCompleter {
+    type Candidate = String;
+
+    fn complete(&self, _line: &str, _pos: usize) -> Result<(usize, Vec<String>)> {
+        Ok((0, vec!["max".to_owned(), "min".to_owned()]))
+    }
+
+    fn candidate_doc(&self, candidate: &String) -> Option<String> {
+        self.0.borrow_mut().push(candidate.clone());
+        Some(format!("{}(x, y)", candidate))
+    }
+}
+
+#[test]
+fn candidate_doc() {
+    let mut out = Sink::new();
+    let mut s = init_state(&mut out, "m", 1);
+    let config = Config::default();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(HashMap::new())));
+    let keys = &[KeyPress::Tab, KeyPress::Enter];
+    let mut rdr = keys.iter();
+    let completer = DocCompleter(RefCell::new(Vec::new()));
+    let cmd = super::complete_line(
+        &mut rdr,
+        &mut s,
+        &mut input_state,
+        &completer,
+        None,
+        &config,
+    ).unwrap();
+    assert_eq!(Some(Cmd::AcceptLine), cmd);
+    assert_eq!("min", s.line.as_str());
+    assert_eq!(vec!["max".to_owned(), "min".to_owned()], *completer.0.borrow());
+    assert_eq!(None, s.menu);
+    assert_eq!("abc\nde", super::doc_pane("abcd\nde\nf\ng", 4, 6, false));
+}
+
+#[test]
+fn select_completion() {
+    let config = Config::builder()
+        .completion_type(CompletionType::Menu)
+        .build();
+    let select = |keys: &[KeyPress]| {
+        let mut out = Sink::new();
+        let mut s = init_state(&mut out, "m", 1);
+        let mut input_state = InputState::new(&config, Arc::new(RwLock::new(HashMap::new())));
+        let mut rdr = keys.iter();
+        let completer = DocCompleter(RefCell::new(Vec::new()));
+        let cmd = super::complete_line(
+            &mut rdr,
+            &mut s,
+            &mut input_state,
+            &completer,
+            None,
+            &config,
+        ).unwrap();
+        assert_eq!(None, s.menu);
+        (cmd, s.line.as_str().to_owned())
+    };
+    assert_eq!((None, "min".to_owned()), select(&[KeyPress::Tab, KeyPress::Enter]));
+    assert_eq!(
+        (None, "max".to_owned()),
+        select(&[KeyPress::Tab, KeyPress::BackTab, KeyPress::Enter])
+    );
+    assert_eq!((None, "m".to_owned()), select(&[KeyPress::Right, KeyPress::Esc]));
+    assert_eq!(
+        (Some(Cmd::SelfInsert(1, 'x')), "max".to_owned()),
+        select(&[KeyPress::Char('x')])
+    );
+
+    let candidates: Vec<String> = ["max", "min", "mod", "mul"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    assert_eq!(2, super::menu_columns(&candidates[..2], 12, false));
+    assert_eq!(
+        " max [min]",
+        super::selection_menu(&candidates[..2], &[], 1, 12, (2, 1), None, false)
+    );
+    assert_eq!(
+        "[max]\n min ",
+        super::selection_menu(&candidates[..2], &[], 0, 12, (1, 2), None, false)
+    );
+    // second page
+    let matches = vec![vec![], vec![], vec![0, 2], vec![0]];
+    let theme = Some(Theme::new().candidate_match("4"));
+    assert_eq!(
+        " \x1b[4mm\x1b[0mo\x1b[4md\x1b[0m \n \x1b[7mmul\x1b[0m ",
+        super::selection_menu(&candidates, &matches, 3, 12, (1, 2), theme, false)
+    );
+}
+
+#[test]
+fn fuzzy_completion() {
+    let mut out = Sink::new();
+    let mut s = init_state(&mut out, "mn", 2);
+    let config = Config::builder().fuzzy_completion(true).build();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(HashMap::new())));
+    let keys = &[KeyPress::Enter];
+    let mut rdr = keys.iter();
+    let completer = DocCompleter(RefCell::new(Vec::new()));
+    let cmd = super::complete_line(
+        &mut rdr,
+        &mut s,
+        &mut input_state,
+        &completer,
+        None,
+        &config,
+    ).unwrap();
+    assert_eq!(Some(Cmd::AcceptLine), cmd);
+    assert_eq!("min", s.line.as_str());
+    assert_eq!(vec!["min".to_owned()], *completer.0.borrow());
+}
+
+#[test]
+fn candidate_description() {
+    let described = |option: &str, description: &str| Described {
+        display: option.to_owned(),
+        replacement: option.to_owned(),
+        description: description.to_owned(),
+    };
+    let candidates = vec![described("-p", "port"), described("-P", "parallel")];
+    assert_eq!(1, super::menu_columns(&candidates, 80, false));
+    assert_eq!(
+        "[-p]           port\n -P        parallel",
+        super::selection_menu(&candidates, &[], 0, 20, (1, 5), None, false)
+    );
+    // truncated
+    let mut row = String::from(" -P ");
+    super::push_description(&mut row, 4, "parallel\nmore", 12, None, false);
+    assert_eq!(" -P   paral", row);
+    // no room
+    let mut row = String::from(" -P ");
+    super::push_description(&mut row, 4, "parallel", 6, Some("2"), false);
+    assert_eq!(" -P ", row);
+}
+
 // `keys`: keys to press
 // `expected_line`: line after enter key
 fn assert_line(mode: EditMode, keys: &[KeyPress], expected_line: &str) {
@@ -104,3 +258,939 @@ fn unknown_esc_key() {
         assert_line(*mode, &[KeyPress::UnknownEscSeq, KeyPress::Enter], "");
     }
 }
+
+#[test]
+fn readline_with_options() {
+    let config = Config::builder().auto_add_history(true).build();
+    let mut editor = Editor::<()>::with_config(config);
+    editor.history.add("line1");
+    editor
+        .term
+        .keys
+        .extend(&[KeyPress::Up, KeyPress::Char('a'), KeyPress::Enter]);
+    let options = ReadlineOptions::new().auto_add_history(false).history(false);
+    let line = editor.readline_with("", &options).unwrap();
+    assert_eq!("a", line);
+    assert_eq!(1, editor.history.len());
+    assert_eq!(config, editor.config);
+}
+
+struct ManyCompleter;
+impl Completer for ManyCompleter {
+    type Candidate = String;
+
+    fn complete(&self, _line: &str, _pos: usize) -> Result<(usize, Vec<String>)> {
+        Ok((0, (0..30).map(|i| format!("a{:02}", i)).collect()))
+    }
+}
+impl Hinter for ManyCompleter {
+    fn hint(&self, _line: &str, _pos: usize) -> Option<String> {
+        None
+    }
+}
+impl Highlighter for ManyCompleter {}
+impl Helper for ManyCompleter {}
+
+struct HistoryCompleter;
+impl Completer for HistoryCompleter {
+    type Candidate = String;
+
+    fn complete(&self, _line: &str, _pos: usize) -> Result<(usize, Vec<String>)> {
+        unreachable!()
+    }
+
+    fn complete_with_context(
+        &self,
+        line: &str,
+        _pos: usize,
+        ctx: &HelperContext,
+    ) -> Result<(usize, Vec<String>)> {
+        let history = ctx.history().unwrap();
+        assert_eq!(history.len(), ctx.history_index());
+        Ok((
+            0,
+            history
+                .iter()
+                .filter(|entry| entry.starts_with(line))
+                .cloned()
+                .collect(),
+        ))
+    }
+}
+impl Hinter for HistoryCompleter {
+    fn hint(&self, _line: &str, _pos: usize) -> Option<String> {
+        None
+    }
+}
+impl Highlighter for HistoryCompleter {}
+impl Helper for HistoryCompleter {}
+
+#[test]
+fn complete_from_history() {
+    let mut editor = Editor::new();
+    editor
+        .term
+        .keys
+        .extend(&[KeyPress::Char('g'), KeyPress::Tab, KeyPress::Enter]);
+    editor.add_history_entry("git pull");
+    editor.add_history_entry("ls");
+    editor.set_helper(Some(HistoryCompleter));
+    assert_eq!("git pull", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn page_completions() {
+    for &(paged, ref keys) in &[
+        (
+            true,
+            vec![KeyPress::Tab, KeyPress::Tab, KeyPress::Char('q'), KeyPress::Enter],
+        ),
+        (false, vec![KeyPress::Tab, KeyPress::Tab, KeyPress::Enter]),
+    ] {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .completion_display_width(Some(0))
+            .page_completions(paged)
+            .build();
+        let mut editor = Editor::with_config(config);
+        editor.set_helper(Some(ManyCompleter));
+        editor.term.keys.extend(keys);
+        let line = editor.readline_with_initial("", ("a", "")).unwrap();
+        assert_eq!("a", line);
+    }
+}
+
+#[test]
+fn max_refresh_rate() {
+    let config = Config::builder().max_refresh_rate(Some(1)).build();
+    let mut editor = Editor::<()>::with_config(config);
+    editor
+        .term
+        .keys
+        .extend(&[KeyPress::Char('a'), KeyPress::Char('b'), KeyPress::Enter]);
+    let line = editor.readline("").unwrap();
+    assert_eq!("ab", line);
+}
+
+#[test]
+fn large_paste() {
+    let mut keys = vec![KeyPress::Char('-'); 10_000];
+    keys.push(KeyPress::Enter);
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!(10_000, editor.readline("").unwrap().len());
+
+    // a single undo record
+    keys.insert(10_000, KeyPress::Ctrl('_'));
+    keys.insert(10_001, KeyPress::Char('b'));
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!("b", editor.readline("").unwrap());
+}
+
+#[test]
+fn large_bracketed_paste() {
+    let mut keys = vec![KeyPress::Char('a'), KeyPress::BracketedPasteStart];
+    keys.extend(vec![KeyPress::Char('-'); 100_000]);
+    keys.push(KeyPress::BracketedPasteEnd);
+    keys.push(KeyPress::Enter);
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!(100_001, editor.readline("").unwrap().len());
+
+    // a single undo record
+    keys.insert(keys.len() - 1, KeyPress::Ctrl('_'));
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!("a", editor.readline("").unwrap());
+}
+
+#[test]
+fn read_line_non_interactive() {
+    let mut input = Cursor::new("one\r\ntwo\n\nthree");
+    assert_eq!("one", super::read_line(&mut input).unwrap());
+    assert_eq!("two", super::read_line(&mut input).unwrap());
+    assert_eq!("", super::read_line(&mut input).unwrap());
+    assert_eq!("three", super::read_line(&mut input).unwrap());
+    assert_matches!(super::read_line(&mut input), Err(ReadlineError::Eof));
+}
+
+#[test]
+fn error_kind() {
+    let mut editor = init_editor(EditMode::Emacs, &[]);
+    let err = editor.readline("").unwrap_err();
+    assert_eq!(ErrorKind::Eof, err.kind());
+}
+
+struct Delegating(());
+delegate_helper!(Completer for Delegating => 0: ());
+delegate_helper!(Hinter for Delegating => 0);
+delegate_helper!(Highlighter for Delegating => 0);
+impl Helper for Delegating {}
+
+#[test]
+fn delegate_helper() {
+    let mut editor = Editor::new();
+    editor.set_helper(Some(Delegating(())));
+    editor.term.keys.extend(&[KeyPress::Tab, KeyPress::Enter]);
+    let line = editor.readline_with_initial("", ("a", "b")).unwrap();
+    assert_eq!("ab", line);
+}
+
+#[test]
+fn abbreviations() {
+    let expand = |keys: &[KeyPress]| {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.add_abbreviation("gco", "git checkout");
+        editor.add_abbreviation("fe", "for ${1} in ${2}; do ${3}; done");
+        editor.readline(">>").unwrap()
+    };
+    assert_eq!(
+        "git checkout main",
+        expand(&[
+            KeyPress::Char('g'),
+            KeyPress::Char('c'),
+            KeyPress::Char('o'),
+            KeyPress::Char(' '),
+            KeyPress::Char('m'),
+            KeyPress::Char('a'),
+            KeyPress::Char('i'),
+            KeyPress::Char('n'),
+            KeyPress::Enter,
+        ])
+    );
+    assert_eq!(
+        "git checkout",
+        expand(&[
+            KeyPress::Char('g'),
+            KeyPress::Char('c'),
+            KeyPress::Char('o'),
+            KeyPress::Enter,
+        ])
+    );
+    assert_eq!(
+        "xgco",
+        expand(&[
+            KeyPress::Char('x'),
+            KeyPress::Char('g'),
+            KeyPress::Char('c'),
+            KeyPress::Char('o'),
+            KeyPress::Enter,
+        ])
+    );
+    assert_eq!(
+        "gco ",
+        expand(&[
+            KeyPress::Char('g'),
+            KeyPress::Char('c'),
+            KeyPress::Char('o'),
+            KeyPress::Ctrl('V'),
+            KeyPress::Char(' '),
+            KeyPress::Enter,
+        ])
+    );
+    assert_eq!(
+        "for x in yy; do z; done",
+        expand(&[
+            KeyPress::Char('f'),
+            KeyPress::Char('e'),
+            KeyPress::Char(' '),
+            KeyPress::Char('x'),
+            KeyPress::Tab,
+            KeyPress::Char('y'),
+            KeyPress::Char('y'),
+            KeyPress::Tab,
+            KeyPress::Char('z'),
+            KeyPress::Enter,
+        ])
+    );
+}
+
+struct Commands;
+impl Corrector for Commands {
+    fn is_unknown(&self, word: &str) -> bool {
+        !self.words().iter().any(|w| w == word)
+    }
+
+    fn words(&self) -> Vec<String> {
+        vec!["git".to_owned(), "gist".to_owned()]
+    }
+}
+
+#[test]
+fn correction() {
+    let correct = |keys: &[KeyPress]| {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.set_corrector(Some(Box::new(Commands)));
+        editor.readline(">>").unwrap()
+    };
+    let gitt = [
+        KeyPress::Char('g'),
+        KeyPress::Char('i'),
+        KeyPress::Char('t'),
+        KeyPress::Char('t'),
+        KeyPress::Char(' '),
+        KeyPress::Char('x'),
+        KeyPress::Enter,
+    ];
+    // pick the second suggestion
+    let mut keys = gitt.to_vec();
+    keys.push(KeyPress::Char('2'));
+    assert_eq!("gist x", correct(&keys));
+    // keep the line unchanged
+    let mut keys = gitt.to_vec();
+    keys.push(KeyPress::Enter);
+    assert_eq!("gitt x", correct(&keys));
+    // go on editing
+    let mut keys = gitt.to_vec();
+    keys.extend_from_slice(&[KeyPress::Esc, KeyPress::Char('y'), KeyPress::Enter, KeyPress::Enter]);
+    assert_eq!("gitt xy", correct(&keys));
+    // known word
+    assert_eq!("git", correct(&[
+        KeyPress::Char('g'),
+        KeyPress::Char('i'),
+        KeyPress::Char('t'),
+        KeyPress::Enter,
+    ]));
+}
+
+#[test]
+fn completion_menu() {
+    let candidates = vec!["alpha".to_owned(), "beta".to_owned(), "gamma".to_owned()];
+    assert_eq!(
+        "alpha  beta\ngamma",
+        super::completion_menu(&candidates, 20, 5, false, false)
+    );
+    assert_eq!(
+        "alpha  beta",
+        super::completion_menu(&candidates, 20, 1, false, false)
+    );
+    assert_eq!("alp\nbet", super::completion_menu(&candidates, 4, 2, false, false));
+    assert_eq!(
+        "1 alpha  2 beta\n3 gamma",
+        super::completion_menu(&candidates, 20, 5, true, false)
+    );
+}
+
+#[test]
+fn nested_prompt() {
+    let register = |keys: &[KeyPress]| {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.add_command(
+            "insert-register",
+            Box::new(|ctx: &mut Context| {
+                if let Some(name) = try!(ctx.read_line("Register name: ")) {
+                    let line = format!("{}{}", ctx.line(), name);
+                    ctx.set_line(&line, line.len());
+                }
+                Ok(())
+            }),
+        );
+        editor.bind_sequence(KeyPress::Meta('r'), Cmd::Custom("insert-register".to_owned()));
+        editor.readline(">>").unwrap()
+    };
+    assert_eq!(
+        "axyb",
+        register(&[
+            KeyPress::Char('a'),
+            KeyPress::Meta('r'),
+            KeyPress::Char('x'),
+            KeyPress::Char('y'),
+            KeyPress::Enter,
+            KeyPress::Char('b'),
+            KeyPress::Enter,
+        ])
+    );
+    assert_eq!(
+        "ab",
+        register(&[
+            KeyPress::Char('a'),
+            KeyPress::Meta('r'),
+            KeyPress::Char('x'),
+            KeyPress::Esc,
+            KeyPress::Char('b'),
+            KeyPress::Enter,
+        ])
+    );
+}
+
+#[test]
+fn execute_named_command() {
+    let named = |line: &str, name: &str| {
+        let mut keys: Vec<KeyPress> = line.chars().map(KeyPress::Char).collect();
+        keys.push(KeyPress::Meta('x'));
+        keys.extend(name.chars().map(KeyPress::Char));
+        keys.push(KeyPress::Enter);
+        keys.push(KeyPress::Enter);
+        let mut editor = init_editor(EditMode::Emacs, &keys);
+        editor.add_command(
+            "hello",
+            Box::new(|ctx: &mut Context| {
+                ctx.insert("hi");
+                Ok(())
+            }),
+        );
+        assert_eq!(vec!["hello"], editor.command_names());
+        editor.readline(">>").unwrap()
+    };
+    assert_eq!("a hi", named("a ", "hello"));
+    assert_eq!("", named("abc", "kill-whole-line"));
+    // unknown command
+    assert_eq!("abc", named("abc", "nope"));
+}
+
+#[test]
+fn confirm() {
+    let mut editor = init_editor(EditMode::Emacs, &[KeyPress::Char('x'), KeyPress::Char('Y')]);
+    assert!(editor.confirm("really?").unwrap());
+    editor.term.keys = vec![KeyPress::Esc];
+    assert!(!editor.confirm("really?").unwrap());
+
+    editor.term.keys = vec![
+        KeyPress::Char('a'),
+        KeyPress::Ctrl('K'),
+        KeyPress::Char('n'),
+        KeyPress::Ctrl('K'),
+        KeyPress::Char('y'),
+        KeyPress::Enter,
+    ];
+    editor.add_command(
+        "clear",
+        Box::new(|ctx: &mut Context| {
+            if try!(ctx.confirm("clear the line?")) {
+                ctx.set_line("", 0);
+            }
+            Ok(())
+        }),
+    );
+    editor.bind_sequence(KeyPress::Ctrl('K'), Cmd::Custom("clear".to_owned()));
+    assert_eq!("", editor.readline(">>").unwrap());
+    editor.term.keys = vec![KeyPress::Char('a'), KeyPress::Ctrl('K'), KeyPress::Char('n'), KeyPress::Enter];
+    assert_eq!("a", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn paste_clipboard() {
+    let mut keys = vec![KeyPress::Char('a'), KeyPress::F(1)];
+    keys.extend("\x1b]52;c;cGFzdGU=\x07".chars().map(KeyPress::Char));
+    keys.push(KeyPress::Enter);
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(KeyPress::F(1), Cmd::PasteClipboard);
+    assert_eq!("apaste", editor.readline(">>").unwrap());
+    assert_eq!(6, editor.term.cursor - 2);
+    // single undo unit
+    editor.term.keys.insert(keys.len() - 1, KeyPress::Ctrl('_'));
+    assert_eq!("a", editor.readline(">>").unwrap());
+    // beyond MAX_LINE
+    let reply = format!("\x1b]52;c;{}\x07", "eHh4".repeat(2000));
+    editor.term.keys = vec![KeyPress::F(1)];
+    editor.term.keys.extend(reply.chars().map(KeyPress::Char));
+    editor.term.keys.push(KeyPress::Enter);
+    assert_eq!("x".repeat(6000), editor.readline(">>").unwrap());
+}
+
+#[test]
+fn paste() {
+    let keys = [
+        KeyPress::Char('a'),
+        KeyPress::BracketedPasteStart,
+        KeyPress::Char('b'),
+        KeyPress::Enter,
+        KeyPress::Char('c'),
+        KeyPress::BracketedPasteEnd,
+        KeyPress::Enter,
+    ];
+    let mut editor = init_editor(EditMode::Vi, &keys);
+    // the pasted new line does not accept the line
+    assert_eq!("ab\nc", editor.readline(">>").unwrap());
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!("ab\nc", editor.readline(">>").unwrap());
+    editor.set_paste_handler(Some(Box::new(|text: &str, _: &mut Context| {
+        Ok(Some(text.replace('\n', " ")))
+    })));
+    assert_eq!("ab c", editor.readline(">>").unwrap());
+    editor.set_paste_handler(Some(Box::new(|_: &str, _: &mut Context| Ok(None))));
+    assert_eq!("a", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn paste_beyond_max_line() {
+    let mut keys = vec![KeyPress::BracketedPasteStart];
+    keys.extend(vec![KeyPress::Char('-'); 5000]);
+    keys.push(KeyPress::BracketedPasteEnd);
+    keys.push(KeyPress::Enter);
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_eq!("-".repeat(5000), editor.readline(">>").unwrap());
+}
+
+#[test]
+fn picker() {
+    let pick = |keys: &[KeyPress]| {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.set_picker(Some(Box::new(|ctx: &mut Context| {
+            let emojis = ["\u{1f600}", "\u{1f389}"];
+            try!(ctx.set_menu(Some("1) \u{1f600} 2) \u{1f389}".to_owned())));
+            Ok(match try!(ctx.next_key()) {
+                KeyPress::Char(c) => c
+                    .to_digit(10)
+                    .and_then(|i| emojis.get(i as usize - 1))
+                    .map(|emoji| (*emoji).to_owned()),
+                _ => None,
+            })
+        })));
+        editor.bind_sequence(KeyPress::F(1), Cmd::Pick);
+        editor.readline(">>").unwrap()
+    };
+    assert_eq!(
+        "a\u{1f389}b",
+        pick(&[
+            KeyPress::Char('a'),
+            KeyPress::F(1),
+            KeyPress::Char('2'),
+            KeyPress::Char('b'),
+            KeyPress::Enter,
+        ])
+    );
+    assert_eq!(
+        "ab",
+        pick(&[
+            KeyPress::Char('a'),
+            KeyPress::F(1),
+            KeyPress::Esc,
+            KeyPress::Char('b'),
+            KeyPress::Enter,
+        ])
+    );
+}
+
+/// Keys separated by silences (`None`) lasting one idle tick
+struct Silences(VecDeque<Option<KeyPress>>);
+
+impl RawReader for Silences {
+    fn next_key(&mut self, _: bool) -> Result<KeyPress> {
+        Ok(self.0.pop_front().unwrap().unwrap())
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        unimplemented!();
+    }
+
+    fn wait_for_input(&mut self, _: Duration) -> bool {
+        match self.0.front() {
+            Some(&None) => {
+                self.0.pop_front();
+                false
+            }
+            _ => true,
+        }
+    }
+}
+
+/// Candidates streamed in two batches, the sender kept open when `hold`
+#[derive(Default)]
+struct StreamCompleter {
+    hold: bool,
+    sender: RefCell<Option<CandidateSender<String>>>,
+}
+impl Completer for StreamCompleter {
+    type Candidate = String;
+
+    fn complete(&self, _line: &str, _pos: usize) -> Result<(usize, Vec<String>)> {
+        unreachable!()
+    }
+
+    fn complete_stream(
+        &self,
+        line: &str,
+        _pos: usize,
+        _ctx: &HelperContext,
+    ) -> Option<(usize, CandidateStream<String>)> {
+        let (sender, stream) = candidate_stream();
+        assert!(sender.send(vec![line.to_owned() + "t"]));
+        assert!(sender.send(vec![line.to_owned() + "y"]));
+        if self.hold {
+            *self.sender.borrow_mut() = Some(sender);
+        }
+        Some((0, stream))
+    }
+}
+
+#[test]
+fn complete_stream() {
+    let mut out = Sink::new();
+    let mut s = init_state(&mut out, "rus", 3);
+    let config = Config::default();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(HashMap::new())));
+    let keys = &[KeyPress::Tab, KeyPress::Enter];
+    let mut rdr = keys.iter();
+    let completer = StreamCompleter::default();
+    let cmd = super::complete_line(
+        &mut rdr,
+        &mut s,
+        &mut input_state,
+        &completer,
+        None,
+        &config,
+    ).unwrap();
+    assert_eq!(Some(Cmd::AcceptLine), cmd);
+    assert_eq!("rusy", s.line.as_str());
+
+    // cancelled by a key
+    let mut s = init_state(&mut out, "rus", 3);
+    let mut rdr = Silences(vec![None, Some(KeyPress::Char('x'))].into_iter().collect());
+    let completer = StreamCompleter {
+        hold: true,
+        ..StreamCompleter::default()
+    };
+    let cmd = super::complete_line(
+        &mut rdr,
+        &mut s,
+        &mut input_state,
+        &completer,
+        None,
+        &config,
+    ).unwrap();
+    assert_eq!(None, cmd);
+    assert_eq!("rus", s.line.as_str());
+    assert_eq!(None, s.menu);
+    assert_eq!(1, rdr.0.len());
+    let sender = completer.sender.borrow_mut().take().unwrap();
+    assert!(sender.is_cancelled());
+    assert!(!sender.send(vec!["rusty".to_owned()]));
+}
+
+struct DelegatingStream(StreamCompleter);
+delegate_helper!(Completer for DelegatingStream => 0: StreamCompleter);
+
+#[test]
+fn delegate_complete_stream() {
+    let mut out = Sink::new();
+    let mut s = init_state(&mut out, "rus", 3);
+    let config = Config::default();
+    let mut input_state = InputState::new(&config, Arc::new(RwLock::new(HashMap::new())));
+    let keys = &[KeyPress::Tab, KeyPress::Enter];
+    let mut rdr = keys.iter();
+    let completer = DelegatingStream(StreamCompleter::default());
+    let cmd = super::complete_line(
+        &mut rdr,
+        &mut s,
+        &mut input_state,
+        &completer,
+        None,
+        &config,
+    ).unwrap();
+    assert_eq!(Some(Cmd::AcceptLine), cmd);
+    assert_eq!("rusy", s.line.as_str());
+}
+
+#[derive(Default)]
+struct IdleHelper {
+    ticks: RefCell<Vec<String>>,
+    hints: Cell<usize>,
+}
+impl Completer for IdleHelper {
+    type Candidate = String;
+
+    fn complete(&self, _line: &str, _pos: usize) -> Result<(usize, Vec<String>)> {
+        Ok((0, Vec::new()))
+    }
+}
+impl Hinter for IdleHelper {
+    fn hint(&self, _line: &str, _pos: usize) -> Option<String> {
+        self.hints.set(self.hints.get() + 1);
+        None
+    }
+}
+impl Highlighter for IdleHelper {}
+impl Helper for IdleHelper {
+    fn on_idle(&self, idle: &mut Idle) {
+        let mut ticks = self.ticks.borrow_mut();
+        ticks.push(idle.line().to_owned());
+        if ticks.len() == 2 {
+            idle.refresh();
+        }
+    }
+}
+
+#[test]
+fn on_idle() {
+    let config = Config::builder().idle_tick(Some(10)).build();
+    let mut editor = Editor::with_config(config);
+    editor.set_helper(Some(IdleHelper::default()));
+    let mut rdr = Silences(
+        vec![
+            Some(KeyPress::Char('a')),
+            None,
+            None,
+            Some(KeyPress::Char('b')),
+            None,
+            Some(KeyPress::Enter),
+        ].into_iter()
+        .collect(),
+    );
+    let line = super::readline_edit(
+        ">>",
+        None,
+        &mut editor,
+        &ReadlineOptions::default(),
+        &mut Sink::new(),
+        &mut rdr,
+        None,
+    ).unwrap();
+    assert_eq!("ab", line);
+    let helper = editor.helper().unwrap();
+    assert_eq!(vec!["a", "a", "ab"], *helper.ticks.borrow());
+    // initial refresh, 'a', idle refresh and 'b'
+    assert_eq!(4, helper.hints.get());
+}
+
+#[test]
+fn key_remapper() {
+    for mode in &[EditMode::Emacs, EditMode::Vi] {
+        let mut editor = init_editor(
+            *mode,
+            &[
+                KeyPress::Char('a'),
+                KeyPress::Char('b'),
+                KeyPress::F(2),
+                KeyPress::Char('c'),
+                KeyPress::Enter,
+            ],
+        );
+        editor.set_key_remapper(Some(Box::new(|key| match key {
+            KeyPress::F(2) => KeyPress::Left,
+            key => key,
+        })));
+        assert_eq!("acb", editor.readline(">>").unwrap());
+    }
+}
+
+#[test]
+fn compose() {
+    let mut editor = init_editor(
+        EditMode::Emacs,
+        &[
+            KeyPress::F(1),
+            KeyPress::Char('\''),
+            KeyPress::Char('e'),
+            KeyPress::F(1),
+            KeyPress::Char('t'),
+            KeyPress::Char('-'),
+            KeyPress::F(1),
+            KeyPress::Char('x'),
+            KeyPress::Char('x'),
+            KeyPress::Enter,
+        ],
+    );
+    editor.bind_sequence(KeyPress::F(1), Cmd::Compose);
+    editor.add_compose_sequence('-', 't', '\u{2020}');
+    assert_eq!("é\u{2020}", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn history_list() {
+    let keys = [
+        KeyPress::Meta('h'),
+        KeyPress::Char('g'),
+        KeyPress::Char('i'),
+        KeyPress::Char('t'),
+        KeyPress::Down,
+        KeyPress::Enter,
+        KeyPress::Enter,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(KeyPress::Meta('h'), Cmd::HistoryList);
+    editor.add_history_entry("git pull");
+    editor.add_history_entry("ls");
+    editor.add_history_entry("git push");
+    assert_eq!("git pull", editor.readline(">>").unwrap());
+
+    let keys = [KeyPress::Meta('h'), KeyPress::Esc, KeyPress::Enter];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(KeyPress::Meta('h'), Cmd::HistoryList);
+    editor.add_history_entry("ls");
+    assert_eq!("", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn snapshot() {
+    let keys = [
+        KeyPress::Char('h'),
+        KeyPress::Char('i'),
+        KeyPress::Ctrl('W'),
+        KeyPress::Char('a'),
+        KeyPress::Char('b'),
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    assert_matches!(editor.readline(">>"), Err(ReadlineError::Eof));
+    let text = editor.take_snapshot().unwrap().to_string();
+    assert!(editor.take_snapshot().is_none());
+
+    // resumed by another editor
+    for &(ref keys, line) in &[
+        (vec![KeyPress::Ctrl('Y'), KeyPress::Enter], "abhi"),
+        (vec![KeyPress::Ctrl('_'), KeyPress::Ctrl('_'), KeyPress::Enter], "hi"),
+    ] {
+        let mut editor = init_editor(EditMode::Emacs, keys);
+        editor.restore_snapshot(Snapshot::parse(&text).unwrap());
+        assert_eq!(line, editor.readline(">>").unwrap());
+    }
+
+    // beyond MAX_LINE, with its undo history
+    let text = format!("line\t{}\nundo\tinsert\t4999\tx\n", "x".repeat(5000));
+    let mut editor = init_editor(EditMode::Emacs, &[KeyPress::Ctrl('_'), KeyPress::Enter]);
+    editor.restore_snapshot(Snapshot::parse(&text).unwrap());
+    assert_eq!("x".repeat(4999), editor.readline(">>").unwrap());
+}
+
+#[test]
+fn form() {
+    // the same keys are read for each field
+    let mut editor = init_editor(EditMode::Emacs, &[KeyPress::Char('8'), KeyPress::Enter]);
+    let answers = editor
+        .form()
+        .field("host")
+        .default("localhost")
+        .field("port")
+        .validator(|port| port.parse::<u16>().map(|_| ()).map_err(|e| e.to_string()))
+        .field_secret("password")
+        .run()
+        .unwrap();
+    assert_eq!(Some("localhost8"), answers.get("host"));
+    assert_eq!(Some("8"), answers.get("port"));
+    assert_eq!(Some("8"), answers.get("password"));
+    assert_eq!(None, answers.get("user"));
+    assert_eq!(0, editor.history().len());
+}
+
+#[test]
+fn set_edit_mode() {
+    let keys = [
+        KeyPress::Char('a'),
+        KeyPress::Char('b'),
+        KeyPress::Meta('v'),
+        KeyPress::Esc,
+        KeyPress::Char('x'),
+        KeyPress::Enter,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.bind_sequence(KeyPress::Meta('v'), Cmd::SetEditMode(EditMode::Vi));
+    assert_eq!("a", editor.readline(">>").unwrap());
+    // kept for the next line
+    assert_eq!(EditMode::Vi, editor.config.edit_mode());
+}
+
+#[test]
+fn output_writer() {
+    let out = Arc::new(Mutex::new(Vec::new()));
+    let mut editor = init_editor(EditMode::Emacs, &[KeyPress::Char('a'), KeyPress::Enter]);
+    editor.set_output_writer(Some(out.clone()));
+    assert_eq!("a", editor.readline(">> ").unwrap());
+    assert!(String::from_utf8_lossy(&out.lock().unwrap()).contains("\r>> a"));
+
+    out.lock().unwrap().clear();
+    editor.set_output_writer(None);
+    assert_eq!("a", editor.readline(">> ").unwrap());
+    assert!(out.lock().unwrap().is_empty());
+}
+
+struct SlowCompleter;
+impl Completer for SlowCompleter {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, _pos: usize) -> Result<(usize, Vec<String>)> {
+        thread::sleep(Duration::from_millis(300));
+        Ok((0, vec![line.to_owned() + "t"]))
+    }
+}
+impl Hinter for SlowCompleter {
+    fn hint(&self, _line: &str, _pos: usize) -> Option<String> {
+        None
+    }
+}
+impl Highlighter for SlowCompleter {}
+impl Helper for SlowCompleter {}
+
+#[test]
+fn spinner() {
+    let out = Arc::new(Mutex::new(Vec::new()));
+    let mut editor = Editor::new();
+    editor.set_helper(Some(SlowCompleter));
+    editor.term.keys.extend(&[KeyPress::Tab, KeyPress::Enter]);
+    // displayed on the output of the editor
+    editor.set_output_writer(Some(out.clone()));
+    let line = editor.readline_with_initial("", ("rus", "")).unwrap();
+    assert_eq!("rust", line);
+    assert!(String::from_utf8_lossy(&out.lock().unwrap()).contains("completing... |"));
+}
+
+#[test]
+fn readline_template() {
+    let renderings = Arc::new(AtomicUsize::new(0));
+    let counter = renderings.clone();
+    let template = PromptTemplate::new("{n}> ").provider("n", move || {
+        (counter.fetch_add(1, Ordering::SeqCst) + 1).to_string()
+    });
+    let mut editor = init_editor(EditMode::Emacs, &[KeyPress::Char('a'), KeyPress::Enter]);
+    editor.set_prompt_template(Some(template));
+    assert_eq!("a", editor.readline_template().unwrap());
+    // rendered again on repaint
+    assert!(renderings.load(Ordering::SeqCst) > 1);
+}
+
+#[test]
+fn validator() {
+    let keys = [
+        KeyPress::Char('f'),
+        KeyPress::Char('('),
+        KeyPress::Enter,
+        KeyPress::Char(')'),
+        KeyPress::Enter,
+    ];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.set_validator(Some(Box::new(|input: &str| {
+        if input.matches('(').count() > input.matches(')').count() {
+            ValidationResult::Incomplete("  ".to_owned())
+        } else {
+            ValidationResult::Valid
+        }
+    })));
+    assert_eq!("f(\n  )", editor.readline(">>").unwrap());
+}
+
+#[test]
+fn draft() {
+    let path = env::temp_dir().join(format!("rustyline-draft-{}", process::id()));
+    fs::write(&path, "ab").unwrap();
+    let keys = [KeyPress::Char('y'), KeyPress::Char('c'), KeyPress::Enter];
+    let mut editor = init_editor(EditMode::Emacs, &keys);
+    editor.set_draft_file(Some(path.clone()));
+    assert_eq!("abc", editor.readline(">>").unwrap());
+    assert!(!path.exists());
+
+    // kept when reading a key fails
+    editor.term.keys.clear();
+    editor.term.keys.push(KeyPress::Char('x'));
+    assert_matches!(editor.readline(">>"), Err(ReadlineError::Eof));
+    assert_eq!("x", fs::read_to_string(&path).unwrap());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn recover_line() {
+    let mut editor = init_editor(EditMode::Emacs, &[KeyPress::Char('a'), KeyPress::Ctrl('C')]);
+    editor.bind_sequence(KeyPress::F(2), Cmd::RecoverLine);
+    assert_matches!(editor.readline(">>"), Err(ReadlineError::Interrupted));
+    assert_eq!(Some("a"), editor.discarded_line());
+
+    editor.term.keys.clear();
+    editor
+        .term
+        .keys
+        .extend(&[KeyPress::Char('b'), KeyPress::F(2), KeyPress::Enter]);
+    assert_eq!("a", editor.readline(">>").unwrap());
+    assert_eq!(Some("b"), editor.discarded_line());
+}
+
+#[test]
+fn editor_is_send() {
+    fn assert_send<T: Send>() {}
+    assert_send::<Editor<()>>();
+}
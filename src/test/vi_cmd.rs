@@ -322,6 +322,22 @@ fn u() {
     );
 }
 
+#[test]
+fn ctrl_r() {
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello, ", "world"),
+        &[
+            KeyPress::Esc,
+            KeyPress::Ctrl('W'),
+            KeyPress::Char('u'),
+            KeyPress::Ctrl('R'),
+            KeyPress::Enter,
+        ],
+        ("", " world"),
+    );
+}
+
 #[test]
 fn w() {
     assert_cursor(
@@ -477,6 +493,26 @@ fn p() {
     );
 }
 
+#[test]
+fn count_p() {
+    assert_cursor(
+        EditMode::Vi,
+        ("Hello, ", "world"),
+        &[
+            KeyPress::Esc,
+            KeyPress::Char('d'),
+            KeyPress::Char('b'),
+            KeyPress::Char('l'),
+            KeyPress::Char('d'),
+            KeyPress::Char('e'),
+            KeyPress::Char('2'),
+            KeyPress::Char('p'),
+            KeyPress::Enter,
+        ],
+        ("Hello ", ","),
+    );
+}
+
 #[test]
 fn uppercase_p() {
     assert_cursor(
@@ -1,7 +1,8 @@
 //! Vi insert mode specific key bindings
 use super::assert_cursor;
-use config::EditMode;
+use config::{Config, EditMode};
 use keys::KeyPress;
+use Editor;
 
 #[test]
 fn insert_mode_by_default() {
@@ -54,3 +55,24 @@ fn esc() {
         ("", "a"),
     );
 }
+
+#[test]
+fn insert_escape() {
+    let config = Config::builder()
+        .edit_mode(EditMode::Vi)
+        .vi_insert_escape(Some(('j', 'k')))
+        .build();
+    for &(ref keys, line) in &[
+        (vec!['a', 'j', 'k', 'x'], ""),
+        (vec!['a', 'j', 'x', 'j'], "ajxj"),
+        (vec!['a', 'j', 'j', 'k', 'x'], "a"),
+    ] {
+        let mut editor = Editor::<()>::with_config(config);
+        editor
+            .term
+            .keys
+            .extend(keys.iter().map(|&c| KeyPress::Char(c)));
+        editor.term.keys.push(KeyPress::Enter);
+        assert_eq!(line, editor.readline(">>").unwrap());
+    }
+}
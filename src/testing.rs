@@ -0,0 +1,781 @@
+//! Fake terminal for integration tests
+//!
+//! Drive an `Editor` with scripted keys (no real terminal needed) and
+//! inspect what would have been displayed (see `FakeTerminal::screen`).
+//!
+//! ```
+//! use rustyline::testing::FakeTerminal;
+//! use rustyline::{Editor, KeyPress};
+//!
+//! let mut rl = Editor::<()>::new();
+//! let mut term = FakeTerminal::new(&[KeyPress::Char('a'), KeyPress::Enter]);
+//! assert_eq!("a", term.readline(&mut rl, "> ").unwrap());
+//! assert_eq!("> a", term.line());
+//!
+//! // tmux `send-keys` like script
+//! let mut term = FakeTerminal::from_script("hello C-a C-k world Enter");
+//! assert_eq!("world", term.readline(&mut rl, "> ").unwrap());
+//! ```
+use std::collections::VecDeque;
+use std::time::Duration;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{readline_edit, Editor, Helper, ReadlineOptions, Result};
+use embed::Display;
+use error::ReadlineError;
+use keys::{char_to_key_press, KeyPress};
+use trace;
+use tty::RawReader;
+
+/// Scripted keys and captured output.
+#[derive(Clone, Debug)]
+pub struct FakeTerminal {
+    keys: VecDeque<KeyPress>,
+    display: Display,
+}
+
+impl FakeTerminal {
+    /// Create a 80x24 terminal which will feed `keys` to the editor.
+    pub fn new(keys: &[KeyPress]) -> FakeTerminal {
+        FakeTerminal {
+            keys: keys.iter().cloned().collect(),
+            display: Display::new(80, 24),
+        }
+    }
+
+    /// Create a 80x24 terminal which will feed the keys described by
+    /// `script` (see `parse_keys`) to the editor.
+    pub fn from_script(script: &str) -> FakeTerminal {
+        Self::new(&parse_keys(script))
+    }
+
+    /// Create a 80x24 terminal which will feed the keys recorded in `trace`
+    /// (see `Editor::set_trace`) to the editor, to replay a bug report.
+    pub fn from_trace(trace: &str) -> FakeTerminal {
+        Self::from_script(&trace::recorded_keys(trace))
+    }
+
+    /// Change the terminal size.
+    pub fn set_size(&mut self, cols: usize, rows: usize) {
+        self.display.screen.cols = cols;
+        self.display.screen.rows = rows;
+    }
+
+    /// Append `keys` to the keys not already read.
+    pub fn push_keys(&mut self, keys: &[KeyPress]) {
+        self.keys.extend(keys);
+    }
+
+    /// Append the keys described by `script` (see `parse_keys`) to the keys
+    /// not already read.
+    pub fn push_script(&mut self, script: &str) {
+        self.keys.extend(parse_keys(script));
+    }
+
+    /// Keys not already read.
+    pub fn remaining_keys(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Like `Editor::readline` but keys are read from this terminal.
+    ///
+    /// # Errors
+    /// Will return `ReadlineError::Eof` when there is no more key to read
+    /// before the line is accepted.
+    pub fn readline<H: Helper>(&mut self, editor: &mut Editor<H>, prompt: &str) -> Result<String> {
+        self.readline_with(editor, prompt, &ReadlineOptions::default())
+    }
+
+    /// Like `Editor::readline_with` but keys are read from this terminal.
+    pub fn readline_with<H: Helper>(
+        &mut self,
+        editor: &mut Editor<H>,
+        prompt: &str,
+        options: &ReadlineOptions,
+    ) -> Result<String> {
+        let mut rdr = Reader(&mut self.keys);
+        let user_input = readline_edit(
+            prompt,
+            None,
+            editor,
+            options,
+            &mut self.display,
+            &mut rdr,
+            None,
+        );
+        if options.apply(&editor.config).auto_add_history() {
+            if let Ok(ref line) = user_input {
+                editor.add_history_entry(line.as_str());
+            }
+        }
+        self.display.screen.put("\n");
+        user_input
+    }
+
+    /// Everything but the edited line which has been written (completion
+    /// list, ...).
+    pub fn output(&self) -> &str {
+        &self.display.output
+    }
+
+    /// Last displayed prompt, line and hint (highlighted if colors are
+    /// enabled).
+    pub fn line(&self) -> &str {
+        &self.display.line
+    }
+
+    /// Last cursor position (relative to the start of the prompt).
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.display.cursor.col, self.display.cursor.row)
+    }
+
+    /// Number of times the bell has been rung.
+    pub fn beeps(&self) -> usize {
+        self.display.beeps
+    }
+
+    /// Current screen contents: one string per row, without styles (ANSI
+    /// escape sequences) nor trailing spaces.
+    ///
+    /// Rows which scrolled out of the screen are lost.
+    pub fn screen(&self) -> Vec<String> {
+        let screen = &self.display.screen;
+        (0..screen.cells.len())
+            .map(|row| screen.text(row))
+            .collect()
+    }
+
+    /// Current cursor position on the screen (column, row).
+    pub fn screen_cursor(&self) -> (usize, usize) {
+        (self.display.screen.pos.col, self.display.screen.pos.row)
+    }
+
+    /// Screen contents joined by newlines, with the cursor marked by `|`
+    /// (handy for snapshot tests).
+    pub fn snapshot(&self) -> String {
+        let mut screen = self.screen();
+        let pos = self.display.screen.pos;
+        {
+            let row = &mut screen[pos.row];
+            let mut idx = row.len();
+            let mut col = 0;
+            for (i, g) in row.grapheme_indices(true) {
+                if col >= pos.col {
+                    idx = i;
+                    break;
+                }
+                col += g.width();
+            }
+            while col < pos.col {
+                row.push(' ');
+                col += 1;
+                idx = row.len();
+            }
+            row.insert(idx, '|');
+        }
+        screen.join("\n")
+    }
+}
+
+/// Convert a [tmux send-keys](http://man.openbsd.org/OpenBSD-current/man1/tmux.1#send-keys)
+/// like `script` to keys.
+///
+/// `script` is a list of whitespace separated tokens. Each token is either a
+/// key name (`Enter`, `Tab`, `BTab`, `Escape`, `BSpace`, `DC`, `IC`, `Home`,
+/// `End`, `Up`, `Down`, `Left`, `Right`, `PPage`, `NPage`, `Space`, `F1`...),
+/// optionally prefixed by `C-` (control), `M-` (meta) or `S-` (shift), or
+/// some literal text which is typed character by character.
+///
+/// ```
+/// use rustyline::testing::parse_keys;
+/// use rustyline::KeyPress;
+/// assert_eq!(
+///     vec![KeyPress::Char('h'), KeyPress::Char('i'), KeyPress::Ctrl('A'), KeyPress::Meta('f')],
+///     parse_keys("hi C-a M-f")
+/// );
+/// ```
+pub fn parse_keys(script: &str) -> Vec<KeyPress> {
+    let mut keys = Vec::new();
+    for token in script.split_whitespace() {
+        if let Some(key) = parse_key(token) {
+            keys.push(key);
+        } else {
+            keys.extend(token.chars().map(char_to_key_press));
+        }
+    }
+    keys
+}
+
+fn parse_key(token: &str) -> Option<KeyPress> {
+    let (prefix, key) = if token.len() > 2 && token.is_char_boundary(2) {
+        token.split_at(2)
+    } else {
+        ("", token)
+    };
+    match prefix {
+        "C-" => match key {
+            "Up" => Some(KeyPress::ControlUp),
+            "Down" => Some(KeyPress::ControlDown),
+            "Left" => Some(KeyPress::ControlLeft),
+            "Right" => Some(KeyPress::ControlRight),
+            "Space" => Some(KeyPress::Ctrl(' ')),
+            key => single_char(key).map(|c| char_to_key_press(control(c))),
+        },
+        "M-" => match parse_key(key) {
+            Some(KeyPress::Char(c)) => Some(KeyPress::Meta(c)),
+            Some(KeyPress::Ctrl(' ')) => Some(KeyPress::Meta('\x00')),
+            Some(KeyPress::Ctrl(c)) => Some(KeyPress::Meta(control(c))),
+            Some(KeyPress::Backspace) if key == "BSpace" => Some(KeyPress::Meta('\x7f')),
+            Some(KeyPress::Backspace) => Some(KeyPress::Meta('\x08')),
+            Some(KeyPress::Enter) => Some(KeyPress::Meta('\r')),
+            Some(KeyPress::Tab) => Some(KeyPress::Meta('\t')),
+            Some(KeyPress::ShiftLeft) => Some(KeyPress::MetaShiftLeft),
+            Some(KeyPress::ShiftRight) => Some(KeyPress::MetaShiftRight),
+            Some(_) => None,
+            None => single_char(key).map(KeyPress::Meta),
+        },
+        "S-" => match key {
+            "Up" => Some(KeyPress::ShiftUp),
+            "Down" => Some(KeyPress::ShiftDown),
+            "Left" => Some(KeyPress::ShiftLeft),
+            "Right" => Some(KeyPress::ShiftRight),
+            "Home" => Some(KeyPress::ShiftHome),
+            "End" => Some(KeyPress::ShiftEnd),
+            "Tab" => Some(KeyPress::BackTab),
+            _ => None,
+        },
+        _ => match token {
+            "Enter" => Some(KeyPress::Enter),
+            "Tab" => Some(KeyPress::Tab),
+            "BTab" => Some(KeyPress::BackTab),
+            "Escape" => Some(KeyPress::Esc),
+            "BSpace" => Some(KeyPress::Backspace),
+            "DC" => Some(KeyPress::Delete),
+            "IC" => Some(KeyPress::Insert),
+            "Home" => Some(KeyPress::Home),
+            "End" => Some(KeyPress::End),
+            "Up" => Some(KeyPress::Up),
+            "Down" => Some(KeyPress::Down),
+            "Left" => Some(KeyPress::Left),
+            "Right" => Some(KeyPress::Right),
+            "PPage" | "PageUp" => Some(KeyPress::PageUp),
+            "NPage" | "PageDown" => Some(KeyPress::PageDown),
+            "Space" => Some(KeyPress::Char(' ')),
+            _ if token.len() > 1 && token.starts_with('F') => {
+                token[1..].parse().ok().map(KeyPress::F)
+            }
+            _ => None,
+        },
+    }
+}
+
+/// Token of `key` in the `parse_keys` syntax (`None` when there is none).
+pub(crate) fn key_name(key: KeyPress) -> Option<String> {
+    let name = match key {
+        KeyPress::Char(' ') => "Space",
+        KeyPress::Char(c) if c.is_whitespace() => return None,
+        KeyPress::Char(c) => return Some(c.to_string()),
+        KeyPress::Ctrl(' ') => "C-Space",
+        KeyPress::Ctrl(c) => return Some(format!("C-{}", c.to_ascii_lowercase())),
+        KeyPress::Meta('\x00') => "M-C-Space",
+        KeyPress::Meta('\x7f') => "M-BSpace",
+        KeyPress::Meta('\r') => "M-Enter",
+        KeyPress::Meta('\t') => "M-Tab",
+        KeyPress::Meta(' ') => "M-Space",
+        KeyPress::Meta('\x1b') => return None,
+        KeyPress::Meta(c) if c.is_ascii_control() => {
+            let c = ((c as u8) | 0x40) as char;
+            return Some(format!("M-C-{}", c.to_ascii_lowercase()));
+        }
+        KeyPress::Meta(c) if c.is_whitespace() => return None,
+        KeyPress::Meta(c) => return Some(format!("M-{}", c)),
+        KeyPress::Enter => "Enter",
+        KeyPress::Tab => "Tab",
+        KeyPress::BackTab => "BTab",
+        KeyPress::Esc => "Escape",
+        KeyPress::Backspace => "BSpace",
+        KeyPress::Delete => "DC",
+        KeyPress::Insert => "IC",
+        KeyPress::Home => "Home",
+        KeyPress::End => "End",
+        KeyPress::Up => "Up",
+        KeyPress::Down => "Down",
+        KeyPress::Left => "Left",
+        KeyPress::Right => "Right",
+        KeyPress::PageUp => "PPage",
+        KeyPress::PageDown => "NPage",
+        KeyPress::F(n) => return Some(format!("F{}", n)),
+        KeyPress::ControlUp => "C-Up",
+        KeyPress::ControlDown => "C-Down",
+        KeyPress::ControlLeft => "C-Left",
+        KeyPress::ControlRight => "C-Right",
+        KeyPress::ShiftUp => "S-Up",
+        KeyPress::ShiftDown => "S-Down",
+        KeyPress::ShiftLeft => "S-Left",
+        KeyPress::ShiftRight => "S-Right",
+        KeyPress::ShiftHome => "S-Home",
+        KeyPress::ShiftEnd => "S-End",
+        KeyPress::MetaShiftLeft => "M-S-Left",
+        KeyPress::MetaShiftRight => "M-S-Right",
+        KeyPress::BracketedPasteStart | KeyPress::BracketedPasteEnd => return None,
+        KeyPress::UnknownEscSeq | KeyPress::Null => return None,
+    };
+    Some(name.to_owned())
+}
+
+/// Return the only char of `s`.
+fn single_char(s: &str) -> Option<char> {
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+/// Control character corresponding to `c` (`'a'` => `'\x01'`).
+fn control(c: char) -> char {
+    if c.is_ascii() {
+        ((c.to_ascii_uppercase() as u8) & 0x1f) as char
+    } else {
+        c
+    }
+}
+
+struct Reader<'k>(&'k mut VecDeque<KeyPress>);
+
+impl<'k> RawReader for Reader<'k> {
+    fn next_key(&mut self, _: bool) -> Result<KeyPress> {
+        match self.0.pop_front() {
+            Some(key) => Ok(key),
+            None => Err(ReadlineError::Eof),
+        }
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        match self.0.pop_front() {
+            Some(KeyPress::Char(c)) => Ok(c),
+            Some(KeyPress::Tab) => Ok('\t'),
+            Some(KeyPress::Enter) => Ok('\r'),
+            Some(KeyPress::Esc) => Ok('\x1b'),
+            Some(KeyPress::Ctrl(c)) => Ok(((c as u8) & 0x1f) as char),
+            Some(_) => Ok('\u{fffd}'),
+            None => Err(ReadlineError::Eof),
+        }
+    }
+
+    #[cfg(unix)]
+    fn unread(&mut self, text: &str) {
+        for c in text.chars().rev() {
+            self.0.push_front(char_to_key_press(c));
+        }
+    }
+
+    fn has_pending_input(&mut self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn wait_for_input(&mut self, _: Duration) -> bool {
+        // once the keys are exhausted, `next_key` fails without blocking
+        // (like a closed terminal)
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+
+    use super::{key_name, parse_keys, FakeTerminal};
+    use completion::Completer;
+    use config::{AmbiguousWidth, ColorMode, CompletionType, Config, EditMode};
+    use highlight::{Highlighter, Theme};
+    use hint::Hinter;
+    use keys::KeyPress;
+    use {Cmd, Editor, Helper, Movement, Result};
+
+    #[test]
+    fn readline() {
+        let config = Config::builder().auto_add_history(true).build();
+        let mut editor = Editor::<()>::with_config(config);
+        let mut term = FakeTerminal::new(&[
+            KeyPress::Char('a'),
+            KeyPress::Char('b'),
+            KeyPress::Left,
+            KeyPress::Enter,
+            KeyPress::Up,
+        ]);
+        assert_eq!("ab", term.readline(&mut editor, "> ").unwrap());
+        assert_eq!("> ab", term.line());
+        assert_eq!((4, 0), term.cursor());
+        assert_eq!(1, editor.history().len());
+        assert_eq!(1, term.remaining_keys());
+
+        term.push_keys(&[KeyPress::Enter]);
+        assert_eq!("ab", term.readline(&mut editor, "> ").unwrap());
+        assert!(term.readline(&mut editor, "> ").is_err());
+    }
+
+    #[test]
+    fn beep() {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor = Editor::<()>::with_config(config);
+        editor.set_helper(Some(()));
+        let mut term = FakeTerminal::new(&[KeyPress::Tab, KeyPress::Enter]);
+        assert_eq!("", term.readline(&mut editor, "").unwrap());
+        assert_eq!(1, term.beeps());
+    }
+
+    #[test]
+    fn screen_reader() {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .screen_reader(true)
+            .build();
+        let mut editor = Editor::<()>::with_config(config);
+        editor.set_helper(Some(()));
+        let mut term = FakeTerminal::from_script("ab BSpace c Enter");
+        assert_eq!("ac", term.readline(&mut editor, "> ").unwrap());
+        // only echoed
+        assert_eq!("ab\x08 \x08c", term.output());
+        assert_eq!("> ac", term.screen()[0]);
+
+        let rung = Arc::new(AtomicUsize::new(0));
+        let bell = Arc::clone(&rung);
+        editor.set_bell(Some(Box::new(move || {
+            bell.fetch_add(1, Ordering::SeqCst);
+        })));
+        let mut term = FakeTerminal::new(&[KeyPress::Tab, KeyPress::Enter]);
+        assert_eq!("", term.readline(&mut editor, "").unwrap());
+        assert_eq!(0, term.beeps());
+        assert_eq!(1, rung.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn refresh_span() {
+        let mut editor = Editor::<()>::new();
+        let mut term = FakeTerminal::from_script("abcdefghijk C-a xy C-e BSpace C-a C-d");
+        term.set_size(8, 24);
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!(vec!["> yabcde", "fghij"], term.screen());
+        assert_eq!((2, 0), term.cursor());
+
+        let mut term = FakeTerminal::from_script("abcdefgh C-a C-d");
+        term.set_size(8, 24);
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!(vec!["> bcdefg", "h"], term.screen());
+    }
+
+    #[test]
+    fn scroll() {
+        // a line taller than the screen: the rows around the cursor only
+        let mut editor = Editor::<()>::new();
+        let text = "abcdefghijklmnopqrstuvwxyz0123456789";
+        let mut term = FakeTerminal::from_script(&format!("{} C-a", text));
+        term.set_size(10, 3);
+        assert!(term.readline(&mut editor, "> ").is_err());
+        let rows = vec!["> abcdefgh", "ijklmnopqr", "stuvwxyz01"];
+        assert_eq!(rows, term.screen());
+        assert_eq!((2, 0), term.cursor());
+
+        let mut term = FakeTerminal::from_script(&format!("{} C-a C-e", text));
+        term.set_size(10, 3);
+        assert!(term.readline(&mut editor, "> ").is_err());
+        // scrolled by the last line feed
+        assert_eq!(vec!["stuvwxyz01", "23456789", ""], term.screen());
+        assert_eq!((8, 3), term.cursor());
+    }
+
+    #[test]
+    fn search_match() {
+        let mut editor = Editor::<()>::new();
+        editor.add_history_entry("cargo build");
+        editor.add_history_entry("rustc");
+        let mut term = FakeTerminal::from_script("C-r a r");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!(
+            "(reverse-i-search)`ar': c\x1b[4mar\x1b[0mgo build",
+            term.line()
+        );
+
+        // the prompt is restored without the match
+        let mut term = FakeTerminal::from_script("C-r a r Enter");
+        assert_eq!("cargo build", term.readline(&mut editor, "> ").unwrap());
+        assert_eq!("> cargo build", term.line());
+    }
+
+    #[test]
+    fn selection_without_colors() {
+        let config = Config::builder().color_mode(ColorMode::Disabled).build();
+        let mut editor = Editor::<()>::with_config(config);
+        let mut term = FakeTerminal::from_script("abc S-Left S-Left");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        // in reverse video
+        assert_eq!("> a\x1b[7mbc\x1b[0m", term.line());
+    }
+
+    #[test]
+    fn ambiguous_width() {
+        // each editor measures with its own setting
+        let config = Config::builder()
+            .ambiguous_width(AmbiguousWidth::Wide)
+            .build();
+        let mut editor = Editor::<()>::with_config(config);
+        let mut term = FakeTerminal::from_script("\u{b1}");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!((4, 0), term.cursor());
+
+        let mut editor = Editor::<()>::new();
+        let mut term = FakeTerminal::from_script("\u{b1}");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!((3, 0), term.cursor());
+    }
+
+    #[test]
+    fn paste_clipboard_without_reply() {
+        let mut editor = Editor::<()>::new();
+        editor.bind_sequence(KeyPress::F(1), Cmd::PasteClipboard);
+        // the keys typed after the query are not taken for its reply
+        let mut term = FakeTerminal::from_script("a F1 b Enter");
+        assert_eq!("ab", term.readline(&mut editor, "> ").unwrap());
+    }
+
+    #[test]
+    fn events() {
+        let mut editor = Editor::<()>::new();
+        let events = editor.event_sender();
+        thread::spawn(move || {
+            events.print("<bob> hello");
+            events.send_cmd(Cmd::Insert(1, "hello".to_owned()));
+            events.send_cmd(Cmd::Kill(Movement::WholeLine));
+            events.send_cmd(Cmd::Insert(1, "hi".to_owned()));
+            events.print("<bob> bye\n");
+        })
+        .join()
+        .unwrap();
+        let mut term = FakeTerminal::from_script("Space bob Enter");
+        term.set_size(20, 4);
+        assert_eq!("hi bob", term.readline(&mut editor, "> ").unwrap());
+        let rows = vec!["<bob> hello", "<bob> bye", "> hi bob", ""];
+        assert_eq!(rows, term.screen());
+    }
+
+    #[test]
+    fn idle_tick() {
+        let config = Config::builder().idle_tick(Some(10)).build();
+        let mut editor = Editor::<()>::with_config(config);
+        editor.set_helper(Some(()));
+        let mut term = FakeTerminal::from_script("ab Enter");
+        assert_eq!("ab", term.readline(&mut editor, "> ").unwrap());
+        // the keys exhausted while idle
+        let mut term = FakeTerminal::from_script("ab");
+        assert!(term.readline(&mut editor, "> ").is_err());
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            vec![
+                KeyPress::Char('a'),
+                KeyPress::Char(' '),
+                KeyPress::Ctrl('K'),
+                KeyPress::Backspace,
+                KeyPress::Enter,
+                KeyPress::Meta('\x08'),
+                KeyPress::Meta('b'),
+                KeyPress::ShiftLeft,
+                KeyPress::F(2),
+                KeyPress::Char('C'),
+                KeyPress::Char('-'),
+            ],
+            parse_keys("a Space C-k C-h C-m M-C-h M-b S-Left F2 C-")
+        );
+        assert_eq!(
+            vec![KeyPress::Char('F'), KeyPress::Char('x')],
+            parse_keys("Fx")
+        );
+        for &key in &[
+            KeyPress::Char('F'),
+            KeyPress::Ctrl('K'),
+            KeyPress::Ctrl(']'),
+            KeyPress::Meta('\x08'),
+            KeyPress::Meta('\x7f'),
+            KeyPress::Meta('\x1d'),
+            KeyPress::Meta('.'),
+            KeyPress::MetaShiftLeft,
+            KeyPress::F(12),
+        ] {
+            assert_eq!(vec![key], parse_keys(&key_name(key).unwrap()));
+        }
+    }
+
+    #[test]
+    fn script() {
+        let mut editor =
+            Editor::<()>::with_config(Config::builder().edit_mode(EditMode::Vi).build());
+        let mut term = FakeTerminal::from_script("one Space two Escape 0 d w Enter");
+        assert_eq!("two", term.readline(&mut editor, "").unwrap());
+        term.push_script("abc Escape x Enter");
+        assert_eq!("ab", term.readline(&mut editor, "").unwrap());
+    }
+
+    #[test]
+    fn screen_wrap() {
+        let mut editor = Editor::<()>::new();
+        let mut term = FakeTerminal::from_script("0123456789abc Enter");
+        term.set_size(10, 5);
+        assert_eq!("0123456789abc", term.readline(&mut editor, "> ").unwrap());
+        assert_eq!(vec!["> 01234567", "89abc", ""], term.screen());
+        assert_eq!("> 01234567\n89abc\n|", term.snapshot());
+
+        term.push_script("01234567 Home Enter");
+        term.readline(&mut editor, "> ").unwrap();
+        assert_eq!(
+            vec!["> 01234567", "89abc", "> 01234567", "", ""],
+            term.screen()
+        );
+        term.push_script("a b c d C-a");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!("89abc\n> 01234567\n\n> abcd\n|", term.snapshot());
+    }
+
+    #[test]
+    fn screen_wide_prompt() {
+        let mut editor = Editor::<()>::new();
+        let mut term = FakeTerminal::from_script("a b c Left Left C-a C-e d C-u e f Enter");
+        term.set_size(10, 8);
+        assert_eq!(
+            "ef",
+            term.readline(&mut editor, "0123456789abcdef> ").unwrap()
+        );
+        // the row of "d" is cleared
+        assert_eq!(vec!["0123456789", "abcdef> ef", "", ""], term.screen());
+
+        // prompt as wide as the screen
+        term.push_script("a b Home Enter");
+        assert_eq!("ab", term.readline(&mut editor, "0123456789").unwrap());
+        assert_eq!(
+            vec!["0123456789", "abcdef> ef", "", "0123456789", "ab", ""],
+            term.screen()
+        );
+        term.push_script("Enter");
+        term.readline(&mut editor, "0123456789").unwrap();
+        assert_eq!(
+            "0123456789\nabcdef> ef\n\n0123456789\nab\n0123456789\n\n|",
+            term.snapshot()
+        );
+    }
+
+    struct SimpleHelper;
+    impl Completer for SimpleHelper {
+        type Candidate = String;
+
+        fn complete(&self, _line: &str, _pos: usize) -> Result<(usize, Vec<String>)> {
+            Ok((0, vec!["abc".to_owned(), "abd".to_owned()]))
+        }
+    }
+    impl Hinter for SimpleHelper {
+        fn hint(&self, line: &str, _pos: usize) -> Option<String> {
+            if line == "a" {
+                Some("bc".to_owned())
+            } else {
+                None
+            }
+        }
+    }
+    impl Highlighter for SimpleHelper {}
+    impl Helper for SimpleHelper {}
+
+    /// Keep the lines given to `update_after_edit` (parsed once)
+    #[derive(Default)]
+    struct ParsingHelper(RefCell<Vec<String>>);
+    impl Completer for ParsingHelper {
+        type Candidate = String;
+
+        fn complete(&self, line: &str, _: usize) -> Result<(usize, Vec<String>)> {
+            assert_eq!(Some(line), self.0.borrow().last().map(String::as_str));
+            Ok((0, Vec::new()))
+        }
+    }
+    impl Hinter for ParsingHelper {
+        fn hint(&self, line: &str, _: usize) -> Option<String> {
+            assert_eq!(Some(line), self.0.borrow().last().map(String::as_str));
+            None
+        }
+    }
+    impl Highlighter for ParsingHelper {}
+    impl Helper for ParsingHelper {
+        fn update_after_edit(&self, line: &str, _: usize) {
+            self.0.borrow_mut().push(line.to_owned());
+        }
+    }
+
+    #[test]
+    fn update_after_edit() {
+        let mut editor = Editor::new();
+        editor.set_helper(Some(ParsingHelper::default()));
+        let mut term = FakeTerminal::from_script("ab C-a C-f Tab c Left Right Enter");
+        assert_eq!("acb", term.readline(&mut editor, "> ").unwrap());
+        let parsed = editor.helper().unwrap().0.borrow();
+        assert_eq!(vec!["", "ab", "acb"], *parsed);
+    }
+
+    #[test]
+    fn screen_hint_and_list() {
+        let config = Config::builder()
+            .completion_type(CompletionType::List)
+            .build();
+        let mut editor = Editor::with_config(config);
+        editor.set_helper(Some(SimpleHelper));
+        let mut term = FakeTerminal::from_script("a");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!("> abc\n|", term.snapshot());
+
+        term.push_script("a Tab Tab");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!(vec!["> abc", "> ab", "abc  abd", "> ab", ""], term.screen());
+        assert_eq!("\nabc  abd\n", term.output());
+    }
+
+    #[test]
+    fn monochrome_hint() {
+        let theme = Theme::new().hint("90").hint_markers("[", "]");
+        let config = Config::builder()
+            .color_mode(ColorMode::Monochrome)
+            .theme(theme)
+            .build();
+        let mut editor = Editor::with_config(config);
+        editor.set_helper(Some(SimpleHelper));
+        let mut term = FakeTerminal::from_script("a");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!("> a[bc]", term.line());
+
+        // styled with an attribute
+        let theme = Theme::new().hint("1;90").hint_markers("[", "]");
+        let config = Config::builder()
+            .color_mode(ColorMode::Monochrome)
+            .theme(theme)
+            .build();
+        let mut editor = Editor::with_config(config);
+        editor.set_helper(Some(SimpleHelper));
+        let mut term = FakeTerminal::from_script("a");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!("> a\x1b[1;90mbc\x1b[0m", term.line());
+
+        let config = Config::builder()
+            .color_mode(ColorMode::Disabled)
+            .theme(theme)
+            .build();
+        let mut editor = Editor::with_config(config);
+        editor.set_helper(Some(SimpleHelper));
+        let mut term = FakeTerminal::from_script("a");
+        assert!(term.readline(&mut editor, "> ").is_err());
+        assert_eq!("> a[bc]", term.line());
+    }
+}
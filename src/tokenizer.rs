@@ -0,0 +1,228 @@
+//! Line tokenizer with a configurable syntax (quotes, escape, comment and
+//! operators), shared by completers, validators and highlighters so that an
+//! application has a single definition of a word.
+//!
+//! When set with `Builder::tokenizer`, its words are also used by the word
+//! motions and kills (except for `Word::Big`).
+use std::ops::Range;
+
+/// Kind of token
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Sequence of unquoted non-blank characters and quoted strings
+    Word,
+    /// One of `Tokenizer::operators`
+    Operator,
+    /// From the comment character to the end of the line
+    Comment,
+}
+
+/// Token found by `Tokenizer::tokenize`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte span in the line (quotes included)
+    pub span: Range<usize>,
+    /// Unquoted and unescaped text (as is for operators and comments)
+    pub text: String,
+}
+
+/// Line syntax: shell-like by default (`"` and `'` quotes, `\` escape, no
+/// comment and no operator).
+///
+/// ```
+/// use rustyline::tokenizer::{TokenKind, Tokenizer};
+/// let tokenizer = Tokenizer::new()
+///     .comment_char(Some('#'))
+///     .operators(&["&&", "||", "|", ";"]);
+/// let tokens = tokenizer.tokenize("ls 'My Docs'|wc # count");
+/// assert_eq!("My Docs", tokens[1].text);
+/// assert_eq!(TokenKind::Operator, tokens[2].kind);
+/// assert_eq!(TokenKind::Comment, tokens[4].kind);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tokenizer {
+    quotes: &'static [char],
+    escape_char: Option<char>,
+    comment_char: Option<char>,
+    operators: &'static [&'static str],
+}
+
+impl Default for Tokenizer {
+    fn default() -> Tokenizer {
+        Tokenizer {
+            quotes: &['"', '\''],
+            escape_char: Some('\\'),
+            comment_char: None,
+            operators: &[],
+        }
+    }
+}
+
+impl Tokenizer {
+    pub fn new() -> Tokenizer {
+        Self::default()
+    }
+
+    /// Characters opening (and closing) a quoted string.
+    pub fn quotes(mut self, quotes: &'static [char]) -> Tokenizer {
+        self.quotes = quotes;
+        self
+    }
+
+    /// Escape character (`None` when there is none). In a quoted string, it
+    /// only escapes the quote and itself.
+    pub fn escape_char(mut self, escape_char: Option<char>) -> Tokenizer {
+        self.escape_char = escape_char;
+        self
+    }
+
+    /// Character starting a comment where a token can start (`None` when
+    /// there is none).
+    pub fn comment_char(mut self, comment_char: Option<char>) -> Tokenizer {
+        self.comment_char = comment_char;
+        self
+    }
+
+    /// Unquoted operators, which end the current word (the longest one
+    /// matches).
+    pub fn operators(mut self, operators: &'static [&'static str]) -> Tokenizer {
+        self.operators = operators;
+        self
+    }
+
+    /// Split `line` into tokens separated by unquoted whitespaces.
+    ///
+    /// An unclosed quoted string goes to the end of the line.
+    pub fn tokenize(&self, line: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut word: Option<Token> = None;
+        let mut quote = None;
+        let mut escaped = false;
+        let mut i = 0;
+        while let Some(c) = line[i..].chars().next() {
+            let next = i + c.len_utf8();
+            if escaped {
+                let word = word.as_mut().unwrap();
+                if quote.is_some() && Some(c) != quote && Some(c) != self.escape_char {
+                    word.text.push(self.escape_char.unwrap());
+                }
+                word.text.push(c);
+                word.span.end = next;
+                escaped = false;
+            } else if let Some(q) = quote {
+                let word = word.as_mut().unwrap();
+                word.span.end = next;
+                if c == q {
+                    quote = None;
+                } else if Some(c) == self.escape_char {
+                    escaped = true;
+                } else {
+                    word.text.push(c);
+                }
+            } else if c.is_whitespace() {
+                tokens.extend(word.take());
+            } else if word.is_none() && Some(c) == self.comment_char {
+                let end = line[i..].find('\n').map_or(line.len(), |j| i + j);
+                tokens.push(Token {
+                    kind: TokenKind::Comment,
+                    span: i..end,
+                    text: line[i..end].to_owned(),
+                });
+                i = end;
+                continue;
+            } else if let Some(op) = self.operator_at(&line[i..]) {
+                tokens.extend(word.take());
+                tokens.push(Token {
+                    kind: TokenKind::Operator,
+                    span: i..i + op.len(),
+                    text: op.to_owned(),
+                });
+                i += op.len();
+                continue;
+            } else {
+                let word = word.get_or_insert_with(|| Token {
+                    kind: TokenKind::Word,
+                    span: i..i,
+                    text: String::new(),
+                });
+                word.span.end = next;
+                if self.quotes.contains(&c) {
+                    quote = Some(c);
+                } else if Some(c) == self.escape_char {
+                    escaped = true;
+                } else {
+                    word.text.push(c);
+                }
+            }
+            i = next;
+        }
+        if let Some(mut word) = word {
+            if escaped {
+                // trailing escape char
+                word.text.push(self.escape_char.unwrap());
+            }
+            tokens.push(word);
+        }
+        tokens
+    }
+
+    fn operator_at(&self, s: &str) -> Option<&'static str> {
+        self.operators
+            .iter()
+            .filter(|op| !op.is_empty() && s.starts_with(*op))
+            .max_by_key(|op| op.len())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Token, TokenKind, Tokenizer};
+
+    fn words(tokens: &[Token]) -> Vec<(usize, usize, &str)> {
+        tokens
+            .iter()
+            .map(|token| (token.span.start, token.span.end, token.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn tokenize() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            vec![(0, 2, "ls"), (4, 9, "a b"), (10, 12, ""), (13, 17, "c d")],
+            words(&tokenizer.tokenize("ls  \"a b\" '' c\\ d"))
+        );
+        assert_eq!(
+            vec![(0, 9, "a\"\\b\\c")],
+            words(&tokenizer.tokenize("\"a\\\"\\\\b\\c"))
+        );
+        assert!(tokenizer.tokenize(" ").is_empty());
+
+        let sql = Tokenizer::new()
+            .quotes(&['\''])
+            .escape_char(None)
+            .comment_char(Some('-'))
+            .operators(&["<", "<=", "(", ")", ","]);
+        let tokens = sql.tokenize("f(a,'b c')<=1 -x\ny");
+        assert_eq!(
+            vec![
+                (0, 1, "f"),
+                (1, 2, "("),
+                (2, 3, "a"),
+                (3, 4, ","),
+                (4, 9, "b c"),
+                (9, 10, ")"),
+                (10, 12, "<="),
+                (12, 13, "1"),
+                (14, 16, "-x"),
+                (17, 18, "y"),
+            ],
+            words(&tokens)
+        );
+        assert_eq!(TokenKind::Operator, tokens[6].kind);
+        assert_eq!(TokenKind::Comment, tokens[8].kind);
+        assert_eq!(TokenKind::Word, tokens[9].kind);
+    }
+}
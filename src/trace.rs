@@ -0,0 +1,217 @@
+//! Recording of the keys, commands and refresh decisions of the edits (set
+//! with `Editor::set_trace`), to attach to a bug report: the keys can be
+//! replayed with `testing::FakeTerminal::from_trace`.
+//!
+//! Each record is a line with the time elapsed since the creation of the
+//! trace (in seconds), the kind of event (`key`, `cmd` or `refresh`) and
+//! its value, separated by tabs:
+//!
+//! ```text
+//! 0.000740  key      a
+//! 0.000803  cmd      SelfInsert(1, 'a')
+//! 0.000812  refresh  echo
+//! ```
+//!
+//! The refresh decisions are `repaint` (of the whole line), `echo` (of the
+//! inserted character), `erase` (with backspaces), `span` (of the line
+//! from the first modified character), `move` (of the cursor only) and
+//! `defer` (of an insertion, more keys being pending).
+//!
+//! ```no_run
+//! use rustyline::trace::Trace;
+//! use rustyline::Editor;
+//! let mut editor = Editor::<()>::new();
+//! editor.set_trace(Some(Trace::create("rustyline.trace").unwrap().scrub(true)));
+//! ```
+use std::cell::RefCell;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use super::Result;
+use history;
+use keymap::Cmd;
+use keys::KeyPress;
+use testing;
+use tty::RawReader;
+
+/// Trace file (see the module documentation)
+pub struct Trace {
+    out: RefCell<Box<Write + Send>>,
+    start: Instant,
+    scrub: bool,
+}
+
+impl Trace {
+    /// Record in the `path` file (created or truncated, only readable by the
+    /// user).
+    pub fn create<P: AsRef<Path> + ?Sized>(path: &P) -> Result<Trace> {
+        let file = try!(history::create_private(path.as_ref()));
+        Ok(Self::new(Box::new(BufWriter::new(file))))
+    }
+
+    /// Record in `out`.
+    pub fn new(out: Box<Write + Send>) -> Trace {
+        Trace {
+            out: RefCell::new(out),
+            start: Instant::now(),
+            scrub: false,
+        }
+    }
+
+    /// Replace the typed characters by `x` (the keys of secret lines are
+    /// always scrubbed).
+    pub fn scrub(mut self, yes: bool) -> Trace {
+        self.scrub = yes;
+        self
+    }
+
+    pub(crate) fn key(&self, key: KeyPress, secret: bool) {
+        let name = match key {
+            KeyPress::Char(c) if (secret || self.scrub) && c != ' ' => "x".to_owned(),
+            key => testing::key_name(key).unwrap_or_else(|| format!("{:?}", key)),
+        };
+        self.record("key", &name);
+    }
+
+    pub(crate) fn cmd(&self, cmd: &Cmd, secret: bool) {
+        let mut text = format!("{:?}", cmd);
+        if (secret || self.scrub) && text.contains(&['\'', '"'][..]) {
+            // drop the inserted text
+            if let Some(i) = text.find('(') {
+                text.truncate(i);
+            }
+        }
+        self.record("cmd", &text);
+    }
+
+    pub(crate) fn refresh(&self, decision: &str) {
+        self.record("refresh", decision);
+    }
+
+    fn record(&self, kind: &str, value: &str) {
+        let elapsed = self.start.elapsed();
+        let mut out = self.out.borrow_mut();
+        let rc = writeln!(
+            out,
+            "{}.{:06}\t{}\t{}",
+            elapsed.as_secs(),
+            elapsed.subsec_micros(),
+            kind,
+            value
+        )
+        .and_then(|_| out.flush());
+        if let Err(err) = rc {
+            debug!(target: "rustyline", "cannot write the trace: {}", err);
+        }
+    }
+}
+
+/// Keys recorded in `trace`, in the `testing::parse_keys` syntax.
+pub(crate) fn recorded_keys(trace: &str) -> String {
+    let mut keys = String::new();
+    for record in trace.lines() {
+        let mut fields = record.splitn(3, '\t').skip(1);
+        if let (Some("key"), Some(key)) = (fields.next(), fields.next()) {
+            keys.push_str(key);
+            keys.push(' ');
+        }
+    }
+    keys
+}
+
+/// Reader recording each key read
+pub(crate) struct Traced<'r> {
+    pub rdr: &'r mut RawReader,
+    pub trace: &'r Trace,
+    pub secret: bool,
+}
+
+impl<'r> RawReader for Traced<'r> {
+    fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyPress> {
+        let key = try!(self.rdr.next_key(single_esc_abort));
+        self.trace.key(key, self.secret);
+        Ok(key)
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        self.rdr.next_char()
+    }
+
+    fn has_pending_input(&mut self) -> bool {
+        self.rdr.has_pending_input()
+    }
+
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        self.rdr.wait_for_input(timeout)
+    }
+
+    #[cfg(unix)]
+    fn unread(&mut self, text: &str) {
+        self.rdr.unread(text)
+    }
+
+    #[cfg(unix)]
+    fn read_osc_reply(&mut self, prefix: &str, timeout: Duration) -> Result<Option<String>> {
+        self.rdr.read_osc_reply(prefix, timeout)
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        self.rdr.read_pasted_text()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    use super::Trace;
+    use config::ReadlineOptions;
+    use testing::FakeTerminal;
+    use Editor;
+
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(trace: Trace, out: &Arc<Mutex<Vec<u8>>>, options: &ReadlineOptions) -> String {
+        let mut editor = Editor::<()>::new();
+        editor.set_trace(Some(trace));
+        let mut term = FakeTerminal::from_script("ab C-a M-f BSpace Enter");
+        assert_eq!("a", term.readline_with(&mut editor, "> ", options).unwrap());
+        String::from_utf8(out.lock().unwrap().split_off(0)).unwrap()
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let out = Arc::new(Mutex::new(Vec::new()));
+        let trace = Trace::new(Box::new(Shared(out.clone())));
+        let text = record(trace, &out, &ReadlineOptions::default());
+        assert!(text.contains("\tkey\tM-f\n"));
+        assert!(text.contains("\tcmd\tSelfInsert(1, 'a')\n"));
+        // pending keys: the insertions are displayed by one repaint
+        assert!(text.contains("\trefresh\tdefer\n"));
+        assert!(text.contains("\trefresh\tmove\n"));
+        assert!(text.contains("\trefresh\tspan\n"));
+        let mut editor = Editor::<()>::new();
+        let mut term = FakeTerminal::from_trace(&text);
+        assert_eq!("a", term.readline(&mut editor, "> ").unwrap());
+        assert_eq!(0, term.remaining_keys());
+
+        let trace = Trace::new(Box::new(Shared(out.clone())));
+        let text = record(trace, &out, &ReadlineOptions::new().secret(true));
+        assert!(!text.contains("\tkey\ta\n"));
+        assert!(text.contains("\tkey\tx\n"));
+        assert!(text.contains("\tcmd\tSelfInsert\n"));
+    }
+}
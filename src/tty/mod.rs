@@ -1,11 +1,17 @@
 //! This module implements and describes common TTY methods & traits
+use std::cmp;
+use std::env;
 use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 use unicode_segmentation::UnicodeSegmentation;
-use unicode_width::UnicodeWidthStr;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-use config::{ColorMode, Config};
+use config::{AmbiguousWidth, ColorMode, Config, OutputStreamType};
 use highlight::Highlighter;
-use keys::KeyPress;
+use keys::{KeyPress, KeyRemapper};
 use line_buffer::LineBuffer;
 use Result;
 
@@ -22,6 +28,144 @@ pub trait RawReader {
     /// For CTRL-V support
     #[cfg(unix)]
     fn next_char(&mut self) -> Result<char>;
+    /// Tell if some input can be read without blocking (used to repaint only
+    /// once on paste or key repeat).
+    fn has_pending_input(&mut self) -> bool {
+        false
+    }
+    /// Wait at most the specified duration for some input, telling if there
+    /// is some.
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        if self.has_pending_input() {
+            return true;
+        }
+        // no way to be woken up: the input is checked again after `timeout`
+        thread::sleep(timeout);
+        self.has_pending_input()
+    }
+    /// Give back the chars read ahead (like the keys typed while waiting
+    /// for a reply): they are read again by `next_key`.
+    ///
+    /// By default, they are lost.
+    #[cfg(unix)]
+    fn unread(&mut self, _text: &str) {}
+    /// Read the reply to an OSC query (`ESC ] ... BEL` or `ESC ] ... ESC \`)
+    /// starting with `prefix`, or `None` when nothing is received within
+    /// `timeout`.
+    ///
+    /// The input which does not start like the reply (keys typed meanwhile,
+    /// on a terminal which does not reply) is given back with `unread`.
+    #[cfg(unix)]
+    fn read_osc_reply(&mut self, prefix: &str, timeout: Duration) -> Result<Option<String>> {
+        let mut reply = String::new();
+        loop {
+            if !self.wait_for_input(timeout) {
+                break;
+            }
+            let c = try!(self.next_char());
+            reply.push(c);
+            if !reply.starts_with(prefix) {
+                if !prefix.starts_with(reply.as_str()) {
+                    break;
+                }
+            } else if c == '\x07' || reply.ends_with("\x1b\\") {
+                return Ok(Some(reply));
+            }
+        }
+        if !reply.starts_with(prefix) {
+            self.unread(&reply);
+        }
+        Ok(None)
+    }
+    #[cfg(windows)]
+    fn read_osc_reply(&mut self, _: &str, _: Duration) -> Result<Option<String>> {
+        Ok(None)
+    }
+    /// Read the text pasted after a `KeyPress::BracketedPasteStart`, until
+    /// the end of the bracketed paste.
+    fn read_pasted_text(&mut self) -> Result<String> {
+        let mut text = String::new();
+        loop {
+            match try!(self.next_key(false)) {
+                KeyPress::BracketedPasteEnd => return Ok(text),
+                KeyPress::Char(c) => text.push(c),
+                KeyPress::Enter => text.push('\n'),
+                KeyPress::Tab => text.push('\t'),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: RawReader + ?Sized> RawReader for &mut R {
+    fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyPress> {
+        (**self).next_key(single_esc_abort)
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        (**self).next_char()
+    }
+
+    fn has_pending_input(&mut self) -> bool {
+        (**self).has_pending_input()
+    }
+
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        (**self).wait_for_input(timeout)
+    }
+
+    #[cfg(unix)]
+    fn unread(&mut self, text: &str) {
+        (**self).unread(text)
+    }
+
+    fn read_osc_reply(&mut self, prefix: &str, timeout: Duration) -> Result<Option<String>> {
+        (**self).read_osc_reply(prefix, timeout)
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        (**self).read_pasted_text()
+    }
+}
+
+/// Reader rewriting the keys with a `KeyRemapper`
+pub(crate) struct Remapped<'r> {
+    pub rdr: &'r mut RawReader,
+    pub remapper: &'r KeyRemapper,
+}
+
+impl<'r> RawReader for Remapped<'r> {
+    fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyPress> {
+        let key = try!(self.rdr.next_key(single_esc_abort));
+        Ok(self.remapper.remap(key))
+    }
+
+    #[cfg(unix)]
+    fn next_char(&mut self) -> Result<char> {
+        self.rdr.next_char()
+    }
+
+    fn has_pending_input(&mut self) -> bool {
+        self.rdr.has_pending_input()
+    }
+
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        self.rdr.wait_for_input(timeout)
+    }
+
+    #[cfg(unix)]
+    fn unread(&mut self, text: &str) {
+        self.rdr.unread(text)
+    }
+
+    fn read_osc_reply(&mut self, prefix: &str, timeout: Duration) -> Result<Option<String>> {
+        self.rdr.read_osc_reply(prefix, timeout)
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        self.rdr.read_pasted_text()
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
@@ -30,6 +174,69 @@ pub struct Position {
     pub row: usize,
 }
 
+/// Rows displayed of a line taller than the screen: `rows` rows from `top`
+/// (relative to the start of the prompt), from the byte `start` to `end`.
+/// All the rows are displayed when `rows` is 0.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct View {
+    pub top: usize,
+    pub rows: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl View {
+    /// View of `line` (displayed from `prompt_size` on a `cols` x `rows`
+    /// screen) scrolled from this one just enough to display the `cursor`.
+    pub fn scroll(
+        self,
+        line: &LineBuffer,
+        prompt_size: Position,
+        (cols, rows): (usize, usize),
+        (cursor, end_pos): (Position, Position),
+    ) -> View {
+        if rows == 0 || end_pos.row < rows {
+            return View::default();
+        }
+        let top = cmp::min(self.top, end_pos.row + 1 - rows);
+        let top = if cursor.row < top {
+            cursor.row
+        } else if cursor.row >= top + rows {
+            cursor.row + 1 - rows
+        } else {
+            top
+        };
+        View {
+            top,
+            rows,
+            start: line.row_offset(top, prompt_size, cols),
+            end: line.row_offset(top + rows, prompt_size, cols),
+        }
+    }
+
+    /// Tell if the `row` is displayed.
+    pub fn is_visible(self, row: usize) -> bool {
+        self.rows == 0 || (row >= self.top && row < self.top + self.rows)
+    }
+
+    /// Row on the screen (relative to the first displayed row) of the `row`
+    /// (clamped to the displayed rows).
+    pub fn screen_row(self, row: usize) -> usize {
+        if self.rows == 0 {
+            return row;
+        }
+        cmp::min(row.saturating_sub(self.top), self.rows - 1)
+    }
+
+    /// Position on the screen of `pos` (see `screen_row`).
+    pub fn screen_pos(self, pos: Position) -> Position {
+        Position {
+            col: pos.col,
+            row: self.screen_row(pos.row),
+        }
+    }
+}
+
 /// Display prompt, line and cursor in terminal output
 pub trait Renderer {
     fn move_cursor(&mut self, old: Position, new: Position) -> Result<()>;
@@ -46,6 +253,23 @@ pub trait Renderer {
         highlighter: Option<&Highlighter>,
     ) -> Result<(Position, Position)>;
 
+    /// Repaint the line from the byte `from` only (the prompt and the text
+    /// before being still displayed), the cursor being at `current`: the
+    /// rows above the one of `from` are left untouched.
+    ///
+    /// Return the cursor and end positions, or `None` when the terminal
+    /// cannot (then the whole line is repainted with `refresh_line`).
+    fn refresh_span(
+        &mut self,
+        _: Position,
+        _: &LineBuffer,
+        _: usize,
+        _: Option<String>,
+        _: Position,
+    ) -> Result<Option<(Position, Position)>> {
+        Ok(None)
+    }
+
     /// Calculate the number of columns and rows used to display `s` on a
     /// `cols` width terminal starting at `orig`.
     fn calculate_position(&self, s: &str, orig: Position) -> Position;
@@ -55,12 +279,17 @@ pub trait Renderer {
     /// Beep, used for completion when there is nothing to complete or when all
     /// the choices were already shown.
     fn beep(&mut self) -> Result<()> {
-        // TODO bell-style
         try!(io::stderr().write_all(b"\x07"));
         try!(io::stderr().flush());
         Ok(())
     }
 
+    /// Flash the screen instead of beeping (`BellStyle::Visible`), when the
+    /// terminal supports it.
+    fn flash(&mut self) -> Result<()> {
+        Ok(())
+    }
+
     /// Clear the screen. Used to handle ctrl+l
     fn clear_screen(&mut self) -> Result<()>;
 
@@ -72,6 +301,36 @@ pub trait Renderer {
     fn get_columns(&self) -> usize;
     /// Get the number of rows in the current terminal.
     fn get_rows(&self) -> usize;
+
+    /// Tell if the `row` (relative to the start of the prompt) is displayed:
+    /// only the rows around the cursor of a line taller than the screen are.
+    fn is_row_visible(&self, _: usize) -> bool {
+        true
+    }
+
+    /// Tell if the terminal only understands carriage returns (no escape
+    /// sequence): the cursor is then moved by repainting the line.
+    fn is_dumb(&self) -> bool {
+        false
+    }
+
+    /// Ask the terminal to bracket the pasted text (or to stop doing so).
+    fn set_bracketed_paste(&mut self, _: bool) -> Result<()> {
+        Ok(())
+    }
+
+    /// Writer to the same output, for a transient message displayed by
+    /// another thread while this one is busy (`None` when it cannot be
+    /// shared).
+    fn shared_writer(&self) -> Option<Box<Write + Send>> {
+        None
+    }
+
+    /// Measure East Asian ambiguous characters as wide (see
+    /// `Config::ambiguous_width`).
+    fn set_wide_ambiguous(&mut self, wide: bool);
+    /// Tell if East Asian ambiguous characters are measured as wide.
+    fn is_wide_ambiguous(&self) -> bool;
 }
 
 impl<'a, R: Renderer + ?Sized> Renderer for &'a mut R {
@@ -100,6 +359,17 @@ impl<'a, R: Renderer + ?Sized> Renderer for &'a mut R {
         )
     }
 
+    fn refresh_span(
+        &mut self,
+        prompt_size: Position,
+        line: &LineBuffer,
+        from: usize,
+        hint: Option<String>,
+        current: Position,
+    ) -> Result<Option<(Position, Position)>> {
+        (**self).refresh_span(prompt_size, line, from, hint, current)
+    }
+
     fn calculate_position(&self, s: &str, orig: Position) -> Position {
         (**self).calculate_position(s, orig)
     }
@@ -112,6 +382,10 @@ impl<'a, R: Renderer + ?Sized> Renderer for &'a mut R {
         (**self).beep()
     }
 
+    fn flash(&mut self) -> Result<()> {
+        (**self).flash()
+    }
+
     fn clear_screen(&mut self) -> Result<()> {
         (**self).clear_screen()
     }
@@ -131,6 +405,30 @@ impl<'a, R: Renderer + ?Sized> Renderer for &'a mut R {
     fn get_rows(&self) -> usize {
         (**self).get_rows()
     }
+
+    fn is_row_visible(&self, row: usize) -> bool {
+        (**self).is_row_visible(row)
+    }
+
+    fn is_dumb(&self) -> bool {
+        (**self).is_dumb()
+    }
+
+    fn set_bracketed_paste(&mut self, enabled: bool) -> Result<()> {
+        (**self).set_bracketed_paste(enabled)
+    }
+
+    fn shared_writer(&self) -> Option<Box<Write + Send>> {
+        (**self).shared_writer()
+    }
+
+    fn set_wide_ambiguous(&mut self, wide: bool) {
+        (**self).set_wide_ambiguous(wide)
+    }
+
+    fn is_wide_ambiguous(&self) -> bool {
+        (**self).is_wide_ambiguous()
+    }
 }
 
 /// Terminal contract
@@ -139,7 +437,7 @@ pub trait Term {
     type Writer: Renderer; // rl_outstream
     type Mode: RawMode;
 
-    fn new(color_mode: ColorMode) -> Self;
+    fn new(color_mode: ColorMode, stream: OutputStreamType) -> Self;
     /// Check if current terminal can provide a rich line-editing user
     /// interface.
     fn is_unsupported(&self) -> bool;
@@ -147,6 +445,8 @@ pub trait Term {
     fn is_stdin_tty(&self) -> bool;
     /// Check if output supports colors.
     fn colors_enabled(&self) -> bool;
+    /// Check if output supports only text attributes (no colors).
+    fn is_monochrome(&self) -> bool;
     /// Enable RAW mode for the terminal.
     fn enable_raw_mode(&mut self) -> Result<Self::Mode>;
     /// Create a RAW reader
@@ -155,12 +455,12 @@ pub trait Term {
     fn create_writer(&self) -> Self::Writer;
 }
 
-fn truncate(text: &str, col: usize, max_col: usize) -> &str {
+pub(crate) fn truncate(text: &str, col: usize, max_col: usize, wide: bool) -> &str {
     let mut col = col;
     let mut esc_seq = 0;
     let mut end = text.len();
     for (i, s) in text.grapheme_indices(true) {
-        col += width(s, &mut esc_seq);
+        col += width(s, &mut esc_seq, wide);
         if col > max_col {
             end = i;
             break;
@@ -169,7 +469,341 @@ fn truncate(text: &str, col: usize, max_col: usize) -> &str {
     &text[..end]
 }
 
-fn width(s: &str, esc_seq: &mut u8) -> usize {
+/// Repaint the last line of `prompt` and `line` with only carriage returns
+/// and spaces (for dumb terminals): `line` is scrolled horizontally so that
+/// the cursor at `pos` stays visible on a single row of `cols` columns.
+///
+/// Return the cursor and end positions (on row 0).
+pub(crate) fn dumb_refresh(
+    buffer: &mut String,
+    prompt: &str,
+    line: &str,
+    pos: usize,
+    cols: usize,
+    wide: bool,
+) -> (Position, Position) {
+    let prompt = &prompt[prompt.rfind('\n').map_or(0, |i| i + 1)..];
+    let prompt_width = str_width(prompt, wide);
+    // keep the last column free to not wrap
+    let max_width = cmp::max(cols.saturating_sub(prompt_width + 1), 1);
+    let mut start = 0;
+    for (i, g) in line[..pos].grapheme_indices(true) {
+        if str_width(&line[i..pos], wide) <= max_width {
+            break;
+        }
+        start = i + g.len();
+    }
+    let mut end = start;
+    let mut width = 0;
+    for (i, g) in line[start..].grapheme_indices(true) {
+        let w = str_width(g, wide);
+        if width + w > max_width || g.chars().any(char::is_control) {
+            break;
+        }
+        width += w;
+        end = start + i + g.len();
+    }
+    buffer.push('\r');
+    buffer.push_str(prompt);
+    buffer.push_str(&line[start..end]);
+    // erase the end of the previous line
+    for _ in width..max_width {
+        buffer.push(' ');
+    }
+    buffer.push('\r');
+    buffer.push_str(prompt);
+    buffer.push_str(&line[start..pos]);
+    let cursor = Position {
+        col: prompt_width + str_width(&line[start..pos], wide),
+        row: 0,
+    };
+    let end_pos = Position {
+        col: prompt_width + width,
+        row: 0,
+    };
+    (cursor, end_pos)
+}
+
+/// Move the cursor from `old` to `new` with ANSI escape sequences (shared by
+/// the Unix renderer and the Windows one on MSYS/Cygwin ptys).
+#[cfg(not(test))]
+pub(crate) fn vt_move_cursor(ab: &mut String, old: Position, new: Position) {
+    use std::fmt::Write;
+    if new.row > old.row {
+        // move down
+        let row_shift = new.row - old.row;
+        if row_shift == 1 {
+            ab.push_str("\x1b[B");
+        } else {
+            write!(ab, "\x1b[{}B", row_shift).unwrap();
+        }
+    } else if new.row < old.row {
+        // move up
+        let row_shift = old.row - new.row;
+        if row_shift == 1 {
+            ab.push_str("\x1b[A");
+        } else {
+            write!(ab, "\x1b[{}A", row_shift).unwrap();
+        }
+    }
+    if new.col > old.col {
+        // move right
+        let col_shift = new.col - old.col;
+        if col_shift == 1 {
+            ab.push_str("\x1b[C");
+        } else {
+            write!(ab, "\x1b[{}C", col_shift).unwrap();
+        }
+    } else if new.col < old.col {
+        // move left
+        let col_shift = old.col - new.col;
+        if col_shift == 1 {
+            ab.push_str("\x1b[D");
+        } else {
+            write!(ab, "\x1b[{}D", col_shift).unwrap();
+        }
+    }
+}
+
+/// Repaint `prompt`, `line` and `hint` with ANSI escape sequences (shared by
+/// the Unix renderer and the Windows one on MSYS/Cygwin ptys), the end of
+/// the line being at `end_pos` and the cursor at `cursor`.
+///
+/// Only the rows of `view` are displayed (and those of `old_view` cleared):
+/// the text of a line taller than the screen is not highlighted.
+///
+/// `hint` must already be truncated to the end of the row.
+#[cfg(not(test))]
+pub(crate) fn vt_refresh(
+    buffer: &mut String,
+    prompt: &str,
+    line: &LineBuffer,
+    hint: Option<&str>,
+    (current_row, old_rows, old_view): (usize, usize, View),
+    highlighter: Option<&Highlighter>,
+    (cursor, end_pos, view): (Position, Position, View),
+) {
+    use std::fmt::Write;
+    let current_row = old_view.screen_row(current_row);
+    let old_rows = old_view.screen_row(old_rows);
+    let cursor_row_movement = old_rows.saturating_sub(current_row);
+    // move the cursor down as required
+    if cursor_row_movement > 0 {
+        write!(buffer, "\x1b[{}B", cursor_row_movement).unwrap();
+    }
+    // clear old rows
+    for _ in 0..old_rows {
+        buffer.push_str("\r\x1b[0K\x1b[A");
+    }
+    // clear the line
+    buffer.push_str("\r\x1b[0K");
+
+    let (cursor, end_pos) = if view.rows > 0 {
+        // display the prompt
+        if view.top == 0 {
+            match highlighter {
+                Some(highlighter) => buffer.push_str(&highlighter.highlight_prompt(prompt)),
+                None => buffer.push_str(prompt),
+            }
+        }
+        // display the rows of the view (the line feed after the last one
+        // would scroll the screen)
+        let text = &line[view.start..view.end];
+        if view.end < line.len() && text.ends_with('\n') {
+            buffer.push_str(&text[..text.len() - 1]);
+        } else {
+            buffer.push_str(text);
+        }
+        if !view.is_visible(end_pos.row) {
+            vt_row_to_cursor(buffer, view.rows - 1, view.screen_pos(cursor));
+            return;
+        }
+        (view.screen_pos(cursor), view.screen_pos(end_pos))
+    } else if let Some(highlighter) = highlighter {
+        // display the prompt
+        buffer.push_str(&highlighter.highlight_prompt(prompt));
+        // display the input line
+        buffer.push_str(&highlighter.highlight(line, line.pos()));
+        (cursor, end_pos)
+    } else {
+        // display the prompt
+        buffer.push_str(prompt);
+        // display the input line
+        buffer.push_str(line);
+        (cursor, end_pos)
+    };
+    // display hint
+    if let Some(hint) = hint {
+        if let Some(highlighter) = highlighter {
+            buffer.push_str(&highlighter.highlight_hint(hint));
+        } else {
+            buffer.push_str(hint);
+        }
+    }
+    vt_end_to_cursor(buffer, cursor, end_pos);
+}
+
+/// Repaint `line` from the byte `from` (displayed at `start`, the cursor
+/// being at `current`) with ANSI escape sequences: the rows from the one of
+/// `start` are cleared, the text before is left as is.
+///
+/// `hint` must already be truncated to the end of the row.
+#[cfg(not(test))]
+pub(crate) fn vt_refresh_span(
+    buffer: &mut String,
+    line: &LineBuffer,
+    (from, hint): (usize, Option<&str>),
+    (current, start): (Position, Position),
+    (cursor, end_pos): (Position, Position),
+) {
+    vt_move_cursor(buffer, current, start);
+    // clear to the end of the screen
+    buffer.push_str("\x1b[J");
+    buffer.push_str(&line[from..]);
+    if let Some(hint) = hint {
+        buffer.push_str(hint);
+    }
+    vt_end_to_cursor(buffer, cursor, end_pos);
+}
+
+/// Move the cursor from the end of the displayed line (`end_pos`, before the
+/// hint) to `cursor`.
+#[cfg(not(test))]
+fn vt_end_to_cursor(buffer: &mut String, cursor: Position, end_pos: Position) {
+    // we have to generate our own newline on line wrap
+    if end_pos.col == 0 && end_pos.row > 0 {
+        buffer.push('\n');
+    }
+    vt_row_to_cursor(buffer, end_pos.row, cursor);
+}
+
+/// Move the cursor from the `row` to `cursor`.
+#[cfg(not(test))]
+fn vt_row_to_cursor(buffer: &mut String, row: usize, cursor: Position) {
+    use std::fmt::Write;
+    // position the cursor
+    let cursor_row_movement = row - cursor.row;
+    // move the cursor up as required
+    if cursor_row_movement > 0 {
+        write!(buffer, "\x1b[{}A", cursor_row_movement).unwrap();
+    }
+    // position the cursor within the line
+    if cursor.col > 0 {
+        write!(buffer, "\r\x1b[{}C", cursor.col).unwrap();
+    } else {
+        buffer.push('\r');
+    }
+}
+
+/// Message displayed at the cursor by `Spinner`
+pub(crate) const SPINNER_MSG: &str = "completing... |";
+
+/// Transient message displayed while a slow operation runs on the current
+/// thread (written from a timer thread through `Renderer::shared_writer`:
+/// the current thread does not render meanwhile).
+pub(crate) struct Spinner {
+    stop: mpsc::Sender<()>,
+    timer: thread::JoinHandle<bool>,
+}
+
+impl Spinner {
+    /// Display `SPINNER_MSG` at the cursor with `out` after `delay` (and
+    /// animate it) until `stop` is called.
+    pub fn start(delay: Duration, mut out: Box<Write + Send>) -> Spinner {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let timer = thread::spawn(move || {
+            let frames = ['|', '/', '-', '\\'];
+            let mut shown = false;
+            let mut timeout = delay;
+            let mut i = 0;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(timeout) {
+                let msg = format!(
+                    "{}{}{}",
+                    &SPINNER_MSG[..SPINNER_MSG.len() - 1],
+                    frames[i % frames.len()],
+                    "\x08".repeat(SPINNER_MSG.len())
+                );
+                if out.write_all(msg.as_bytes()).is_err() || out.flush().is_err() {
+                    break;
+                }
+                shown = true;
+                timeout = Duration::from_millis(100);
+                i += 1;
+            }
+            shown
+        });
+        Spinner { stop, timer }
+    }
+
+    /// Tell if the message has been displayed (and must be erased).
+    pub fn stop(self) -> bool {
+        drop(self.stop);
+        self.timer.join().unwrap_or(true)
+    }
+}
+
+static FORCE_REFRESH: AtomicBool = AtomicBool::new(false);
+
+/// Request a repaint (async-signal-safe).
+pub(crate) fn force_refresh() {
+    FORCE_REFRESH.store(true, Ordering::SeqCst);
+}
+
+/// Tell if a repaint has been requested.
+#[cfg(all(unix, not(test)))]
+pub(crate) fn is_refresh_forced() -> bool {
+    FORCE_REFRESH.load(Ordering::SeqCst)
+}
+
+/// Tell if a repaint has been requested, and reset the request.
+pub(crate) fn take_forced_refresh() -> bool {
+    FORCE_REFRESH.swap(false, Ordering::SeqCst)
+}
+
+/// Tell if East Asian ambiguous characters are measured as wide (the `wide`
+/// argument of the width functions).
+pub(crate) fn is_wide_ambiguous(ambiguous_width: AmbiguousWidth) -> bool {
+    match ambiguous_width {
+        AmbiguousWidth::Auto => is_cjk_locale(),
+        AmbiguousWidth::Narrow => false,
+        AmbiguousWidth::Wide => true,
+    }
+}
+
+/// Check the first non-empty of `LC_ALL`, `LC_CTYPE` and `LANG`.
+fn is_cjk_locale() -> bool {
+    for name in &["LC_ALL", "LC_CTYPE", "LANG"] {
+        if let Ok(locale) = env::var(name) {
+            if !locale.is_empty() {
+                return locale.starts_with("zh")
+                    || locale.starts_with("ja")
+                    || locale.starts_with("ko");
+            }
+        }
+    }
+    false
+}
+
+/// Display width of `s` (ignoring escape sequences), East Asian ambiguous
+/// characters being 2 columns wide when `wide`.
+pub(crate) fn str_width(s: &str, wide: bool) -> usize {
+    if wide {
+        s.width_cjk()
+    } else {
+        s.width()
+    }
+}
+
+/// Display width of `c` (`None` for control characters).
+pub(crate) fn char_width(c: char, wide: bool) -> Option<usize> {
+    if wide {
+        c.width_cjk()
+    } else {
+        c.width()
+    }
+}
+
+pub(crate) fn width(s: &str, esc_seq: &mut u8, wide: bool) -> usize {
     if *esc_seq == 1 {
         if s == "[" {
             // CSI
@@ -195,10 +829,12 @@ fn width(s: &str, esc_seq: &mut u8) -> usize {
     } else if s == "\n" {
         0
     } else {
-        s.width()
+        str_width(s, wide)
     }
 }
 
+pub(crate) mod vt;
+
 // If on Windows platform import Windows TTY module
 // and re-export into mod.rs scope
 #[cfg(all(windows, not(test)))]
@@ -208,6 +844,63 @@ pub use self::windows::*;
 
 // If on Unix platform import Unix TTY module
 // and re-export into mod.rs scope
+/// Decode the bytes read from stdin to chars.
+#[cfg(unix)]
+pub(crate) struct Utf8Decoder {
+    parser: ::utf8parse::Parser,
+    receiver: Utf8,
+    replace_invalid_input: bool,
+}
+
+#[cfg(unix)]
+struct Utf8 {
+    c: Option<char>,
+    valid: bool,
+}
+
+#[cfg(unix)]
+impl Utf8Decoder {
+    pub(crate) fn new(replace_invalid_input: bool) -> Utf8Decoder {
+        Utf8Decoder {
+            parser: ::utf8parse::Parser::new(),
+            receiver: Utf8 {
+                c: None,
+                valid: true,
+            },
+            replace_invalid_input,
+        }
+    }
+
+    /// Feed one byte, returns `None` while the char is incomplete.
+    /// An invalid sequence is replaced by U+FFFD if `replace_invalid_input`.
+    pub(crate) fn decode(&mut self, b: u8) -> Option<Result<char>> {
+        self.parser.advance(&mut self.receiver, b);
+        if !self.receiver.valid {
+            if self.replace_invalid_input {
+                self.receiver.valid = true;
+                return Some(Ok('\u{FFFD}'));
+            }
+            return Some(Err(::error::ReadlineError::Utf8Error));
+        }
+        self.receiver.c.take().map(Ok)
+    }
+}
+
+#[cfg(unix)]
+impl ::utf8parse::Receiver for Utf8 {
+    /// Called whenever a codepoint is parsed successfully
+    fn codepoint(&mut self, c: char) {
+        self.c = Some(c);
+        self.valid = true;
+    }
+
+    /// Called when an invalid_sequence is detected
+    fn invalid_sequence(&mut self) {
+        self.c = None;
+        self.valid = false;
+    }
+}
+
 #[cfg(all(unix, not(test)))]
 mod unix;
 #[cfg(all(unix, not(test)))]
@@ -217,3 +910,51 @@ pub use self::unix::*;
 mod test;
 #[cfg(test)]
 pub use self::test::*;
+
+#[cfg(test)]
+mod tests {
+    use super::{dumb_refresh, Position};
+
+    #[cfg(unix)]
+    fn decode(replace_invalid_input: bool, bytes: &[u8]) -> Vec<Option<char>> {
+        let mut decoder = super::Utf8Decoder::new(replace_invalid_input);
+        bytes
+            .iter()
+            .map(|&b| decoder.decode(b).map(|c| c.unwrap()))
+            .collect()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn replace_invalid_input() {
+        // lone continuation byte, then a valid char
+        assert_eq!(
+            vec![Some('\u{FFFD}'), None, Some('\u{e9}')],
+            decode(true, b"\x80\xc3\xa9")
+        );
+        // the decoder recovers after an invalid byte
+        assert_eq!(vec![Some('\u{FFFD}'), Some('a')], decode(true, b"\xffa"));
+
+        let mut decoder = super::Utf8Decoder::new(false);
+        match decoder.decode(0x80) {
+            Some(Err(::error::ReadlineError::Utf8Error)) => {}
+            _ => panic!("invalid input without replacement"),
+        }
+    }
+
+    #[test]
+    fn dumb() {
+        let mut buffer = String::new();
+        let (cursor, end) = dumb_refresh(&mut buffer, "> ", "abc", 1, 10, false);
+        assert_eq!("\r> abc    \r> a", buffer);
+        assert_eq!(Position { col: 3, row: 0 }, cursor);
+        assert_eq!(Position { col: 5, row: 0 }, end);
+
+        // scrolled to keep the cursor visible
+        buffer.clear();
+        let (cursor, end) = dumb_refresh(&mut buffer, "ok\n> ", "abcdefghij", 9, 8, false);
+        assert_eq!("\r> efghi\r> efghi", buffer);
+        assert_eq!(Position { col: 7, row: 0 }, cursor);
+        assert_eq!(Position { col: 7, row: 0 }, end);
+    }
+}
@@ -1,10 +1,14 @@
 //! Tests specific definitions
+use std::fmt;
+use std::io::{self, Write};
 use std::iter::IntoIterator;
 use std::slice::Iter;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use std::vec::IntoIter;
 
 use super::{truncate, Position, RawMode, RawReader, Renderer, Term};
-use config::{ColorMode, Config};
+use config::{ColorMode, Config, OutputStreamType};
 use error::ReadlineError;
 use highlight::Highlighter;
 use keys::KeyPress;
@@ -31,6 +35,15 @@ impl<'a> RawReader for Iter<'a, KeyPress> {
     fn next_char(&mut self) -> Result<char> {
         unimplemented!();
     }
+
+    fn has_pending_input(&mut self) -> bool {
+        self.len() > 0
+    }
+
+    fn wait_for_input(&mut self, _: Duration) -> bool {
+        // `next_key` never blocks
+        true
+    }
 }
 
 impl RawReader for IntoIter<KeyPress> {
@@ -49,13 +62,52 @@ impl RawReader for IntoIter<KeyPress> {
             _ => unimplemented!(),
         }
     }
+
+    fn has_pending_input(&mut self) -> bool {
+        self.len() > 0
+    }
+
+    fn wait_for_input(&mut self, _: Duration) -> bool {
+        // `next_key` never blocks
+        true
+    }
 }
 
-pub struct Sink {}
+/// Caller-provided writer receiving the refreshed prompt and line
+#[derive(Clone)]
+pub enum Output {
+    Writer(Arc<Mutex<Write + Send>>),
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Writer")
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Output::Writer(ref writer) = *self;
+        writer.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let Output::Writer(ref writer) = *self;
+        writer.lock().unwrap().flush()
+    }
+}
+
+pub struct Sink {
+    out: Option<Output>,
+    wide_ambiguous: bool,
+}
 
 impl Sink {
     pub fn new() -> Sink {
-        Sink {}
+        Sink {
+            out: None,
+            wide_ambiguous: false,
+        }
     }
 }
 
@@ -66,7 +118,7 @@ impl Renderer for Sink {
 
     fn refresh_line(
         &mut self,
-        _: &str,
+        prompt: &str,
         prompt_size: Position,
         line: &LineBuffer,
         hint: Option<String>,
@@ -74,9 +126,12 @@ impl Renderer for Sink {
         _: usize,
         _: Option<&Highlighter>,
     ) -> Result<(Position, Position)> {
+        if let Some(ref mut out) = self.out {
+            try!(write!(out, "\r{}{}", prompt, line.as_str()));
+        }
         let cursor = self.calculate_position(&line[..line.pos()], prompt_size);
         if let Some(hint) = hint {
-            truncate(&hint, 0, 80);
+            truncate(&hint, 0, 80, self.wide_ambiguous);
         }
         let end = self.calculate_position(&line, prompt_size);
         Ok((cursor, end))
@@ -113,6 +168,20 @@ impl Renderer for Sink {
     fn get_rows(&self) -> usize {
         24
     }
+
+    fn shared_writer(&self) -> Option<Box<Write + Send>> {
+        self.out
+            .clone()
+            .map(|out| Box::new(out) as Box<Write + Send>)
+    }
+
+    fn set_wide_ambiguous(&mut self, wide: bool) {
+        self.wide_ambiguous = wide;
+    }
+
+    fn is_wide_ambiguous(&self) -> bool {
+        self.wide_ambiguous
+    }
 }
 
 pub type Terminal = DummyTerminal;
@@ -122,6 +191,17 @@ pub struct DummyTerminal {
     pub keys: Vec<KeyPress>,
     pub cursor: usize, // cursor position before last command
     pub color_mode: ColorMode,
+    output: Option<Output>,
+}
+
+impl DummyTerminal {
+    pub(crate) fn set_output_stream(&mut self, _: OutputStreamType) {
+        self.output = None;
+    }
+
+    pub(crate) fn set_output(&mut self, output: Output) {
+        self.output = Some(output);
+    }
 }
 
 impl Term for DummyTerminal {
@@ -129,11 +209,12 @@ impl Term for DummyTerminal {
     type Reader = IntoIter<KeyPress>;
     type Writer = Sink;
 
-    fn new(color_mode: ColorMode) -> DummyTerminal {
+    fn new(color_mode: ColorMode, _: OutputStreamType) -> DummyTerminal {
         DummyTerminal {
             keys: Vec::new(),
             cursor: 0,
             color_mode: color_mode,
+            output: None,
         }
     }
 
@@ -151,6 +232,10 @@ impl Term for DummyTerminal {
         false
     }
 
+    fn is_monochrome(&self) -> bool {
+        self.color_mode == ColorMode::Monochrome
+    }
+
     // Interactive loop:
 
     fn enable_raw_mode(&mut self) -> Result<Mode> {
@@ -162,7 +247,10 @@ impl Term for DummyTerminal {
     }
 
     fn create_writer(&self) -> Sink {
-        Sink {}
+        Sink {
+            out: self.output.clone(),
+            wide_ambiguous: false,
+        }
     }
 }
 
@@ -1,8 +1,12 @@
 //! Unix specific definitions
 use std;
-use std::io::{self, Read, Stdout, Write};
+use std::cmp;
+use std::fmt;
+use std::io::{self, Read, Write};
 use std::sync;
 use std::sync::atomic;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use libc;
 use nix;
@@ -11,10 +15,12 @@ use nix::sys::signal;
 use nix::sys::termios;
 use nix::sys::termios::SetArg;
 use unicode_segmentation::UnicodeSegmentation;
-use utf8parse::{Parser, Receiver};
 
-use super::{truncate, width, Position, RawMode, RawReader, Renderer, Term};
-use config::{ColorMode, Config};
+use super::{
+    dumb_refresh, truncate, vt, vt_move_cursor, vt_refresh, vt_refresh_span, width, Position,
+    RawMode, RawReader, Renderer, Term, Utf8Decoder, View,
+};
+use config::{ColorMode, Config, OutputStreamType};
 use error;
 use highlight::Highlighter;
 use keys::{self, KeyPress};
@@ -23,19 +29,22 @@ use Result;
 
 const STDIN_FILENO: libc::c_int = libc::STDIN_FILENO;
 const STDOUT_FILENO: libc::c_int = libc::STDOUT_FILENO;
+const STDERR_FILENO: libc::c_int = libc::STDERR_FILENO;
 
 /// Unsupported Terminals that don't support RAW mode
-static UNSUPPORTED_TERM: [&'static str; 3] = ["dumb", "cons25", "emacs"];
+static UNSUPPORTED_TERM: [&str; 3] = ["dumb", "cons25", "emacs"];
+/// Terminals without colors (as well as the `-m` and `-mono` variants)
+static MONOCHROME_TERM: [&str; 4] = ["vt100", "vt102", "vt220", "vt320"];
 
 //#[allow(clippy::identity_conversion)]
-fn get_win_size() -> (usize, usize) {
+fn get_win_size(fd: libc::c_int) -> (usize, usize) {
     use std::mem::zeroed;
 
     unsafe {
         let mut size: libc::winsize = zeroed();
         // https://github.com/rust-lang/libc/pull/704
         // FIXME: ".into()" used as a temporary fix for a libc bug
-        match libc::ioctl(STDOUT_FILENO, libc::TIOCGWINSZ.into(), &mut size) {
+        match libc::ioctl(fd, libc::TIOCGWINSZ.into(), &mut size) {
             0 => (size.ws_col as usize, size.ws_row as usize), // TODO getCursorPosition
             _ => (80, 24),
         }
@@ -58,6 +67,19 @@ fn is_unsupported_term() -> bool {
     }
 }
 
+/// Check TERM environment variable to see if current term is monochrome
+fn is_monochrome_term() -> bool {
+    match std::env::var("TERM") {
+        Ok(term) => {
+            let term = term.to_ascii_lowercase();
+            term.ends_with("-m")
+                || term.ends_with("-mono")
+                || MONOCHROME_TERM.iter().any(|t| *t == term)
+        }
+        Err(_) => false,
+    }
+}
+
 /// Return whether or not STDIN, STDOUT or STDERR is a TTY
 fn is_a_tty(fd: libc::c_int) -> bool {
     unsafe { libc::isatty(fd) != 0 }
@@ -91,6 +113,7 @@ impl Read for StdinRaw {
                 let error = io::Error::last_os_error();
                 if error.kind() != io::ErrorKind::Interrupted
                     || SIGWINCH.load(atomic::Ordering::Relaxed)
+                    || super::is_refresh_forced()
                 {
                     return Err(error);
                 }
@@ -106,13 +129,9 @@ pub struct PosixRawReader {
     stdin: StdinRaw,
     timeout_ms: i32,
     buf: [u8; 1],
-    parser: Parser,
-    receiver: Utf8,
-}
-
-struct Utf8 {
-    c: Option<char>,
-    valid: bool,
+    decoder: Utf8Decoder,
+    /// Chars given back by `unread` (in reverse order)
+    unread: Vec<char>,
 }
 
 impl PosixRawReader {
@@ -121,202 +140,8 @@ impl PosixRawReader {
             stdin: StdinRaw {},
             timeout_ms: config.keyseq_timeout(),
             buf: [0; 1],
-            parser: Parser::new(),
-            receiver: Utf8 {
-                c: None,
-                valid: true,
-            },
-        })
-    }
-
-    /// Handle ESC <seq1> sequences
-    fn escape_sequence(&mut self) -> Result<KeyPress> {
-        // Read the next byte representing the escape sequence.
-        let seq1 = try!(self.next_char());
-        if seq1 == '[' {
-            // ESC [ sequences. (CSI)
-            self.escape_csi()
-        } else if seq1 == 'O' {
-            // xterm
-            // ESC O sequences. (SS3)
-            self.escape_o()
-        } else if seq1 == '\x1b' {
-            // ESC ESC
-            Ok(KeyPress::Esc)
-        } else {
-            // TODO ESC-R (r): Undo all changes made to this line.
-            Ok(KeyPress::Meta(seq1))
-        }
-    }
-
-    /// Handle ESC [ <seq2> escape sequences
-    fn escape_csi(&mut self) -> Result<KeyPress> {
-        let seq2 = try!(self.next_char());
-        if seq2.is_digit(10) {
-            match seq2 {
-                '0' | '9' => {
-                    debug!(target: "rustyline", "unsupported esc sequence: ESC [ {:?}", seq2);
-                    Ok(KeyPress::UnknownEscSeq)
-                }
-                _ => {
-                    // Extended escape, read additional byte.
-                    self.extended_escape(seq2)
-                }
-            }
-        } else if seq2 == '[' {
-            let seq3 = try!(self.next_char());
-            // Linux console
-            Ok(match seq3 {
-                'A' => KeyPress::F(1),
-                'B' => KeyPress::F(2),
-                'C' => KeyPress::F(3),
-                'D' => KeyPress::F(4),
-                'E' => KeyPress::F(5),
-                _ => {
-                    debug!(target: "rustyline", "unsupported esc sequence: ESC [ [ {:?}", seq3);
-                    KeyPress::UnknownEscSeq
-                }
-            })
-        } else {
-            // ANSI
-            Ok(match seq2 {
-                'A' => KeyPress::Up,    // kcuu1
-                'B' => KeyPress::Down,  // kcud1
-                'C' => KeyPress::Right, // kcuf1
-                'D' => KeyPress::Left,  // kcub1
-                'F' => KeyPress::End,
-                'H' => KeyPress::Home, // khome
-                'Z' => KeyPress::BackTab,
-                _ => {
-                    debug!(target: "rustyline", "unsupported esc sequence: ESC [ {:?}", seq2);
-                    KeyPress::UnknownEscSeq
-                }
-            })
-        }
-    }
-
-    /// Handle ESC [ <seq2:digit> escape sequences
-    fn extended_escape(&mut self, seq2: char) -> Result<KeyPress> {
-        let seq3 = try!(self.next_char());
-        if seq3 == '~' {
-            Ok(match seq2 {
-                '1' | '7' => KeyPress::Home, // tmux, xrvt
-                '2' => KeyPress::Insert,
-                '3' => KeyPress::Delete,    // kdch1
-                '4' | '8' => KeyPress::End, // tmux, xrvt
-                '5' => KeyPress::PageUp,    // kpp
-                '6' => KeyPress::PageDown,  // knp
-                _ => {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: ESC [ {} ~", seq2);
-                    KeyPress::UnknownEscSeq
-                }
-            })
-        } else if seq3.is_digit(10) {
-            let seq4 = try!(self.next_char());
-            if seq4 == '~' {
-                Ok(match (seq2, seq3) {
-                    ('1', '1') => KeyPress::F(1),  // rxvt-unicode
-                    ('1', '2') => KeyPress::F(2),  // rxvt-unicode
-                    ('1', '3') => KeyPress::F(3),  // rxvt-unicode
-                    ('1', '4') => KeyPress::F(4),  // rxvt-unicode
-                    ('1', '5') => KeyPress::F(5),  // kf5
-                    ('1', '7') => KeyPress::F(6),  // kf6
-                    ('1', '8') => KeyPress::F(7),  // kf7
-                    ('1', '9') => KeyPress::F(8),  // kf8
-                    ('2', '0') => KeyPress::F(9),  // kf9
-                    ('2', '1') => KeyPress::F(10), // kf10
-                    ('2', '3') => KeyPress::F(11), // kf11
-                    ('2', '4') => KeyPress::F(12), // kf12
-                    _ => {
-                        debug!(target: "rustyline",
-                               "unsupported esc sequence: ESC [ {}{} ~", seq2, seq3);
-                        KeyPress::UnknownEscSeq
-                    }
-                })
-            } else if seq4 == ';' {
-                let seq5 = try!(self.next_char());
-                if seq5.is_digit(10) {
-                    let seq6 = try!(self.next_char()); // '~' expected
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: ESC [ {}{} ; {} {}", seq2, seq3, seq5, seq6);
-                } else {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: ESC [ {}{} ; {:?}", seq2, seq3, seq5);
-                }
-                Ok(KeyPress::UnknownEscSeq)
-            } else {
-                debug!(target: "rustyline",
-                       "unsupported esc sequence: ESC [ {}{} {:?}", seq2, seq3, seq4);
-                Ok(KeyPress::UnknownEscSeq)
-            }
-        } else if seq3 == ';' {
-            let seq4 = try!(self.next_char());
-            if seq4.is_digit(10) {
-                let seq5 = try!(self.next_char());
-                if seq2 == '1' {
-                    Ok(match (seq4, seq5) {
-                        ('5', 'A') => KeyPress::ControlUp,
-                        ('5', 'B') => KeyPress::ControlDown,
-                        ('5', 'C') => KeyPress::ControlRight,
-                        ('5', 'D') => KeyPress::ControlLeft,
-                        ('2', 'A') => KeyPress::ShiftUp,
-                        ('2', 'B') => KeyPress::ShiftDown,
-                        ('2', 'C') => KeyPress::ShiftRight,
-                        ('2', 'D') => KeyPress::ShiftLeft,
-                        _ => {
-                            debug!(target: "rustyline",
-                                   "unsupported esc sequence: ESC [ 1 ; {} {:?}", seq4, seq5);
-                            KeyPress::UnknownEscSeq
-                        }
-                    })
-                } else {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: ESC [ {} ; {} {:?}", seq2, seq4, seq5);
-                    Ok(KeyPress::UnknownEscSeq)
-                }
-            } else {
-                debug!(target: "rustyline",
-                       "unsupported esc sequence: ESC [ {} ; {:?}", seq2, seq4);
-                Ok(KeyPress::UnknownEscSeq)
-            }
-        } else {
-            Ok(match (seq2, seq3) {
-                ('5', 'A') => KeyPress::ControlUp,
-                ('5', 'B') => KeyPress::ControlDown,
-                ('5', 'C') => KeyPress::ControlRight,
-                ('5', 'D') => KeyPress::ControlLeft,
-                _ => {
-                    debug!(target: "rustyline",
-                           "unsupported esc sequence: ESC [ {} {:?}", seq2, seq3);
-                    KeyPress::UnknownEscSeq
-                }
-            })
-        }
-    }
-
-    /// Handle ESC O <seq2> escape sequences
-    fn escape_o(&mut self) -> Result<KeyPress> {
-        let seq2 = try!(self.next_char());
-        Ok(match seq2 {
-            'A' => KeyPress::Up,    // kcuu1
-            'B' => KeyPress::Down,  // kcud1
-            'C' => KeyPress::Right, // kcuf1
-            'D' => KeyPress::Left,  // kcub1
-            'F' => KeyPress::End,   // kend
-            'H' => KeyPress::Home,  // khome
-            'P' => KeyPress::F(1),  // kf1
-            'Q' => KeyPress::F(2),  // kf2
-            'R' => KeyPress::F(3),  // kf3
-            'S' => KeyPress::F(4),  // kf4
-            'a' => KeyPress::ControlUp,
-            'b' => KeyPress::ControlDown,
-            'c' => KeyPress::ControlRight, // rxvt
-            'd' => KeyPress::ControlLeft,  // rxvt
-            _ => {
-                debug!(target: "rustyline", "unsupported esc sequence: ESC O {:?}", seq2);
-                KeyPress::UnknownEscSeq
-            }
+            decoder: Utf8Decoder::new(config.replace_invalid_input()),
+            unread: Vec::new(),
         })
     }
 }
@@ -327,19 +152,22 @@ impl RawReader for PosixRawReader {
 
         let mut key = keys::char_to_key_press(c);
         if key == KeyPress::Esc {
-            let timeout_ms = if single_esc_abort && self.timeout_ms == -1 {
+            let timeout_ms = if !self.unread.is_empty() {
+                // the rest of the sequence is already read
+                0
+            } else if single_esc_abort && self.timeout_ms == -1 {
                 0
             } else {
                 self.timeout_ms
             };
             let mut fds = [poll::PollFd::new(STDIN_FILENO, EventFlags::POLLIN)];
             match poll::poll(&mut fds, timeout_ms) {
-                Ok(n) if n == 0 => {
+                Ok(n) if n == 0 && self.unread.is_empty() => {
                     // single escape
                 }
                 Ok(_) => {
                     // escape sequence
-                    key = try!(self.escape_sequence())
+                    key = try!(vt::escape_sequence(&mut || self.next_char()))
                 }
                 // Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
                 Err(e) => return Err(e.into()),
@@ -349,94 +177,127 @@ impl RawReader for PosixRawReader {
         Ok(key)
     }
 
+    fn has_pending_input(&mut self) -> bool {
+        self.wait_for_input(Duration::from_millis(0))
+    }
+
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        if !self.unread.is_empty() {
+            return true;
+        }
+        let ms = timeout.as_secs() * 1000 + u64::from(timeout.subsec_millis());
+        let mut fds = [poll::PollFd::new(STDIN_FILENO, EventFlags::POLLIN)];
+        match poll::poll(&mut fds, cmp::min(ms, i32::MAX as u64) as i32) {
+            Ok(n) => n > 0,
+            Err(_) => false,
+        }
+    }
+
+    fn unread(&mut self, text: &str) {
+        self.unread.extend(text.chars().rev());
+    }
+
+    fn read_pasted_text(&mut self) -> Result<String> {
+        vt::pasted_text(&mut || self.next_char())
+    }
+
     fn next_char(&mut self) -> Result<char> {
+        if let Some(c) = self.unread.pop() {
+            return Ok(c);
+        }
         loop {
             let n = try!(self.stdin.read(&mut self.buf));
             if n == 0 {
                 return Err(error::ReadlineError::Eof);
             }
-            let b = self.buf[0];
-            self.parser.advance(&mut self.receiver, b);
-            if !self.receiver.valid {
-                return Err(error::ReadlineError::Utf8Error);
-            } else if self.receiver.c.is_some() {
-                return Ok(self.receiver.c.take().unwrap());
+            if let Some(c) = self.decoder.decode(self.buf[0]) {
+                return c;
             }
         }
     }
 }
 
-impl Receiver for Utf8 {
-    /// Called whenever a codepoint is parsed successfully
-    fn codepoint(&mut self, c: char) {
-        self.c = Some(c);
-        self.valid = true;
+/// Destination of the rendering
+#[derive(Clone)]
+pub enum Output {
+    Stream(OutputStreamType),
+    /// Caller-provided writer (like a file opened on `/dev/tty`)
+    Writer(Arc<Mutex<Write + Send>>),
+}
+
+impl Output {
+    /// File descriptor of the terminal
+    fn fd(&self) -> libc::c_int {
+        match *self {
+            Output::Stream(OutputStreamType::Stdout) => STDOUT_FILENO,
+            Output::Stream(OutputStreamType::Stderr) => STDERR_FILENO,
+            Output::Writer(_) => STDIN_FILENO,
+        }
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Output::Stream(stream) => write!(f, "Stream({:?})", stream),
+            Output::Writer(_) => f.write_str("Writer"),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match *self {
+            Output::Stream(OutputStreamType::Stdout) => io::stdout().write(buf),
+            Output::Stream(OutputStreamType::Stderr) => io::stderr().write(buf),
+            Output::Writer(ref writer) => writer.lock().unwrap().write(buf),
+        }
     }
 
-    /// Called when an invalid_sequence is detected
-    fn invalid_sequence(&mut self) {
-        self.c = None;
-        self.valid = false;
+    fn flush(&mut self) -> io::Result<()> {
+        match *self {
+            Output::Stream(OutputStreamType::Stdout) => io::stdout().flush(),
+            Output::Stream(OutputStreamType::Stderr) => io::stderr().flush(),
+            Output::Writer(ref writer) => writer.lock().unwrap().flush(),
+        }
     }
 }
 
 /// Console output writer
 pub struct PosixRenderer {
-    out: Stdout,
+    out: Output,
     cols: usize, // Number of columns in terminal
+    rows: usize, // Number of rows in terminal
     buffer: String,
+    dumb: bool, // unsupported terminal: no escape sequence
+    view: View, // Displayed rows of the line
+    wide_ambiguous: bool,
 }
 
 impl PosixRenderer {
-    fn new() -> PosixRenderer {
-        let (cols, _) = get_win_size();
+    fn new(out: Output, dumb: bool) -> PosixRenderer {
+        let (cols, rows) = get_win_size(out.fd());
         PosixRenderer {
-            out: io::stdout(),
+            out,
             cols,
+            rows,
             buffer: String::with_capacity(1024),
+            dumb,
+            view: View::default(),
+            wide_ambiguous: false,
         }
     }
 }
 
 impl Renderer for PosixRenderer {
     fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
-        use std::fmt::Write;
-        let mut ab = String::new();
-        if new.row > old.row {
-            // move down
-            let row_shift = new.row - old.row;
-            if row_shift == 1 {
-                ab.push_str("\x1b[B");
-            } else {
-                write!(ab, "\x1b[{}B", row_shift).unwrap();
-            }
-        } else if new.row < old.row {
-            // move up
-            let row_shift = old.row - new.row;
-            if row_shift == 1 {
-                ab.push_str("\x1b[A");
-            } else {
-                write!(ab, "\x1b[{}A", row_shift).unwrap();
-            }
-        }
-        if new.col > old.col {
-            // move right
-            let col_shift = new.col - old.col;
-            if col_shift == 1 {
-                ab.push_str("\x1b[C");
-            } else {
-                write!(ab, "\x1b[{}C", col_shift).unwrap();
-            }
-        } else if new.col < old.col {
-            // move left
-            let col_shift = old.col - new.col;
-            if col_shift == 1 {
-                ab.push_str("\x1b[D");
-            } else {
-                write!(ab, "\x1b[{}D", col_shift).unwrap();
-            }
-        }
-        self.write_and_flush(ab.as_bytes())
+        // reuse the refresh buffer to avoid an allocation on each cursor move
+        let ab = &mut self.buffer;
+        ab.clear();
+        vt_move_cursor(ab, self.view.screen_pos(old), self.view.screen_pos(new));
+        try!(self.out.write_all(ab.as_bytes()));
+        try!(self.out.flush());
+        Ok(())
     }
 
     fn refresh_line(
@@ -449,71 +310,79 @@ impl Renderer for PosixRenderer {
         old_rows: usize,
         highlighter: Option<&Highlighter>,
     ) -> Result<(Position, Position)> {
-        use std::fmt::Write;
         self.buffer.clear();
 
+        if self.dumb {
+            // no hint nor highlighting
+            let (cursor, end_pos) = dumb_refresh(
+                &mut self.buffer,
+                prompt,
+                line,
+                line.pos(),
+                self.cols,
+                self.wide_ambiguous,
+            );
+            try!(self.out.write_all(self.buffer.as_bytes()));
+            try!(self.out.flush());
+            return Ok((cursor, end_pos));
+        }
+
         // calculate the position of the end of the input line
-        let end_pos = self.calculate_position(line, prompt_size);
+        let end_pos = line.position(line.len(), prompt_size, self.cols);
         // calculate the desired position of the cursor
-        let cursor = self.calculate_position(&line[..line.pos()], prompt_size);
-
-        // self.old_rows < self.cursor.row if the prompt spans multiple lines and if
-        // this is the default State.
-        let cursor_row_movement = old_rows.checked_sub(current_row).unwrap_or(0);
-        // move the cursor down as required
-        if cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{}B", cursor_row_movement).unwrap();
-        }
-        // clear old rows
-        for _ in 0..old_rows {
-            self.buffer.push_str("\r\x1b[0K\x1b[A");
-        }
-        // clear the line
-        self.buffer.push_str("\r\x1b[0K");
-
-        if let Some(highlighter) = highlighter {
-            // display the prompt
-            self.buffer.push_str(&highlighter.highlight_prompt(prompt));
-            // display the input line
-            self.buffer
-                .push_str(&highlighter.highlight(line, line.pos()));
-        } else {
-            // display the prompt
-            self.buffer.push_str(prompt);
-            // display the input line
-            self.buffer.push_str(line);
-        }
-        // display hint
-        if let Some(hint) = hint {
-            let truncate = truncate(&hint, end_pos.col, self.cols);
-            if let Some(highlighter) = highlighter {
-                self.buffer.push_str(&highlighter.highlight_hint(truncate));
-            } else {
-                self.buffer.push_str(truncate);
-            }
-        }
-        // we have to generate our own newline on line wrap
-        if end_pos.col == 0 && end_pos.row > 0 {
-            self.buffer.push_str("\n");
-        }
-        // position the cursor
-        let cursor_row_movement = end_pos.row - cursor.row;
-        // move the cursor up as required
-        if cursor_row_movement > 0 {
-            write!(self.buffer, "\x1b[{}A", cursor_row_movement).unwrap();
-        }
-        // position the cursor within the line
-        if cursor.col > 0 {
-            write!(self.buffer, "\r\x1b[{}C", cursor.col).unwrap();
-        } else {
-            self.buffer.push('\r');
-        }
+        let cursor = line.position(line.pos(), prompt_size, self.cols);
+
+        let hint = hint
+            .as_ref()
+            .map(|hint| truncate(hint, end_pos.col, self.cols, self.wide_ambiguous));
+        let old_view = self.view;
+        self.view = old_view.scroll(line, prompt_size, (self.cols, self.rows), (cursor, end_pos));
+        vt_refresh(
+            &mut self.buffer,
+            prompt,
+            line,
+            hint,
+            (current_row, old_rows, old_view),
+            highlighter,
+            (cursor, end_pos, self.view),
+        );
 
         try!(self.out.write_all(self.buffer.as_bytes()));
         try!(self.out.flush());
         Ok((cursor, end_pos))
     }
 
+    fn refresh_span(
+        &mut self,
+        prompt_size: Position,
+        line: &LineBuffer,
+        from: usize,
+        hint: Option<String>,
+        current: Position,
+    ) -> Result<Option<(Position, Position)>> {
+        let end_pos = line.position(line.len(), prompt_size, self.cols);
+        if self.dumb || self.view.rows > 0 || end_pos.row >= self.rows {
+            // the rows of the line are not all displayed
+            return Ok(None);
+        }
+        let start = line.position(from, prompt_size, self.cols);
+        let cursor = line.position(line.pos(), prompt_size, self.cols);
+        let hint = hint
+            .as_ref()
+            .map(|hint| truncate(hint, end_pos.col, self.cols, self.wide_ambiguous));
+        self.buffer.clear();
+        vt_refresh_span(
+            &mut self.buffer,
+            line,
+            (from, hint),
+            (current, start),
+            (cursor, end_pos),
+        );
+        try!(self.out.write_all(self.buffer.as_bytes()));
+        try!(self.out.flush());
+        Ok(Some((cursor, end_pos)))
+    }
+
     fn write_and_flush(&mut self, buf: &[u8]) -> Result<()> {
         try!(self.out.write_all(buf));
         try!(self.out.flush());
@@ -531,7 +400,7 @@ impl Renderer for PosixRenderer {
                 pos.col = 0;
                 continue;
             }
-            let cw = width(c, &mut esc_seq);
+            let cw = width(c, &mut esc_seq, self.wide_ambiguous);
             pos.col += cw;
             if pos.col > self.cols {
                 pos.row += 1;
@@ -547,6 +416,10 @@ impl Renderer for PosixRenderer {
 
     /// Clear the screen. Used to handle ctrl+l
     fn clear_screen(&mut self) -> Result<()> {
+        if self.dumb {
+            // the line is repainted on the next row
+            return self.write_and_flush(b"\n");
+        }
         self.write_and_flush(b"\x1b[H\x1b[2J")
     }
 
@@ -557,8 +430,9 @@ impl Renderer for PosixRenderer {
 
     /// Try to update the number of columns in the current terminal,
     fn update_size(&mut self) {
-        let (cols, _) = get_win_size();
+        let (cols, rows) = get_win_size(self.out.fd());
         self.cols = cols;
+        self.rows = rows;
     }
 
     fn get_columns(&self) -> usize {
@@ -568,8 +442,52 @@ impl Renderer for PosixRenderer {
     /// Try to get the number of rows in the current terminal,
     /// or assume 24 if it fails.
     fn get_rows(&self) -> usize {
-        let (_, rows) = get_win_size();
-        rows
+        self.rows
+    }
+
+    fn is_row_visible(&self, row: usize) -> bool {
+        self.view.is_visible(row)
+    }
+
+    fn is_dumb(&self) -> bool {
+        self.dumb
+    }
+
+    fn shared_writer(&self) -> Option<Box<Write + Send>> {
+        if self.dumb {
+            None
+        } else {
+            Some(Box::new(self.out.clone()))
+        }
+    }
+
+    fn set_wide_ambiguous(&mut self, wide: bool) {
+        self.wide_ambiguous = wide;
+    }
+
+    fn is_wide_ambiguous(&self) -> bool {
+        self.wide_ambiguous
+    }
+
+    fn flash(&mut self) -> Result<()> {
+        if self.dumb {
+            return self.beep();
+        }
+        // reverse video (DECSCNM)
+        try!(self.write_and_flush(b"\x1b[?5h"));
+        std::thread::sleep(Duration::from_millis(100));
+        self.write_and_flush(b"\x1b[?5l")
+    }
+
+    fn set_bracketed_paste(&mut self, enabled: bool) -> Result<()> {
+        if self.dumb {
+            return Ok(());
+        }
+        self.write_and_flush(if enabled {
+            b"\x1b[?2004h"
+        } else {
+            b"\x1b[?2004l"
+        })
     }
 }
 
@@ -600,6 +518,21 @@ pub struct PosixTerminal {
     stdin_isatty: bool,
     stdout_isatty: bool,
     pub(crate) color_mode: ColorMode,
+    output: Output,
+}
+
+impl PosixTerminal {
+    pub(crate) fn set_output_stream(&mut self, stream: OutputStreamType) {
+        self.set_output(Output::Stream(stream));
+    }
+
+    pub(crate) fn set_output(&mut self, output: Output) {
+        self.stdout_isatty = is_a_tty(output.fd());
+        self.output = output;
+        if !self.unsupported && self.stdin_isatty && self.stdout_isatty {
+            install_sigwinch_handler();
+        }
+    }
 }
 
 impl Term for PosixTerminal {
@@ -607,12 +540,14 @@ impl Term for PosixTerminal {
     type Reader = PosixRawReader;
     type Writer = PosixRenderer;
 
-    fn new(color_mode: ColorMode) -> PosixTerminal {
+    fn new(color_mode: ColorMode, stream: OutputStreamType) -> PosixTerminal {
+        let output = Output::Stream(stream);
         let term = PosixTerminal {
             unsupported: is_unsupported_term(),
             stdin_isatty: is_a_tty(STDIN_FILENO),
-            stdout_isatty: is_a_tty(STDOUT_FILENO),
+            stdout_isatty: is_a_tty(output.fd()),
             color_mode,
+            output,
         };
         if !term.unsupported && term.stdin_isatty && term.stdout_isatty {
             install_sigwinch_handler();
@@ -635,13 +570,21 @@ impl Term for PosixTerminal {
 
     /// Check if output supports colors.
     fn colors_enabled(&self) -> bool {
-        match self.color_mode {
-            ColorMode::Enabled => self.stdout_isatty,
+        match self.color_mode.resolve() {
+            ColorMode::Enabled | ColorMode::Monochrome => self.stdout_isatty,
             ColorMode::Forced => true,
             ColorMode::Disabled => false,
         }
     }
 
+    fn is_monochrome(&self) -> bool {
+        match self.color_mode.resolve() {
+            ColorMode::Enabled => is_monochrome_term(),
+            ColorMode::Monochrome => true,
+            ColorMode::Forced | ColorMode::Disabled => false,
+        }
+    }
+
     // Interactive loop:
 
     fn enable_raw_mode(&mut self) -> Result<Mode> {
@@ -654,6 +597,8 @@ impl Term for PosixTerminal {
         let mut raw = original_mode.clone();
         // disable BREAK interrupt, CR to NL conversion on input,
         // input parity check, strip high bit (bit 8), output flow control
+        // (so that Ctrl-S and Ctrl-Q reach the editor, the original mode is
+        // restored when `readline` returns)
         raw.input_flags &= !(InputFlags::BRKINT
             | InputFlags::ICRNL
             | InputFlags::INPCK
@@ -680,7 +625,7 @@ impl Term for PosixTerminal {
     }
 
     fn create_writer(&self) -> PosixRenderer {
-        PosixRenderer::new()
+        PosixRenderer::new(self.output.clone(), self.unsupported)
     }
 }
 
@@ -712,5 +657,13 @@ mod test {
 
         ::std::env::set_var("TERM", "dumb");
         assert_eq!(true, super::is_unsupported_term());
+
+        // in the same test as `TERM` is shared
+        ::std::env::set_var("TERM", "xterm-256color");
+        assert_eq!(false, super::is_monochrome_term());
+        ::std::env::set_var("TERM", "vt100");
+        assert_eq!(true, super::is_monochrome_term());
+        ::std::env::set_var("TERM", "xterm-mono");
+        assert_eq!(true, super::is_monochrome_term());
     }
 }
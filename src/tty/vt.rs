@@ -0,0 +1,261 @@
+//! Decoding of the escape sequences sent by VT terminals for the keys which
+//! are not characters (arrows, function keys...), shared by the Unix reader
+//! and the Windows one in VT input mode.
+use keys::KeyPress;
+use Result;
+
+/// Handle ESC <seq1> sequences
+pub fn escape_sequence(next_char: &mut FnMut() -> Result<char>) -> Result<KeyPress> {
+    // Read the next byte representing the escape sequence.
+    let seq1 = try!(next_char());
+    if seq1 == '[' {
+        // ESC [ sequences. (CSI)
+        escape_csi(next_char)
+    } else if seq1 == 'O' {
+        // xterm
+        // ESC O sequences. (SS3)
+        escape_o(next_char)
+    } else if seq1 == '\x1b' {
+        // ESC ESC
+        Ok(KeyPress::Esc)
+    } else {
+        // TODO ESC-R (r): Undo all changes made to this line.
+        Ok(KeyPress::Meta(seq1))
+    }
+}
+
+/// Handle ESC [ <seq2> escape sequences
+fn escape_csi(next_char: &mut FnMut() -> Result<char>) -> Result<KeyPress> {
+    let seq2 = try!(next_char());
+    if seq2.is_digit(10) {
+        match seq2 {
+            '0' | '9' => {
+                debug!(target: "rustyline", "unsupported esc sequence: ESC [ {:?}", seq2);
+                Ok(KeyPress::UnknownEscSeq)
+            }
+            _ => {
+                // Extended escape, read additional byte.
+                extended_escape(next_char, seq2)
+            }
+        }
+    } else if seq2 == '[' {
+        let seq3 = try!(next_char());
+        // Linux console
+        Ok(match seq3 {
+            'A' => KeyPress::F(1),
+            'B' => KeyPress::F(2),
+            'C' => KeyPress::F(3),
+            'D' => KeyPress::F(4),
+            'E' => KeyPress::F(5),
+            _ => {
+                debug!(target: "rustyline", "unsupported esc sequence: ESC [ [ {:?}", seq3);
+                KeyPress::UnknownEscSeq
+            }
+        })
+    } else {
+        // ANSI
+        Ok(match seq2 {
+            'A' => KeyPress::Up,    // kcuu1
+            'B' => KeyPress::Down,  // kcud1
+            'C' => KeyPress::Right, // kcuf1
+            'D' => KeyPress::Left,  // kcub1
+            'F' => KeyPress::End,
+            'H' => KeyPress::Home, // khome
+            'Z' => KeyPress::BackTab,
+            _ => {
+                debug!(target: "rustyline", "unsupported esc sequence: ESC [ {:?}", seq2);
+                KeyPress::UnknownEscSeq
+            }
+        })
+    }
+}
+
+/// Handle ESC [ <seq2:digit> escape sequences
+fn extended_escape(next_char: &mut FnMut() -> Result<char>, seq2: char) -> Result<KeyPress> {
+    let seq3 = try!(next_char());
+    if seq3 == '~' {
+        Ok(match seq2 {
+            '1' | '7' => KeyPress::Home, // tmux, xrvt
+            '2' => KeyPress::Insert,
+            '3' => KeyPress::Delete,    // kdch1
+            '4' | '8' => KeyPress::End, // tmux, xrvt
+            '5' => KeyPress::PageUp,    // kpp
+            '6' => KeyPress::PageDown,  // knp
+            _ => {
+                debug!(target: "rustyline",
+                       "unsupported esc sequence: ESC [ {} ~", seq2);
+                KeyPress::UnknownEscSeq
+            }
+        })
+    } else if seq3.is_digit(10) {
+        let seq4 = try!(next_char());
+        if seq4 == '~' {
+            Ok(match (seq2, seq3) {
+                ('1', '1') => KeyPress::F(1),  // rxvt-unicode
+                ('1', '2') => KeyPress::F(2),  // rxvt-unicode
+                ('1', '3') => KeyPress::F(3),  // rxvt-unicode
+                ('1', '4') => KeyPress::F(4),  // rxvt-unicode
+                ('1', '5') => KeyPress::F(5),  // kf5
+                ('1', '7') => KeyPress::F(6),  // kf6
+                ('1', '8') => KeyPress::F(7),  // kf7
+                ('1', '9') => KeyPress::F(8),  // kf8
+                ('2', '0') => KeyPress::F(9),  // kf9
+                ('2', '1') => KeyPress::F(10), // kf10
+                ('2', '3') => KeyPress::F(11), // kf11
+                ('2', '4') => KeyPress::F(12), // kf12
+                _ => {
+                    debug!(target: "rustyline",
+                           "unsupported esc sequence: ESC [ {}{} ~", seq2, seq3);
+                    KeyPress::UnknownEscSeq
+                }
+            })
+        } else if seq4.is_ascii_digit() {
+            let seq5 = try!(next_char());
+            Ok(match (seq2, seq3, seq4, seq5) {
+                ('2', '0', '0', '~') => KeyPress::BracketedPasteStart,
+                ('2', '0', '1', '~') => KeyPress::BracketedPasteEnd,
+                _ => {
+                    debug!(target: "rustyline",
+                           "unsupported esc sequence: ESC [ {}{}{} {:?}", seq2, seq3, seq4, seq5);
+                    KeyPress::UnknownEscSeq
+                }
+            })
+        } else if seq4 == ';' {
+            let seq5 = try!(next_char());
+            if seq5.is_digit(10) {
+                let seq6 = try!(next_char()); // '~' expected
+                debug!(target: "rustyline",
+                       "unsupported esc sequence: ESC [ {}{} ; {} {}", seq2, seq3, seq5, seq6);
+            } else {
+                debug!(target: "rustyline",
+                       "unsupported esc sequence: ESC [ {}{} ; {:?}", seq2, seq3, seq5);
+            }
+            Ok(KeyPress::UnknownEscSeq)
+        } else {
+            debug!(target: "rustyline",
+                   "unsupported esc sequence: ESC [ {}{} {:?}", seq2, seq3, seq4);
+            Ok(KeyPress::UnknownEscSeq)
+        }
+    } else if seq3 == ';' {
+        let seq4 = try!(next_char());
+        if seq4.is_digit(10) {
+            let seq5 = try!(next_char());
+            if seq2 == '1' {
+                Ok(match (seq4, seq5) {
+                    ('5', 'A') => KeyPress::ControlUp,
+                    ('5', 'B') => KeyPress::ControlDown,
+                    ('5', 'C') => KeyPress::ControlRight,
+                    ('5', 'D') => KeyPress::ControlLeft,
+                    ('2', 'A') => KeyPress::ShiftUp,
+                    ('2', 'B') => KeyPress::ShiftDown,
+                    ('2', 'C') => KeyPress::ShiftRight,
+                    ('2', 'D') => KeyPress::ShiftLeft,
+                    ('2', 'F') => KeyPress::ShiftEnd,
+                    ('2', 'H') => KeyPress::ShiftHome,
+                    ('4', 'C') => KeyPress::MetaShiftRight,
+                    ('4', 'D') => KeyPress::MetaShiftLeft,
+                    _ => {
+                        debug!(target: "rustyline",
+                               "unsupported esc sequence: ESC [ 1 ; {} {:?}", seq4, seq5);
+                        KeyPress::UnknownEscSeq
+                    }
+                })
+            } else {
+                debug!(target: "rustyline",
+                       "unsupported esc sequence: ESC [ {} ; {} {:?}", seq2, seq4, seq5);
+                Ok(KeyPress::UnknownEscSeq)
+            }
+        } else {
+            debug!(target: "rustyline",
+                   "unsupported esc sequence: ESC [ {} ; {:?}", seq2, seq4);
+            Ok(KeyPress::UnknownEscSeq)
+        }
+    } else {
+        Ok(match (seq2, seq3) {
+            ('5', 'A') => KeyPress::ControlUp,
+            ('5', 'B') => KeyPress::ControlDown,
+            ('5', 'C') => KeyPress::ControlRight,
+            ('5', 'D') => KeyPress::ControlLeft,
+            _ => {
+                debug!(target: "rustyline",
+                       "unsupported esc sequence: ESC [ {} {:?}", seq2, seq3);
+                KeyPress::UnknownEscSeq
+            }
+        })
+    }
+}
+
+/// Handle ESC O <seq2> escape sequences
+fn escape_o(next_char: &mut FnMut() -> Result<char>) -> Result<KeyPress> {
+    let seq2 = try!(next_char());
+    Ok(match seq2 {
+        'A' => KeyPress::Up,    // kcuu1
+        'B' => KeyPress::Down,  // kcud1
+        'C' => KeyPress::Right, // kcuf1
+        'D' => KeyPress::Left,  // kcub1
+        'F' => KeyPress::End,   // kend
+        'H' => KeyPress::Home,  // khome
+        'P' => KeyPress::F(1),  // kf1
+        'Q' => KeyPress::F(2),  // kf2
+        'R' => KeyPress::F(3),  // kf3
+        'S' => KeyPress::F(4),  // kf4
+        'a' => KeyPress::ControlUp,
+        'b' => KeyPress::ControlDown,
+        'c' => KeyPress::ControlRight, // rxvt
+        'd' => KeyPress::ControlLeft,  // rxvt
+        _ => {
+            debug!(target: "rustyline", "unsupported esc sequence: ESC O {:?}", seq2);
+            KeyPress::UnknownEscSeq
+        }
+    })
+}
+
+/// Read the text of a bracketed paste until its end (`ESC [ 201 ~`), the
+/// new lines being normalized to `\n`.
+#[cfg(unix)]
+pub fn pasted_text(next_char: &mut FnMut() -> Result<char>) -> Result<String> {
+    const END: &str = "\x1b[201~";
+    let mut text = String::new();
+    while !text.ends_with(END) {
+        text.push(try!(next_char()));
+    }
+    let len = text.len() - END.len();
+    text.truncate(len);
+    Ok(text.replace("\r\n", "\n").replace('\r', "\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use error::ReadlineError;
+    use keys::KeyPress;
+    use Result;
+
+    /// Decode `seq` (without the leading ESC)
+    fn decode(seq: &str) -> Result<KeyPress> {
+        let mut chars = seq.chars();
+        super::escape_sequence(&mut || chars.next().ok_or(ReadlineError::Eof))
+    }
+
+    #[test]
+    fn escape_sequence() {
+        assert_eq!(KeyPress::Up, decode("[A").unwrap());
+        assert_eq!(KeyPress::Home, decode("OH").unwrap());
+        assert_eq!(KeyPress::Delete, decode("[3~").unwrap());
+        assert_eq!(KeyPress::F(5), decode("[15~").unwrap());
+        assert_eq!(KeyPress::ControlRight, decode("[1;5C").unwrap());
+        assert_eq!(KeyPress::MetaShiftLeft, decode("[1;4D").unwrap());
+        assert_eq!(KeyPress::Meta('b'), decode("b").unwrap());
+        assert_eq!(KeyPress::UnknownEscSeq, decode("[0").unwrap());
+        assert_eq!(KeyPress::BracketedPasteStart, decode("[200~").unwrap());
+        assert!(decode("[").is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn pasted_text() {
+        let mut chars = "a\r\nb\rc\x1b[A\x1b[201~d".chars();
+        let text = super::pasted_text(&mut || chars.next().ok_or(ReadlineError::Eof));
+        assert_eq!("a\nb\nc\x1b[A", text.unwrap());
+        assert_eq!(Some('d'), chars.next());
+    }
+}
@@ -1,23 +1,38 @@
 //! Windows specific definitions
-use std::io::{self, Stdout, Write};
+use std::cmp;
+use std::io::{self, Write};
 use std::mem;
+use std::process::{Command, Stdio};
+use std::ptr;
+use std::slice;
+use std::str;
 use std::sync::atomic;
-
-use unicode_width::UnicodeWidthChar;
-use winapi::shared::minwindef::{DWORD, WORD};
-use winapi::um::winnt::{CHAR, HANDLE};
-use winapi::um::{consoleapi, handleapi, processenv, winbase, wincon, winuser};
-
-use super::{truncate, Position, RawMode, RawReader, Renderer, Term};
-use config::{ColorMode, Config};
+use std::thread;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::{DWORD, MAX_PATH, WORD};
+use winapi::um::winnt::{CHAR, HANDLE, WCHAR};
+use winapi::um::{
+    consoleapi, fileapi, handleapi, minwinbase, namedpipeapi, processenv, synchapi, winbase,
+    wincon, winuser,
+};
+
+use super::{
+    char_width, truncate, vt, vt_move_cursor, vt_refresh, vt_refresh_span, width, Position,
+    RawMode, RawReader, Renderer, Term, View,
+};
+use config::{ColorMode, Config, OutputStreamType};
 use error;
 use highlight::Highlighter;
-use keys::{self, KeyPress};
+use keys::{self, KeyPress, Utf16Decoder};
 use line_buffer::LineBuffer;
 use Result;
 
 const STDIN_FILENO: DWORD = winbase::STD_INPUT_HANDLE;
 const STDOUT_FILENO: DWORD = winbase::STD_OUTPUT_HANDLE;
+const STDERR_FILENO: DWORD = winbase::STD_ERROR_HANDLE;
 
 fn get_std_handle(fd: DWORD) -> Result<HANDLE> {
     let handle = unsafe { processenv::GetStdHandle(fd) };
@@ -60,19 +75,80 @@ fn get_console_mode(handle: HANDLE) -> Result<DWORD> {
     Ok(original_mode)
 }
 
+/// Tell if `handle` is the named pipe of a MSYS or Cygwin pty (mintty, Git
+/// Bash), like `\msys-1888ae32e00d56aa-pty0-from-master`.
+fn is_msys_pty(handle: HANDLE) -> bool {
+    if handle.is_null() {
+        return false;
+    }
+    let size = mem::size_of::<fileapi::FILE_NAME_INFO>() + MAX_PATH * mem::size_of::<WCHAR>();
+    // `DWORD`s for the alignment of `FILE_NAME_INFO`
+    let mut buf: Vec<DWORD> = vec![0; (size + 3) / 4];
+    let rc = unsafe {
+        winbase::GetFileInformationByHandleEx(
+            handle,
+            minwinbase::FileNameInfo,
+            buf.as_mut_ptr() as *mut c_void,
+            (buf.len() * 4) as DWORD,
+        )
+    };
+    if rc == 0 {
+        return false;
+    }
+    let name = unsafe {
+        let info = &*(buf.as_ptr() as *const fileapi::FILE_NAME_INFO);
+        let len = cmp::min(info.FileNameLength as usize / 2, MAX_PATH);
+        String::from_utf16_lossy(slice::from_raw_parts(info.FileName.as_ptr(), len))
+    };
+    (name.contains("msys-") || name.contains("cygwin-")) && name.contains("-pty")
+}
+
+/// Run the MSYS/Cygwin `stty` on the pty connected to stdin (the pipe modes
+/// cannot be changed by a native program).
+fn stty(args: &[&str]) -> Result<String> {
+    let output = try!(Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output());
+    if !output.status.success() {
+        try!(Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("stty {} failed", args.join(" ")),
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+/// Size of the pty (columns, rows), or 80x24 if `stty` fails.
+fn get_pty_size() -> (usize, usize) {
+    let size = stty(&["size"]).unwrap_or_default();
+    let mut size = size.split_whitespace().map(str::parse::<usize>);
+    match (size.next(), size.next()) {
+        (Some(Ok(rows)), Some(Ok(cols))) if rows > 0 && cols > 0 => (cols, rows),
+        _ => (80, 24),
+    }
+}
+
 pub type Mode = ConsoleMode;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 pub struct ConsoleMode {
     original_stdin_mode: DWORD,
     stdin_handle: HANDLE,
     original_stdout_mode: Option<DWORD>,
     stdout_handle: HANDLE,
+    /// `stty -g` settings of a MSYS/Cygwin pty
+    original_pty_mode: Option<String>,
 }
 
 impl RawMode for Mode {
     /// Disable RAW mode for the terminal.
     fn disable_raw_mode(&self) -> Result<()> {
+        if let Some(ref settings) = self.original_pty_mode {
+            try!(stty(&[settings.as_str()]));
+            return Ok(());
+        }
         check!(consoleapi::SetConsoleMode(
             self.stdin_handle,
             self.original_stdin_mode,
@@ -90,30 +166,89 @@ impl RawMode for Mode {
 /// Console input reader
 pub struct ConsoleRawReader {
     handle: HANDLE,
-    buf: [u16; 2],
+    decoder: Utf16Decoder,
+    replace_invalid_input: bool,
+    /// Keys which are not characters received as escape sequences
+    vt_input: bool,
+    timeout_ms: i32,
+    /// Bytes read from a MSYS/Cygwin pty instead of console input events
+    pty: bool,
 }
 
 impl ConsoleRawReader {
-    pub fn new() -> Result<ConsoleRawReader> {
+    pub fn new(config: &Config, vt_input: bool, pty: bool) -> Result<ConsoleRawReader> {
         let handle = try!(get_std_handle(STDIN_FILENO));
         Ok(ConsoleRawReader {
             handle,
-            buf: [0; 2],
+            decoder: Utf16Decoder::default(),
+            replace_invalid_input: config.replace_invalid_input(),
+            vt_input,
+            timeout_ms: config.keyseq_timeout(),
+            pty,
         })
     }
+
+    /// Wait at most `timeout_ms` (or indefinitely when negative) for some
+    /// input event.
+    fn poll(&self, timeout_ms: i32) -> bool {
+        let timeout = if timeout_ms < 0 {
+            winbase::INFINITE
+        } else {
+            timeout_ms as DWORD
+        };
+        unsafe { synchapi::WaitForSingleObject(self.handle, timeout) == winbase::WAIT_OBJECT_0 }
+    }
+
+    /// Next character of an escape sequence (in VT input mode)
+    fn next_char(&mut self) -> Result<char> {
+        let mut rec: wincon::INPUT_RECORD = unsafe { mem::zeroed() };
+        let mut count = 0;
+        loop {
+            check!(consoleapi::ReadConsoleInputW(
+                self.handle,
+                &mut rec,
+                1 as DWORD,
+                &mut count,
+            ));
+            if rec.EventType == wincon::WINDOW_BUFFER_SIZE_EVENT {
+                SIGWINCH.store(true, atomic::Ordering::SeqCst);
+                continue;
+            } else if rec.EventType != wincon::KEY_EVENT {
+                continue;
+            }
+            let key_event = unsafe { rec.Event.KeyEvent() };
+            let utf16 = unsafe { *key_event.uChar.UnicodeChar() };
+            if key_event.bKeyDown == 0 || utf16 == 0 {
+                continue;
+            }
+            match self.decoder.decode(utf16) {
+                Some(Ok(c)) => return Ok(c),
+                Some(Err(_)) if self.replace_invalid_input => return Ok('\u{FFFD}'),
+                Some(Err(err)) => return Err(err.into()),
+                None => continue,
+            }
+        }
+    }
 }
 
 impl RawReader for ConsoleRawReader {
-    fn next_key(&mut self, _: bool) -> Result<KeyPress> {
-        use std::char::decode_utf16;
+    fn next_key(&mut self, single_esc_abort: bool) -> Result<KeyPress> {
         use winapi::um::wincon::{
             LEFT_ALT_PRESSED, LEFT_CTRL_PRESSED, RIGHT_ALT_PRESSED, RIGHT_CTRL_PRESSED,
             SHIFT_PRESSED,
         };
 
+        if self.pty {
+            return self.next_pty_key(single_esc_abort);
+        }
+        if let Some(unit) = self.decoder.take_retry() {
+            // received just after an unpaired high surrogate
+            if let Some(rc) = self.decoder.decode(unit) {
+                return self.char_key(rc, false, false, false);
+            }
+        }
         let mut rec: wincon::INPUT_RECORD = unsafe { mem::zeroed() };
         let mut count = 0;
-        let mut surrogate = false;
         loop {
             // TODO GetNumberOfConsoleInputEvents
             check!(consoleapi::ReadConsoleInputW(
@@ -132,7 +267,14 @@ impl RawReader for ConsoleRawReader {
             }
             let key_event = unsafe { rec.Event.KeyEvent() };
             // writeln!(io::stderr(), "key_event: {:?}", key_event).unwrap();
-            if key_event.bKeyDown == 0 && key_event.wVirtualKeyCode != winuser::VK_MENU as WORD {
+            let utf16 = unsafe { *key_event.uChar.UnicodeChar() };
+            // characters composed with Alt+numpad are delivered on the Alt key
+            // release, and the ones committed by an IME may be delivered
+            // without any virtual key (nor key press)
+            if key_event.bKeyDown == 0
+                && !(utf16 != 0 && key_event.wVirtualKeyCode == winuser::VK_MENU as WORD)
+                && !(utf16 != 0 && key_event.wVirtualKeyCode == 0)
+            {
                 continue;
             }
             // key_event.wRepeatCount seems to be always set to 1 (maybe because we only
@@ -145,12 +287,13 @@ impl RawReader for ConsoleRawReader {
             let meta = alt && !alt_gr;
             let shift = key_event.dwControlKeyState & SHIFT_PRESSED != 0;
 
-            let utf16 = unsafe { *key_event.uChar.UnicodeChar() };
             if utf16 == 0 {
                 match key_event.wVirtualKeyCode as i32 {
                     winuser::VK_LEFT => {
                         return Ok(if ctrl {
                             KeyPress::ControlLeft
+                        } else if meta && shift {
+                            KeyPress::MetaShiftLeft
                         } else if shift {
                             KeyPress::ShiftLeft
                         } else {
@@ -160,6 +303,8 @@ impl RawReader for ConsoleRawReader {
                     winuser::VK_RIGHT => {
                         return Ok(if ctrl {
                             KeyPress::ControlRight
+                        } else if meta && shift {
+                            KeyPress::MetaShiftRight
                         } else if shift {
                             KeyPress::ShiftRight
                         } else {
@@ -185,8 +330,20 @@ impl RawReader for ConsoleRawReader {
                         })
                     }
                     winuser::VK_DELETE => return Ok(KeyPress::Delete),
-                    winuser::VK_HOME => return Ok(KeyPress::Home),
-                    winuser::VK_END => return Ok(KeyPress::End),
+                    winuser::VK_HOME => {
+                        return Ok(if shift {
+                            KeyPress::ShiftHome
+                        } else {
+                            KeyPress::Home
+                        })
+                    }
+                    winuser::VK_END => {
+                        return Ok(if shift {
+                            KeyPress::ShiftEnd
+                        } else {
+                            KeyPress::End
+                        })
+                    }
                     winuser::VK_PRIOR => return Ok(KeyPress::PageUp),
                     winuser::VK_NEXT => return Ok(KeyPress::PageDown),
                     winuser::VK_INSERT => return Ok(KeyPress::Insert),
@@ -207,57 +364,187 @@ impl RawReader for ConsoleRawReader {
                     _ => continue,
                 };
             } else if utf16 == 27 {
+                if self.vt_input {
+                    let timeout_ms = if single_esc_abort && self.timeout_ms == -1 {
+                        0
+                    } else {
+                        self.timeout_ms
+                    };
+                    if self.poll(timeout_ms) {
+                        let key = try!(vt::escape_sequence(&mut || self.next_char()));
+                        debug!(target: "rustyline", "key: {:?}", key);
+                        return Ok(key);
+                    }
+                }
                 return Ok(KeyPress::Esc);
             } else {
-                if utf16 >= 0xD800 && utf16 < 0xDC00 {
-                    surrogate = true;
-                    self.buf[0] = utf16;
-                    continue;
-                }
-                let buf = if surrogate {
-                    self.buf[1] = utf16;
-                    &self.buf[..]
-                } else {
-                    self.buf[0] = utf16;
-                    &self.buf[..1]
-                };
-                let orc = decode_utf16(buf.iter().cloned()).next();
-                if orc.is_none() {
-                    return Err(error::ReadlineError::Eof);
-                }
-                let c = try!(orc.unwrap());
-                if meta {
-                    return Ok(KeyPress::Meta(c));
-                } else {
-                    let mut key = keys::char_to_key_press(c);
-                    if key == KeyPress::Tab && shift {
-                        key = KeyPress::BackTab;
-                    } else if key == KeyPress::Char(' ') && ctrl {
-                        key = KeyPress::Ctrl(' ');
-                    }
-                    return Ok(key);
+                // the high surrogate of a pair is kept until the low one is
+                // received (in the next event)
+                match self.decoder.decode(utf16) {
+                    Some(rc) => return self.char_key(rc, meta, ctrl, shift),
+                    None => continue,
                 }
             }
         }
     }
+
+    fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        let ms = timeout.as_secs() * 1000 + u64::from(timeout.subsec_millis());
+        let ms = cmp::min(ms, i32::MAX as u64) as i32;
+        if self.pty {
+            self.poll_pty(ms)
+        } else {
+            self.poll(ms)
+        }
+    }
+}
+
+impl ConsoleRawReader {
+    /// Like on Unix, the keys which are not characters are received from a
+    /// MSYS/Cygwin pty as escape sequences.
+    fn next_pty_key(&mut self, single_esc_abort: bool) -> Result<KeyPress> {
+        let c = try!(self.next_pty_char());
+        let mut key = keys::char_to_key_press(c);
+        if key == KeyPress::Esc {
+            let timeout_ms = if single_esc_abort && self.timeout_ms == -1 {
+                0
+            } else {
+                self.timeout_ms
+            };
+            if self.poll_pty(timeout_ms) {
+                key = try!(vt::escape_sequence(&mut || self.next_pty_char()));
+            }
+        }
+        debug!(target: "rustyline", "key: {:?}", key);
+        Ok(key)
+    }
+
+    /// Wait at most `timeout_ms` (or indefinitely when negative) for some
+    /// bytes in the pty pipe (which cannot be waited on).
+    fn poll_pty(&self, timeout_ms: i32) -> bool {
+        let start = Instant::now();
+        loop {
+            let mut available: DWORD = 0;
+            let rc = unsafe {
+                namedpipeapi::PeekNamedPipe(
+                    self.handle,
+                    ptr::null_mut(),
+                    0,
+                    ptr::null_mut(),
+                    &mut available,
+                    ptr::null_mut(),
+                )
+            };
+            if rc == 0 {
+                return false;
+            } else if available > 0 {
+                return true;
+            } else if timeout_ms >= 0 && start.elapsed() >= Duration::from_millis(timeout_ms as u64)
+            {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn read_pty_byte(&self) -> Result<u8> {
+        let mut byte = 0u8;
+        let mut count: DWORD = 0;
+        check!(fileapi::ReadFile(
+            self.handle,
+            &mut byte as *mut u8 as *mut c_void,
+            1,
+            &mut count,
+            ptr::null_mut(),
+        ));
+        if count == 0 {
+            return Err(error::ReadlineError::Eof);
+        }
+        Ok(byte)
+    }
+
+    /// Next UTF-8 encoded character read from a MSYS/Cygwin pty
+    fn next_pty_char(&mut self) -> Result<char> {
+        let mut bytes = [try!(self.read_pty_byte()), 0, 0, 0];
+        let len = match bytes[0] {
+            0x00...0x7f => 1,
+            0xc0...0xdf => 2,
+            0xe0...0xef => 3,
+            0xf0...0xf7 => 4,
+            _ => 1,
+        };
+        for byte in bytes.iter_mut().take(len).skip(1) {
+            *byte = try!(self.read_pty_byte());
+        }
+        match str::from_utf8(&bytes[..len]) {
+            Ok(s) => Ok(s.chars().next().unwrap()),
+            Err(_) if self.replace_invalid_input => Ok('\u{FFFD}'),
+            Err(err) => Err(io::Error::new(io::ErrorKind::InvalidData, err).into()),
+        }
+    }
+}
+
+impl ConsoleRawReader {
+    fn char_key(
+        &self,
+        rc: ::std::result::Result<char, ::std::char::DecodeUtf16Error>,
+        meta: bool,
+        ctrl: bool,
+        shift: bool,
+    ) -> Result<KeyPress> {
+        let c = match rc {
+            Ok(c) => c,
+            Err(_) if self.replace_invalid_input => '\u{FFFD}',
+            Err(err) => return Err(err.into()),
+        };
+        if meta {
+            Ok(KeyPress::Meta(c))
+        } else {
+            let mut key = keys::char_to_key_press(c);
+            if key == KeyPress::Tab && shift {
+                key = KeyPress::BackTab;
+            } else if key == KeyPress::Char(' ') && ctrl {
+                key = KeyPress::Ctrl(' ');
+            }
+            Ok(key)
+        }
+    }
 }
 
 pub struct ConsoleRenderer {
-    out: Stdout,
+    out: Box<Write>,
+    stream: OutputStreamType,
     handle: HANDLE,
     cols: usize, // Number of columns in terminal
+    rows: usize, // Number of rows in terminal
     buffer: String,
+    pty: bool,  // ANSI escape sequences instead of console functions
+    view: View, // Displayed rows of the line (on a pty)
+    wide_ambiguous: bool,
 }
 
 impl ConsoleRenderer {
-    fn new(handle: HANDLE) -> ConsoleRenderer {
+    fn new(handle: HANDLE, stream: OutputStreamType, pty: bool) -> ConsoleRenderer {
         // Multi line editing is enabled by ENABLE_WRAP_AT_EOL_OUTPUT mode
-        let (cols, _) = get_win_size(handle);
+        let (cols, rows) = if pty {
+            get_pty_size()
+        } else {
+            get_win_size(handle)
+        };
+        let out: Box<Write> = match stream {
+            OutputStreamType::Stdout => Box::new(io::stdout()),
+            OutputStreamType::Stderr => Box::new(io::stderr()),
+        };
         ConsoleRenderer {
-            out: io::stdout(),
+            out,
+            stream,
             handle,
             cols,
+            rows,
             buffer: String::with_capacity(1024),
+            pty,
+            view: View::default(),
+            wide_ambiguous: false,
         }
     }
 
@@ -287,6 +574,14 @@ impl ConsoleRenderer {
 
 impl Renderer for ConsoleRenderer {
     fn move_cursor(&mut self, old: Position, new: Position) -> Result<()> {
+        if self.pty {
+            self.buffer.clear();
+            let (old, new) = (self.view.screen_pos(old), self.view.screen_pos(new));
+            vt_move_cursor(&mut self.buffer, old, new);
+            try!(self.out.write_all(self.buffer.as_bytes()));
+            try!(self.out.flush());
+            return Ok(());
+        }
         let mut info = try!(self.get_console_screen_buffer_info());
         if new.row > old.row {
             info.dwCursorPosition.Y += (new.row - old.row) as i16;
@@ -316,6 +611,28 @@ impl Renderer for ConsoleRenderer {
         // calculate the desired position of the cursor
         let cursor = self.calculate_position(&line[..line.pos()], prompt_size);
 
+        if self.pty {
+            self.buffer.clear();
+            let hint = hint
+                .as_ref()
+                .map(|hint| truncate(hint, end_pos.col, self.cols, self.wide_ambiguous));
+            let old_view = self.view;
+            let size = (self.cols, self.rows);
+            self.view = old_view.scroll(line, prompt_size, size, (cursor, end_pos));
+            vt_refresh(
+                &mut self.buffer,
+                prompt,
+                line,
+                hint,
+                (current_row, old_rows, old_view),
+                highlighter,
+                (cursor, end_pos, self.view),
+            );
+            try!(self.out.write_all(self.buffer.as_bytes()));
+            try!(self.out.flush());
+            return Ok((cursor, end_pos));
+        }
+
         // position at the start of the prompt, clear to end of previous input
         let mut info = try!(self.get_console_screen_buffer_info());
         info.dwCursorPosition.X = 0;
@@ -341,7 +658,7 @@ impl Renderer for ConsoleRenderer {
         }
         // display hint
         if let Some(hint) = hint {
-            let truncate = truncate(&hint, end_pos.col, self.cols);
+            let truncate = truncate(&hint, end_pos.col, self.cols, self.wide_ambiguous);
             if let Some(highlighter) = highlighter {
                 self.buffer.push_str(&highlighter.highlight_hint(truncate));
             } else {
@@ -353,12 +670,53 @@ impl Renderer for ConsoleRenderer {
 
         // position the cursor
         let mut info = try!(self.get_console_screen_buffer_info());
+        // with VT processing, the console wraps only on the next character
+        if end_pos.col == 0 && end_pos.row > 0 && info.dwCursorPosition.X != 0 {
+            try!(self.out.write_all(b"\n"));
+            try!(self.out.flush());
+            info = try!(self.get_console_screen_buffer_info());
+        }
         info.dwCursorPosition.X = cursor.col as i16;
         info.dwCursorPosition.Y -= (end_pos.row - cursor.row) as i16;
         try!(self.set_console_cursor_position(info.dwCursorPosition));
         Ok((cursor, end_pos))
     }
 
+    fn refresh_span(
+        &mut self,
+        prompt_size: Position,
+        line: &LineBuffer,
+        from: usize,
+        hint: Option<String>,
+        current: Position,
+    ) -> Result<Option<(Position, Position)>> {
+        if !self.pty {
+            // only with ANSI escape sequences
+            return Ok(None);
+        }
+        let start = self.calculate_position(&line[..from], prompt_size);
+        let end_pos = self.calculate_position(&line[from..], start);
+        if self.view.rows > 0 || end_pos.row >= self.rows {
+            // the rows of the line are not all displayed
+            return Ok(None);
+        }
+        let cursor = self.calculate_position(&line[..line.pos()], prompt_size);
+        let hint = hint
+            .as_ref()
+            .map(|hint| truncate(hint, end_pos.col, self.cols, self.wide_ambiguous));
+        self.buffer.clear();
+        vt_refresh_span(
+            &mut self.buffer,
+            line,
+            (from, hint),
+            (current, start),
+            (cursor, end_pos),
+        );
+        try!(self.out.write_all(self.buffer.as_bytes()));
+        try!(self.out.flush());
+        Ok(Some((cursor, end_pos)))
+    }
+
     fn write_and_flush(&mut self, buf: &[u8]) -> Result<()> {
         try!(self.out.write_all(buf));
         try!(self.out.flush());
@@ -368,13 +726,35 @@ impl Renderer for ConsoleRenderer {
     /// Characters with 2 column width are correctly handled (not splitted).
     fn calculate_position(&self, s: &str, orig: Position) -> Position {
         let mut pos = orig;
+        if self.pty {
+            // ANSI escape sequences are interpreted by the terminal
+            let mut esc_seq = 0;
+            for g in s.graphemes(true) {
+                if g == "\n" {
+                    pos.row += 1;
+                    pos.col = 0;
+                    continue;
+                }
+                let cw = width(g, &mut esc_seq, self.wide_ambiguous);
+                pos.col += cw;
+                if pos.col > self.cols {
+                    pos.row += 1;
+                    pos.col = cw;
+                }
+            }
+            if pos.col == self.cols {
+                pos.col = 0;
+                pos.row += 1;
+            }
+            return pos;
+        }
         for c in s.chars() {
             let cw = if c == '\n' {
                 pos.col = 0;
                 pos.row += 1;
                 None
             } else {
-                c.width()
+                char_width(c, self.wide_ambiguous)
             };
             if let Some(cw) = cw {
                 pos.col += cw;
@@ -393,6 +773,9 @@ impl Renderer for ConsoleRenderer {
 
     /// Clear the screen. Used to handle ctrl+l
     fn clear_screen(&mut self) -> Result<()> {
+        if self.pty {
+            return self.write_and_flush(b"\x1b[H\x1b[2J");
+        }
         let info = try!(self.get_console_screen_buffer_info());
         let coord = wincon::COORD { X: 0, Y: 0 };
         check!(wincon::SetConsoleCursorPosition(self.handle, coord));
@@ -407,8 +790,13 @@ impl Renderer for ConsoleRenderer {
     /// Try to get the number of columns in the current terminal,
     /// or assume 80 if it fails.
     fn update_size(&mut self) {
-        let (cols, _) = get_win_size(self.handle);
+        let (cols, rows) = if self.pty {
+            get_pty_size()
+        } else {
+            get_win_size(self.handle)
+        };
         self.cols = cols;
+        self.rows = rows;
     }
 
     fn get_columns(&self) -> usize {
@@ -418,8 +806,26 @@ impl Renderer for ConsoleRenderer {
     /// Try to get the number of rows in the current terminal,
     /// or assume 24 if it fails.
     fn get_rows(&self) -> usize {
-        let (_, rows) = get_win_size(self.handle);
-        rows
+        self.rows
+    }
+
+    fn is_row_visible(&self, row: usize) -> bool {
+        self.view.is_visible(row)
+    }
+
+    fn shared_writer(&self) -> Option<Box<Write + Send>> {
+        Some(match self.stream {
+            OutputStreamType::Stdout => Box::new(io::stdout()),
+            OutputStreamType::Stderr => Box::new(io::stderr()),
+        })
+    }
+
+    fn set_wide_ambiguous(&mut self, wide: bool) {
+        self.wide_ambiguous = wide;
+    }
+
+    fn is_wide_ambiguous(&self) -> bool {
+        self.wide_ambiguous
     }
 }
 
@@ -434,18 +840,53 @@ pub struct Console {
     stdout_isatty: bool,
     stdout_handle: HANDLE,
     pub(crate) color_mode: ColorMode,
+    stream: OutputStreamType,
     ansi_colors_supported: bool,
+    vt_input_supported: bool,
+    /// Stdin and output are not a console but a MSYS/Cygwin pty (mintty)
+    pty: bool,
 }
 
-impl Console {}
+impl Console {
+    pub(crate) fn set_output_stream(&mut self, stream: OutputStreamType) {
+        let (stdout_handle, stdout_isatty) = output_handle(stream);
+        self.stdout_handle = stdout_handle;
+        self.stdout_isatty = stdout_isatty;
+        self.stream = stream;
+        self.pty = is_pty(
+            self.stdin_isatty,
+            self.stdin_handle,
+            stdout_isatty,
+            stdout_handle,
+        );
+    }
+}
+
+fn is_pty(stdin_isatty: bool, stdin: HANDLE, stdout_isatty: bool, stdout: HANDLE) -> bool {
+    !stdin_isatty && !stdout_isatty && is_msys_pty(stdin) && is_msys_pty(stdout)
+}
+
+/// Handle of the output `stream`, and if it is a console
+fn output_handle(stream: OutputStreamType) -> (HANDLE, bool) {
+    let fd = match stream {
+        OutputStreamType::Stdout => STDOUT_FILENO,
+        OutputStreamType::Stderr => STDERR_FILENO,
+    };
+    match get_std_handle(fd) {
+        Ok(handle) => {
+            // If this function doesn't fail then fd is a TTY
+            (handle, get_console_mode(handle).is_ok())
+        }
+        Err(_) => (ptr::null_mut(), false),
+    }
+}
 
 impl Term for Console {
     type Mode = Mode;
     type Reader = ConsoleRawReader;
     type Writer = ConsoleRenderer;
 
-    fn new(color_mode: ColorMode) -> Console {
-        use std::ptr;
+    fn new(color_mode: ColorMode, stream: OutputStreamType) -> Console {
         let stdin_handle = get_std_handle(STDIN_FILENO);
         let stdin_isatty = match stdin_handle {
             Ok(handle) => {
@@ -454,22 +895,19 @@ impl Term for Console {
             }
             Err(_) => false,
         };
-        let stdout_handle = get_std_handle(STDOUT_FILENO);
-        let stdout_isatty = match stdout_handle {
-            Ok(handle) => {
-                // If this function doesn't fail then fd is a TTY
-                get_console_mode(handle).is_ok()
-            }
-            Err(_) => false,
-        };
+        let stdin_handle = stdin_handle.unwrap_or(ptr::null_mut());
+        let (stdout_handle, stdout_isatty) = output_handle(stream);
 
         Console {
             stdin_isatty,
-            stdin_handle: stdin_handle.unwrap_or(ptr::null_mut()),
+            stdin_handle,
             stdout_isatty,
-            stdout_handle: stdout_handle.unwrap_or(ptr::null_mut()),
+            stdout_handle,
             color_mode,
+            stream,
             ansi_colors_supported: false,
+            vt_input_supported: false,
+            pty: is_pty(stdin_isatty, stdin_handle, stdout_isatty, stdout_handle),
         }
     }
 
@@ -479,24 +917,41 @@ impl Term for Console {
     }
 
     fn is_stdin_tty(&self) -> bool {
-        self.stdin_isatty
+        self.stdin_isatty || self.pty
     }
 
     fn colors_enabled(&self) -> bool {
         // TODO ANSI Colors & Windows <10
-        match self.color_mode {
-            ColorMode::Enabled => self.stdout_isatty && self.ansi_colors_supported,
+        match self.color_mode.resolve() {
+            ColorMode::Enabled | ColorMode::Monochrome => {
+                self.stdout_isatty && self.ansi_colors_supported || self.pty
+            }
             ColorMode::Forced => true,
             ColorMode::Disabled => false,
         }
     }
 
+    fn is_monochrome(&self) -> bool {
+        self.color_mode.resolve() == ColorMode::Monochrome
+    }
+
     // pub fn install_sigwinch_handler(&mut self) {
     // See ReadConsoleInputW && WINDOW_BUFFER_SIZE_EVENT
     // }
 
     /// Enable RAW mode for the terminal.
     fn enable_raw_mode(&mut self) -> Result<Mode> {
+        if self.pty {
+            let settings = try!(stty(&["-g"]));
+            try!(stty(&["raw", "-echo"]));
+            return Ok(Mode {
+                original_stdin_mode: 0,
+                stdin_handle: self.stdin_handle,
+                original_stdout_mode: None,
+                stdout_handle: self.stdout_handle,
+                original_pty_mode: Some(settings),
+            });
+        }
         if !self.stdin_isatty {
             try!(Err(io::Error::new(
                 io::ErrorKind::Other,
@@ -513,7 +968,17 @@ impl Term for Console {
         raw |= wincon::ENABLE_INSERT_MODE;
         raw |= wincon::ENABLE_QUICK_EDIT_MODE;
         raw |= wincon::ENABLE_WINDOW_INPUT;
-        check!(consoleapi::SetConsoleMode(self.stdin_handle, raw));
+        // Keys received as escape sequences like on Unix (Windows 10 only),
+        // or as virtual key codes by older consoles
+        self.vt_input_supported = unsafe {
+            consoleapi::SetConsoleMode(
+                self.stdin_handle,
+                raw | wincon::ENABLE_VIRTUAL_TERMINAL_INPUT,
+            ) != 0
+        };
+        if !self.vt_input_supported {
+            check!(consoleapi::SetConsoleMode(self.stdin_handle, raw));
+        }
 
         let original_stdout_mode = if self.stdout_isatty {
             let original_stdout_mode = try!(get_console_mode(self.stdout_handle));
@@ -534,14 +999,15 @@ impl Term for Console {
             stdin_handle: self.stdin_handle,
             original_stdout_mode,
             stdout_handle: self.stdout_handle,
+            original_pty_mode: None,
         })
     }
 
-    fn create_reader(&self, _: &Config) -> Result<ConsoleRawReader> {
-        ConsoleRawReader::new()
+    fn create_reader(&self, config: &Config) -> Result<ConsoleRawReader> {
+        ConsoleRawReader::new(config, self.vt_input_supported, self.pty)
     }
 
     fn create_writer(&self) -> ConsoleRenderer {
-        ConsoleRenderer::new(self.stdout_handle)
+        ConsoleRenderer::new(self.stdout_handle, self.stream, self.pty)
     }
 }
@@ -1,8 +1,11 @@
 //! Undo API
 use std::fmt::Debug;
+use std::time::{Duration, Instant};
 
+use config::UndoGrouping;
 use keymap::RepeatCount;
 use line_buffer::{ChangeListener, DeleteListener, Direction, LineBuffer};
+use snapshot::{escape, unescape};
 use unicode_segmentation::UnicodeSegmentation;
 
 enum Change {
@@ -22,11 +25,42 @@ enum Change {
         idx: usize,
         old: String,
         new: String,
-    }, /* CapitalizeWord, Complete, DowncaseWord, Replace, TransposeChars, TransposeWords,
-        * UpcaseWord, YankPop */
+    }, /* CapitalizeWord, Complete, DowncaseWord, Replace, ToggleCase, TransposeChars,
+        * TransposeWords, UpcaseWord, YankPop */
 }
 
 impl Change {
+    /// Text record (fields separated by tabs) saved in a `Snapshot`
+    fn record(&self) -> String {
+        match *self {
+            Change::Begin => "begin".to_owned(),
+            Change::End => "end".to_owned(),
+            Change::Insert { idx, ref text } => format!("insert\t{}\t{}", idx, escape(text)),
+            Change::Delete { idx, ref text } => format!("delete\t{}\t{}", idx, escape(text)),
+            Change::Replace {
+                idx,
+                ref old,
+                ref new,
+            } => format!("replace\t{}\t{}\t{}", idx, escape(old), escape(new)),
+        }
+    }
+
+    fn parse(record: &str) -> Option<Change> {
+        let fields: Vec<&str> = record.split('\t').collect();
+        let idx = fields.get(1).and_then(|idx| idx.parse().ok());
+        let text = |i: usize| fields.get(i).and_then(|text| unescape(text));
+        match (fields[0], fields.len()) {
+            ("begin", 1) => Some(Change::Begin),
+            ("end", 1) => Some(Change::End),
+            ("insert", 3) => idx.and_then(|idx| text(2).map(|text| Change::Insert { idx, text })),
+            ("delete", 3) => idx.and_then(|idx| text(2).map(|text| Change::Delete { idx, text })),
+            ("replace", 4) => idx.and_then(|idx| {
+                text(2).and_then(|old| text(3).map(|new| Change::Replace { idx, old, new }))
+            }),
+            _ => None,
+        }
+    }
+
     fn undo(&self, line: &mut LineBuffer) {
         match *self {
             Change::Begin | Change::End => {
@@ -49,7 +83,6 @@ impl Change {
         }
     }
 
-    #[cfg(test)]
     fn redo(&self, line: &mut LineBuffer) {
         match *self {
             Change::Begin | Change::End => {
@@ -57,6 +90,7 @@ impl Change {
             }
             Change::Insert { idx, ref text } => {
                 line.insert_str(idx, text);
+                line.set_pos(idx + text.len());
             }
             Change::Delete { idx, ref text } => {
                 line.delete_range(idx..idx + text.len());
@@ -71,6 +105,36 @@ impl Change {
         }
     }
 
+    /// Undo the change in `line` (as restored from a `Snapshot`), or return
+    /// `false` when its index or length does not match `line`.
+    fn undo_text(&self, line: &mut String) -> bool {
+        match *self {
+            Change::Begin | Change::End => true,
+            Change::Insert { idx, ref text } => replace_text(line, idx, text.len(), ""),
+            Change::Delete { idx, ref text } => replace_text(line, idx, 0, text),
+            Change::Replace {
+                idx,
+                ref old,
+                ref new,
+            } => replace_text(line, idx, new.len(), old),
+        }
+    }
+
+    /// Redo the change in `line` (as restored from a `Snapshot`), or return
+    /// `false` when its index or length does not match `line`.
+    fn redo_text(&self, line: &mut String) -> bool {
+        match *self {
+            Change::Begin | Change::End => true,
+            Change::Insert { idx, ref text } => replace_text(line, idx, 0, text),
+            Change::Delete { idx, ref text } => replace_text(line, idx, text.len(), ""),
+            Change::Replace {
+                idx,
+                ref old,
+                ref new,
+            } => replace_text(line, idx, old.len(), new),
+        }
+    }
+
     fn insert_seq(&self, indx: usize) -> bool {
         if let Change::Insert { idx, ref text } = *self {
             idx + text.len() == indx
@@ -97,10 +161,26 @@ impl Change {
     }
 }
 
+/// Replace the `len` bytes at `idx` in `line` by `text` (`false` when they
+/// are out of `line` or not at char boundaries).
+fn replace_text(line: &mut String, idx: usize, len: usize, text: &str) -> bool {
+    if idx > line.len() || len > line.len() - idx {
+        return false;
+    }
+    if !line.is_char_boundary(idx) || !line.is_char_boundary(idx + len) {
+        return false;
+    }
+    line.replace_range(idx..idx + len, text);
+    true
+}
+
 pub struct Changeset {
     undo_group_level: u32,
     undos: Vec<Change>, // undoable changes
     redos: Vec<Change>, // undone changes, redoable
+    grouping: UndoGrouping,
+    /// Time of the last insertion or deletion of a character
+    last_change: Instant,
 }
 
 impl Changeset {
@@ -109,9 +189,74 @@ impl Changeset {
             undo_group_level: 0,
             undos: Vec::new(),
             redos: Vec::new(),
+            grouping: UndoGrouping::Word,
+            last_change: Instant::now(),
+        }
+    }
+
+    pub(crate) fn set_grouping(&mut self, grouping: UndoGrouping) {
+        self.grouping = grouping;
+    }
+
+    /// Tell if the insertion or deletion of a character (`alphanumeric` or
+    /// not) can be grouped with the previous one.
+    fn is_grouped(&mut self, alphanumeric: bool) -> bool {
+        let elapsed = self.last_change.elapsed();
+        self.last_change = Instant::now();
+        match self.grouping {
+            UndoGrouping::Char => false,
+            UndoGrouping::Word => alphanumeric,
+            UndoGrouping::Pause(ms) => elapsed <= Duration::from_millis(ms as u64),
         }
     }
 
+    /// Text records of the undoable and redoable changes (for `Snapshot`)
+    pub(crate) fn records(&self) -> Vec<String> {
+        let undos = self
+            .undos
+            .iter()
+            .map(|change| format!("undo\t{}", change.record()));
+        let redos = self
+            .redos
+            .iter()
+            .map(|change| format!("redo\t{}", change.record()));
+        undos.chain(redos).collect()
+    }
+
+    /// Append the change of a `records` entry (return `false` when invalid).
+    pub(crate) fn push_record(&mut self, record: &str) -> bool {
+        let (stack, change) = if record.starts_with("undo\t") {
+            (&mut self.undos, Change::parse(&record[5..]))
+        } else if record.starts_with("redo\t") {
+            (&mut self.redos, Change::parse(&record[5..]))
+        } else {
+            return false;
+        };
+        match change {
+            Some(change) => {
+                stack.push(change);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Tell if all the changes can be undone and redone from `line` (so that
+    /// the records of a stale or tampered `Snapshot` are rejected).
+    pub(crate) fn is_valid_for(&self, line: &str) -> bool {
+        let mut undone = line.to_owned();
+        let mut redone = line.to_owned();
+        self.undos
+            .iter()
+            .rev()
+            .all(|change| change.undo_text(&mut undone))
+            && self
+                .redos
+                .iter()
+                .rev()
+                .all(|change| change.redo_text(&mut redone))
+    }
+
     pub fn begin(&mut self) -> usize {
         debug!(target: "rustyline", "Changeset::begin");
         self.redos.clear();
@@ -144,11 +289,12 @@ impl Changeset {
     pub fn insert(&mut self, idx: usize, c: char) {
         debug!(target: "rustyline", "Changeset::insert({}, {:?})", idx, c);
         self.redos.clear();
-        if !c.is_alphanumeric() || !self.undos.last().map_or(false, |lc| lc.insert_seq(idx)) {
+        let grouped = self.is_grouped(c.is_alphanumeric());
+        if !grouped || !self.undos.last().map_or(false, |lc| lc.insert_seq(idx)) {
             self.undos.push(Self::insert_char(idx, c));
             return;
         }
-        // merge consecutive char insertions when char is alphanumeric
+        // merge consecutive char insertions (of a word by default)
         let mut last_change = self.undos.pop().unwrap();
         if let Change::Insert { ref mut text, .. } = last_change {
             text.push(c);
@@ -177,7 +323,11 @@ impl Changeset {
             return;
         }
 
-        if !Self::single_char(string.as_ref()) || !self
+        let grouped = match Self::single_char(string.as_ref()) {
+            Some(alphanumeric) => self.is_grouped(alphanumeric),
+            None => false,
+        };
+        if !grouped || !self
             .undos
             .last()
             .map_or(false, |lc| lc.delete_seq(indx, string.as_ref().len()))
@@ -188,7 +338,7 @@ impl Changeset {
             });
             return;
         }
-        // merge consecutive char deletions when char is alphanumeric
+        // merge consecutive char deletions (of a word by default)
         let mut last_change = self.undos.pop().unwrap();
         if let Change::Delete {
             ref mut idx,
@@ -207,11 +357,14 @@ impl Changeset {
         self.undos.push(last_change);
     }
 
-    fn single_char(s: &str) -> bool {
+    /// Tell if `s` is a single grapheme, alphanumeric or not (`None` when it
+    /// is not a single grapheme).
+    fn single_char(s: &str) -> Option<bool> {
         let mut graphemes = s.graphemes(true);
-        graphemes.next().map_or(false, |grapheme| {
-            grapheme.chars().all(|c| c.is_alphanumeric())
-        }) && graphemes.next().is_none()
+        match (graphemes.next(), graphemes.next()) {
+            (Some(grapheme), None) => Some(grapheme.chars().all(|c| c.is_alphanumeric())),
+            _ => None,
+        }
     }
 
     pub fn replace<S: AsRef<str> + Into<String> + Debug>(&mut self, indx: usize, old_: S, new_: S) {
@@ -281,8 +434,9 @@ impl Changeset {
         self.undos.truncate(len);
     }
 
-    #[cfg(test)]
-    pub fn redo(&mut self, line: &mut LineBuffer) -> bool {
+    pub fn redo(&mut self, line: &mut LineBuffer, n: RepeatCount) -> bool {
+        debug!(target: "rustyline", "Changeset::redo");
+        let mut count = 0;
         let mut waiting_for_end = 0;
         let mut redone = false;
         loop {
@@ -304,7 +458,10 @@ impl Changeset {
                 break;
             }
             if waiting_for_end <= 0 {
-                break;
+                count += 1;
+                if count >= n {
+                    break;
+                }
             }
         }
         redone
@@ -352,7 +509,11 @@ impl ChangeListener for Changeset {
 
 #[cfg(test)]
 mod tests {
+    use std::thread;
+    use std::time::Duration;
+
     use super::Changeset;
+    use config::UndoGrouping;
     use line_buffer::LineBuffer;
 
     #[test]
@@ -366,6 +527,43 @@ mod tests {
         assert_eq!(2, cs.undos.len());
     }
 
+    #[test]
+    fn test_grouping() {
+        let mut cs = Changeset::new();
+        cs.set_grouping(UndoGrouping::Char);
+        cs.insert(0, 'H');
+        cs.insert(1, 'i');
+        assert_eq!(2, cs.undos.len());
+
+        let mut cs = Changeset::new();
+        cs.set_grouping(UndoGrouping::Pause(60_000));
+        cs.insert(0, 'H');
+        cs.insert(1, 'i');
+        cs.insert(2, ' ');
+        cs.insert(3, '!');
+        assert_eq!(1, cs.undos.len());
+        cs.set_grouping(UndoGrouping::Pause(0));
+        thread::sleep(Duration::from_millis(1));
+        cs.insert(4, '!');
+        assert_eq!(2, cs.undos.len());
+    }
+
+    #[test]
+    fn test_redo_count() {
+        let mut buf = LineBuffer::init("", 0, None);
+        let mut cs = Changeset::new();
+        buf.insert_str(0, "a b");
+        cs.insert_str(0, "a");
+        cs.insert_str(1, " ");
+        cs.insert_str(2, "b");
+        cs.undo(&mut buf, 3);
+        assert_eq!("", buf.as_str());
+        cs.redo(&mut buf, 2);
+        assert_eq!("a ", buf.as_str());
+        assert_eq!(2, buf.pos());
+        assert_eq!(1, cs.redos.len());
+    }
+
     #[test]
     fn test_insert_strings() {
         let mut cs = Changeset::new();
@@ -390,7 +588,7 @@ mod tests {
         assert_eq!(1, cs.redos.len());
         assert_eq!(buf.as_str(), "Hello");
 
-        cs.redo(&mut buf);
+        cs.redo(&mut buf, 1);
         assert_eq!(1, cs.undos.len());
         assert_eq!(0, cs.redos.len());
         assert_eq!(buf.as_str(), "Hello, world!");
@@ -408,7 +606,7 @@ mod tests {
         cs.undo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hello, world!");
 
-        cs.redo(&mut buf);
+        cs.redo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hello");
     }
 
@@ -454,7 +652,7 @@ mod tests {
         cs.undo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hello, world!");
 
-        cs.redo(&mut buf);
+        cs.redo(&mut buf, 1);
         assert_eq!(buf.as_str(), "Hi, world!");
     }
 
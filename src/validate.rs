@@ -0,0 +1,49 @@
+//! Validation of the accepted input: an incomplete input (like an unclosed
+//! bracket) is continued on a new line.
+
+/// Result of `Validator::validate`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ValidationResult {
+    /// The input is accepted.
+    Valid,
+    /// The input goes on with a new line, starting with the given text (like
+    /// an indentation after `:`, empty for none).
+    Incomplete(String),
+    /// The input is not accepted: the message (if any) is displayed under the
+    /// line, which is edited again.
+    Invalid(Option<String>),
+}
+
+/// Validation provider (see `Editor::set_validator`)
+///
+/// ```
+/// use rustyline::validate::ValidationResult;
+/// use rustyline::Editor;
+/// let mut editor = Editor::<()>::new();
+/// editor.set_validator(Some(Box::new(|input: &str| {
+///     let last = input.lines().last().unwrap_or("");
+///     if last.ends_with(':') {
+///         // indent the block
+///         let indent = last.len() - last.trim_start().len();
+///         ValidationResult::Incomplete(" ".repeat(indent + 4))
+///     } else if last.trim().is_empty() || !input.contains('\n') {
+///         ValidationResult::Valid
+///     } else {
+///         let indent = last.len() - last.trim_start().len();
+///         ValidationResult::Incomplete(" ".repeat(indent))
+///     }
+/// })));
+/// ```
+pub trait Validator {
+    /// Check the whole input (with its previous lines) when it is accepted.
+    fn validate(&self, input: &str) -> ValidationResult;
+}
+
+impl<F> Validator for F
+where
+    F: Fn(&str) -> ValidationResult,
+{
+    fn validate(&self, input: &str) -> ValidationResult {
+        self(input)
+    }
+}